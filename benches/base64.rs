@@ -0,0 +1,80 @@
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use criterion::{criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, BenchmarkId, Criterion};
+use halo2_base::halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_base::halo2_proofs::dev::MockProver;
+use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use halo2_base::halo2_proofs::plonk::{Circuit, ConstraintSystem, Error};
+use halo2_base::utils::PrimeField;
+use halo2_base64::Base64Config;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::marker::PhantomData;
+
+const K: u32 = 12;
+
+/// A circuit that only decodes the base64 encoding of a `DIGEST_BYTES`-byte digest with [`Base64Config::assign_values`].
+#[derive(Debug, Clone)]
+struct Base64DecodeCircuit<F: PrimeField, const DIGEST_BYTES: usize> {
+    encoded: Vec<u8>,
+    _f: PhantomData<F>,
+}
+
+impl<F: PrimeField, const DIGEST_BYTES: usize> Base64DecodeCircuit<F, DIGEST_BYTES> {
+    fn random() -> Self {
+        let mut digest = vec![0u8; DIGEST_BYTES];
+        OsRng.fill_bytes(&mut digest);
+        Self {
+            encoded: BASE64_STANDARD.encode(&digest).into_bytes(),
+            _f: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField, const DIGEST_BYTES: usize> Circuit<F> for Base64DecodeCircuit<F, DIGEST_BYTES> {
+    type Config = Base64Config<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            encoded: vec![],
+            _f: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        Base64Config::configure(meta, DIGEST_BYTES)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        config.load(&mut layouter)?;
+        layouter.assign_region(
+            || "base64 decode",
+            |mut region| {
+                config.assign_values(&mut region, &self.encoded)?;
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
+
+fn bench_digest_size<const DIGEST_BYTES: usize>(group: &mut BenchmarkGroup<WallTime>) {
+    let circuit = Base64DecodeCircuit::<Fr, DIGEST_BYTES>::random();
+    MockProver::run(K, &circuit, vec![]).unwrap().assert_satisfied();
+    group.bench_with_input(BenchmarkId::from_parameter(format!("{} bytes", DIGEST_BYTES)), &circuit, |b, circuit| {
+        b.iter(|| MockProver::run(K, circuit, vec![]).unwrap())
+    });
+}
+
+// 20, 32, and 64 bytes are the digest sizes of SHA1, SHA256, and SHA512, respectively.
+fn bench_base64_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("base64 decode assignment");
+    group.sample_size(10);
+    bench_digest_size::<20>(&mut group);
+    bench_digest_size::<32>(&mut group);
+    bench_digest_size::<64>(&mut group);
+    group.finish();
+}
+
+criterion_group!(benches, bench_base64_decode,);
+criterion_main!(benches);
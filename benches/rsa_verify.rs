@@ -0,0 +1,142 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use halo2_base::gates::{range::RangeConfig, range::RangeStrategy::Vertical, GateInstructions};
+use halo2_base::halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+use halo2_base::halo2_proofs::dev::MockProver;
+use halo2_base::halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+use halo2_base::halo2_proofs::plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error};
+use halo2_base::halo2_proofs::poly::{
+    commitment::{Params, ParamsProver},
+    kzg::commitment::ParamsKZG,
+};
+use halo2_base::utils::{decompose_biguint, PrimeField};
+use halo2_base::{Context, ContextParams, SKIP_FIRST_PASS};
+use halo2_rsa::{RSAInstructions, RSAPubE, RSAPublicKey, RSASignature};
+use halo2_zk_email::sign_verify::{SignVerifyConfig, LIMB_BITS};
+use num_bigint::BigUint;
+use rand::{rngs::OsRng, thread_rng};
+use rsa::{traits::PublicKeyParts, Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+use snark_verifier_sdk::halo2::gen_proof_shplonk;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::marker::PhantomData;
+
+const K: u32 = 17;
+const NUM_FLEX_ADVICE: usize = 8;
+const NUM_RANGE_LOOKUP_ADVICE: usize = 1;
+const NUM_FLEX_FIXED: usize = 1;
+const RANGE_LOOKUP_BITS: usize = 16;
+const PUBLIC_KEY_BITS: usize = 2048;
+const DEFAULT_E: u64 = 65537;
+
+/// A circuit that only verifies a PKCS#1 v1.5 RSA signature of a SHA-256 hash given as witnesses with `halo2-rsa`, without hashing anything.
+#[derive(Debug, Clone)]
+struct RsaVerifyCircuit<F: PrimeField> {
+    hash: Vec<u8>,
+    public_key_n: BigUint,
+    signature: BigUint,
+    _f: PhantomData<F>,
+}
+
+impl<F: PrimeField> RsaVerifyCircuit<F> {
+    fn random() -> Self {
+        let private_key = RsaPrivateKey::new(&mut thread_rng(), PUBLIC_KEY_BITS).unwrap();
+        let hash = Sha256::digest(b"email was meant for @zkemailverify.").to_vec();
+        let signature = private_key.sign(Pkcs1v15Sign::new::<Sha256>(), &hash).unwrap();
+        Self {
+            hash,
+            public_key_n: BigUint::from_bytes_be(&private_key.n().to_bytes_be()),
+            signature: BigUint::from_bytes_be(&signature),
+            _f: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for RsaVerifyCircuit<F> {
+    type Config = (RangeConfig<F>, SignVerifyConfig<F>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let range_config = RangeConfig::configure(
+            meta,
+            Vertical,
+            &[NUM_FLEX_ADVICE],
+            &[NUM_RANGE_LOOKUP_ADVICE],
+            NUM_FLEX_FIXED,
+            RANGE_LOOKUP_BITS,
+            0,
+            K as usize,
+        );
+        (range_config.clone(), SignVerifyConfig::configure(range_config, PUBLIC_KEY_BITS, None))
+    }
+
+    fn synthesize(&self, (range, config): Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        range.load_lookup_table(&mut layouter)?;
+        let mut first_pass = SKIP_FIRST_PASS;
+        layouter.assign_region(
+            || "rsa verify",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let gate = range.gate.clone();
+                let ctx = &mut Context::new(
+                    region,
+                    ContextParams {
+                        max_rows: gate.max_rows,
+                        num_context_ids: 1,
+                        fixed_columns: gate.constants.clone(),
+                    },
+                );
+                let hash_limbs = decompose_biguint::<F>(&BigUint::from_bytes_be(&self.hash), self.hash.len() * 8 / LIMB_BITS, LIMB_BITS);
+                let hashed_u64s = gate.assign_witnesses(ctx, hash_limbs.into_iter().map(Value::known).collect());
+                let public_key = RSAPublicKey::<F>::new(Value::known(self.public_key_n.clone()), RSAPubE::Fix(BigUint::from(DEFAULT_E)));
+                let signature = RSASignature::<F>::new(Value::known(self.signature.clone()));
+                let public_key = config.assign_public_key(ctx, public_key)?;
+                let signature = config.assign_signature(ctx, signature)?;
+                let is_sign_valid = config.rsa_config.verify_pkcs1v15_signature(ctx, &public_key, &hashed_u64s, &signature)?;
+                gate.assert_is_const(ctx, &is_sign_valid, F::one());
+                range.finalize(ctx);
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
+
+fn gen_or_get_params(k: usize) -> ParamsKZG<Bn256> {
+    let path = format!("params_{}.bin", k);
+    match File::open(&path) {
+        Ok(f) => {
+            let mut reader = BufReader::new(f);
+            ParamsKZG::read(&mut reader).unwrap()
+        }
+        Err(_) => {
+            let params = ParamsKZG::<Bn256>::setup(k as u32, OsRng);
+            params.write(&mut BufWriter::new(File::create(&path).unwrap())).unwrap();
+            params
+        }
+    }
+}
+
+// Only the RSA verification of a 2048-bit signature is proven, so the SHA-256 and regex costs of the email circuits are excluded.
+// Compare the time with `bench_sign_only` in `sign_only.rs` to see the share of the RSA verification, or with itself before and after optimizing `SignVerifyConfig`.
+fn bench_rsa_verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rsa verify proving");
+    group.sample_size(10);
+    let params = gen_or_get_params(K as usize);
+    let circuit = RsaVerifyCircuit::<Fr>::random();
+    MockProver::run(K, &circuit, vec![]).unwrap().assert_satisfied();
+    let vk = keygen_vk(&params, &circuit).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit).unwrap();
+    group.bench_function("bench rsa verify", |b| b.iter(|| gen_proof_shplonk(&params, &pk, circuit.clone(), vec![], &mut OsRng, None)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_rsa_verify,);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+use cfdkim::*;
+use criterion::{criterion_group, criterion_main, Criterion};
+use halo2_base::halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+use halo2_base::halo2_proofs::plonk::{keygen_pk, keygen_vk};
+use halo2_base::halo2_proofs::{
+    dev::MockProver,
+    poly::{
+        commitment::{Params, ParamsProver},
+        kzg::commitment::ParamsKZG,
+    },
+};
+use halo2_zk_email::sign_only::SignOnlyCircuit;
+use halo2_zk_email::{default_config_params, EMAIL_VERIFY_CONFIG_ENV};
+use num_bigint::BigUint;
+use rand::rngs::OsRng;
+use rsa::PublicKeyParts;
+use snark_verifier_sdk::halo2::gen_proof_shplonk;
+use snark_verifier_sdk::CircuitExt;
+use std::env::set_var;
+use std::{
+    fs::File,
+    io::{prelude::*, BufReader, BufWriter},
+};
+use tokio::runtime::Runtime;
+
+fn gen_or_get_params(k: usize) -> ParamsKZG<Bn256> {
+    let path = format!("params_{}.bin", k);
+    match File::open(&path) {
+        Ok(f) => {
+            let mut reader = BufReader::new(f);
+            ParamsKZG::read(&mut reader).unwrap()
+        }
+        Err(_) => {
+            let params = ParamsKZG::<Bn256>::setup(k as u32, OsRng);
+            params.write(&mut BufWriter::new(File::create(&path).unwrap())).unwrap();
+            params
+        }
+    }
+}
+
+// Compare with `bench_email_verify1` in `email_verify.rs`, which proves the same email with the default circuit.
+// They are in separate benchmarks because the configuration parameters are loaded only once per process.
+fn bench_sign_only(c: &mut Criterion) {
+    let mut group = c.benchmark_group("email bench sign only");
+    group.sample_size(10);
+    set_var(EMAIL_VERIFY_CONFIG_ENV, "./configs/sign_only.config");
+    let config_params = default_config_params();
+    let params = gen_or_get_params(config_params.degree as usize);
+    let email_bytes = {
+        let mut f = File::open("./test_data/test_email1.eml").unwrap();
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+        buf
+    };
+    let logger = slog::Logger::root(slog::Discard, slog::o!());
+    let runtime = Runtime::new().unwrap();
+    let public_key = runtime.block_on(async { resolve_public_key(&logger, &email_bytes).await }).unwrap();
+    let public_key = match public_key {
+        cfdkim::DkimPublicKey::Rsa(pk) => pk,
+        _ => panic!("not supportted public key type."),
+    };
+    let public_key_n = BigUint::from_radix_le(&public_key.n().clone().to_radix_le(16), 16).unwrap();
+    let circuit = SignOnlyCircuit::<Fr>::new(email_bytes, public_key_n);
+
+    MockProver::run(params.k(), &circuit, circuit.instances()).unwrap().assert_satisfied();
+    let vk = keygen_vk(&params, &circuit).unwrap();
+    let pk = keygen_pk(&params, vk.clone(), &circuit).unwrap();
+    group.bench_function("bench sign only", |b| {
+        b.iter(|| gen_proof_shplonk(&params, &pk, circuit.clone(), circuit.instances(), &mut OsRng, None))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_sign_only,);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use halo2_base::halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+use halo2_base::halo2_proofs::plonk::{keygen_pk, keygen_vk};
+use halo2_base::halo2_proofs::{
+    dev::MockProver,
+    poly::{
+        commitment::{Params, ParamsProver},
+        kzg::commitment::ParamsKZG,
+    },
+};
+use halo2_zk_email::signature_presence::SignaturePresenceCircuit;
+use halo2_zk_email::{default_config_params, EMAIL_VERIFY_CONFIG_ENV};
+use rand::rngs::OsRng;
+use snark_verifier_sdk::halo2::gen_proof_shplonk;
+use snark_verifier_sdk::CircuitExt;
+use std::env::set_var;
+use std::{
+    fs::File,
+    io::{prelude::*, BufReader, BufWriter},
+};
+
+fn gen_or_get_params(k: usize) -> ParamsKZG<Bn256> {
+    let path = format!("params_{}.bin", k);
+    match File::open(&path) {
+        Ok(f) => {
+            let mut reader = BufReader::new(f);
+            ParamsKZG::read(&mut reader).unwrap()
+        }
+        Err(_) => {
+            let params = ParamsKZG::<Bn256>::setup(k as u32, OsRng);
+            params.write(&mut BufWriter::new(File::create(&path).unwrap())).unwrap();
+            params
+        }
+    }
+}
+
+// Compare with `bench_sign_only` in `sign_only.rs`, which verifies the RSA signature of the same email.
+// Unlike it, this benchmark needs no DKIM public key because the signature is never verified.
+fn bench_signature_presence(c: &mut Criterion) {
+    let mut group = c.benchmark_group("email bench signature presence");
+    group.sample_size(10);
+    set_var(EMAIL_VERIFY_CONFIG_ENV, "./configs/signature_presence.config");
+    let config_params = default_config_params();
+    let params = gen_or_get_params(config_params.degree as usize);
+    let email_bytes = {
+        let mut f = File::open("./test_data/test_email1.eml").unwrap();
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+        buf
+    };
+    let circuit = SignaturePresenceCircuit::<Fr>::new(email_bytes);
+
+    MockProver::run(params.k(), &circuit, circuit.instances()).unwrap().assert_satisfied();
+    let vk = keygen_vk(&params, &circuit).unwrap();
+    let pk = keygen_pk(&params, vk.clone(), &circuit).unwrap();
+    group.bench_function("bench signature presence", |b| {
+        b.iter(|| gen_proof_shplonk(&params, &pk, circuit.clone(), circuit.instances(), &mut OsRng, None))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_signature_presence,);
+criterion_main!(benches);
@@ -0,0 +1,421 @@
+use crate::dkim::DkimError;
+use base64::{engine::general_purpose, Engine as _};
+use mailparse::{parse_headers, MailHeader};
+use num_bigint::BigUint;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+/// The name of the ARC seal header.
+pub const ARC_SEAL_HEADER: &str = "ARC-Seal";
+/// The name of the ARC message signature header, which signs the header fields and the body hash like the DKIM signature.
+pub const ARC_MESSAGE_SIGNATURE_HEADER: &str = "ARC-Message-Signature";
+/// The name of the ARC authentication results header.
+pub const ARC_AUTHENTICATION_RESULTS_HEADER: &str = "ARC-Authentication-Results";
+
+/// The ARC headers added by one intermediary, e.g., Gmail when it forwards an email, as specified in RFC 8617.
+/// Each value is kept as it appears in the email, including the folding whitespaces, since the ARC signatures are verified over their canonicalized forms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArcSet {
+    /// The instance number, i.e., the `i=` tag, which is 1 for the first intermediary.
+    pub instance: u32,
+    /// The raw value of the [`ARC_AUTHENTICATION_RESULTS_HEADER`] header.
+    pub authentication_results: String,
+    /// The raw value of the [`ARC_MESSAGE_SIGNATURE_HEADER`] header.
+    pub message_signature: String,
+    /// The raw value of the [`ARC_SEAL_HEADER`] header.
+    pub seal: String,
+}
+
+impl ArcSet {
+    /// Get the value of the given tag in the [`ARC_SEAL_HEADER`] header, e.g., `d` and `s` to resolve the public key of the sealer.
+    pub fn seal_tag(&self, tag_name: &str) -> Option<String> {
+        find_tag(&self.seal, tag_name)
+    }
+
+    /// Get the value of the given tag in the [`ARC_MESSAGE_SIGNATURE_HEADER`] header, e.g., `d` and `s` to resolve the public key of the signer.
+    pub fn message_signature_tag(&self, tag_name: &str) -> Option<String> {
+        find_tag(&self.message_signature, tag_name)
+    }
+}
+
+/// Get the ARC sets of the given email and check that they form a chain, i.e., the instances are numbered from 1 without gaps,
+/// each instance has exactly one header of each kind, and the chain validation status `cv=` of the seals is `none` for the first instance and `pass` for the others.
+/// The outermost seal itself is verified by [`verify_arc`].
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return the ARC sets in the ascending order of the instances, which is empty if the email has no ARC headers, otherwise [`DkimError`].
+pub fn get_arc_sets(email_bytes: &[u8]) -> Result<Vec<ArcSet>, DkimError> {
+    let (headers, _) = parse_headers(email_bytes).map_err(|err| DkimError::InvalidEmail(err.to_string()))?;
+    let mut fields: Vec<[Option<String>; 3]> = vec![];
+    for header in headers.iter() {
+        let kind = match [ARC_AUTHENTICATION_RESULTS_HEADER, ARC_MESSAGE_SIGNATURE_HEADER, ARC_SEAL_HEADER]
+            .iter()
+            .position(|name| header.get_key().eq_ignore_ascii_case(name))
+        {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let value = raw_value(header);
+        let instance = find_tag(&value, "i")
+            .and_then(|instance| instance.parse::<usize>().ok())
+            .filter(|instance| (1..=50).contains(instance))
+            .ok_or_else(|| DkimError::InvalidArcChain(format!("the {} header has no valid i= tag", header.get_key())))?;
+        if fields.len() < instance {
+            fields.resize(instance, [None, None, None]);
+        }
+        if fields[instance - 1][kind].replace(value).is_some() {
+            return Err(DkimError::InvalidArcChain(format!("the instance {} has multiple {} headers", instance, header.get_key())));
+        }
+    }
+    let mut sets = vec![];
+    for (idx, [authentication_results, message_signature, seal]) in fields.into_iter().enumerate() {
+        let instance = idx as u32 + 1;
+        let (authentication_results, message_signature, seal) = match (authentication_results, message_signature, seal) {
+            (Some(authentication_results), Some(message_signature), Some(seal)) => (authentication_results, message_signature, seal),
+            _ => return Err(DkimError::InvalidArcChain(format!("the instance {} does not have all of the three ARC headers", instance))),
+        };
+        let expected_cv = if instance == 1 { "none" } else { "pass" };
+        let cv = find_tag(&seal, "cv").unwrap_or_default();
+        if !cv.eq_ignore_ascii_case(expected_cv) {
+            return Err(DkimError::InvalidArcChain(format!("the seal of the instance {} has cv={}, but cv={} is expected", instance, cv, expected_cv)));
+        }
+        sets.push(ArcSet {
+            instance,
+            authentication_results,
+            message_signature,
+            seal,
+        });
+    }
+    Ok(sets)
+}
+
+/// Canonicalize the email for the outermost ARC message signature in the same form as a DKIM signature, so that it can be proven by the email verification circuit.
+/// The canonicalized header consists of the header fields selected by its `h=` tag followed by the ARC message signature header without its `b=` value,
+/// and the canonicalized body is truncated to its `l=` tag if any.
+/// Only the `relaxed` header canonicalization is supported, which is the one used by Gmail.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return the canonicalized header, the signed part of the canonicalized body, and the signature bytes, otherwise [`DkimError`].
+pub fn canonicalize_arc_message_signature(email_bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), DkimError> {
+    let sets = get_arc_sets(email_bytes)?;
+    let outermost = sets.last().ok_or_else(|| DkimError::InvalidArcChain("the email has no ARC headers".to_string()))?;
+    let signature = &outermost.message_signature;
+    let algorithm = find_tag(signature, "a").ok_or_else(|| DkimError::MissingTag("a".to_string()))?;
+    if !crate::dkim::SUPPORTED_ALGORITHMS.iter().any(|supported| supported.eq_ignore_ascii_case(&algorithm)) {
+        return Err(DkimError::UnsupportedAlgorithm(algorithm));
+    }
+    let canonicalization = find_tag(signature, "c").unwrap_or_else(|| "simple/simple".to_string()).to_ascii_lowercase();
+    let (header_canonicalization, body_canonicalization) = canonicalization.split_once('/').unwrap_or((&canonicalization, "simple"));
+    if header_canonicalization != "relaxed" {
+        return Err(DkimError::InvalidArcChain(format!("the ARC message signature uses the unsupported header canonicalization {}", header_canonicalization)));
+    }
+
+    let (headers, body_offset) = parse_headers(email_bytes).map_err(|err| DkimError::InvalidEmail(err.to_string()))?;
+    let names = find_tag(signature, "h").ok_or_else(|| DkimError::MissingTag("h".to_string()))?;
+    let mut is_selected = vec![false; headers.len()];
+    let mut header_bytes = vec![];
+    for name in names.split(':').filter(|name| !name.is_empty()) {
+        if let Some(idx) = (0..headers.len()).rev().find(|idx| !is_selected[*idx] && headers[*idx].get_key().eq_ignore_ascii_case(name)) {
+            is_selected[idx] = true;
+            header_bytes.extend(relaxed_header(name, &raw_value(&headers[idx])).into_bytes());
+            header_bytes.extend(b"\r\n");
+        }
+    }
+    header_bytes.extend(relaxed_header(ARC_MESSAGE_SIGNATURE_HEADER, &without_signature(signature)).into_bytes());
+
+    let mut body_bytes = canonicalize_body(&email_bytes[body_offset..], body_canonicalization == "relaxed");
+    if let Some(length) = find_tag(signature, "l") {
+        let invalid = || DkimError::InvalidBodyLength {
+            length: length.clone(),
+            body_len: body_bytes.len(),
+        };
+        let body_len = length.parse::<usize>().map_err(|_| invalid())?;
+        if body_len > body_bytes.len() {
+            return Err(invalid());
+        }
+        body_bytes.truncate(body_len);
+    }
+    let signature_bytes = general_purpose::STANDARD
+        .decode(find_tag(signature, "b").unwrap_or_default())
+        .map_err(|err| DkimError::InvalidEmail(format!("the b= tag of the ARC message signature is not base64: {}", err)))?;
+    Ok((header_bytes, body_bytes, signature_bytes))
+}
+
+/// Compute the data signed by the outermost ARC seal, i.e., the ARC headers of all the instances in the ascending order,
+/// each of which is canonicalized with the `relaxed` header canonicalization, where the outermost seal is the last one without its `b=` value.
+///
+/// # Arguments
+/// * `sets` - the ARC sets returned by [`get_arc_sets`].
+/// # Return values
+/// Return the signed data.
+pub fn arc_seal_signed_data(sets: &[ArcSet]) -> Vec<u8> {
+    let mut data = vec![];
+    for (idx, set) in sets.iter().enumerate() {
+        data.extend(relaxed_header(ARC_AUTHENTICATION_RESULTS_HEADER, &set.authentication_results).into_bytes());
+        data.extend(b"\r\n");
+        data.extend(relaxed_header(ARC_MESSAGE_SIGNATURE_HEADER, &set.message_signature).into_bytes());
+        data.extend(b"\r\n");
+        if idx + 1 == sets.len() {
+            data.extend(relaxed_header(ARC_SEAL_HEADER, &without_signature(&set.seal)).into_bytes());
+        } else {
+            data.extend(relaxed_header(ARC_SEAL_HEADER, &set.seal).into_bytes());
+            data.extend(b"\r\n");
+        }
+    }
+    data
+}
+
+/// Verify the ARC chain of the given email natively, i.e., the outermost seal, and the body hash and the signature of the outermost message signature.
+/// The email verification circuit then proves the outermost message signature with the returned parts in place of the DKIM signature,
+/// so that the body fields of a forwarded email can be extracted even if its original DKIM signature is broken.
+///
+/// Note that the seal is verified only natively, and the circuit exposes the hash of the public key of the message signature.
+/// The verifier must still check it against a trusted key of the intermediary, e.g., that of `google.com` for an email forwarded by Gmail.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `seal_key_n` - the `n` parameter of the RSA public key of the outermost seal.
+/// * `message_key_n` - the `n` parameter of the RSA public key of the outermost message signature, which is usually the same as `seal_key_n`.
+/// # Return values
+/// Return the parts returned by [`canonicalize_arc_message_signature`] if the chain is valid, otherwise [`DkimError`].
+pub fn verify_arc(email_bytes: &[u8], seal_key_n: &BigUint, message_key_n: &BigUint) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), DkimError> {
+    let sets = get_arc_sets(email_bytes)?;
+    let outermost = sets.last().ok_or_else(|| DkimError::InvalidArcChain("the email has no ARC headers".to_string()))?;
+    let seal_signature = general_purpose::STANDARD
+        .decode(outermost.seal_tag("b").unwrap_or_default())
+        .map_err(|err| DkimError::InvalidEmail(format!("the b= tag of the ARC seal is not base64: {}", err)))?;
+    if !verify_rsa_sha256(seal_key_n, &arc_seal_signed_data(&sets), &seal_signature) {
+        return Err(DkimError::InvalidArcSeal(outermost.instance));
+    }
+    let (header_bytes, body_bytes, signature_bytes) = canonicalize_arc_message_signature(email_bytes)?;
+    let header_body_hash = outermost.message_signature_tag("bh").ok_or_else(|| DkimError::MissingTag("bh".to_string()))?;
+    let computed_body_hash = general_purpose::STANDARD.encode(Sha256::digest(&body_bytes));
+    if header_body_hash != computed_body_hash {
+        return Err(DkimError::BodyHashMismatch {
+            header: header_body_hash,
+            computed: computed_body_hash,
+        });
+    }
+    if !verify_rsa_sha256(message_key_n, &header_bytes, &signature_bytes) {
+        return Err(DkimError::NoMatchingPublicKey(1));
+    }
+    Ok((header_bytes, body_bytes, signature_bytes))
+}
+
+/// Canonicalize the body with the `simple` or `relaxed` body canonicalization of RFC 6376 section 3.4.
+fn canonicalize_body(body: &[u8], relaxed: bool) -> Vec<u8> {
+    let mut lines = body
+        .split(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .map(|line| if relaxed { relaxed_body_line(line) } else { line.to_vec() })
+        .collect::<Vec<_>>();
+    while lines.last().map_or(false, |line| line.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return if relaxed { vec![] } else { b"\r\n".to_vec() };
+    }
+    lines.into_iter().flat_map(|line| line.into_iter().chain(*b"\r\n")).collect()
+}
+
+fn relaxed_body_line(line: &[u8]) -> Vec<u8> {
+    let mut canonicalized = vec![];
+    let mut in_whitespace = false;
+    for byte in line {
+        if *byte == b' ' || *byte == b'\t' {
+            in_whitespace = true;
+        } else {
+            if in_whitespace {
+                canonicalized.push(b' ');
+            }
+            in_whitespace = false;
+            canonicalized.push(*byte);
+        }
+    }
+    canonicalized
+}
+
+/// Canonicalize a header field with the `relaxed` header canonicalization, i.e., lowercase the name, unfold the value, and compress and trim its whitespaces.
+fn relaxed_header(name: &str, value: &str) -> String {
+    let unfolded = value.replace("\r\n", "").replace('\n', "");
+    let value = unfolded.split(|c| c == ' ' || c == '\t').filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ");
+    format!("{}:{}", name.trim().to_ascii_lowercase(), value)
+}
+
+/// Remove the value of the `b=` tag, keeping the tag itself, as the signature is computed over the header without it.
+fn without_signature(value: &str) -> String {
+    value
+        .split(';')
+        .map(|tag| match tag.split_once('=') {
+            Some((name, _)) if name.trim() == "b" => format!("{}=", name),
+            _ => tag.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn find_tag(value: &str, tag_name: &str) -> Option<String> {
+    value.split(';').find_map(|tag| {
+        let (name, value) = tag.split_once('=')?;
+        (name.trim() == tag_name).then(|| value.chars().filter(|c| !c.is_whitespace()).collect())
+    })
+}
+
+fn raw_value(header: &MailHeader) -> String {
+    String::from_utf8_lossy(header.get_value_raw()).to_string()
+}
+
+fn verify_rsa_sha256(public_key_n: &BigUint, data: &[u8], signature: &[u8]) -> bool {
+    RsaPublicKey::new(rsa::BigUint::from_bytes_be(&public_key_n.to_bytes_be()), rsa::BigUint::from(65537u64))
+        .map(|public_key| public_key.verify(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(data), signature).is_ok())
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::EmailCircuitBuilder;
+    use crate::{default_config_params, EMAIL_VERIFY_CONFIG_ENV};
+    use halo2_base::halo2_proofs::dev::MockProver;
+    use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+    use halo2_regex::vrm::DecomposedRegexConfig;
+    use rand::thread_rng;
+    use rsa::traits::PublicKeyParts;
+    use rsa::RsaPrivateKey;
+    use snark_verifier_sdk::CircuitExt;
+    use std::fs::File;
+    use std::path::Path;
+
+    const BODY: &str = "email was meant for @zkemailverify.\r\n";
+
+    fn sign(private_key: &RsaPrivateKey, data: &[u8]) -> String {
+        general_purpose::STANDARD.encode(private_key.sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(data)).unwrap())
+    }
+
+    fn arc_email(body_hash: &str, message_signature: &str, seal: &str, body: &str) -> Vec<u8> {
+        format!(
+            "ARC-Seal: i=1; a=rsa-sha256; t=1700000000; cv=none; d=google.com; s=arc-20160816;\r\n b={}\r\n\
+             ARC-Message-Signature: i=1; a=rsa-sha256; c=relaxed/relaxed; d=google.com; s=arc-20160816;\r\n h=from:subject; bh={}; b={}\r\n\
+             ARC-Authentication-Results: i=1; mx.google.com; dkim=fail header.i=@zkemail.com\r\n\
+             From: alice@zkemail.com\r\nSubject:  Fwd:   receipt \r\n\r\n{}",
+            seal, body_hash, message_signature, body
+        )
+        .into_bytes()
+    }
+
+    // An email forwarded by Gmail, whose ARC set of the instance 1 is signed with `private_key`.
+    fn gen_arc_email(private_key: &RsaPrivateKey) -> Vec<u8> {
+        let body_hash = general_purpose::STANDARD.encode(Sha256::digest(canonicalize_body(BODY.as_bytes(), true)));
+        let (header_bytes, _, _) = canonicalize_arc_message_signature(&arc_email(&body_hash, "", "", BODY)).unwrap();
+        let message_signature = sign(private_key, &header_bytes);
+        let sets = get_arc_sets(&arc_email(&body_hash, &message_signature, "", BODY)).unwrap();
+        let seal = sign(private_key, &arc_seal_signed_data(&sets));
+        arc_email(&body_hash, &message_signature, &seal, BODY)
+    }
+
+    #[test]
+    fn test_verify_arc() {
+        let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+        let public_key_n = BigUint::from_bytes_be(&private_key.n().to_bytes_be());
+        let email_bytes = gen_arc_email(&private_key);
+        let sets = get_arc_sets(&email_bytes).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].seal_tag("d"), Some("google.com".to_string()));
+        let (header_bytes, body_bytes, _) = verify_arc(&email_bytes, &public_key_n, &public_key_n).unwrap();
+        assert!(header_bytes.starts_with(b"from:alice@zkemail.com\r\nsubject:Fwd: receipt\r\narc-message-signature:i=1; a=rsa-sha256;"));
+        assert!(header_bytes.ends_with(b"; b="));
+        assert_eq!(body_bytes, BODY.as_bytes());
+
+        let other_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+        let other_n = BigUint::from_bytes_be(&other_key.n().to_bytes_be());
+        assert_eq!(verify_arc(&email_bytes, &other_n, &public_key_n), Err(DkimError::InvalidArcSeal(1)));
+        assert_eq!(verify_arc(&email_bytes, &public_key_n, &other_n), Err(DkimError::NoMatchingPublicKey(1)));
+        let forwarded_body = String::from_utf8(email_bytes.clone()).unwrap().replace("zkemailverify", "mallory");
+        assert!(matches!(
+            verify_arc(forwarded_body.as_bytes(), &public_key_n, &public_key_n),
+            Err(DkimError::BodyHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_arc_sets() {
+        assert_eq!(get_arc_sets(b"From: alice@zkemail.com\r\n\r\nbody").unwrap(), vec![]);
+        let email = String::from_utf8(arc_email("AAAA", "BBBB", "CCCC", BODY)).unwrap();
+        assert!(matches!(get_arc_sets(email.replace("cv=none", "cv=fail").as_bytes()), Err(DkimError::InvalidArcChain(_))));
+        assert!(matches!(get_arc_sets(email.replace("ARC-Authentication-Results: i=1", "ARC-Authentication-Results: i=2").as_bytes()), Err(DkimError::InvalidArcChain(_))));
+        let duplicated = format!("ARC-Authentication-Results: i=1; mx.google.com\r\n{}", email);
+        assert!(matches!(get_arc_sets(duplicated.as_bytes()), Err(DkimError::InvalidArcChain(_))));
+    }
+
+    #[test]
+    fn test_canonicalization_same_as_dkim() {
+        let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+        let message = format!("From: alice@zkemail.com\r\nSubject:  Fwd:   receipt \r\n\r\n{}\r\n\r\n", BODY.replace(' ', "  "));
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = cfdkim::SignerBuilder::new()
+            .with_signed_headers(&["From", "Subject"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&mailparse::parse_mail(message.as_bytes()).unwrap()).unwrap();
+        // The ARC message signature has the same tags as the DKIM signature except for the instance.
+        let (_, dkim_value) = signature.split_once(':').unwrap();
+        let email_bytes = format!(
+            "ARC-Seal: i=1; cv=none; b=\r\nARC-Message-Signature: i=1;{}\r\nARC-Authentication-Results: i=1; mx.google.com\r\n{}\r\n{}",
+            dkim_value, signature, message
+        )
+        .into_bytes();
+        let (dkim_header, dkim_body, dkim_signature) = cfdkim::canonicalize_signed_email(&email_bytes).unwrap();
+        let (header_bytes, body_bytes, signature_bytes) = canonicalize_arc_message_signature(&email_bytes).unwrap();
+        let expected_header = String::from_utf8(dkim_header).unwrap().replace("dkim-signature:", "arc-message-signature:i=1; ");
+        assert_eq!(String::from_utf8(header_bytes).unwrap(), expected_header);
+        assert_eq!(body_bytes, dkim_body);
+        assert_eq!(signature_bytes, dkim_signature);
+    }
+
+    #[test]
+    fn test_arc_email_verify() {
+        for (defs_path, prefix) in [
+            ("./test_data/arc_bodyhash_defs.json", "arc_bodyhash"),
+            ("./test_data/from_defs.json", "from"),
+            ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+        ] {
+            let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open(defs_path).unwrap()).unwrap();
+            decomposed
+                .gen_regex_files(
+                    &Path::new(&format!("./test_data/{}_allstr.txt", prefix)).to_path_buf(),
+                    &[Path::new(&format!("./test_data/{}_substr_0.txt", prefix)).to_path_buf()],
+                )
+                .unwrap();
+        }
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_arc_email_verify.config"), || {
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+            let public_key_n = BigUint::from_bytes_be(&private_key.n().to_bytes_be());
+            let email_bytes = gen_arc_email(&private_key);
+            let (header_bytes, body_bytes, signature_bytes) = verify_arc(&email_bytes, &public_key_n, &public_key_n).unwrap();
+            let circuit = EmailCircuitBuilder::new()
+                .header(header_bytes)
+                .body(body_bytes)
+                .signature(signature_bytes)
+                .public_key(public_key_n)
+                .build::<Fr>()
+                .unwrap();
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.header_substrs, vec!["alice@zkemail.com".to_string()]);
+            assert_eq!(public_input.body_substrs, vec!["zkemailverify".to_string()]);
+            let prover = MockProver::run(default_config_params().degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+}
@@ -1,11 +1,24 @@
 use clap::{Parser, Subcommand};
 use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use halo2_base::halo2_proofs::SerdeFormat;
+use halo2_zk_email::build_info::BuildInfo;
+use halo2_zk_email::circuit_description::CircuitDescription;
+use halo2_zk_email::credential::{prove_credential, verify_credential};
+use halo2_zk_email::dkim::{canonicalize_signed_email_with_body_length, parse_pinned_public_key, public_key_fingerprint, DkimError, DkimOptions, PublicKeySource, list_dkim_signatures};
+use halo2_zk_email::params;
+use halo2_zk_email::utils::EmailEncoding;
+#[cfg(not(target_arch = "wasm32"))]
+use halo2_zk_email::witness_json::EmailWitnessJson;
 #[cfg(not(target_arch = "wasm32"))]
 use halo2_zk_email::helpers::*;
 use halo2_zk_email::*;
+#[cfg(not(target_arch = "wasm32"))]
+use snark_verifier_sdk::CircuitExt;
+use log::{Level, LevelFilter};
 use std::env::set_var;
-use std::fs::File;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Parser, Debug, Clone)]
@@ -13,6 +26,39 @@ use std::path::PathBuf;
 struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// email verification circuit configure json string, which is used instead of the file of `circuit_config_path`
+    #[arg(long, global = true)]
+    pub circuit_config_json: Option<String>,
+    /// directory from which the default pathes of the setup parameters, keys, proofs, and public inputs are derived
+    #[arg(long, global = true, default_value = "./build")]
+    pub build_dir: String,
+    /// fail instead of overwriting an existing output file
+    #[arg(long, global = true, overrides_with = "overwrite")]
+    pub no_clobber: bool,
+    /// overwrite existing output files, which is the default and cancels `--no-clobber`
+    #[arg(long, global = true, overrides_with = "no_clobber")]
+    pub overwrite: bool,
+    /// append the domain, the selector, the key fingerprint, and the time of every DKIM public key resolved from DNS to this file as json lines
+    #[arg(long, global = true)]
+    pub dkim_audit_log: Option<String>,
+    /// fail if a DKIM key record resolved from DNS is not authenticated by DNSSEC
+    #[arg(long, global = true)]
+    pub require_dnssec: bool,
+    /// DNSSEC-validating DNS-over-HTTPS resolver answering in json, from whose authenticated answer the DKIM key is taken with `--require-dnssec` [default: https://dns.google/resolve]
+    #[arg(long, global = true, requires = "require_dnssec")]
+    pub dnssec_resolver: Option<String>,
+    /// index of the DKIM signature to prove among those listed by `list-signatures` [default: 0]
+    #[arg(long, global = true)]
+    pub dkim_signature: Option<usize>,
+    /// print only errors
+    #[arg(short, long, global = true, conflicts_with_all = ["verbose", "log_level"])]
+    pub quiet: bool,
+    /// print debug messages including the timing of each step, which is the same as `--log-level debug`
+    #[arg(short, long, global = true, conflicts_with = "log_level")]
+    pub verbose: bool,
+    /// maximum level of printed messages: off, error, warn, info, debug, or trace [default: info]
+    #[arg(long, global = true)]
+    pub log_level: Option<LevelFilter>,
 }
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Debug, Subcommand, Clone)]
@@ -20,164 +66,829 @@ enum Commands {
     /// Generate a setup parameter (not for production).
     GenParams {
         /// k parameter for the one email verification circuit.
+        #[arg(long, required_unless_present = "circuit_config_path")]
+        k: Option<u32>,
+        /// email verification circuit configure file, whose degree is used as k
+        #[arg(short, long, visible_alias = "circuit-config", conflicts_with = "k")]
+        circuit_config_path: Option<String>,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// serialization format of the output file: raw (uncompressed points) or processed (compressed points)
+        #[arg(long, default_value = "raw", value_parser = params::parse_format)]
+        params_format: SerdeFormat,
+        /// compress the output file with gzip
+        #[arg(long, default_value_t = false)]
+        compress: bool,
+    },
+    /// Validate the setup parameters and report their k.
+    CheckParams {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+    },
+    /// Downsize the setup parameters, and then check them by proving and verifying a trivial circuit at the new k.
+    DownsizeAndVerify {
+        /// original setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// downsized setup parameters path
+        #[arg(long)]
+        new_params_path: String,
+        /// k parameter of the downsized setup parameters
         #[arg(long)]
         k: u32,
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// serialization format of the downsized setup parameters: raw (uncompressed points) or processed (compressed points)
+        #[arg(long, default_value = "raw", value_parser = params::parse_format)]
+        params_format: SerdeFormat,
+        /// compress the output file with gzip
+        #[arg(long, default_value_t = false)]
+        compress: bool,
     },
     /// Generate proving keys and verifying keys.
     GenKeys {
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
         /// email verification circuit configure file
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
-        /// emails path
+        /// email file path or http(s) URL
+        #[arg(short, long, default_value = "./examples/demo.eml")]
+        email_path: String,
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
+        /// generate and verify a proof for the given email before writing the keys
+        #[arg(long, default_value_t = false)]
+        verify_keys: bool,
+    },
+    /// Check whether the proofs for an old verifying key remain valid for the current circuit, and print the differences of the keys otherwise.
+    CheckCompat {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// current email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// email file path or http(s) URL
         #[arg(short, long, default_value = "./examples/demo.eml")]
         email_path: String,
-        /// proving key path
-        #[arg(long, default_value = "./build/app.pk")]
-        pk_path: String,
-        /// verifying key file
-        #[arg(long, default_value = "./build/app.vk")]
-        vk_path: String,
+        /// old verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        old_vk_path: Option<String>,
+    },
+    /// Generate proving keys and verifying keys for the aggregation circuit.
+    GenAggKeys {
+        /// setup parameters path for the email verification circuit [default: <build-dir>/app_params.bin]
+        #[arg(long)]
+        app_params_path: Option<String>,
+        /// setup parameters path for the aggregation circuit [default: <build-dir>/agg_params.bin]
+        #[arg(long)]
+        agg_params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// aggregation circuit configure file
+        #[arg(long, default_value = "./configs/agg_bench.config")]
+        agg_circuit_config_path: String,
+        /// comma-separated emails pathes whose proofs are aggregated
+        #[arg(short, long, value_delimiter = ',', default_value = "./examples/demo.eml")]
+        email_pathes: Vec<String>,
+        /// proving key path for the email verification circuit [default: <build-dir>/app.pk]
+        #[arg(long)]
+        app_pk_path: Option<String>,
+        /// proving key path for the aggregation circuit [default: <build-dir>/agg.pk]
+        #[arg(long)]
+        agg_pk_path: Option<String>,
+        /// verifying key file for the aggregation circuit [default: <build-dir>/agg.vk]
+        #[arg(long)]
+        agg_vk_path: Option<String>,
+        /// append the proofs to the running aggregation in `acc_path` and update it
+        #[arg(long, default_value_t = false)]
+        append: bool,
+        /// running aggregation file used with `--append` [default: <build-dir>/agg_acc.snark]
+        #[arg(long)]
+        acc_path: Option<String>,
+        /// generate and verify an aggregation proof for the given emails before writing the keys
+        #[arg(long, default_value_t = false)]
+        verify_keys: bool,
+    },
+    /// Report the rows used by the aggregation circuit and the minimal `k` for its configuration.
+    AggCost {
+        /// setup parameters path for the email verification circuit [default: <build-dir>/app_params.bin]
+        #[arg(long)]
+        app_params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// aggregation circuit configure file, whose `degree` is where the search starts
+        #[arg(long, default_value = "./configs/agg_bench.config")]
+        agg_circuit_config_path: String,
+        /// comma-separated emails pathes whose proofs are aggregated
+        #[arg(short, long, value_delimiter = ',', default_value = "./examples/demo.eml")]
+        email_pathes: Vec<String>,
+        /// proving key path for the email verification circuit [default: <build-dir>/app.pk]
+        #[arg(long)]
+        app_pk_path: Option<String>,
+        /// number of the aggregated proofs, to which the last email is repeated [default: `num_snarks` of the configuration or the number of the emails]
+        #[arg(long)]
+        arity: Option<usize>,
+        /// print the report as json
+        #[arg(long)]
+        json: bool,
     },
     Prove {
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
         /// email verification circuit configure file
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
-        /// proving key path
-        #[arg(long, default_value = "./build/app.pk")]
-        pk_path: String,
-        /// emails path
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// email file path or http(s) URL
         #[arg(short, long, default_value = "./examples/demo.eml")]
         email_path: String,
-        /// output proof file
-        #[arg(long, default_value = "./build/app.proof")]
-        proof_path: String,
-        /// public input file
-        #[arg(long, default_value = "./build/public_input.json")]
-        public_input_path: String,
+        /// output proof file [default: <build-dir>/app.proof]
+        #[arg(long)]
+        proof_path: Option<String>,
+        /// public input file [default: <build-dir>/public_input.json]
+        #[arg(long)]
+        public_input_path: Option<String>,
+        /// compress the output proof file with gzip
+        #[arg(long, default_value_t = false)]
+        compress: bool,
+        /// directory to which the intermediate witnesses are written as json before proving
+        #[arg(long)]
+        debug_witness: Option<String>,
+        /// print the numbers of used and available cells at the configured degree while proving
+        #[arg(long, default_value_t = false)]
+        stats: bool,
+        /// comma-separated signing domains allowed to be proven (all domains are allowed if omitted)
+        #[arg(long, value_delimiter = ',')]
+        allowed_domains: Vec<String>,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+        /// generate a reproducible proof with a fixed seed (only for tests; it breaks zero-knowledge)
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+        /// how non-UTF8 bytes in the email are decoded to extract the substrings natively: strict or lossy [default: lossy]
+        #[arg(long)]
+        email_encoding: Option<String>,
+        /// how the field elements in the public input file are serialized: decimal, hex, or le-bytes [default: decimal]
+        #[arg(long)]
+        output_public_input_format: Option<String>,
+        /// pre-computed witness json (canonicalized header and body, signature and public key limbs, and substrings) used instead of the email
+        #[arg(long, conflicts_with_all = ["allowed_domains", "use_embedded_key"])]
+        witness_path: Option<String>,
+        /// write the proof and the public input as a single bundle file instead of the proof and public input files
+        #[arg(long, default_value_t = false, conflicts_with_all = ["compress", "output_public_input_format"])]
+        bundle: bool,
+        /// output bundle file used with `--bundle` [default: <build-dir>/app_bundle.json]
+        #[arg(long)]
+        bundle_path: Option<String>,
+        /// write the proof and the revealed and committed fields as a W3C Verifiable Credential instead of the proof and public input files
+        #[arg(long, default_value_t = false, conflicts_with_all = ["compress", "output_public_input_format", "bundle"])]
+        credential: bool,
+        /// output credential file used with `--credential` [default: <build-dir>/app_credential.json]
+        #[arg(long)]
+        credential_path: Option<String>,
+        /// file of a DKIM key record (`v=DKIM1; k=rsa; p=...`) tried if the key resolved from DNS does not verify the signature, e.g., after a key rotation
+        #[arg(long, conflicts_with = "witness_path")]
+        pinned_key_path: Option<String>,
+        /// prove the outermost ARC message signature of a forwarded email, e.g., by Gmail, instead of its DKIM signature (the configuration must match the ARC-Message-Signature header)
+        #[arg(long, default_value_t = false, conflicts_with_all = ["witness_path", "pinned_key_path", "use_embedded_key"])]
+        arc: bool,
+        /// number of a recent block to which the proof is bound (requires `bind_block_hash` in the configuration)
+        #[arg(long, requires = "block_hash")]
+        block_number: Option<u64>,
+        /// hex hash of the block given by `--block-number`
+        #[arg(long, requires = "block_number")]
+        block_hash: Option<String>,
+    },
+    /// Generate a proof for each message in an mbox archive.
+    ProveMbox {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// mbox archive path
+        #[arg(short, long)]
+        mbox_path: String,
+        /// output directory of the proofs and public inputs [default: <build-dir>/mbox]
+        #[arg(short, long)]
+        output_dir: Option<String>,
+        /// compress the output proof files with gzip
+        #[arg(long, default_value_t = false)]
+        compress: bool,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+        /// generate reproducible proofs with a fixed seed (only for tests; it breaks zero-knowledge)
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+        /// skip the emails whose DKIM public key cannot be resolved instead of aborting
+        #[arg(long, default_value_t = false)]
+        skip_unresolvable: bool,
+    },
+    /// Generate a proof for each message in a Maildir.
+    ProveMaildir {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// Maildir path, which has the `new` and `cur` directories, or one of those directories
+        #[arg(short, long)]
+        maildir_path: String,
+        /// output directory of the proofs and public inputs [default: <build-dir>/maildir]
+        #[arg(short, long)]
+        output_dir: Option<String>,
+        /// compress the output proof files with gzip
+        #[arg(long, default_value_t = false)]
+        compress: bool,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+        /// generate reproducible proofs with a fixed seed (only for tests; it breaks zero-knowledge)
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+        /// skip the emails whose DKIM public key cannot be resolved instead of aborting
+        #[arg(long, default_value_t = false)]
+        skip_unresolvable: bool,
+    },
+    /// Serve `POST /prove`, which returns a proof and its public input for the email in the request body.
+    Serve {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
     },
     EVMProve {
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
         /// email verification circuit configure file
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
-        /// proving key path
-        #[arg(long, default_value = "./build/app.pk")]
-        pk_path: String,
-        /// emails path
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// email file path or http(s) URL
         #[arg(short, long, default_value = "./examples/demo.eml")]
         email_path: String,
-        /// output proof file
-        #[arg(long, default_value = "./build/app_evm.proof")]
-        proof_path: String,
-        /// public input file
-        #[arg(long, default_value = "./build/public_input.json")]
-        public_input_path: String,
+        /// output proof file [default: <build-dir>/app_evm.proof]
+        #[arg(long)]
+        proof_path: Option<String>,
+        /// public input file [default: <build-dir>/public_input.json]
+        #[arg(long)]
+        public_input_path: Option<String>,
+        /// comma-separated signing domains allowed to be proven (all domains are allowed if omitted)
+        #[arg(long, value_delimiter = ',')]
+        allowed_domains: Vec<String>,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+        /// generate a reproducible proof with a fixed seed (only for tests; it breaks zero-knowledge)
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+        /// how non-UTF8 bytes in the email are decoded to extract the substrings natively: strict or lossy [default: lossy]
+        #[arg(long)]
+        email_encoding: Option<String>,
+        /// how the field elements in the public input file are serialized: decimal, hex, or le-bytes [default: decimal]
+        #[arg(long)]
+        output_public_input_format: Option<String>,
+        /// number of a recent block to which the proof is bound (requires `bind_block_hash` in the configuration)
+        #[arg(long, requires = "block_hash")]
+        block_number: Option<u64>,
+        /// hex hash of the block given by `--block-number`
+        #[arg(long, requires = "block_number")]
+        block_hash: Option<String>,
+    },
+    /// Generate an aggregation proof of the email verification proofs verifiable on EVM.
+    EVMProveAgg {
+        /// setup parameters path for the email verification circuit [default: <build-dir>/app_params.bin]
+        #[arg(long)]
+        app_params_path: Option<String>,
+        /// setup parameters path for the aggregation circuit [default: <build-dir>/agg_params.bin]
+        #[arg(long)]
+        agg_params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// aggregation circuit configure file
+        #[arg(long, default_value = "./configs/agg_bench.config")]
+        agg_circuit_config_path: String,
+        /// comma-separated emails pathes whose proofs are aggregated
+        #[arg(short, long, value_delimiter = ',', default_value = "./examples/demo.eml")]
+        email_pathes: Vec<String>,
+        /// proving key path for the email verification circuit [default: <build-dir>/app.pk]
+        #[arg(long)]
+        app_pk_path: Option<String>,
+        /// proving key path for the aggregation circuit [default: <build-dir>/agg.pk]
+        #[arg(long)]
+        agg_pk_path: Option<String>,
+        /// output proof file [default: <build-dir>/agg_evm.proof]
+        #[arg(long)]
+        proof_path: Option<String>,
+        /// public input file [default: <build-dir>/agg_public_input.json]
+        #[arg(long)]
+        public_input_path: Option<String>,
+        /// append the proofs to the running aggregation in `acc_path` and update it
+        #[arg(long, default_value_t = false)]
+        append: bool,
+        /// running aggregation file used with `--append` [default: <build-dir>/agg_acc.snark]
+        #[arg(long)]
+        acc_path: Option<String>,
+        /// reject more emails than this before generating any proof
+        #[arg(long, visible_alias = "limit")]
+        max_proofs: Option<usize>,
+        /// verify each email's proof before aggregating them and stop at the first invalid one
+        #[arg(long, default_value_t = false)]
+        fail_fast: bool,
     },
     Verify {
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
+        /// output proof file [default: <build-dir>/app.proof]
+        #[arg(long)]
+        proof_path: Option<String>,
+        /// public input file [default: <build-dir>/public_input.json]
+        #[arg(long)]
+        public_input_path: Option<String>,
+        /// proven email file to explain why the proof is invalid with the mock prover
+        #[arg(long)]
+        explain_email_path: Option<String>,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+        /// hex keccak256 digest that the ABI encoded public input must have, e.g., publicInputsHash stored on-chain
+        #[arg(long)]
+        expected_digest: Option<String>,
+        /// reject the public input unless each of its fields has exactly the number of the entries documented for the circuit configuration
+        #[arg(long, default_value_t = false, conflicts_with = "expected_digest")]
+        strict_instance_order: bool,
+    },
+    /// Verify a proof bundle written by `prove --bundle` against the circuit configuration and the verifying key.
+    VerifyBundle {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
+        /// proof bundle file [default: <build-dir>/app_bundle.json]
+        #[arg(long)]
+        bundle_path: Option<String>,
+    },
+    /// Verify a W3C Verifiable Credential written by `prove --credential` against the circuit configuration and the verifying key.
+    VerifyCredential {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
+        /// credential file [default: <build-dir>/app_credential.json]
+        #[arg(long)]
+        credential_path: Option<String>,
+    },
+    /// Generate a proof for the email and verify it natively without writing any file.
+    ProveAndVerify {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
         /// email verification circuit configure file
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
-        /// verifying key file
-        #[arg(long, default_value = "./build/app.vk")]
-        vk_path: String,
-        /// output proof file
-        #[arg(long, default_value = "./build/app.proof")]
-        proof_path: String,
-        /// public input file
-        #[arg(long, default_value = "./build/public_input.json")]
-        public_input_path: String,
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// email file path or http(s) URL
+        #[arg(short, long, default_value = "./examples/demo.eml")]
+        email_path: String,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+        /// generate a reproducible proof with a fixed seed (only for tests; it breaks zero-knowledge)
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+    },
+    /// Time the end-to-end pipeline of loading the keys, building the circuit from an email, proving, and verifying, and print the latency of each stage.
+    BenchE2e {
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// proving key path [default: <build-dir>/app.pk]
+        #[arg(long)]
+        pk_path: Option<String>,
+        /// email file path or http(s) URL
+        #[arg(short, long, default_value = "./examples/demo.eml")]
+        email_path: String,
+        /// use the DKIM public key embedded in the email instead of querying DNS if present
+        #[arg(long, default_value_t = false)]
+        use_embedded_key: bool,
+    },
+    /// Print the keccak256 hash of the verifying key for on-chain registration.
+    VkHash {
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
     },
     VerifyWasm {
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
         /// email verification circuit configure file
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
-        /// verifying key file
-        #[arg(long, default_value = "./build/app.vk")]
-        vk_path: String,
-        /// proof hex file
-        #[arg(long, default_value = "./build/app_proof.hex")]
-        proof_hex_path: String,
-        /// public input file
-        #[arg(long, default_value = "./build/public_input.json")]
-        public_input_path: String,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
+        /// proof hex file [default: <build-dir>/app_proof.hex]
+        #[arg(long)]
+        proof_hex_path: Option<String>,
+        /// public input file [default: <build-dir>/public_input.json]
+        #[arg(long)]
+        public_input_path: Option<String>,
     },
     GenEVMVerifier {
-        /// setup parameters path
-        #[arg(short, long, default_value = "./build/params.bin")]
-        params_path: String,
+        /// setup parameters path [default: <build-dir>/params.bin]
+        #[arg(short, long)]
+        params_path: Option<String>,
         /// email verification circuit configure file
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
         // /// emails path
         // #[arg(short, long, default_value = "./examples/demo.eml")]
         // email_path: String,
-        /// verifying key file
-        #[arg(long, default_value = "./build/app.vk")]
-        vk_path: String,
-        /// soldity files directory
-        #[arg(short, long, default_value = "./build/sols")]
-        sols_dir: String,
+        /// verifying key file [default: <build-dir>/app.vk]
+        #[arg(long)]
+        vk_path: Option<String>,
+        /// soldity files directory [default: <build-dir>/sols]
+        #[arg(short, long)]
+        sols_dir: Option<String>,
         /// the maximum bytes size of each output Solidity code.
         #[arg(short, long)]
         max_line_size_per_file: Option<usize>,
+        /// the version in the pragma line of the output Solidity codes [default: ^0.8.17]
+        #[arg(long)]
+        solidity_version: Option<String>,
+        /// emit a `Verified(bytes32 publicInputsHash)` event on successful verification
+        #[arg(long, default_value_t = false)]
+        emit_verified_event: bool,
+        /// re-indent the output Solidity codes so that they are diffable
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
     },
     EVMVerify {
         #[arg(short, long, default_value = "./configs/default_app.config")]
         circuit_config_path: String,
-        /// soldity files directory
-        #[arg(short, long, default_value = "./build/sols")]
-        sols_dir: String,
-        /// output proof file
-        #[arg(long, default_value = "./build/app_evm.proof")]
-        proof_path: String,
-        /// public input file
-        #[arg(long, default_value = "./build/public_input.json")]
-        public_input_path: String,
+        /// soldity files directory [default: <build-dir>/sols]
+        #[arg(short, long)]
+        sols_dir: Option<String>,
+        /// output proof file [default: <build-dir>/app_evm.proof]
+        #[arg(long)]
+        proof_path: Option<String>,
+        /// public input file [default: <build-dir>/public_input.json]
+        #[arg(long)]
+        public_input_path: Option<String>,
         #[arg(short, long)]
         gas_limit: Option<u64>,
     },
+    /// Extract the substrings that the circuit would expose from an email without generating any proof.
+    Extract {
+        /// email file path or http(s) URL
+        #[arg(default_value = "./examples/demo.eml")]
+        email_path: String,
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// how non-UTF8 bytes in the email are decoded to extract the substrings natively: strict or lossy [default: lossy]
+        #[arg(long)]
+        email_encoding: Option<String>,
+    },
+    /// Print the canonicalized header and body and the signature bytes that the circuit takes from an email.
+    Canonicalize {
+        /// email file path or http(s) URL
+        #[arg(default_value = "./examples/demo.eml")]
+        email_path: String,
+    },
+    /// List the DKIM signatures of an email with their algorithms, whose index is passed to `--dkim-signature`.
+    ListSignatures {
+        /// email file path or http(s) URL
+        #[arg(default_value = "./examples/demo.eml")]
+        email_path: String,
+    },
+    /// Generate a standalone Rust test that checks the circuit for the email with MockProver and asserts the extracted substrings.
+    GenTest {
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// email file path or http(s) URL
+        #[arg(short, long, default_value = "./examples/demo.eml")]
+        email_path: String,
+        /// name of the test function
+        #[arg(long, default_value = "test_email_verify")]
+        test_name: String,
+        /// output Rust file [default: <build-dir>/email_verify_test.rs]
+        #[arg(short, long)]
+        output_path: Option<String>,
+    },
+    /// Describe the instance layout and the revealed fields of the circuit as json.
+    DescribeCircuit {
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// output json file (printed to stdout if omitted)
+        #[arg(short, long)]
+        output_path: Option<String>,
+    },
+    /// Check the circuit configurations and their regex files before generating any key, and report all the problems found.
+    ValidateConfig {
+        /// email verification circuit configure file
+        #[arg(short, long, default_value = "./configs/default_app.config")]
+        circuit_config_path: String,
+        /// aggregation circuit configure file (not checked if omitted)
+        #[arg(long)]
+        agg_circuit_config_path: Option<String>,
+    },
     GenRegexFiles {
         #[arg(short, long, default_value = "./configs/decomposed_regex_config.json")]
         decomposed_regex_config_path: String,
-        #[arg(long, default_value = "./build")]
-        regex_dir_path: String,
+        /// regex files directory [default: <build-dir>]
+        #[arg(long)]
+        regex_dir_path: Option<String>,
         #[arg(short, long)]
         regex_files_prefix: String,
     },
+    /// Print the enabled features and the build configuration to be attached to bug reports.
+    Features {
+        /// print them as json
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    init_logger(cli.quiet, cli.verbose, cli.log_level);
+    if let Some(circuit_config_json) = cli.circuit_config_json.as_ref() {
+        set_var(EMAIL_VERIFY_CONFIG_JSON_ENV, circuit_config_json);
+    }
+    let dkim_options = DkimOptions {
+        signature_index: cli.dkim_signature,
+        audit_log_path: cli.dkim_audit_log.clone(),
+        require_dnssec: cli.require_dnssec,
+        dnssec_resolver_url: cli.dnssec_resolver.clone(),
+    };
+    let build_dir = BuildDir(cli.build_dir);
+    let no_clobber = cli.no_clobber && !cli.overwrite;
     match cli.command {
-        Commands::GenParams { k, params_path } => gen_params(&params_path, k).unwrap(),
+        Commands::GenParams {
+            k,
+            circuit_config_path,
+            params_path,
+            params_format,
+            compress,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            check_no_clobber_or_exit(no_clobber, &[&params_path]);
+            match (k, circuit_config_path) {
+                (Some(k), _) => gen_params(&params_path, k, params_format, compress).unwrap(),
+                (None, Some(circuit_config_path)) => {
+                    let k = gen_params_for_circuit(&params_path, &circuit_config_path, params_format, compress).unwrap();
+                    log::info!("params of k = {} generated for {}", k, circuit_config_path);
+                }
+                (None, None) => unreachable!("clap requires k or circuit_config_path"),
+            }
+        }
+        Commands::CheckParams { params_path } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            match check_params(&params_path) {
+                Ok(k) => log::info!("{} is valid: k = {}", params_path, k),
+                Err(err) => {
+                    log::error!("{} is invalid: {}", params_path, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::DownsizeAndVerify {
+            params_path,
+            new_params_path,
+            k,
+            params_format,
+            compress,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            check_no_clobber_or_exit(no_clobber, &[&new_params_path]);
+            match downsize_and_verify_params(&params_path, &new_params_path, k, params_format, compress) {
+                Ok(true) => log::info!("{} is valid: k = {}", new_params_path, k),
+                Ok(false) => {
+                    log::error!("{} is invalid: the proof of the trivial circuit does not verify", new_params_path);
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    log::error!("{} is unusable at k = {}: {}", new_params_path, k, err);
+                    std::process::exit(1);
+                }
+            }
+        }
         Commands::GenKeys {
             params_path,
             circuit_config_path,
             email_path,
             pk_path,
             vk_path,
+            verify_keys,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            check_no_clobber_or_exit(no_clobber, &[&pk_path, &vk_path]);
+            let circuit = gen_circuit_from_email_path(&email_path, false, &dkim_options).await;
+            gen_keys(&params_path, &circuit_config_path, &pk_path, &vk_path, circuit, verify_keys).expect("key generation failed");
+        }
+        Commands::CheckCompat {
+            params_path,
+            circuit_config_path,
+            email_path,
+            old_vk_path,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let old_vk_path = build_dir.path(old_vk_path, "app.vk");
+            let circuit = gen_circuit_from_email_path(&email_path, false, &dkim_options).await;
+            let compatibility = check_vk_compat(&params_path, &circuit_config_path, &old_vk_path, circuit).expect("compatibility check failed");
+            if compatibility.compatible {
+                println!("compatible");
+            } else {
+                println!("incompatible");
+                for diff in compatibility.diffs.iter() {
+                    println!("{}: {} -> {}", diff.item, diff.old, diff.new);
+                }
+                std::process::exit(1);
+            }
+        }
+        Commands::GenAggKeys {
+            app_params_path,
+            agg_params_path,
+            circuit_config_path,
+            agg_circuit_config_path,
+            email_pathes,
+            app_pk_path,
+            agg_pk_path,
+            agg_vk_path,
+            append,
+            acc_path,
+            verify_keys,
+        } => {
+            let app_params_path = build_dir.path(app_params_path, "app_params.bin");
+            let agg_params_path = build_dir.path(agg_params_path, "agg_params.bin");
+            let app_pk_path = build_dir.path(app_pk_path, "app.pk");
+            let agg_pk_path = build_dir.path(agg_pk_path, "agg.pk");
+            let agg_vk_path = build_dir.path(agg_vk_path, "agg.vk");
+            let acc_path = append.then(|| build_dir.path(acc_path, "agg_acc.snark"));
+            check_no_clobber_or_exit(no_clobber, &[&agg_pk_path, &agg_vk_path]);
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let app_circuits = gen_circuits_from_email_pathes(&email_pathes, &dkim_options).await;
+            gen_agg_key(
+                &app_params_path,
+                &agg_params_path,
+                &circuit_config_path,
+                &agg_circuit_config_path,
+                &app_pk_path,
+                &agg_pk_path,
+                &agg_vk_path,
+                acc_path.as_deref(),
+                app_circuits,
+                verify_keys,
+            )
+            .expect("aggregation key generation failed");
+        }
+        Commands::AggCost {
+            app_params_path,
+            circuit_config_path,
+            agg_circuit_config_path,
+            email_pathes,
+            app_pk_path,
+            arity,
+            json,
+        } => {
+            let app_params_path = build_dir.path(app_params_path, "app_params.bin");
+            let app_pk_path = build_dir.path(app_pk_path, "app.pk");
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let app_circuits = gen_circuits_from_email_pathes(&email_pathes, &dkim_options).await;
+            let cost = match estimate_agg_cost(&app_params_path, &circuit_config_path, &agg_circuit_config_path, &app_pk_path, app_circuits, arity) {
+                Ok(cost) => cost,
+                Err(err) => {
+                    log::error!("the aggregation circuit does not fit in any k up to {}: {}", MAX_PARAMS_K, err);
+                    std::process::exit(1);
+                }
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&cost).unwrap());
+            } else {
+                println!("aggregated proofs: {}", cost.num_snarks);
+                if cost.exceeded_rows > 0 {
+                    println!("rows used per advice column: more than {} and at most {} of {} columns", cost.exceeded_rows, cost.usable_rows, cost.num_advice);
+                } else {
+                    println!("rows used per advice column: at most {} of {} columns", cost.usable_rows, cost.num_advice);
+                }
+                println!("recommended k: {}", cost.k);
+            }
+        }
+        Commands::EVMProveAgg {
+            app_params_path,
+            agg_params_path,
+            circuit_config_path,
+            agg_circuit_config_path,
+            email_pathes,
+            app_pk_path,
+            agg_pk_path,
+            proof_path,
+            public_input_path,
+            append,
+            acc_path,
+            max_proofs,
+            fail_fast,
         } => {
-            let circuit = DefaultEmailVerifyCircuit::<Fr>::gen_circuit_from_email_path(&email_path).await;
-            gen_keys(&params_path, &circuit_config_path, &pk_path, &vk_path, circuit).expect("key generation failed");
+            if let Some(max_proofs) = max_proofs {
+                if email_pathes.len() > max_proofs {
+                    log::error!("{} emails are given, but at most {} proofs can be aggregated per run (--max-proofs)", email_pathes.len(), max_proofs);
+                    std::process::exit(1);
+                }
+            }
+            let app_params_path = build_dir.path(app_params_path, "app_params.bin");
+            let agg_params_path = build_dir.path(agg_params_path, "agg_params.bin");
+            let app_pk_path = build_dir.path(app_pk_path, "app.pk");
+            let agg_pk_path = build_dir.path(agg_pk_path, "agg.pk");
+            let proof_path = build_dir.path(proof_path, "agg_evm.proof");
+            let public_input_path = build_dir.path(public_input_path, "agg_public_input.json");
+            let acc_path = append.then(|| build_dir.path(acc_path, "agg_acc.snark"));
+            check_no_clobber_or_exit(no_clobber, &[&proof_path, &public_input_path]);
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let app_circuits = gen_circuits_from_email_pathes(&email_pathes, &dkim_options).await;
+            evm_prove_agg(
+                &app_params_path,
+                &agg_params_path,
+                &circuit_config_path,
+                &agg_circuit_config_path,
+                &app_pk_path,
+                &agg_pk_path,
+                &proof_path,
+                &public_input_path,
+                acc_path.as_deref(),
+                app_circuits,
+                fail_fast,
+            )
+            .unwrap();
         }
         Commands::Prove {
             params_path,
@@ -186,12 +897,139 @@ async fn main() {
             email_path,
             proof_path,
             public_input_path,
+            compress,
+            debug_witness,
+            stats,
+            allowed_domains,
+            use_embedded_key,
+            deterministic,
+            email_encoding,
+            output_public_input_format,
+            witness_path,
+            bundle,
+            bundle_path,
+            credential,
+            credential_path,
+            pinned_key_path,
+            arc,
+            block_number,
+            block_hash,
         } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let proof_path = build_dir.path(proof_path, "app.proof");
+            let public_input_path = build_dir.path(public_input_path, "public_input.json");
+            let bundle_path = bundle.then(|| build_dir.path(bundle_path, "app_bundle.json"));
+            let credential_path = credential.then(|| build_dir.path(credential_path, "app_credential.json"));
+            match (bundle_path.as_ref(), credential_path.as_ref()) {
+                (Some(bundle_path), _) => check_no_clobber_or_exit(no_clobber, &[bundle_path]),
+                (None, Some(credential_path)) => check_no_clobber_or_exit(no_clobber, &[credential_path]),
+                (None, None) => check_no_clobber_or_exit(no_clobber, &[&proof_path, &public_input_path]),
+            }
+            if witness_path.is_none() {
+                check_allowed_domain_or_exit(&email_path, &allowed_domains, &dkim_options, arc).await;
+            }
+            let encoding = email_encoding_or_exit(email_encoding);
+            let format = public_input_format_or_exit(output_public_input_format);
             set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
-            let circuit = DefaultEmailVerifyCircuit::<Fr>::gen_circuit_from_email_path(&email_path).await;
+            check_key_config_or_exit(&pk_path);
+            let circuit = match witness_path.as_ref() {
+                Some(witness_path) => circuit_from_witness_or_exit(witness_path),
+                None if arc => gen_arc_circuit_or_exit(&email_path, &dkim_options).await,
+                None => match pinned_key_path.as_ref() {
+                    Some(pinned_key_path) => gen_circuit_with_pinned_key_or_exit(&email_path, use_embedded_key, pinned_key_path, &dkim_options).await,
+                    None => gen_circuit_from_email_path(&email_path, use_embedded_key, &dkim_options).await,
+                },
+            };
+            check_email_encoding_or_exit(encoding, &circuit);
+            let circuit = bind_block_or_exit(circuit, block_number, block_hash);
             let public_input = circuit.gen_default_public_input();
-            prove(&params_path, &circuit_config_path, &pk_path, &proof_path, circuit).unwrap();
-            serde_json::to_writer_pretty(File::create(&public_input_path).unwrap(), &public_input).unwrap();
+            if let Some(debug_witness_dir) = debug_witness.as_ref() {
+                let is_satisfied = dump_debug_witness(&circuit_config_path, debug_witness_dir, &circuit).unwrap();
+                log::info!("constraints satisfied: {}", is_satisfied);
+            }
+            let circuit = if stats { circuit.with_stats() } else { circuit };
+            match (bundle_path, credential_path) {
+                (Some(bundle_path), _) => prove_bundle(&params_path, &circuit_config_path, &pk_path, &bundle_path, circuit, &public_input, deterministic).unwrap(),
+                (None, Some(credential_path)) => prove_credential(&params_path, &circuit_config_path, &pk_path, &credential_path, circuit, &public_input, deterministic).unwrap(),
+                (None, None) => {
+                    prove(&params_path, &circuit_config_path, &pk_path, &proof_path, circuit, compress, deterministic).unwrap();
+                    public_input.write_file_with_format(&public_input_path, format);
+                }
+            }
+        }
+        Commands::ProveMbox {
+            params_path,
+            circuit_config_path,
+            pk_path,
+            mbox_path,
+            output_dir,
+            compress,
+            use_embedded_key,
+            deterministic,
+            skip_unresolvable,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let output_dir = build_dir.path(output_dir, "mbox");
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let mbox_bytes = std::fs::read(&mbox_path).expect("fail to read the mbox file");
+            let messages = halo2_zk_email::utils::split_mbox(&mbox_bytes).into_iter().enumerate().collect::<Vec<_>>();
+            let batch_prover = BatchProver {
+                params_path,
+                circuit_config_path,
+                pk_path,
+                output_dir,
+                compress,
+                use_embedded_key,
+                dkim_options,
+                deterministic,
+                skip_unresolvable,
+                no_clobber,
+            };
+            batch_prover.prove_messages(messages).await;
+        }
+        Commands::ProveMaildir {
+            params_path,
+            circuit_config_path,
+            pk_path,
+            maildir_path,
+            output_dir,
+            compress,
+            use_embedded_key,
+            deterministic,
+            skip_unresolvable,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let output_dir = build_dir.path(output_dir, "maildir");
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let messages = halo2_zk_email::utils::read_maildir(Path::new(&maildir_path)).expect("fail to read the Maildir");
+            let batch_prover = BatchProver {
+                params_path,
+                circuit_config_path,
+                pk_path,
+                output_dir,
+                compress,
+                use_embedded_key,
+                dkim_options,
+                deterministic,
+                skip_unresolvable,
+                no_clobber,
+            };
+            batch_prover.prove_messages(messages).await;
+        }
+        Commands::Serve {
+            params_path,
+            circuit_config_path,
+            pk_path,
+            addr,
+            use_embedded_key,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let context = ProverContext::new::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &pk_path);
+            server::serve(&addr, context, use_embedded_key, dkim_options).await.unwrap();
         }
         Commands::EVMProve {
             params_path,
@@ -200,12 +1038,30 @@ async fn main() {
             email_path,
             proof_path,
             public_input_path,
+            allowed_domains,
+            use_embedded_key,
+            deterministic,
+            email_encoding,
+            output_public_input_format,
+            block_number,
+            block_hash,
         } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let proof_path = build_dir.path(proof_path, "app_evm.proof");
+            let public_input_path = build_dir.path(public_input_path, "public_input.json");
+            check_no_clobber_or_exit(no_clobber, &[&proof_path, &public_input_path]);
+            check_allowed_domain_or_exit(&email_path, &allowed_domains, &dkim_options, false).await;
+            let encoding = email_encoding_or_exit(email_encoding);
+            let format = public_input_format_or_exit(output_public_input_format);
             set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
-            let circuit = DefaultEmailVerifyCircuit::<Fr>::gen_circuit_from_email_path(&email_path).await;
+            check_key_config_or_exit(&pk_path);
+            let circuit = gen_circuit_from_email_path(&email_path, use_embedded_key, &dkim_options).await;
+            check_email_encoding_or_exit(encoding, &circuit);
+            let circuit = bind_block_or_exit(circuit, block_number, block_hash);
             let public_input = circuit.gen_default_public_input();
-            evm_prove(&params_path, &circuit_config_path, &pk_path, &proof_path, circuit).unwrap();
-            serde_json::to_writer_pretty(File::create(&public_input_path).unwrap(), &public_input).unwrap();
+            evm_prove(&params_path, &circuit_config_path, &pk_path, &proof_path, circuit, deterministic).unwrap();
+            public_input.write_file_with_format(&public_input_path, format);
         }
         Commands::Verify {
             params_path,
@@ -213,14 +1069,115 @@ async fn main() {
             vk_path,
             proof_path,
             public_input_path,
+            explain_email_path,
+            use_embedded_key,
+            expected_digest,
+            strict_instance_order,
         } => {
-            let result = verify::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &proof_path, &public_input_path).unwrap();
-            if result {
-                println!("proof is valid");
+            let params_path = build_dir.path(params_path, "params.bin");
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            let proof_path = build_dir.path(proof_path, "app.proof");
+            let public_input_path = build_dir.path(public_input_path, "public_input.json");
+            let result = match expected_digest {
+                Some(expected_digest) => {
+                    verify_with_digest::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &proof_path, &public_input_path, &expected_digest)
+                        .unwrap()
+                }
+                None if strict_instance_order => {
+                    verify_strict::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &proof_path, &public_input_path).unwrap()
+                }
+                None => verify::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &proof_path, &public_input_path).unwrap(),
+            };
+            print_verification_outcome(&result);
+            if let (false, Some(email_path)) = (result.valid, explain_email_path.as_ref()) {
+                let circuit = gen_circuit_from_email_path(email_path, use_embedded_key, &dkim_options).await;
+                let explanation = explain_verification_failure(&circuit_config_path, &circuit, &public_input_path).unwrap();
+                print_verification_failure_explanation(&explanation);
+            }
+        }
+        Commands::VerifyBundle {
+            params_path,
+            circuit_config_path,
+            vk_path,
+            bundle_path,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            let bundle_path = build_dir.path(bundle_path, "app_bundle.json");
+            let result = verify_bundle::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &bundle_path).unwrap();
+            print_verification_outcome(&result);
+        }
+        Commands::VerifyCredential {
+            params_path,
+            circuit_config_path,
+            vk_path,
+            credential_path,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            let credential_path = build_dir.path(credential_path, "app_credential.json");
+            let result = verify_credential::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &credential_path).unwrap();
+            print_verification_outcome(&result);
+        }
+        Commands::ProveAndVerify {
+            params_path,
+            circuit_config_path,
+            pk_path,
+            email_path,
+            use_embedded_key,
+            deterministic,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let circuit = gen_circuit_from_email_path(&email_path, use_embedded_key, &dkim_options).await;
+            if prove_and_verify(&params_path, &circuit_config_path, &pk_path, circuit, deterministic).unwrap() {
+                println!("verified");
             } else {
-                println!("proof is invalid");
+                println!("not verified");
+                std::process::exit(1);
             }
         }
+        Commands::BenchE2e {
+            params_path,
+            circuit_config_path,
+            pk_path,
+            email_path,
+            use_embedded_key,
+        } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let pk_path = build_dir.path(pk_path, "app.pk");
+            let mut latencies = vec![];
+            let start = Instant::now();
+            let context = ProverContext::new::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &pk_path);
+            latencies.push(("load params and pk", start.elapsed()));
+            let start = Instant::now();
+            let circuit = gen_circuit_from_email_path(&email_path, use_embedded_key, &dkim_options).await;
+            latencies.push(("build circuit", start.elapsed()));
+            let start = Instant::now();
+            let instances = circuit.instances();
+            latencies.push(("compute instances", start.elapsed()));
+            let start = Instant::now();
+            let proof = context.prove(circuit, false);
+            latencies.push(("prove", start.elapsed()));
+            let start = Instant::now();
+            let valid = context.verify(&proof, &instances).unwrap();
+            latencies.push(("verify", start.elapsed()));
+            for (stage, latency) in latencies.iter() {
+                println!("{:<20} {:>10.3} s", stage, latency.as_secs_f64());
+            }
+            let total = latencies.iter().map(|(_, latency)| *latency).sum::<Duration>();
+            println!("{:<20} {:>10.3} s", "total", total.as_secs_f64());
+            if !valid {
+                log::error!("the proof is not verified");
+                std::process::exit(1);
+            }
+        }
+        Commands::VkHash { circuit_config_path, vk_path } => {
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            let hash = vk_hash::<DefaultEmailVerifyCircuit<Fr>>(&circuit_config_path, &vk_path).unwrap();
+            println!("0x{}", hex::encode(hash));
+        }
         Commands::VerifyWasm {
             params_path,
             circuit_config_path,
@@ -228,12 +1185,12 @@ async fn main() {
             proof_hex_path,
             public_input_path,
         } => {
+            let params_path = build_dir.path(params_path, "params.bin");
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            let proof_hex_path = build_dir.path(proof_hex_path, "app_proof.hex");
+            let public_input_path = build_dir.path(public_input_path, "public_input.json");
             let result = verify_wasm::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &proof_hex_path, &public_input_path).unwrap();
-            if result {
-                println!("proof is valid");
-            } else {
-                println!("proof is invalid");
-            }
+            print_verification_outcome(&result);
         }
         Commands::GenEVMVerifier {
             params_path,
@@ -241,8 +1198,25 @@ async fn main() {
             vk_path,
             sols_dir,
             max_line_size_per_file,
+            solidity_version,
+            emit_verified_event,
+            pretty,
         } => {
-            gen_evm_verifier::<DefaultEmailVerifyCircuit<Fr>>(&params_path, &circuit_config_path, &vk_path, &sols_dir, max_line_size_per_file).unwrap();
+            let params_path = build_dir.path(params_path, "params.bin");
+            let vk_path = build_dir.path(vk_path, "app.vk");
+            let sols_dir = build_dir.path(sols_dir, "sols");
+            check_no_clobber_or_exit(no_clobber, &[PathBuf::from(&sols_dir).join("EmailVerifier.sol")]);
+            gen_evm_verifier::<DefaultEmailVerifyCircuit<Fr>>(
+                &params_path,
+                &circuit_config_path,
+                &vk_path,
+                &sols_dir,
+                max_line_size_per_file,
+                solidity_version.as_deref(),
+                emit_verified_event,
+                pretty,
+            )
+            .unwrap();
         }
         Commands::EVMVerify {
             circuit_config_path,
@@ -251,13 +1225,408 @@ async fn main() {
             public_input_path,
             gas_limit,
         } => {
+            let sols_dir = build_dir.path(sols_dir, "sols");
+            let proof_path = build_dir.path(proof_path, "app_evm.proof");
+            let public_input_path = build_dir.path(public_input_path, "public_input.json");
             evm_verify(&circuit_config_path, &sols_dir, &proof_path, &public_input_path, gas_limit).await.unwrap();
         }
         Commands::GenRegexFiles {
             decomposed_regex_config_path,
             regex_dir_path,
             regex_files_prefix,
-        } => gen_regex_files(&decomposed_regex_config_path, &regex_dir_path, &regex_files_prefix).unwrap(),
+        } => {
+            let regex_dir_path = build_dir.path(regex_dir_path, "");
+            gen_regex_files(&decomposed_regex_config_path, &regex_dir_path, &regex_files_prefix).unwrap();
+        }
+        Commands::Extract {
+            email_path,
+            circuit_config_path,
+            email_encoding,
+        } => {
+            let encoding = email_encoding_or_exit(email_encoding);
+            let email_bytes = read_email_bytes(&email_path).await.expect("fail to read the email");
+            let (header_substrs, body_substrs) = extract_named_substrs(&circuit_config_path, &email_bytes, encoding, &dkim_options).expect("extraction failed");
+            let print_substrs = |part: &str, substrs: &[(String, Option<(usize, String)>)]| {
+                for (idx, (name, substr)) in substrs.iter().enumerate() {
+                    match substr {
+                        Some((start, substr)) => println!("{}[{}] {} (start {}): {}", part, idx, name, start, substr),
+                        None => println!("{}[{}] {}: not found", part, idx, name),
+                    }
+                }
+            };
+            print_substrs("header", &header_substrs);
+            print_substrs("body", &body_substrs);
+        }
+        Commands::Canonicalize { email_path } => {
+            let email_bytes = read_email_bytes(&email_path).await.expect("fail to read the email");
+            let email_bytes = dkim_options.select_signature(email_bytes).unwrap_or_else(|err| panic!("{}", err));
+            let (header_bytes, body_bytes, signature_bytes) = canonicalize_signed_email_with_body_length(&email_bytes).expect("fail to canonicalize the email");
+            // CR and LF are escaped so that the line endings fed to the circuit are visible.
+            println!("header ({} bytes):\n{}\n", header_bytes.len(), header_bytes.escape_ascii());
+            println!("body ({} bytes):\n{}\n", body_bytes.len(), body_bytes.escape_ascii());
+            println!("signature ({} bytes): {}", signature_bytes.len(), hex::encode(&signature_bytes));
+        }
+        Commands::ListSignatures { email_path } => {
+            let email_bytes = read_email_bytes(&email_path).await.expect("fail to read the email");
+            for (idx, summary) in list_dkim_signatures(&email_bytes).iter().enumerate() {
+                let support = if summary.is_supported() { "supported" } else { "unsupported" };
+                println!("[{}] a={} d={} s={} ({})", idx, summary.algorithm, summary.domain, summary.selector, support);
+            }
+        }
+        Commands::GenTest {
+            circuit_config_path,
+            email_path,
+            test_name,
+            output_path,
+        } => {
+            let output_path = build_dir.path(output_path, "email_verify_test.rs");
+            check_no_clobber_or_exit(no_clobber, &[&output_path]);
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let circuit = gen_circuit_from_email_path(&email_path, false, &dkim_options).await;
+            std::fs::write(&output_path, gen_mock_prover_test(&circuit_config_path, &test_name, &circuit)).unwrap();
+            log::info!("the test {} is written to {}", test_name, output_path);
+        }
+        Commands::DescribeCircuit { circuit_config_path, output_path } => {
+            set_var(EMAIL_VERIFY_CONFIG_ENV, &circuit_config_path);
+            let description = CircuitDescription::new(&default_config_params());
+            let description_json = serde_json::to_string_pretty(&description).unwrap();
+            match output_path {
+                Some(output_path) => {
+                    check_no_clobber_or_exit(no_clobber, &[&output_path]);
+                    std::fs::write(output_path, description_json).unwrap()
+                }
+                None => println!("{}", description_json),
+            }
+        }
+        Commands::ValidateConfig {
+            circuit_config_path,
+            agg_circuit_config_path,
+        } => {
+            let problems = validate_config(&circuit_config_path, agg_circuit_config_path.as_deref());
+            if problems.is_empty() {
+                log::info!("{} is valid", circuit_config_path);
+            } else {
+                for problem in problems.iter() {
+                    log::error!("{}", problem);
+                }
+                log::error!("{} problem(s) found", problems.len());
+                std::process::exit(1);
+            }
+        }
+        Commands::Features { json } => {
+            let info = BuildInfo::new();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info).unwrap());
+            } else {
+                println!("{}", info);
+            }
+        }
+    }
+}
+
+/// Directory from which the default file pathes of the commands are derived.
+#[cfg(not(target_arch = "wasm32"))]
+struct BuildDir(String);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BuildDir {
+    /// Return the given path if it is specified, otherwise `file_name` under the build directory.
+    fn path(&self, path: Option<String>, file_name: &str) -> String {
+        path.unwrap_or_else(|| {
+            if file_name.is_empty() {
+                self.0.clone()
+            } else {
+                PathBuf::from(&self.0).join(file_name).to_string_lossy().to_string()
+            }
+        })
+    }
+}
+
+/// Options to prove the messages of an mbox archive or a Maildir one by one.
+#[cfg(not(target_arch = "wasm32"))]
+struct BatchProver {
+    params_path: String,
+    circuit_config_path: String,
+    pk_path: String,
+    output_dir: String,
+    compress: bool,
+    use_embedded_key: bool,
+    dkim_options: DkimOptions,
+    deterministic: bool,
+    skip_unresolvable: bool,
+    no_clobber: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BatchProver {
+    /// Prove each message and write `<label>.proof` and `<label>_public_input.json` in the output directory.
+    async fn prove_messages<L: std::fmt::Display + std::fmt::Debug>(&self, messages: Vec<(L, Vec<u8>)>) {
+        std::fs::create_dir_all(&self.output_dir).unwrap();
+        let mut proven = 0;
+        let mut skipped = vec![];
+        for (label, email_bytes) in messages.into_iter() {
+            let generated =
+                DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(email_bytes, self.use_embedded_key, &[], &self.dkim_options).await;
+            let circuit = match generated {
+                Ok((circuit, _)) => circuit,
+                Err(err @ DkimError::UnresolvablePublicKey(_)) if self.skip_unresolvable => {
+                    log::info!("message {} skipped: {}", label, err);
+                    skipped.push(label);
+                    continue;
+                }
+                Err(err) => panic!("{}", err),
+            };
+            let public_input = circuit.gen_default_public_input();
+            let proof_path = format!("{}/{}.proof", self.output_dir, label);
+            let public_input_path = format!("{}/{}_public_input.json", self.output_dir, label);
+            check_no_clobber_or_exit(self.no_clobber, &[&proof_path, &public_input_path]);
+            prove(
+                &self.params_path,
+                &self.circuit_config_path,
+                &self.pk_path,
+                &proof_path,
+                circuit,
+                self.compress,
+                self.deterministic,
+            )
+            .unwrap();
+            public_input.write_file(&public_input_path);
+            log::info!("message {} proven: {}", label, proof_path);
+            proven += 1;
+        }
+        if self.skip_unresolvable {
+            log::info!("{} messages proven, {} skipped {:?}", proven, skipped.len(), skipped);
+        }
+    }
+}
+
+/// Exit with an error if `no_clobber` is set and any of the output files already exists.
+/// It is called before any expensive computation so that an existing result is neither lost nor recomputed.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_no_clobber_or_exit<P: AsRef<Path>>(no_clobber: bool, output_paths: &[P]) {
+    if !no_clobber {
+        return;
+    }
+    for output_path in output_paths {
+        if output_path.as_ref().exists() {
+            log::error!("{} already exists; remove it or pass --overwrite", output_path.as_ref().display());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Initialize the logger used by all commands with the level given by `--quiet`, `--verbose`, or `--log-level`.
+/// Errors and warnings are written to stderr with their levels, and the other messages are written to stdout as they are.
+/// The messages of the dependencies are limited to warnings.
+#[cfg(not(target_arch = "wasm32"))]
+fn init_logger(quiet: bool, verbose: bool, log_level: Option<LevelFilter>) {
+    let level = match log_level {
+        Some(level) => level,
+        None if quiet => LevelFilter::Error,
+        None if verbose => LevelFilter::Debug,
+        None => LevelFilter::Info,
+    };
+    env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Warn))
+        .filter_module("halo2_zk_email", level)
+        .filter_module("zkemail", level)
+        .target(env_logger::Target::Stdout)
+        .format(|buf, record| {
+            if record.level() <= Level::Warn {
+                eprintln!("{}: {}", record.level().as_str().to_lowercase(), record.args());
+                Ok(())
+            } else {
+                writeln!(buf, "{}", record.args())
+            }
+        })
+        .init();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn print_verification_outcome(outcome: &VerificationOutcome) {
+    if outcome.valid {
+        println!("proof is valid");
+        for (name, value) in outcome.public_fields.iter() {
+            println!("{}: {}", name, String::from_utf8_lossy(value));
+        }
+    } else {
+        println!("proof is invalid");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn print_verification_failure_explanation(explanation: &VerificationFailureExplanation) {
+    for mismatch in explanation.instance_mismatches.iter() {
+        println!(
+            "instance {} (column {}, row {}) mismatched: the public input has {:?}, but the email gives {:?}",
+            mismatch.name, mismatch.column, mismatch.row, mismatch.given, mismatch.expected
+        );
+    }
+    for failure in explanation.failures.iter() {
+        println!("{}", failure);
+    }
+    if explanation.instance_mismatches.is_empty() && explanation.failures.is_empty() {
+        println!("the email and the public input are consistent, so the proof or the verifying key is wrong");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn gen_circuit_from_email_path(email_path: &str, use_embedded_key: bool, dkim_options: &DkimOptions) -> DefaultEmailVerifyCircuit<Fr> {
+    let email_bytes = read_email_bytes(email_path).await.expect("fail to read the email");
+    DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(email_bytes, use_embedded_key, &[], dkim_options)
+        .await
+        .unwrap_or_else(|err| panic!("{}", err))
+        .0
+}
+
+/// Generate the circuit for the outermost ARC message signature given by `--arc`, or exit the process if the ARC chain is invalid.
+#[cfg(not(target_arch = "wasm32"))]
+async fn gen_arc_circuit_or_exit(email_path: &str, dkim_options: &DkimOptions) -> DefaultEmailVerifyCircuit<Fr> {
+    let email_bytes = read_email_bytes(email_path).await.expect("fail to read the email");
+    match DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_arc_email_bytes(email_bytes, dkim_options).await {
+        Ok(circuit) => circuit,
+        Err(err) => {
+            log::error!("Refuse to prove {} with its ARC chain: {}", email_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Generate the circuit with the pinned key given by `--pinned-key-path` as the fallback of the DNS key, reporting which key verified the signature, or exit the process.
+#[cfg(not(target_arch = "wasm32"))]
+async fn gen_circuit_with_pinned_key_or_exit(email_path: &str, use_embedded_key: bool, pinned_key_path: &str, dkim_options: &DkimOptions) -> DefaultEmailVerifyCircuit<Fr> {
+    let record = std::fs::read_to_string(pinned_key_path).expect("fail to read the pinned key");
+    let pinned_key_n = match parse_pinned_public_key(record.trim()) {
+        Ok(pinned_key_n) => pinned_key_n,
+        Err(err) => {
+            log::error!("Invalid pinned key {}: {}", pinned_key_path, err);
+            std::process::exit(1);
+        }
+    };
+    let email_bytes = read_email_bytes(email_path).await.expect("fail to read the email");
+    match DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(email_bytes, use_embedded_key, &[pinned_key_n], dkim_options).await {
+        Ok((circuit, source)) => {
+            if source == PublicKeySource::Pinned {
+                log::info!(
+                    "note: the DKIM signature does not verify with the key resolved from DNS, but with the pinned key {} (fingerprint {})",
+                    pinned_key_path,
+                    public_key_fingerprint(&circuit.public_key_n)
+                );
+            } else {
+                log::info!("the DKIM signature verifies with {}", source);
+            }
+            circuit
+        }
+        Err(err) => {
+            log::error!("Neither the key resolved from DNS nor the pinned key {} verifies the email: {}", pinned_key_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn gen_circuits_from_email_pathes(email_pathes: &[String], dkim_options: &DkimOptions) -> Vec<DefaultEmailVerifyCircuit<Fr>> {
+    let mut circuits = vec![];
+    for email_path in email_pathes.iter() {
+        circuits.push(gen_circuit_from_email_path(email_path, false, dkim_options).await);
+    }
+    circuits
+}
+
+/// Return the [`EmailEncoding`] given by `--email-encoding`, which defaults to [`EmailEncoding::Lossy`], exiting the process if it is unknown.
+#[cfg(not(target_arch = "wasm32"))]
+fn email_encoding_or_exit(email_encoding: Option<String>) -> EmailEncoding {
+    match email_encoding {
+        Some(email_encoding) => EmailEncoding::from_name(&email_encoding).unwrap_or_else(|| {
+            log::error!("Unknown email encoding {}, expected strict or lossy", email_encoding);
+            std::process::exit(1);
+        }),
+        None => EmailEncoding::Lossy,
+    }
+}
+
+/// Exit the process if the canonicalized email of the circuit cannot be decoded with the encoding, i.e., it is not valid UTF-8 with `--email-encoding strict`.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_email_encoding_or_exit(encoding: EmailEncoding, circuit: &DefaultEmailVerifyCircuit<Fr>) {
+    let (header_bytes, body_bytes, _) = circuit.canonicalized_email();
+    for (part, bytes) in [("header", header_bytes), ("body", body_bytes)] {
+        if let Err(offset) = encoding.check(&bytes) {
+            log::error!("The email {} is not valid UTF-8 at byte {}, which is accepted only with the lossy email encoding", part, offset);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Construct the email verification circuit from the witness json given by `--witness-path`, exiting the process if it is invalid.
+#[cfg(not(target_arch = "wasm32"))]
+fn circuit_from_witness_or_exit(witness_path: &str) -> DefaultEmailVerifyCircuit<Fr> {
+    match EmailWitnessJson::read_file(witness_path).and_then(|witness| witness.to_circuit()) {
+        Ok(circuit) => circuit,
+        Err(err) => {
+            log::error!("Invalid witness {}: {}", witness_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Exit the process if the proving key was generated for another circuit configuration than the current one.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_key_config_or_exit(pk_path: &str) {
+    if let Err(err) = check_key_config(pk_path) {
+        log::error!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Return the [`PublicInputFormat`] given by `--output-public-input-format`, exiting the process if it is unknown.
+#[cfg(not(target_arch = "wasm32"))]
+fn public_input_format_or_exit(format: Option<String>) -> PublicInputFormat {
+    match format {
+        Some(format) => PublicInputFormat::from_name(&format).unwrap_or_else(|| {
+            log::error!("Unknown public input format {}, expected decimal, hex, or le-bytes", format);
+            std::process::exit(1);
+        }),
+        None => PublicInputFormat::Decimal,
+    }
+}
+
+/// Bind the circuit to the block given by `--block-number` and `--block-hash`, exiting the process if the block hash is malformed
+/// or the block is missing while `bind_block_hash` is set in the configuration.
+#[cfg(not(target_arch = "wasm32"))]
+fn bind_block_or_exit(circuit: DefaultEmailVerifyCircuit<Fr>, block_number: Option<u64>, block_hash: Option<String>) -> DefaultEmailVerifyCircuit<Fr> {
+    let bind_block_hash = default_config_params().bind_block_hash.unwrap_or(false);
+    match (block_number, block_hash) {
+        (Some(number), Some(hash)) => {
+            if !bind_block_hash {
+                log::warn!("the block is ignored because bind_block_hash is not set in the configuration");
+                return circuit;
+            }
+            let hash = BoundBlock::parse_hash(&hash).unwrap_or_else(|| {
+                log::error!("The block hash {} is not a hex string of 32 bytes", hash);
+                std::process::exit(1);
+            });
+            circuit.with_bound_block(BoundBlock { number, hash })
+        }
+        _ if bind_block_hash => {
+            log::error!("bind_block_hash is set in the configuration, so --block-number and --block-hash are required");
+            std::process::exit(1);
+        }
+        _ => circuit,
+    }
+}
+
+/// Exit the process if the signing domain of the signature to be proven is not in the allowed domains.
+/// It is the DKIM signature selected by `--dkim-signature`, or the outermost ARC message signature with `--arc`.
+/// Nothing is checked if `allowed_domains` is empty.
+#[cfg(not(target_arch = "wasm32"))]
+async fn check_allowed_domain_or_exit(email_path: &str, allowed_domains: &[String], dkim_options: &DkimOptions, arc: bool) {
+    if allowed_domains.is_empty() {
+        return;
+    }
+    let email_bytes = read_email_bytes(email_path).await.expect("fail to read the email");
+    if let Err(err) = halo2_zk_email::dkim::check_allowed_domain(&email_bytes, allowed_domains, dkim_options, arc) {
+        log::error!("Refuse to prove {}: {}", email_path, err);
+        std::process::exit(1);
     }
 }
 
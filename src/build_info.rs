@@ -0,0 +1,96 @@
+/// The elliptic curve over which the proofs, the keys, and the EVM verifiers are generated.
+pub const CURVE: &'static str = "bn254";
+
+/// The halo2 backend that `halo2-base` and `halo2-ecc` are built with.
+pub const HALO2_BACKEND: &'static str = "halo2-pse";
+
+/// The DKIM signature algorithms that the circuit verifies.
+pub const SIGNATURE_ALGORITHMS: [&'static str; 1] = ["rsa-sha256"];
+
+/// The build configuration of this crate, which is printed by `zkemail features` to be attached to bug reports.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The version of this crate.
+    pub version: String,
+    /// The enabled features.
+    /// This crate declares no cargo features, so they are determined by the target: `wasm` for `wasm32` and `native` otherwise.
+    pub features: Vec<String>,
+    /// `debug` if the debug assertions are enabled, and `release` otherwise.
+    pub profile: String,
+    /// The target architecture, e.g., `x86_64`.
+    pub target_arch: String,
+    /// The target operating system, e.g., `linux`.
+    pub target_os: String,
+    /// The rust toolchain used for the build, or `unknown` if it was not built via rustup.
+    pub toolchain: String,
+    /// See [`CURVE`].
+    pub curve: String,
+    /// See [`HALO2_BACKEND`].
+    pub halo2_backend: String,
+    /// See [`SIGNATURE_ALGORITHMS`].
+    pub signature_algorithms: Vec<String>,
+    /// The schemes available for `commitment_scheme` in the circuit configuration.
+    pub commitment_schemes: Vec<String>,
+}
+
+impl BuildInfo {
+    /// Collect the build configuration of the running binary.
+    pub fn new() -> Self {
+        let mut features = vec![];
+        if cfg!(target_arch = "wasm32") {
+            features.push("wasm".to_string());
+        } else {
+            features.push("native".to_string());
+        }
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features,
+            profile: if cfg!(debug_assertions) { "debug" } else { "release" }.to_string(),
+            target_arch: std::env::consts::ARCH.to_string(),
+            target_os: std::env::consts::OS.to_string(),
+            toolchain: option_env!("RUSTUP_TOOLCHAIN").unwrap_or("unknown").to_string(),
+            curve: CURVE.to_string(),
+            halo2_backend: HALO2_BACKEND.to_string(),
+            signature_algorithms: SIGNATURE_ALGORITHMS.iter().map(|algorithm| algorithm.to_string()).collect(),
+            commitment_schemes: vec!["poseidon".to_string(), "keccak".to_string()],
+        }
+    }
+}
+
+impl Default for BuildInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "halo2-zk-email {}", self.version)?;
+        writeln!(f, "features: {}", self.features.join(", "))?;
+        writeln!(f, "profile: {}", self.profile)?;
+        writeln!(f, "target: {}-{}", self.target_arch, self.target_os)?;
+        writeln!(f, "toolchain: {}", self.toolchain)?;
+        writeln!(f, "curve: {}", self.curve)?;
+        writeln!(f, "halo2 backend: {}", self.halo2_backend)?;
+        writeln!(f, "signature algorithms: {}", self.signature_algorithms.join(", "))?;
+        write!(f, "commitment schemes: {}", self.commitment_schemes.join(", "))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = BuildInfo::new();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.features, vec!["native".to_string()]);
+        assert_eq!(info.curve, "bn254");
+        let printed = info.to_string();
+        assert!(printed.starts_with(&format!("halo2-zk-email {}\nfeatures: native\n", info.version)));
+        let info_json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<BuildInfo>(&info_json).unwrap(), info);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::*;
+use thiserror::Error;
+
+/// An error of [`EmailCircuitBuilder::build`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EmailCircuitBuilderError {
+    /// A required input of the circuit is not set.
+    #[error("the {0} is not set")]
+    Missing(&'static str),
+    /// Both the raw email and its canonicalized parts are set.
+    #[error("both the raw email and its canonicalized {0} are set")]
+    Conflict(&'static str),
+    /// The raw email cannot be canonicalized.
+    #[error("failed to canonicalize the email: {0}")]
+    Canonicalize(String),
+    /// The canonicalized header or body does not fit in the circuit.
+    #[error("the canonicalized {part} of {size} bytes needs {padded_size} bytes with the SHA256 padding, which exceeds max_variable_byte_size {max_size} of {part}_config")]
+    TooLarge {
+        part: &'static str,
+        size: usize,
+        padded_size: usize,
+        max_size: usize,
+    },
+    /// The signature is longer than the RSA modulus.
+    #[error("the signature of {size} bytes exceeds public_key_bits {public_key_bits} of sign_verify_config")]
+    SignatureTooLarge { size: usize, public_key_bits: usize },
+    /// The RSA modulus does not have the configured size.
+    #[error("the public key of {bits} bits does not match public_key_bits {public_key_bits} of sign_verify_config")]
+    PublicKeySize { bits: u64, public_key_bits: usize },
+    /// The substring is not defined in the configuration.
+    #[error("the {part} substring {name} is not defined in {part}_config")]
+    UnknownSubstring { part: String, name: String },
+    /// The substring is defined but not found in the email.
+    #[error("the {part} substring {name} is not found in the email")]
+    SubstringNotFound { part: String, name: String },
+    /// The substring contains bytes that are not valid UTF-8, which the lossy email encoding replaced, so it cannot be proven.
+    #[error("the {part} substring {name} at byte {start} contains bytes that are not valid UTF-8")]
+    InvalidUtf8 { part: String, name: String, start: usize },
+}
+
+/// A substring that must be extracted from the email, which is named as [`EmailVerifyConfigParams::extractable_fields`](crate::config_params::EmailVerifyConfigParams::extractable_fields).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubstringDef {
+    /// `header` or `body`.
+    pub part: String,
+    /// The field name, e.g., `from` for `./test_data/from_allstr.txt`.
+    pub name: String,
+}
+
+impl SubstringDef {
+    /// Define a substring of the email header.
+    pub fn header(name: &str) -> Self {
+        Self {
+            part: "header".to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Define a substring of the email body.
+    pub fn body(name: &str) -> Self {
+        Self {
+            part: "body".to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+/// Builder of [`DefaultEmailVerifyCircuit`] that checks the inputs against the configuration given by [`default_config_params`] before producing the circuit.
+///
+/// Set either the raw email with [`EmailCircuitBuilder::email`] or its canonicalized parts with [`EmailCircuitBuilder::header`], [`EmailCircuitBuilder::body`], and [`EmailCircuitBuilder::signature`].
+/// The public key is always required.
+#[derive(Debug, Clone, Default)]
+pub struct EmailCircuitBuilder {
+    email_bytes: Option<Vec<u8>>,
+    header_bytes: Option<Vec<u8>>,
+    body_bytes: Option<Vec<u8>>,
+    signature_bytes: Option<Vec<u8>>,
+    public_key_n: Option<BigUint>,
+    substrings: Vec<SubstringDef>,
+}
+
+impl EmailCircuitBuilder {
+    /// Create an empty [`EmailCircuitBuilder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the raw email bytes, which are canonicalized as specified in their DKIM signature.
+    pub fn email(mut self, email_bytes: Vec<u8>) -> Self {
+        self.email_bytes = Some(email_bytes);
+        self
+    }
+
+    /// Set the canonicalized email header bytes, which end with the DKIM-Signature header without its `b=` value.
+    pub fn header(mut self, header_bytes: Vec<u8>) -> Self {
+        self.header_bytes = Some(header_bytes);
+        self
+    }
+
+    /// Set the canonicalized email body bytes.
+    pub fn body(mut self, body_bytes: Vec<u8>) -> Self {
+        self.body_bytes = Some(body_bytes);
+        self
+    }
+
+    /// Set the RSA signature bytes.
+    pub fn signature(mut self, signature_bytes: Vec<u8>) -> Self {
+        self.signature_bytes = Some(signature_bytes);
+        self
+    }
+
+    /// Set `n` parameter of the RSA public key.
+    pub fn public_key(mut self, public_key_n: BigUint) -> Self {
+        self.public_key_n = Some(public_key_n);
+        self
+    }
+
+    /// Require that the given substring is defined in the configuration and found in the email.
+    pub fn add_substring(mut self, def: SubstringDef) -> Self {
+        self.substrings.push(def);
+        self
+    }
+
+    /// Check the inputs and build a [`DefaultEmailVerifyCircuit`].
+    ///
+    /// # Return values
+    /// Return the circuit, or the first [`EmailCircuitBuilderError`] found in the order of completeness, sizes, and substrings.
+    pub fn build<F: PrimeField>(self) -> Result<DefaultEmailVerifyCircuit<F>, EmailCircuitBuilderError> {
+        let public_key_n = self.public_key_n.ok_or(EmailCircuitBuilderError::Missing("public key"))?;
+        let (header_bytes, body_bytes, signature_bytes) = match self.email_bytes.as_ref() {
+            Some(email_bytes) => {
+                for (name, part) in [("header", &self.header_bytes), ("body", &self.body_bytes), ("signature", &self.signature_bytes)] {
+                    if part.is_some() {
+                        return Err(EmailCircuitBuilderError::Conflict(name));
+                    }
+                }
+                dkim::canonicalize_signed_email_with_body_length(email_bytes).map_err(|err| EmailCircuitBuilderError::Canonicalize(err.to_string()))?
+            }
+            None => (
+                self.header_bytes.ok_or(EmailCircuitBuilderError::Missing("header"))?,
+                self.body_bytes.ok_or(EmailCircuitBuilderError::Missing("body"))?,
+                self.signature_bytes.ok_or(EmailCircuitBuilderError::Missing("signature"))?,
+            ),
+        };
+
+        let config_params = default_config_params();
+        let header_params = config_params.header_config.as_ref().expect("header_config is required");
+        let body_params = config_params.body_config.as_ref().expect("body_config is required");
+        let sign_verify_params = config_params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+        for (part, bytes, max_size) in [
+            ("header", &header_bytes, header_params.max_variable_byte_size),
+            ("body", &body_bytes, body_params.max_variable_byte_size),
+        ] {
+            let padded_size = sha256_padded_size(bytes.len());
+            if padded_size > max_size {
+                return Err(EmailCircuitBuilderError::TooLarge {
+                    part,
+                    size: bytes.len(),
+                    padded_size,
+                    max_size,
+                });
+            }
+        }
+        if public_key_n.bits() != sign_verify_params.public_key_bits as u64 {
+            return Err(EmailCircuitBuilderError::PublicKeySize {
+                bits: public_key_n.bits(),
+                public_key_bits: sign_verify_params.public_key_bits,
+            });
+        }
+        if signature_bytes.len() * 8 > sign_verify_params.public_key_bits {
+            return Err(EmailCircuitBuilderError::SignatureTooLarge {
+                size: signature_bytes.len(),
+                public_key_bits: sign_verify_params.public_key_bits,
+            });
+        }
+
+        let searched_header_bytes = &header_bytes[header_params.skip_prefix_bytes_size.unwrap_or(0)..];
+        let searched_body_bytes = &body_bytes[body_params.skip_prefix_bytes_size.unwrap_or(0)..];
+        let header_str = EmailEncoding::Lossy.decode(searched_header_bytes);
+        let body_str = body_params.searched_str(&body_bytes);
+        let (header_substrs, body_substrs) = get_email_substrs(&header_str, &body_str, header_params.substr_regexes.clone(), body_params.substr_regexes.clone());
+        let header_names = substr_field_names(&header_params.allstr_filepathes, &header_params.substr_filepathes);
+        let body_names = substr_field_names(&body_params.allstr_filepathes, &body_params.substr_filepathes);
+        // The lossy encoding replaces the bytes of invalid UTF-8 sequences with NUL, which the circuit would compare with the original bytes.
+        for (part, bytes, names, substrs) in [("header", searched_header_bytes, &header_names, &header_substrs), ("body", searched_body_bytes, &body_names, &body_substrs)] {
+            for (name, (start, substr)) in names.iter().zip(substrs.iter()).filter_map(|(name, substr)| Some((name, substr.as_ref()?))) {
+                if bytes[*start..*start + substr.len()] != *substr.as_bytes() {
+                    return Err(EmailCircuitBuilderError::InvalidUtf8 {
+                        part: part.to_string(),
+                        name: name.clone(),
+                        start: *start,
+                    });
+                }
+            }
+        }
+
+        for def in self.substrings.iter() {
+            let (names, substrs) = if def.part == "header" { (&header_names, &header_substrs) } else { (&body_names, &body_substrs) };
+            let idx = names.iter().position(|name| *name == def.name).ok_or_else(|| EmailCircuitBuilderError::UnknownSubstring {
+                part: def.part.clone(),
+                name: def.name.clone(),
+            })?;
+            if !matches!(substrs.get(idx), Some(Some(_))) {
+                return Err(EmailCircuitBuilderError::SubstringNotFound {
+                    part: def.part.clone(),
+                    name: def.name.clone(),
+                });
+            }
+        }
+
+        Ok(match self.email_bytes {
+            Some(email_bytes) => DefaultEmailVerifyCircuit::new(email_bytes, public_key_n),
+            None => DefaultEmailVerifyCircuit::from_parts(header_bytes, body_bytes, signature_bytes, public_key_n),
+        })
+    }
+}
+
+/// Return the byte size of the input padded for SHA256, i.e., with `0x80`, zeros, and the 8-byte length, rounded up to a multiple of 64.
+fn sha256_padded_size(size: usize) -> usize {
+    (size + 9 + 63) / 64 * 64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+    use num_traits::One;
+
+    fn read_test_email() -> Vec<u8> {
+        std::fs::read("./test_data/test_email1.eml").unwrap()
+    }
+
+    fn dummy_public_key_n() -> BigUint {
+        let public_key_bits = default_config_params().sign_verify_config.unwrap().public_key_bits;
+        BigUint::one() << (public_key_bits - 1)
+    }
+
+    #[test]
+    fn test_builder_from_parts() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_ex1_email_verify.config"), || {
+            let (header_bytes, body_bytes, signature_bytes) = canonicalize_signed_email(&read_test_email()).unwrap();
+            let circuit = EmailCircuitBuilder::new()
+                .header(header_bytes.clone())
+                .body(body_bytes.clone())
+                .signature(signature_bytes.clone())
+                .public_key(dummy_public_key_n())
+                .add_substring(SubstringDef::header("from"))
+                .build::<Fr>()
+                .unwrap();
+            assert_eq!(circuit.canonicalized_email(), (header_bytes, body_bytes, signature_bytes));
+        });
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_ex1_email_verify.config"), || {
+            let (header_bytes, body_bytes, _) = canonicalize_signed_email(&read_test_email()).unwrap();
+            let partial = EmailCircuitBuilder::new().header(header_bytes.clone()).body(body_bytes).public_key(dummy_public_key_n());
+            assert_eq!(partial.clone().build::<Fr>().unwrap_err(), EmailCircuitBuilderError::Missing("signature"));
+            assert_eq!(
+                partial.clone().email(read_test_email()).build::<Fr>().unwrap_err(),
+                EmailCircuitBuilderError::Conflict("header")
+            );
+            let too_large = partial.clone().header(vec![b'a'; 1024]).signature(vec![1]);
+            assert_eq!(
+                too_large.build::<Fr>().unwrap_err(),
+                EmailCircuitBuilderError::TooLarge {
+                    part: "header",
+                    size: 1024,
+                    padded_size: 1088,
+                    max_size: 1024,
+                }
+            );
+            // A revealed substring whose bytes are not valid UTF-8 is rejected instead of making the circuit unsatisfiable.
+            let from_idx = header_bytes.windows(5).position(|window| window == b"from:").unwrap();
+            let mut invalid_header_bytes = header_bytes.clone();
+            invalid_header_bytes[from_idx + 5] = 0xff;
+            assert_eq!(
+                partial.clone().header(invalid_header_bytes).signature(vec![1]).build::<Fr>().unwrap_err(),
+                EmailCircuitBuilderError::InvalidUtf8 {
+                    part: "header".to_string(),
+                    name: "from".to_string(),
+                    start: from_idx + 5,
+                }
+            );
+            let unknown = partial.signature(vec![1]).add_substring(SubstringDef::header("reply_to"));
+            assert_eq!(
+                unknown.build::<Fr>().unwrap_err(),
+                EmailCircuitBuilderError::UnknownSubstring {
+                    part: "header".to_string(),
+                    name: "reply_to".to_string(),
+                }
+            );
+        });
+    }
+}
@@ -87,6 +87,400 @@ impl<F: PrimeField> CharsShiftConfig<F> {
         (assigned_substr.to_vec(), is_target_vec)
     }
 
+    /// Extract a substring of the target substring id, in which the characters of the other substrings are masked to zero.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `assigned_masked_chars` - a list of the assigned masked characters.
+    /// * `assigned_substr_ids` - a list of the assigned substring ids.
+    /// # Return values
+    /// Return the assigned substring characters padded with zero up to `max_substr_size`.
+    /// It asserts that the substring has at most `max_substr_size` characters instead of truncating it.
+    pub fn extract<'a, 'b: 'a>(
+        &self,
+        ctx: &mut Context<'b, F>,
+        gate: &FlexGateConfig<F>,
+        assigned_masked_chars: &[AssignedValue<'a, F>],
+        assigned_substr_ids: &[AssignedValue<'a, F>],
+    ) -> Vec<AssignedValue<'a, F>> {
+        let mut target_chars = vec![];
+        for (assigned_char, assigned_substr_id) in assigned_masked_chars.iter().zip(assigned_substr_ids.iter()) {
+            let is_target = gate.is_equal(ctx, QuantumCell::Existing(assigned_substr_id), QuantumCell::Constant(F::from(self.target_substr_id)));
+            target_chars.push(gate.mul(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Existing(&is_target)));
+        }
+        // Shift one more character to check that the substring does not overflow `max_substr_size`.
+        let has_overflow_char = self.max_substr_size < self.max_chars_size;
+        let shift_config = if has_overflow_char {
+            Self::configure(self.max_chars_size, self.max_substr_size + 1, self.target_substr_id)
+        } else {
+            self.clone()
+        };
+        let (mut assigned_substr, _) = shift_config.shift(ctx, gate, &target_chars, assigned_substr_ids);
+        if has_overflow_char {
+            let overflow_char = assigned_substr.pop().expect("the shifted characters are not empty");
+            gate.assert_equal(ctx, QuantumCell::Existing(&overflow_char), QuantumCell::Constant(F::zero()));
+        }
+        assigned_substr
+    }
+
+    /// Remove the given characters, e.g., folding whitespace, from the input characters and pack the remaining ones to the front.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `assigned_chars` - a list of the assigned characters.
+    /// * `removed_chars` - the distinct characters to be removed.
+    /// * `max_output_size` - the number of the output characters.
+    /// # Return values
+    /// Return the first `max_output_size` remaining characters, padded with zero if there are fewer of them.
+    pub fn remove_chars<'a, 'b: 'a>(
+        ctx: &mut Context<'b, F>,
+        gate: &FlexGateConfig<F>,
+        assigned_chars: &[AssignedValue<'a, F>],
+        removed_chars: &[u8],
+        max_output_size: usize,
+    ) -> Vec<AssignedValue<'a, F>> {
+        // The output position of each remaining character is the number of the remaining characters before it.
+        let mut position = gate.load_zero(ctx);
+        let mut kept_chars = vec![];
+        for assigned_char in assigned_chars.iter() {
+            let mut is_removed = gate.load_zero(ctx);
+            for removed_char in removed_chars.iter() {
+                let is_equal = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(*removed_char as u64)));
+                is_removed = gate.add(ctx, QuantumCell::Existing(&is_removed), QuantumCell::Existing(&is_equal));
+            }
+            let is_kept = gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_removed));
+            let kept_char = gate.mul(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Existing(&is_kept));
+            kept_chars.push((position.clone(), kept_char, is_kept.clone()));
+            position = gate.add(ctx, QuantumCell::Existing(&position), QuantumCell::Existing(&is_kept));
+        }
+        (0..max_output_size)
+            .map(|out_idx| {
+                let mut output = gate.load_zero(ctx);
+                for (position, kept_char, is_kept) in kept_chars.iter() {
+                    let is_position = gate.is_equal(ctx, QuantumCell::Existing(position), QuantumCell::Constant(F::from(out_idx as u64)));
+                    let is_selected = gate.mul(ctx, QuantumCell::Existing(&is_position), QuantumCell::Existing(is_kept));
+                    output = gate.mul_add(ctx, QuantumCell::Existing(kept_char), QuantumCell::Existing(&is_selected), QuantumCell::Existing(&output));
+                }
+                output
+            })
+            .collect()
+    }
+
+    /// Mask the characters from the end of a DKIM tag value to zero, e.g., for the window of the `bh=` value extracted by [`CharsShiftConfig::shift`].
+    /// The value ends at `;`, at `\r\n` not followed by whitespace, i.e., the end of the header field, or at zero, i.e., the padding after the end of the header.
+    /// It asserts that the end is in the input characters, so that the value does not continue beyond them.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `assigned_chars` - a list of the assigned characters starting at the tag value.
+    /// # Return values
+    /// Return the characters of the tag value followed by zero.
+    pub fn mask_from_tag_value_end<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+        let mut is_ended = gate.load_zero(ctx);
+        let mut masked_chars = vec![];
+        for (idx, assigned_char) in assigned_chars.iter().enumerate() {
+            // The terminators are distinct characters, so at most one of these flags is one.
+            let is_semicolon = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(b';' as u64)));
+            let is_zero = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::zero()));
+            let mut is_end = gate.add(ctx, QuantumCell::Existing(&is_semicolon), QuantumCell::Existing(&is_zero));
+            // A line break is the end of the header field unless the next line starts with whitespace, i.e., it is folded.
+            if idx + 2 < assigned_chars.len() {
+                let is_cr = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(b'\r' as u64)));
+                let is_lf = gate.is_equal(ctx, QuantumCell::Existing(&assigned_chars[idx + 1]), QuantumCell::Constant(F::from(b'\n' as u64)));
+                let is_space = gate.is_equal(ctx, QuantumCell::Existing(&assigned_chars[idx + 2]), QuantumCell::Constant(F::from(b' ' as u64)));
+                let is_tab = gate.is_equal(ctx, QuantumCell::Existing(&assigned_chars[idx + 2]), QuantumCell::Constant(F::from(b'\t' as u64)));
+                let is_folded = gate.add(ctx, QuantumCell::Existing(&is_space), QuantumCell::Existing(&is_tab));
+                let is_not_folded = gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_folded));
+                let is_crlf = gate.mul(ctx, QuantumCell::Existing(&is_cr), QuantumCell::Existing(&is_lf));
+                let is_line_end = gate.mul(ctx, QuantumCell::Existing(&is_crlf), QuantumCell::Existing(&is_not_folded));
+                is_end = gate.add(ctx, QuantumCell::Existing(&is_end), QuantumCell::Existing(&is_line_end));
+            }
+            is_ended = gate.select(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_ended), QuantumCell::Existing(&is_end));
+            masked_chars.push(gate.select(ctx, QuantumCell::Constant(F::zero()), QuantumCell::Existing(assigned_char), QuantumCell::Existing(&is_ended)));
+        }
+        gate.assert_equal(ctx, QuantumCell::Existing(&is_ended), QuantumCell::Constant(F::one()));
+        masked_chars
+    }
+
+    /// Assert that a domain is aligned with the DKIM `d=` domain as in DMARC, i.e., the domain is equal to the DKIM domain or, unless `strict` is true, its subdomain.
+    /// Both domains are compared byte by byte, so they must have the same letter case.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `domain` - the assigned characters of the domain padded with zero, e.g., the `Return-Path` domain extracted by [`CharsShiftConfig::extract`].
+    /// * `dkim_domain` - the assigned characters of the DKIM domain padded with zero, which must not be empty.
+    /// * `strict` - a flag whether the domain must be equal to the DKIM domain.
+    pub fn assert_domain_aligned<'a, 'b: 'a>(
+        ctx: &mut Context<'b, F>,
+        gate: &FlexGateConfig<F>,
+        domain: &[AssignedValue<'a, F>],
+        dkim_domain: &[AssignedValue<'a, F>],
+        strict: bool,
+    ) {
+        let domain_len = Self::count_chars(ctx, gate, domain);
+        let dkim_domain_len = Self::count_chars(ctx, gate, dkim_domain);
+        let is_dkim_domain_empty = gate.is_equal(ctx, QuantumCell::Existing(&dkim_domain_len), QuantumCell::Constant(F::zero()));
+        gate.assert_equal(ctx, QuantumCell::Existing(&is_dkim_domain_empty), QuantumCell::Constant(F::zero()));
+        // The number of the domain characters before the DKIM domain. If the domain is shorter, it wraps around and no character is selected below.
+        let prefix_len = gate.sub(ctx, QuantumCell::Existing(&domain_len), QuantumCell::Existing(&dkim_domain_len));
+        if strict {
+            gate.assert_equal(ctx, QuantumCell::Existing(&prefix_len), QuantumCell::Constant(F::zero()));
+        }
+        for (idx, dkim_char) in dkim_domain.iter().enumerate() {
+            let position = gate.add(ctx, QuantumCell::Existing(&prefix_len), QuantumCell::Constant(F::from(idx as u64)));
+            let domain_char = Self::select_char(ctx, gate, domain, &position);
+            // The padding zeros of the DKIM domain are not compared.
+            let diff = gate.sub(ctx, QuantumCell::Existing(&domain_char), QuantumCell::Existing(dkim_char));
+            let masked_diff = gate.mul(ctx, QuantumCell::Existing(&diff), QuantumCell::Existing(dkim_char));
+            gate.assert_equal(ctx, QuantumCell::Existing(&masked_diff), QuantumCell::Constant(F::zero()));
+        }
+        // A subdomain must have `.` just before the DKIM domain, e.g., `mail.example.com` is aligned with `example.com` but `badexample.com` is not.
+        let dot_position = gate.sub(ctx, QuantumCell::Existing(&prefix_len), QuantumCell::Constant(F::one()));
+        let dot_char = Self::select_char(ctx, gate, domain, &dot_position);
+        let dot_diff = gate.sub(ctx, QuantumCell::Existing(&dot_char), QuantumCell::Constant(F::from(b'.' as u64)));
+        let is_equal_domain = gate.is_equal(ctx, QuantumCell::Existing(&prefix_len), QuantumCell::Constant(F::zero()));
+        let is_subdomain = gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_equal_domain));
+        let masked_dot_diff = gate.mul(ctx, QuantumCell::Existing(&dot_diff), QuantumCell::Existing(&is_subdomain));
+        gate.assert_equal(ctx, QuantumCell::Existing(&masked_dot_diff), QuantumCell::Constant(F::zero()));
+    }
+
+    /// Parse the decimal digits, e.g., the DKIM `t=` timestamp extracted by [`CharsShiftConfig::extract`], to an integer.
+    /// It asserts that every character is an ASCII digit or the padding zero.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `digits` - the assigned characters of the decimal digits padded with zero.
+    /// # Return values
+    /// Return the assigned integer, which must be less than the field modulus.
+    pub fn parse_decimal<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, digits: &[AssignedValue<'a, F>]) -> AssignedValue<'a, F> {
+        let mut value = gate.load_zero(ctx);
+        for assigned_char in digits.iter() {
+            // The product of the differences from the padding zero and the ASCII digits is zero iff the character is one of them.
+            let mut product = assigned_char.clone();
+            for digit in b'0'..=b'9' {
+                let diff = gate.sub(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(digit as u64)));
+                product = gate.mul(ctx, QuantumCell::Existing(&product), QuantumCell::Existing(&diff));
+            }
+            gate.assert_equal(ctx, QuantumCell::Existing(&product), QuantumCell::Constant(F::zero()));
+            let is_padding = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::zero()));
+            let digit = gate.sub(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(b'0' as u64)));
+            let shifted = gate.mul_add(ctx, QuantumCell::Existing(&value), QuantumCell::Constant(F::from(10)), QuantumCell::Existing(&digit));
+            value = gate.select(ctx, QuantumCell::Existing(&value), QuantumCell::Existing(&shifted), QuantumCell::Existing(&is_padding));
+        }
+        value
+    }
+
+    /// Split a list of items separated by `separator`, e.g., the comma-separated addresses in the `To` field, and pack each item to the front.
+    /// The `removed_chars` and the padding zeros are removed from the items.
+    /// It asserts that the list has at most `max_items` items and that each item has at most `max_item_size` characters.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `list` - the assigned characters of the list padded with zero, e.g., the one extracted by [`CharsShiftConfig::extract`].
+    /// * `separator` - the separator character.
+    /// * `removed_chars` - the distinct characters to be removed, which must not include the separator or zero.
+    /// * `max_items` - the number of the output items.
+    /// * `max_item_size` - the number of the characters in each output item.
+    /// # Return values
+    /// Return `max_items` items padded with zero, where the items missing in the list are all zeros.
+    pub fn split_list<'a, 'b: 'a>(
+        ctx: &mut Context<'b, F>,
+        gate: &FlexGateConfig<F>,
+        list: &[AssignedValue<'a, F>],
+        separator: u8,
+        removed_chars: &[u8],
+        max_items: usize,
+        max_item_size: usize,
+    ) -> Vec<Vec<AssignedValue<'a, F>>> {
+        // The item index of each character is the number of the separators before it, and its position restarts from zero after each separator.
+        let mut item_idx = gate.load_zero(ctx);
+        let mut position = gate.load_zero(ctx);
+        let mut kept_chars = vec![];
+        for assigned_char in list.iter() {
+            let is_separator = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(separator as u64)));
+            let is_padding = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::zero()));
+            let mut is_removed = gate.add(ctx, QuantumCell::Existing(&is_separator), QuantumCell::Existing(&is_padding));
+            for removed_char in removed_chars.iter() {
+                let is_equal = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(*removed_char as u64)));
+                is_removed = gate.add(ctx, QuantumCell::Existing(&is_removed), QuantumCell::Existing(&is_equal));
+            }
+            let is_kept = gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_removed));
+            let kept_char = gate.mul(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Existing(&is_kept));
+            kept_chars.push((item_idx.clone(), position.clone(), kept_char));
+            let next_position = gate.add(ctx, QuantumCell::Existing(&position), QuantumCell::Existing(&is_kept));
+            position = gate.select(ctx, QuantumCell::Constant(F::zero()), QuantumCell::Existing(&next_position), QuantumCell::Existing(&is_separator));
+            item_idx = gate.add(ctx, QuantumCell::Existing(&item_idx), QuantumCell::Existing(&is_separator));
+        }
+        let mut is_bounded = gate.load_zero(ctx);
+        for num_separators in 0..max_items {
+            let is_equal = gate.is_equal(ctx, QuantumCell::Existing(&item_idx), QuantumCell::Constant(F::from(num_separators as u64)));
+            is_bounded = gate.add(ctx, QuantumCell::Existing(&is_bounded), QuantumCell::Existing(&is_equal));
+        }
+        gate.assert_equal(ctx, QuantumCell::Existing(&is_bounded), QuantumCell::Constant(F::one()));
+        (0..max_items)
+            .map(|item| {
+                let is_items = kept_chars
+                    .iter()
+                    .map(|(item_idx, _, _)| gate.is_equal(ctx, QuantumCell::Existing(item_idx), QuantumCell::Constant(F::from(item as u64))))
+                    .collect::<Vec<_>>();
+                // Select one more character to check that the item does not overflow `max_item_size`.
+                let mut item_chars = (0..=max_item_size)
+                    .map(|out_idx| {
+                        let mut output = gate.load_zero(ctx);
+                        for ((_, position, kept_char), is_item) in kept_chars.iter().zip(is_items.iter()) {
+                            let is_position = gate.is_equal(ctx, QuantumCell::Existing(position), QuantumCell::Constant(F::from(out_idx as u64)));
+                            let is_selected = gate.mul(ctx, QuantumCell::Existing(&is_position), QuantumCell::Existing(is_item));
+                            output = gate.mul_add(ctx, QuantumCell::Existing(kept_char), QuantumCell::Existing(&is_selected), QuantumCell::Existing(&output));
+                        }
+                        output
+                    })
+                    .collect::<Vec<_>>();
+                let overflow_char = item_chars.pop().expect("the item characters are not empty");
+                gate.assert_equal(ctx, QuantumCell::Existing(&overflow_char), QuantumCell::Constant(F::zero()));
+                item_chars
+            })
+            .collect()
+    }
+
+    /// Convert the ASCII uppercase letters to lowercase, e.g., to commit a domain extracted by [`CharsShiftConfig::extract`] regardless of its case.
+    /// The other characters, including the padding zero, are unchanged.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `chars` - the assigned characters.
+    /// # Return values
+    /// Return the assigned lowercase characters.
+    pub fn to_ascii_lowercase<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, chars: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+        chars
+            .iter()
+            .map(|assigned_char| {
+                let mut is_upper = gate.load_zero(ctx);
+                for letter in b'A'..=b'Z' {
+                    let is_letter = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(letter as u64)));
+                    is_upper = gate.add(ctx, QuantumCell::Existing(&is_upper), QuantumCell::Existing(&is_letter));
+                }
+                gate.mul_add(ctx, QuantumCell::Existing(&is_upper), QuantumCell::Constant(F::from(32)), QuantumCell::Existing(assigned_char))
+            })
+            .collect()
+    }
+
+    /// Decode the hex digits in either case, e.g., a token extracted by [`CharsShiftConfig::extract`], to bytes.
+    /// It asserts that every character is a hex digit or the padding zero and that the padding does not split a byte.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `digits` - the assigned characters of the hex digits padded with zero, whose length must be even.
+    /// # Return values
+    /// Return the assigned bytes padded with zero, whose length is half of that of `digits`.
+    pub fn decode_hex<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, digits: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+        assert_eq!(digits.len() % 2, 0, "the number of the hex digits must be even");
+        let symbols = b"0123456789abcdefABCDEF";
+        let nibble = |idx: usize| if idx < 16 { idx as u64 } else { idx as u64 - 6 };
+        digits
+            .chunks(2)
+            .map(|pair| {
+                let (hi_indicators, hi_padding) = Self::char_indicators(ctx, gate, &pair[0], symbols);
+                let (lo_indicators, lo_padding) = Self::char_indicators(ctx, gate, &pair[1], symbols);
+                gate.assert_equal(ctx, QuantumCell::Existing(&hi_padding), QuantumCell::Existing(&lo_padding));
+                let hi = Self::weighted_sum(ctx, gate, &hi_indicators, nibble);
+                let lo = Self::weighted_sum(ctx, gate, &lo_indicators, nibble);
+                gate.mul_add(ctx, QuantumCell::Existing(&hi), QuantumCell::Constant(F::from(16)), QuantumCell::Existing(&lo))
+            })
+            .collect()
+    }
+
+    /// Decode the base64 characters of the standard alphabet with `=` padding, e.g., a token extracted by [`CharsShiftConfig::extract`], to bytes.
+    /// It asserts that every character is in the alphabet, `=`, or the padding zero and that the padding zero does not split a group of four characters.
+    /// The bytes encoded by `=` are decoded to zero.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `chars` - the assigned base64 characters padded with zero, whose length must be a multiple of 4.
+    /// # Return values
+    /// Return the assigned bytes padded with zero, three for every four characters.
+    pub fn decode_base64<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, chars: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+        assert_eq!(chars.len() % 4, 0, "the number of the base64 characters must be a multiple of 4");
+        let symbols = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+        // `=` at the index 64 encodes zero bits.
+        let sextet = |idx: usize| if idx < 64 { idx as u64 } else { 0 };
+        let mut bytes = vec![];
+        for group in chars.chunks(4) {
+            let decoded = group.iter().map(|assigned_char| Self::char_indicators(ctx, gate, assigned_char, symbols)).collect::<Vec<_>>();
+            for (_, is_padding) in decoded[1..].iter() {
+                gate.assert_equal(ctx, QuantumCell::Existing(&decoded[0].1), QuantumCell::Existing(is_padding));
+            }
+            // The three bytes are `v0 << 2 | v1 >> 4`, `(v1 & 15) << 4 | v2 >> 2`, and `(v2 & 3) << 6 | v3` for the sextets `v0`, `v1`, `v2`, and `v3`.
+            let v0 = Self::weighted_sum(ctx, gate, &decoded[0].0, sextet);
+            let v1_hi = Self::weighted_sum(ctx, gate, &decoded[1].0, |idx| sextet(idx) >> 4);
+            let v1_lo = Self::weighted_sum(ctx, gate, &decoded[1].0, |idx| sextet(idx) & 15);
+            let v2_hi = Self::weighted_sum(ctx, gate, &decoded[2].0, |idx| sextet(idx) >> 2);
+            let v2_lo = Self::weighted_sum(ctx, gate, &decoded[2].0, |idx| sextet(idx) & 3);
+            let v3 = Self::weighted_sum(ctx, gate, &decoded[3].0, sextet);
+            bytes.push(gate.mul_add(ctx, QuantumCell::Existing(&v0), QuantumCell::Constant(F::from(4)), QuantumCell::Existing(&v1_hi)));
+            bytes.push(gate.mul_add(ctx, QuantumCell::Existing(&v1_lo), QuantumCell::Constant(F::from(16)), QuantumCell::Existing(&v2_hi)));
+            bytes.push(gate.mul_add(ctx, QuantumCell::Existing(&v2_lo), QuantumCell::Constant(F::from(64)), QuantumCell::Existing(&v3)));
+        }
+        bytes
+    }
+
+    // Return the indicators of whether the character equals each of `symbols`, and whether it is the padding zero.
+    // It asserts that the character is one of `symbols` or zero.
+    fn char_indicators<'a, 'b: 'a>(
+        ctx: &mut Context<'b, F>,
+        gate: &FlexGateConfig<F>,
+        assigned_char: &AssignedValue<'a, F>,
+        symbols: &[u8],
+    ) -> (Vec<AssignedValue<'a, F>>, AssignedValue<'a, F>) {
+        let is_padding = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::zero()));
+        let mut is_valid = is_padding.clone();
+        let mut indicators = vec![];
+        for symbol in symbols.iter() {
+            let is_equal = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(*symbol as u64)));
+            is_valid = gate.add(ctx, QuantumCell::Existing(&is_valid), QuantumCell::Existing(&is_equal));
+            indicators.push(is_equal);
+        }
+        gate.assert_equal(ctx, QuantumCell::Existing(&is_valid), QuantumCell::Constant(F::one()));
+        (indicators, is_padding)
+    }
+
+    // Return the sum of `weight(idx)` over the indices of the indicators equal to one.
+    fn weighted_sum<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, indicators: &[AssignedValue<'a, F>], weight: impl Fn(usize) -> u64) -> AssignedValue<'a, F> {
+        let mut sum = gate.load_zero(ctx);
+        for (idx, indicator) in indicators.iter().enumerate() {
+            sum = gate.mul_add(ctx, QuantumCell::Existing(indicator), QuantumCell::Constant(F::from(weight(idx))), QuantumCell::Existing(&sum));
+        }
+        sum
+    }
+
+    fn count_chars<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, chars: &[AssignedValue<'a, F>]) -> AssignedValue<'a, F> {
+        let mut len = gate.load_zero(ctx);
+        for assigned_char in chars.iter() {
+            let is_zero = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::zero()));
+            let is_char = gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_zero));
+            len = gate.add(ctx, QuantumCell::Existing(&len), QuantumCell::Existing(&is_char));
+        }
+        len
+    }
+
+    // Return the character at `position`, or zero if `position` is out of the characters.
+    fn select_char<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, chars: &[AssignedValue<'a, F>], position: &AssignedValue<'a, F>) -> AssignedValue<'a, F> {
+        let mut output = gate.load_zero(ctx);
+        for (idx, assigned_char) in chars.iter().enumerate() {
+            let is_position = gate.is_equal(ctx, QuantumCell::Existing(position), QuantumCell::Constant(F::from(idx as u64)));
+            output = gate.mul_add(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Existing(&is_position), QuantumCell::Existing(&output));
+        }
+        output
+    }
+
     fn shift_variable<'a, 'b: 'a>(
         &self,
         ctx: &mut Context<'b, F>,
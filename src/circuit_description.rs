@@ -0,0 +1,231 @@
+use crate::config_params::{EmailVerifyConfigParams, NumericPredicate};
+use crate::utils::instance_position;
+
+/// The names of the instances of the email verification circuit in the flattened order.
+pub const INSTANCE_NAMES: [&'static str; 3] = ["sign_commit", "public_key_hash", "substrs_commit"];
+
+/// The name of the instance of the DKIM `t=` timestamp, which follows [`INSTANCE_NAMES`] if `header_config.dkim_timestamp` is set.
+pub const DKIM_TIMESTAMP_INSTANCE_NAME: &'static str = "dkim_timestamp";
+
+/// The name prefix of the instances of the thresholds of `body_config.numeric_predicates`, which follow the DKIM timestamp, e.g., `predicate_threshold_0`.
+pub const PREDICATE_THRESHOLD_INSTANCE_PREFIX: &'static str = "predicate_threshold_";
+
+/// The names of the instances of the upper and lower 128 bits of the bound block hash, which are the last instances if `bind_block_hash` is set.
+pub const BLOCK_HASH_INSTANCE_NAMES: [&'static str; 2] = ["block_hash_hi", "block_hash_lo"];
+
+/// The semantics of an instance slot of the email verification circuit returned by [`EmailVerifyConfigParams::instance_semantics`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InstanceKind {
+    /// A hash that commits to a private value, i.e., `sign_commit` and `public_key_hash`.
+    Commitment,
+    /// The RLC of the masked characters and the substring ids, i.e., `substrs_commit`, which binds the revealed bytes and the commitments of the hidden fields in the public input.
+    RevealedBytes,
+    /// An integer given in the clear, i.e., the DKIM timestamp and the predicate thresholds.
+    Numeric,
+    /// A half of the hash of the block bound to the proof, which the verifier contract compares with the actual block hash.
+    Binding,
+}
+
+/// Description of an instance of the email verification circuit.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InstanceDescription {
+    /// The name of the instance.
+    pub name: String,
+    /// The index of the instance column.
+    pub column: usize,
+    /// The row index in the instance column.
+    pub row: usize,
+    /// `commitment` if the instance commits to the revealed fields or the signature, or `integer` if it is an integer given in the clear, e.g., the DKIM timestamp.
+    pub kind: String,
+    /// What the instance commits to.
+    pub description: String,
+}
+
+/// Description of a substring revealed in the public input.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescription {
+    /// The field name, which is the same as that of [`DefaultEmailVerifyPublicInput::public_fields`](crate::DefaultEmailVerifyPublicInput::public_fields).
+    pub name: String,
+    /// `header` or `body`.
+    pub part: String,
+    /// The substring id in the part, which starts from 1.
+    pub substr_id: usize,
+    /// The maximum byte size of the field, i.e., `max_variable_byte_size` of the part.
+    pub max_size: usize,
+    /// `revealed`, `partially_revealed` if only a byte range of the field is revealed by `body_config.reveal_ranges`,
+    /// `address_commitments` if only the commitments of its addresses are given by `header_config.recipients`,
+    /// `commitment` if only its commitment is given by `header_config.committed_substrs`,
+    /// `set_membership` if it is only asserted to be one of the values in `header_config.membership_sets`,
+    /// or `numeric_predicate` if it is only compared with the thresholds in `body_config.numeric_predicates`.
+    /// The (revealed) field value is in the public input and bound to the proof by `committed_in`.
+    pub kind: String,
+    /// The name of the instance that commits to the field.
+    pub committed_in: String,
+}
+
+/// Self-description of the email verification circuit derived from its configuration, which verifier tooling can use to interpret proofs.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CircuitDescription {
+    /// The degree `k` of the circuit.
+    pub degree: u32,
+    /// The number of the instance columns.
+    pub num_instance_columns: usize,
+    /// The total number of the instances.
+    pub num_instances: usize,
+    /// The instances in the flattened order.
+    pub instances: Vec<InstanceDescription>,
+    /// The maximum byte size of the email header.
+    pub max_header_bytes: usize,
+    /// The maximum byte size of the email body.
+    pub max_body_bytes: usize,
+    /// The revealed header fields followed by the revealed body fields.
+    pub fields: Vec<FieldDescription>,
+}
+
+impl CircuitDescription {
+    /// Describe the email verification circuit of the given configuration.
+    ///
+    /// # Arguments
+    /// * `params` - the configuration parameters of the circuit.
+    /// # Return values
+    /// Return a new [`CircuitDescription`].
+    pub fn new(params: &EmailVerifyConfigParams) -> Self {
+        let header_params = params.header_config.as_ref().expect("header_config is required");
+        let body_params = params.body_config.as_ref().expect("body_config is required");
+        let num_instance_columns = params.num_instance_columns.unwrap_or(1);
+        let descriptions = [
+            "Poseidon hash of the RSA signature limbs, also used as the randomness of substrs_commit",
+            "Poseidon hash of the RSA public key limbs, to be compared with the hash of the key registered in DNS",
+            "RLC of the masked characters and the substring ids of the header and the body, which binds the revealed fields",
+        ];
+        let mut instances = INSTANCE_NAMES
+            .iter()
+            .zip(descriptions.iter())
+            .enumerate()
+            .map(|(idx, (name, description))| {
+                let (column, row) = instance_position(idx, num_instance_columns);
+                InstanceDescription {
+                    name: name.to_string(),
+                    column,
+                    row,
+                    kind: "commitment".to_string(),
+                    description: description.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+        if header_params.dkim_timestamp.is_some() {
+            let (column, row) = instance_position(instances.len(), num_instance_columns);
+            instances.push(InstanceDescription {
+                name: DKIM_TIMESTAMP_INSTANCE_NAME.to_string(),
+                column,
+                row,
+                kind: "integer".to_string(),
+                description: "the t= timestamp of the DKIM signature parsed from its digits, e.g., to enforce a freshness window".to_string(),
+            });
+        }
+        let mut num_thresholds = 0;
+        for predicate in body_params.numeric_predicates.iter().flatten() {
+            let threshold_names: &[&str] = match predicate.predicate {
+                NumericPredicate::Range => &["minimum", "maximum"],
+                NumericPredicate::Gt | NumericPredicate::Lt | NumericPredicate::Eq => &["threshold"],
+            };
+            for threshold_name in threshold_names.iter() {
+                let (column, row) = instance_position(instances.len(), num_instance_columns);
+                instances.push(InstanceDescription {
+                    name: format!("{}{}", PREDICATE_THRESHOLD_INSTANCE_PREFIX, num_thresholds),
+                    column,
+                    row,
+                    kind: "integer".to_string(),
+                    description: format!("the {} of the {:?} predicate on the body substring {}", threshold_name, predicate.predicate, predicate.substr_id),
+                });
+                num_thresholds += 1;
+            }
+        }
+        if params.bind_block_hash.unwrap_or(false) {
+            for (name, half) in BLOCK_HASH_INSTANCE_NAMES.iter().zip(["upper", "lower"]) {
+                let (column, row) = instance_position(instances.len(), num_instance_columns);
+                instances.push(InstanceDescription {
+                    name: name.to_string(),
+                    column,
+                    row,
+                    kind: "integer".to_string(),
+                    description: format!("the {} 128 bits of the hash of the block bound to the proof, e.g., to check that the proof is recent", half),
+                });
+            }
+        }
+        Self {
+            degree: params.degree,
+            num_instance_columns,
+            num_instances: instances.len(),
+            instances,
+            max_header_bytes: header_params.max_variable_byte_size,
+            max_body_bytes: body_params.max_variable_byte_size,
+            fields: params.extractable_fields(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config_params::{default_config_params, EMAIL_VERIFY_CONFIG_ENV};
+
+    #[test]
+    fn test_instance_semantics() {
+        use InstanceKind::*;
+        // The committed header field and the partially revealed body field are both bound by substrs_commit.
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/kyc_domain_last4.config"), || {
+            let mut params = default_config_params().clone();
+            assert_eq!(params.instance_semantics(), vec![Commitment, Commitment, RevealedBytes]);
+            params.bind_block_hash = Some(true);
+            assert_eq!(params.instance_semantics(), vec![Commitment, Commitment, RevealedBytes, Binding, Binding]);
+        });
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_numeric_predicate_email_verify.config"), || {
+            let mut params = default_config_params().clone();
+            assert_eq!(params.instance_semantics(), vec![Commitment, Commitment, RevealedBytes, Numeric]);
+            params.body_config.as_mut().unwrap().numeric_predicates.as_mut().unwrap()[0].predicate = NumericPredicate::Range;
+            assert_eq!(params.instance_semantics(), vec![Commitment, Commitment, RevealedBytes, Numeric, Numeric]);
+            assert_eq!(params.instance_semantics().len(), CircuitDescription::new(&params).num_instances);
+        });
+    }
+
+    #[test]
+    fn test_describe_two_header_fields_and_one_body_field() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_reply_to_email_verify.config"), || {
+            let description = CircuitDescription::new(&default_config_params());
+            assert_eq!(description.num_instance_columns, 1);
+            assert_eq!(description.num_instances, 3);
+            assert_eq!(
+                description.instances.iter().map(|instance| (instance.name.as_str(), instance.column, instance.row)).collect::<Vec<_>>(),
+                vec![("sign_commit", 0, 0), ("public_key_hash", 0, 1), ("substrs_commit", 0, 2)]
+            );
+            assert!(description.instances.iter().all(|instance| instance.kind == "commitment"));
+            assert_eq!(
+                description
+                    .fields
+                    .iter()
+                    .map(|field| (field.name.as_str(), field.part.as_str(), field.substr_id, field.max_size))
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("from_domain", "header", 1, 1024),
+                    ("reply_to_domain", "header", 2, 1024),
+                    ("test1_email_body", "body", 1, 512)
+                ]
+            );
+            assert!(description.fields.iter().all(|field| field.kind == "revealed" && field.committed_in == "substrs_commit"));
+        });
+    }
+
+    #[test]
+    fn test_extractable_fields() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            let fields = default_config_params().extractable_fields();
+            assert_eq!(
+                fields.iter().map(|field| (field.name.as_str(), field.part.as_str(), field.kind.as_str())).collect::<Vec<_>>(),
+                vec![("subject_otp", "header", "revealed"), ("body_otp", "body", "revealed")]
+            );
+        });
+    }
+}
@@ -0,0 +1,81 @@
+use crate::config_params::EmailVerifyConfigParams;
+use halo2_base::{utils::PrimeField, Context};
+
+/// The numbers of cells used in the email verification circuit and available at the configured degree.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CircuitStats {
+    /// The degree `k` of the circuit.
+    pub degree: u32,
+    /// The number of rows usable in each column, i.e., `2^k` minus the rows reserved for blinding.
+    pub usable_rows: usize,
+    /// The number of used advice cells except for the lookup advice columns.
+    pub advice_cells: usize,
+    /// The number of available advice cells except for the lookup advice columns.
+    pub available_advice_cells: usize,
+    /// The number of cells copied to the lookup advice columns.
+    pub lookup_cells: usize,
+    /// The number of available cells in the lookup advice columns.
+    pub available_lookup_cells: usize,
+    /// The number of used fixed cells.
+    pub fixed_cells: usize,
+    /// The number of available fixed cells.
+    pub available_fixed_cells: usize,
+}
+
+impl CircuitStats {
+    /// Collect the statistics from the context.
+    /// It must be called before the range chip finalizes the context, which consumes the cells to be looked up.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context where all the constraints are assigned.
+    /// * `params` - the configuration parameters of the circuit.
+    /// # Return values
+    /// Return a new [`CircuitStats`].
+    pub fn new<F: PrimeField>(ctx: &Context<'_, F>, params: &EmailVerifyConfigParams) -> Self {
+        let usable_rows = ctx.max_rows;
+        Self {
+            degree: params.degree,
+            usable_rows,
+            advice_cells: ctx.total_advice,
+            available_advice_cells: params.num_flex_advice * usable_rows,
+            lookup_cells: ctx.cells_to_lookup.len(),
+            available_lookup_cells: params.num_range_lookup_advice * usable_rows,
+            fixed_cells: ctx.fixed_col * usable_rows + ctx.fixed_offset,
+            // The email verification circuit has as many fixed columns as the advice columns.
+            available_fixed_cells: params.num_flex_advice * usable_rows,
+        }
+    }
+
+    /// Print the used and available cells with the utilization percentages.
+    pub fn print(&self) {
+        log::info!("circuit stats at k = {} ({} usable rows per column):", self.degree, self.usable_rows);
+        for (name, used, available) in [
+            ("advice", self.advice_cells, self.available_advice_cells),
+            ("lookup", self.lookup_cells, self.available_lookup_cells),
+            ("fixed", self.fixed_cells, self.available_fixed_cells),
+        ] {
+            log::info!("  {:<6} {:>10} / {:>10} cells ({:.2}%)", name, used, available, utilization(used, available));
+        }
+    }
+}
+
+/// Return `used / available` in percentage.
+fn utilization(used: usize, available: usize) -> f64 {
+    if available == 0 {
+        0.0
+    } else {
+        100.0 * used as f64 / available as f64
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_utilization() {
+        assert_eq!(utilization(0, 0), 0.0);
+        assert_eq!(utilization(1, 4), 25.0);
+    }
+}
@@ -41,6 +41,9 @@ pub static GLOBAL_BODY_DEFS: OnceCell<Vec<RegexDefs>> = OnceCell::new();
 
 /// The name of env variable for the path to the email configuration json.
 pub const EMAIL_VERIFY_CONFIG_ENV: &'static str = "EMAIL_VERIFY_CONFIG";
+/// The name of env variable for the email configuration json string.
+/// If it is set, it takes precedence over [`EMAIL_VERIFY_CONFIG_ENV`].
+pub const EMAIL_VERIFY_CONFIG_JSON_ENV: &'static str = "EMAIL_VERIFY_CONFIG_JSON";
 
 /// Configuration parameters for [`Sha256DynamicConfig`]
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -64,9 +67,131 @@ pub struct HeaderConfigParams {
     /// The bytes of the skipped email header that do not satisfy the regexes.
     /// It must be multiple of 64 and less than `max_variable_byte_size`.
     pub skip_prefix_bytes_size: Option<usize>,
+    /// Groups of the header substrings whose values must be equal, e.g., the domains of `From`, `Reply-To`, and `Return-Path`.
+    /// If omitted, no equality is asserted.
+    pub substr_equalities: Option<Vec<SubstrEqualityParams>>,
+    /// If true, the RFC2047 encoded words, e.g., `=?UTF-8?B?...?=`, in the revealed header substrings are decoded in [`DefaultEmailVerifyPublicInput::public_fields`](crate::DefaultEmailVerifyPublicInput::public_fields).
+    /// The circuit still commits the encoded form signed by DKIM. It defaults to false.
+    pub decode_encoded_words: Option<bool>,
+    /// If true, the punycode labels of internationalized domains, e.g., `xn--r8jz45g.jp`, in the revealed header substrings are decoded to Unicode in [`DefaultEmailVerifyPublicInput::public_fields`](crate::DefaultEmailVerifyPublicInput::public_fields).
+    /// The circuit still commits the punycode form signed by DKIM. It defaults to false.
+    pub decode_idn_domains: Option<bool>,
+    /// Names of the header fields that must not appear in the signed headers, e.g., `List-Unsubscribe`.
+    /// It requires `skip_prefix_bytes_size` to be omitted or 0. If omitted, no header field is forbidden.
+    pub forbidden_fields: Option<Vec<String>>,
+    /// Header substrings whose domains must be aligned with the DKIM `d=` domain as in DMARC, e.g., the `Return-Path` domain for the SPF alignment.
+    /// If omitted, no alignment is asserted.
+    pub dkim_domain_alignments: Option<Vec<DkimDomainAlignmentParams>>,
+    /// The `t=` timestamp of the DKIM signature exposed as an integer instance after the RLC instance, e.g., to enforce a freshness window.
+    /// If omitted, no timestamp is exposed.
+    pub dkim_timestamp: Option<DkimTimestampParams>,
+    /// Header substrings that must be the given hosts, e.g., the `from` host of a `Received` field to prove that the email passed through a specific MTA.
+    /// Only the signed header fields are in the canonicalized header, so the `Received` field must be listed in the `h=` tag. If omitted, no hop is asserted.
+    pub received_hops: Option<Vec<ReceivedHopParams>>,
+    /// Comma-separated address lists, e.g., the addresses in the `To` and `Cc` fields, whose addresses are committed one by one instead of revealing the lists.
+    /// If omitted, no address is committed.
+    pub recipients: Option<RecipientsParams>,
+    /// Header substrings, e.g., the `From` domain, that are committed instead of being revealed.
+    /// If omitted, no header substring is committed.
+    pub committed_substrs: Option<Vec<CommittedSubstrParams>>,
+    /// Header substrings, e.g., the `From` domain, that must be one of the given values, which are neither revealed nor committed so that the matching value is hidden.
+    /// If omitted, no membership is asserted.
+    pub membership_sets: Option<Vec<MembershipSetParams>>,
+    /// If true, the `bh=` value of the DKIM signature header without folding whitespace is committed in `header_bodyhash_commits` of the public input,
+    /// e.g., to anchor the body hash without recomputing it from the body. It defaults to false.
+    pub commit_bodyhash: Option<bool>,
     // pub expose_substrs: Option<bool>,
 }
 
+/// Configuration parameters to assert that a header substring is one of the public values without revealing which.
+/// The substring is masked in the `substrs_commit` instance, and nothing about it is appended instead.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct MembershipSetParams {
+    /// The id of the substring, which starts from 1 in the order of `header_config.substr_filepathes`. The substring must be found in the email header.
+    pub substr_id: usize,
+    /// The distinct non-empty values, e.g., the allowed domains, which are compared byte by byte.
+    pub values: Vec<String>,
+}
+
+/// Configuration parameters to commit a header substring instead of revealing it.
+/// The substring is masked in the `substrs_commit` instance, and its commitment is appended to it instead.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CommittedSubstrParams {
+    /// The id of the substring, which starts from 1 in the order of `header_config.substr_filepathes`. The substring must be found in the email header.
+    pub substr_id: usize,
+    /// The maximum number of characters in the substring, to which it is padded with zero bytes before being committed.
+    pub max_substr_size: usize,
+    /// If true, the ASCII uppercase letters of the substring are converted to lowercase in the circuit before being committed,
+    /// so that, e.g., the commitments of `ZKEmail.com` and `zkemail.com` are equal. Defaults to false.
+    pub lowercase: Option<bool>,
+}
+
+/// Configuration parameters to assert that a header substring is a specific host, e.g., a hop in a `Received` field.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ReceivedHopParams {
+    /// The id of the host substring, which starts from 1 in the order of `header_config.substr_filepathes`.
+    pub substr_id: usize,
+    /// The expected host, which is compared byte by byte.
+    pub host: String,
+}
+
+/// Configuration parameters to commit each address of comma-separated address lists in the header, e.g., the recipients in the `To` and `Cc` fields.
+/// The lists are masked in the `substrs_commit` instance, and the commitments of their addresses are appended to it instead.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RecipientsParams {
+    /// The ids of the address list substrings, which start from 1 in the order of `header_config.substr_filepathes`. Every list must be found in the email header.
+    pub substr_ids: Vec<usize>,
+    /// The maximum number of characters in each list.
+    pub max_list_size: usize,
+    /// The maximum number of addresses in each list. Each list always yields this number of commitments, where the missing addresses are committed as empty ones.
+    pub max_recipients: usize,
+    /// The maximum number of characters in each address.
+    pub max_address_size: usize,
+}
+
+/// Configuration parameters to expose the `t=` timestamp of the DKIM signature as an integer instance.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DkimTimestampParams {
+    /// The id of the substring of the `t=` value in the `DKIM-Signature` header field, which starts from 1 in the order of `header_config.substr_filepathes`.
+    pub substr_id: usize,
+    /// The maximum number of the digits, which must be at most 19 so that the timestamp fits in `u64`.
+    pub max_substr_size: usize,
+}
+
+/// Configuration parameters to assert that a header substring is a domain aligned with the DKIM `d=` domain.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DkimDomainAlignmentParams {
+    /// The id of the aligned domain substring, e.g., the `Return-Path` domain, which starts from 1 in the order of `header_config.substr_filepathes`.
+    pub substr_id: usize,
+    /// The id of the substring of the `d=` domain in the `DKIM-Signature` header field.
+    pub dkim_domain_substr_id: usize,
+    /// The maximum byte size of the compared domains.
+    pub max_substr_size: usize,
+    /// A flag whether the domains must be equal (strict alignment). Otherwise, the domain may also be a subdomain of the DKIM domain (relaxed alignment). It defaults to false.
+    pub strict: Option<bool>,
+}
+
+/// Configuration parameters to assert that some substrings in the email header are equal.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SubstrEqualityParams {
+    /// The ids of the compared substrings.
+    /// The substring ids start from 1 and are assigned in the order of `substr_filepathes`.
+    pub substr_ids: Vec<usize>,
+    /// The maximum byte size of the compared substrings.
+    pub max_substr_size: usize,
+}
+
+/// Configuration parameters to assert that a substring in the email header is equal to a substring in the email body, e.g., a one-time code in both the subject and the body.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct HeaderBodySubstrEqualityParams {
+    /// The id of the header substring, which starts from 1 in the order of `header_config.substr_filepathes`.
+    pub header_substr_id: usize,
+    /// The id of the body substring, which starts from 1 in the order of `body_config.substr_filepathes`.
+    pub body_substr_id: usize,
+    /// The maximum byte size of the compared substrings.
+    pub max_substr_size: usize,
+}
+
 /// Configuration parameters for [`RegexSha2Base64Config`].
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct BodyConfigParams {
@@ -77,9 +202,195 @@ pub struct BodyConfigParams {
     /// The bytes of the skipped email body that do not satisfy the regexes.
     /// It must be multiple of 64 and less than `max_variable_byte_size`.
     pub skip_prefix_bytes_size: Option<usize>,
+    /// Substrings that must not appear in the email body after the skipped prefix.
+    /// They are searched in the canonicalized body bytes without decoding any `Content-Transfer-Encoding`. If omitted, no substring is forbidden.
+    pub forbidden_substrs: Option<Vec<ForbiddenSubstrParams>>,
+    /// Byte ranges of the body substrings to be revealed, e.g., the last 4 digits of an order number.
+    /// The other bytes of those substrings are hidden and committed instead. If omitted, the body substrings are revealed entirely.
+    pub reveal_ranges: Option<Vec<RevealRangeParams>>,
+    /// Body substrings encoded in hex or base64, e.g., verification tokens, which are decoded in the circuit and whose decoded bytes are committed.
+    /// The substrings themselves are still revealed. If omitted, no substring is decoded.
+    pub decoded_tokens: Option<Vec<DecodedTokenParams>>,
+    /// If true, the body must be an S/MIME `multipart/signed` message, and `substr_regexes` search only its signed content, i.e., the first MIME part.
+    /// The whole body is still hashed for DKIM. It defaults to false.
+    pub smime_signed_content: Option<bool>,
+    /// Body substrings of decimal digits, e.g., the amount of a receipt, that must satisfy a comparison with public thresholds.
+    /// The substrings are hidden, and the thresholds are exposed as integer instances. If omitted, no predicate is asserted.
+    pub numeric_predicates: Option<Vec<NumericPredicateParams>>,
+    /// The bytes size of the body hash, which sizes its base64 encoding compared with the `bh=` value, e.g., 20 for SHA-1 and 64 for SHA-512.
+    /// The body is hashed with SHA256 in the circuit, so [`EmailVerifyConfigParams::check_capacity`] rejects a size other than 32. It defaults to 32.
+    pub hash_len: Option<usize>,
     // pub expose_substrs: Option<bool>,
 }
 
+impl BodyConfigParams {
+    /// Return `hash_len`, which defaults to [`crate::regex_sha2_base64::SHA256_HASH_BYTES_SIZE`].
+    pub fn hash_len(&self) -> usize {
+        self.hash_len.unwrap_or(crate::regex_sha2_base64::SHA256_HASH_BYTES_SIZE)
+    }
+
+    /// Decode the canonicalized email body to the string searched by `substr_regexes`.
+    /// The skipped prefix is removed, and if `smime_signed_content` is true, the bytes outside the S/MIME signed content are replaced with NUL so that the byte offsets are kept.
+    ///
+    /// # Arguments
+    /// * `body_bytes` - the canonicalized email body bytes including the skipped prefix.
+    /// # Return values
+    /// Return the decoded string of the body after the skipped prefix.
+    pub fn searched_str(&self, body_bytes: &[u8]) -> String {
+        let skip_prefix_bytes_size = self.skip_prefix_bytes_size.unwrap_or(0);
+        let body_str = crate::utils::EmailEncoding::Lossy.decode(&body_bytes[skip_prefix_bytes_size..]);
+        if !self.smime_signed_content.unwrap_or(false) {
+            return body_str;
+        }
+        let content_range = crate::utils::smime_signed_content_range(body_bytes).expect("the email body is not an S/MIME multipart/signed message");
+        let content_range = content_range.start.saturating_sub(skip_prefix_bytes_size)..content_range.end.saturating_sub(skip_prefix_bytes_size);
+        let masked_bytes = body_str
+            .bytes()
+            .enumerate()
+            .map(|(idx, byte)| if content_range.contains(&idx) { byte } else { 0 })
+            .collect::<Vec<u8>>();
+        String::from_utf8(masked_bytes).expect("the S/MIME signed content starts and ends at line boundaries")
+    }
+}
+
+/// Configuration parameters to reveal only a byte range of a body substring.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RevealRangeParams {
+    /// The id of the body substring, which starts from 1 in the order of `body_config.substr_filepathes`.
+    pub substr_id: usize,
+    /// The byte offset of the revealed range in the substring, counted from its end if `from_end` is true.
+    pub offset: usize,
+    /// The maximum byte size of the revealed range, which is truncated at the boundary of the substring.
+    pub len: usize,
+    /// A flag whether `offset` is counted from the end of the substring. It defaults to false.
+    pub from_end: Option<bool>,
+    /// A flag whether the whole substring is committed in `body_hidden_commits`. It defaults to true.
+    /// If false, the bytes outside the range are only masked, e.g., so that a short account number cannot be recovered by brute-forcing its commitment.
+    pub commit_hidden: Option<bool>,
+}
+
+impl RevealRangeParams {
+    /// Return the revealed byte range in a substring of `substr_len` bytes.
+    pub fn byte_range(&self, substr_len: usize) -> std::ops::Range<usize> {
+        if self.from_end.unwrap_or(false) {
+            substr_len.saturating_sub(self.offset + self.len)..substr_len.saturating_sub(self.offset)
+        } else {
+            self.offset.min(substr_len)..(self.offset + self.len).min(substr_len)
+        }
+    }
+}
+
+/// Configuration parameters to decode a hex or base64 body substring in the circuit and commit the decoded bytes.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DecodedTokenParams {
+    /// The id of the body substring, which starts from 1 in the order of `body_config.substr_filepathes`. The substring must be found in the email body.
+    pub substr_id: usize,
+    /// The encoding of the substring.
+    pub encoding: TokenEncoding,
+    /// The maximum number of characters in the substring, which must be a multiple of 2 for hex and of 4 for base64.
+    pub max_token_size: usize,
+}
+
+/// The encoding of a body substring decoded in the circuit.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenEncoding {
+    /// Hex digits in either case, two for each byte.
+    Hex,
+    /// The standard base64 alphabet with `=` padding, four characters for every three bytes.
+    Base64,
+}
+
+impl TokenEncoding {
+    /// Return the number of the decoded bytes of a token with `max_token_size` characters.
+    pub fn max_decoded_size(&self, max_token_size: usize) -> usize {
+        match self {
+            Self::Hex => max_token_size / 2,
+            Self::Base64 => max_token_size / 4 * 3,
+        }
+    }
+
+    /// Decode a token natively in the same way as the circuit.
+    ///
+    /// # Arguments
+    /// * `token` - the encoded token.
+    /// # Return values
+    /// Return the decoded bytes, or `None` if the token is not validly encoded.
+    pub fn decode(&self, token: &str) -> Option<Vec<u8>> {
+        match self {
+            Self::Hex => hex::decode(token).ok(),
+            Self::Base64 => {
+                use base64::{engine::general_purpose, Engine as _};
+                general_purpose::STANDARD.decode(token).ok()
+            }
+        }
+    }
+}
+
+/// Configuration parameters to assert that a body substring of decimal digits satisfies a comparison with public thresholds, e.g., `amount > 100`.
+/// The substring is masked in the `substrs_commit` instance, and the thresholds are exposed as integer instances after the DKIM timestamp.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct NumericPredicateParams {
+    /// The id of the body substring, which starts from 1 in the order of `body_config.substr_filepathes`. The substring must be found in the email body.
+    pub substr_id: usize,
+    /// The maximum number of the digits, which must be at most 19 so that the value fits in `u64`.
+    pub max_substr_size: usize,
+    /// The comparison of the value with the thresholds.
+    pub predicate: NumericPredicate,
+    /// The thresholds, i.e., one for `gt`, `lt`, and `eq`, and the inclusive minimum and maximum for `range`.
+    /// They are witnesses constrained by the instances, so changing them does not require new keys.
+    pub thresholds: Vec<u64>,
+}
+
+/// A comparison of a value parsed from a body substring with public thresholds.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumericPredicate {
+    /// The value is greater than the threshold.
+    Gt,
+    /// The value is less than the threshold.
+    Lt,
+    /// The value is equal to the threshold.
+    Eq,
+    /// The value is between the two thresholds inclusive.
+    Range,
+}
+
+impl NumericPredicate {
+    /// Return the number of the thresholds of the predicate.
+    pub fn num_thresholds(&self) -> usize {
+        match self {
+            Self::Range => 2,
+            Self::Gt | Self::Lt | Self::Eq => 1,
+        }
+    }
+
+    /// Evaluate the predicate natively in the same way as the circuit.
+    ///
+    /// # Arguments
+    /// * `value` - the value parsed from the body substring.
+    /// * `thresholds` - the thresholds, whose number must be [`NumericPredicate::num_thresholds`].
+    /// # Return values
+    /// Return true if the value satisfies the predicate.
+    pub fn eval(&self, value: u64, thresholds: &[u64]) -> bool {
+        match self {
+            Self::Gt => value > thresholds[0],
+            Self::Lt => value < thresholds[0],
+            Self::Eq => value == thresholds[0],
+            Self::Range => thresholds[0] <= value && value <= thresholds[1],
+        }
+    }
+}
+
+/// Configuration parameters of a substring that must not appear in the email.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ForbiddenSubstrParams {
+    /// The forbidden substring. It is a literal string, not a regex.
+    pub substr: String,
+    /// A flag whether ASCII letters are compared case-insensitively.
+    pub case_insensitive: Option<bool>,
+}
+
 /// Configuration parameters for [`SignVerifyConfig`].
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct SignVerifyConfigParams {
@@ -87,10 +398,19 @@ pub struct SignVerifyConfigParams {
     pub public_key_bits: usize,
     /// A flag whether the public key is hidden.
     pub hide_public_key: Option<bool>,
+    /// The maximum byte size of the signed email header for [`SignOnlyCircuit`](crate::sign_only::SignOnlyCircuit).
+    /// If omitted, `header_config.max_variable_byte_size` is used.
+    pub max_header_bytes_size: Option<usize>,
+    /// If set, the signature has the RSASSA-PSS padding with SHA-256, MGF1-SHA-256, and a salt of this number of bytes, instead of the PKCS#1 v1.5 padding used by DKIM.
+    /// It is only for experimental deployments and requires `public_key_bits` to be a multiple of 8. If omitted, the PKCS#1 v1.5 padding is verified.
+    pub pss_salt_len: Option<usize>,
 }
 
 /// Configuration parameters for the email verification circuits.
 ///
+/// The circuit shape, i.e., `degree`, `num_flex_advice`, `num_range_lookup_advice`, and `range_lookup_bits`, is determined by these parameters at runtime,
+/// so that you can tune the circuit without rebuilding it. Note that you need to regenerate the proving and verifying keys after changing them.
+///
 /// Although the types of some parameters are defined as [`Option`], you will get an error if they are omitted for [`DefaultEmailVerifyCircuit`].
 /// You can build a circuit that accepts the same format configuration file except that some parameters are omitted.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -102,6 +422,7 @@ pub struct EmailVerifyConfigParams {
     /// The number of advice columns for lookup constraints in [`RangeConfig`].
     pub num_range_lookup_advice: usize,
     /// The number of fix columns in [`FlexGateConfig`].
+    /// [`DefaultEmailVerifyCircuit`] ignores it and has as many fixed columns as `num_flex_advice`, so that its verifying key does not change.
     pub num_flex_fixed: usize,
     /// The bits of lookup table in [`RangeConfig`], which must be less than `degree`.
     pub range_lookup_bits: usize,
@@ -113,6 +434,24 @@ pub struct EmailVerifyConfigParams {
     pub header_config: Option<HeaderConfigParams>,
     /// Configuration parameters for [`RegexSha2Base64Config`].
     pub body_config: Option<BodyConfigParams>,
+    /// The number of instance columns, across which the instances are distributed in a round-robin manner.
+    /// If omitted, one column is used. Note that the generated EVM verifier supports only one column.
+    pub num_instance_columns: Option<usize>,
+    /// Pairs of a header substring and a body substring whose values must be equal.
+    /// If omitted, no equality across the header and the body is asserted.
+    pub header_body_substr_equalities: Option<Vec<HeaderBodySubstrEqualityParams>>,
+    /// If true, every proof is generated with the fixed blinding factors of [`DETERMINISTIC_PROOF_SEED`](crate::helpers::DETERMINISTIC_PROOF_SEED), as with `--deterministic`, so that the same inputs always produce the same proof bytes.
+    /// It breaks zero-knowledge, so use it only for tests. The number of the blinding rows is fixed by halo2 and does not change. It defaults to false.
+    pub disable_blinding: Option<bool>,
+    /// If true, an email whose DKIM signature does not cover the `From` header, i.e., `from` is not in the `h=` tag, can be proven.
+    /// Such a `From` header can be spoofed, so it defaults to false and [`dkim::check_from_signed`](crate::dkim::check_from_signed) rejects the email before proving.
+    pub allow_unsigned_from: Option<bool>,
+    /// The hash function of `body_hidden_commits` and `header_recipient_commits` in the public input, either `poseidon` or `keccak`.
+    /// The `keccak` commitments can be recomputed cheaply on-chain, but each commitment costs one Keccak permutation in the circuit for every 136 committed bytes. It defaults to `poseidon`.
+    pub commitment_scheme: Option<crate::wtns_commit::CommitmentScheme>,
+    /// If true, the hash of a recent block given to the prover is exposed as the last two instances, i.e., its upper and lower 128 bits,
+    /// so that the verifier contract can check that the proof was generated after that block. It defaults to false.
+    pub bind_block_hash: Option<bool>,
 }
 
 impl EmailVerifyConfigParams {
@@ -121,15 +460,240 @@ impl EmailVerifyConfigParams {
         GLOBAL_CONFIG_PARAMS.get().expect("GLOBAL_CONFIG_PARAMS is not set.")
     }
 
-    /// Set the [`EmailVerifyConfigParams`] in the path of [`EMAIL_VERIFY_CONFIG_ENV`]  to [`GLOBAL_CONFIG_PARAMS`].
+    /// Set the [`EmailVerifyConfigParams`] given by [`EmailVerifyConfigParams::get_from_env`] to [`GLOBAL_CONFIG_PARAMS`].
     pub fn set_from_env() {
         let params: Self = Self::get_from_env();
         GLOBAL_CONFIG_PARAMS.set(params).unwrap();
     }
 
-    /// Get the [`EmailVerifyConfigParams`] from the path of [`EMAIL_VERIFY_CONFIG_ENV`].
+    /// Check that the regex files of the header and body configurations are consistent with each other and fit in the lookup tables of `2^degree` rows.
+    ///
+    /// # Return values
+    /// Return `Ok(())` if all the regex files are consistent and fit, otherwise the first [`RegexFilesError`](crate::utils::RegexFilesError).
+    pub fn check_regex_files(&self) -> Result<(), crate::utils::RegexFilesError> {
+        if let Some(header_params) = self.header_config.as_ref() {
+            crate::utils::check_regex_files(&header_params.bodyhash_allstr_filepath, &[header_params.bodyhash_substr_filepath.clone()])?;
+            crate::utils::check_regex_capacity(&header_params.bodyhash_allstr_filepath, self.degree)?;
+            for (allstr_path, substr_pathes) in header_params.allstr_filepathes.iter().zip(header_params.substr_filepathes.iter()) {
+                crate::utils::check_regex_files(allstr_path, substr_pathes)?;
+                crate::utils::check_regex_capacity(allstr_path, self.degree)?;
+            }
+        }
+        if let Some(body_params) = self.body_config.as_ref() {
+            for (allstr_path, substr_pathes) in body_params.allstr_filepathes.iter().zip(body_params.substr_filepathes.iter()) {
+                crate::utils::check_regex_files(allstr_path, substr_pathes)?;
+                crate::utils::check_regex_capacity(allstr_path, self.degree)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the semantics of each instance slot of the email verification circuit of this configuration in the flattened order of [`crate::circuit_description::CircuitDescription::instances`].
+    /// The revealed and committed fields do not add any slot, since all of them are bound by the single `substrs_commit` instance.
+    ///
+    /// # Return values
+    /// Return the [`crate::circuit_description::InstanceKind`] of each instance.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn instance_semantics(&self) -> Vec<crate::circuit_description::InstanceKind> {
+        use crate::circuit_description::InstanceKind;
+        let header_params = self.header_config.as_ref().expect("header_config is required");
+        let body_params = self.body_config.as_ref().expect("body_config is required");
+        let mut kinds = vec![InstanceKind::Commitment, InstanceKind::Commitment, InstanceKind::RevealedBytes];
+        if header_params.dkim_timestamp.is_some() {
+            kinds.push(InstanceKind::Numeric);
+        }
+        let num_thresholds = body_params.numeric_predicates.iter().flatten().map(|predicate| predicate.predicate.num_thresholds()).sum::<usize>();
+        kinds.extend(std::iter::repeat(InstanceKind::Numeric).take(num_thresholds));
+        if self.bind_block_hash.unwrap_or(false) {
+            kinds.extend([InstanceKind::Binding, InstanceKind::Binding]);
+        }
+        kinds
+    }
+
+    /// Enumerate the fields that the email verification circuit of this configuration extracts, without generating any proof.
+    /// Every field is revealed in the public input and committed in the `substrs_commit` instance.
+    /// A body field with a reveal range in `body_config.reveal_ranges` is only partially revealed, and a header field in `header_config.recipients` is only committed address by address.
+    /// A header field in `header_config.committed_substrs` is only committed, and one in `header_config.membership_sets` is only asserted to be one of the values.
+    ///
+    /// # Return values
+    /// Return the descriptions of the header fields followed by those of the body fields.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn extractable_fields(&self) -> Vec<crate::circuit_description::FieldDescription> {
+        let header_params = self.header_config.as_ref().expect("header_config is required");
+        let body_params = self.body_config.as_ref().expect("body_config is required");
+        let fields_of = |part: &str, names: Vec<String>, max_size: usize, partial_ids: Vec<usize>| {
+            names
+                .into_iter()
+                .enumerate()
+                .map(|(idx, name)| crate::circuit_description::FieldDescription {
+                    name,
+                    part: part.to_string(),
+                    substr_id: idx + 1,
+                    max_size,
+                    kind: if partial_ids.contains(&(idx + 1)) { "partially_revealed" } else { "revealed" }.to_string(),
+                    committed_in: crate::circuit_description::INSTANCE_NAMES[2].to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut fields = fields_of(
+            "header",
+            crate::utils::substr_field_names(&header_params.allstr_filepathes, &header_params.substr_filepathes),
+            header_params.max_variable_byte_size,
+            vec![],
+        );
+        for substr_id in header_params.recipients.iter().flat_map(|recipients| recipients.substr_ids.iter()) {
+            if let Some(field) = fields.get_mut(substr_id - 1) {
+                field.kind = "address_commitments".to_string();
+            }
+        }
+        for committed in header_params.committed_substrs.iter().flatten() {
+            if let Some(field) = fields.get_mut(committed.substr_id - 1) {
+                field.kind = "commitment".to_string();
+            }
+        }
+        for set in header_params.membership_sets.iter().flatten() {
+            if let Some(field) = fields.get_mut(set.substr_id - 1) {
+                field.kind = "set_membership".to_string();
+            }
+        }
+        let num_header_fields = fields.len();
+        fields.append(&mut fields_of(
+            "body",
+            crate::utils::substr_field_names(&body_params.allstr_filepathes, &body_params.substr_filepathes),
+            body_params.max_variable_byte_size,
+            body_params.reveal_ranges.iter().flatten().map(|reveal_range| reveal_range.substr_id).collect(),
+        ));
+        for predicate in body_params.numeric_predicates.iter().flatten() {
+            if let Some(field) = fields.get_mut(num_header_fields + predicate.substr_id - 1) {
+                field.kind = "numeric_predicate".to_string();
+            }
+        }
+        fields
+    }
+
+    /// Check that the lookup tables and the byte sizes fit in the `2^degree` rows.
+    /// Every byte of the header and the body occupies one row of the regex columns, so a larger size can never be assigned.
+    /// Passing this check does not guarantee that the flex gate has enough advice columns, which [`CircuitStats`](crate::circuit_stats::CircuitStats) reports.
+    /// It also checks that `body_config.hash_len` is the bytes size of the SHA256 hash computed by the circuit.
+    ///
+    /// # Return values
+    /// Return `Ok(())` if they fit, otherwise the first [`CapacityError`].
+    pub fn check_capacity(&self) -> Result<(), CapacityError> {
+        if let Some(hash_len) = self.body_config.as_ref().map(|body_params| body_params.hash_len()) {
+            if hash_len != crate::regex_sha2_base64::SHA256_HASH_BYTES_SIZE {
+                return Err(CapacityError::HashLen { hash_len });
+            }
+        }
+        let num_rows = 1usize << self.degree;
+        let mut lookup_bits = vec![("range_lookup_bits", self.range_lookup_bits)];
+        if let Some(sha256_params) = self.sha256_config.as_ref() {
+            lookup_bits.push(("sha256_config.num_bits_lookup", sha256_params.num_bits_lookup));
+        }
+        for (name, bits) in lookup_bits {
+            if bits >= self.degree as usize {
+                return Err(CapacityError::LookupBits { name, bits, degree: self.degree });
+            }
+        }
+        let mut byte_sizes = vec![];
+        if let Some(header_params) = self.header_config.as_ref() {
+            byte_sizes.push(("header_config.max_variable_byte_size", header_params.max_variable_byte_size));
+        }
+        if let Some(body_params) = self.body_config.as_ref() {
+            byte_sizes.push(("body_config.max_variable_byte_size", body_params.max_variable_byte_size));
+        }
+        if let Some(max_header_bytes_size) = self.sign_verify_config.as_ref().and_then(|params| params.max_header_bytes_size) {
+            byte_sizes.push(("sign_verify_config.max_header_bytes_size", max_header_bytes_size));
+        }
+        for (name, size) in byte_sizes {
+            if size >= num_rows {
+                return Err(CapacityError::ByteSize { name, size, degree: self.degree });
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the [`EmailVerifyConfigParams`] from the json string of [`EMAIL_VERIFY_CONFIG_JSON_ENV`] if it is set, otherwise from the path of [`EMAIL_VERIFY_CONFIG_ENV`].
+    /// It panics if the parameters do not pass [`EmailVerifyConfigParams::check_capacity`].
     pub fn get_from_env() -> Self {
-        let path = std::env::var(EMAIL_VERIFY_CONFIG_ENV).expect("You must set the configure file path to EMAIL_VERIFY_CONFIG.");
-        serde_json::from_reader(File::open(path.as_str()).expect(&format!("{} does not exist.", path))).expect("File is found but invalid.")
+        let params: Self = if let Ok(json) = std::env::var(EMAIL_VERIFY_CONFIG_JSON_ENV) {
+            serde_json::from_str(&json).expect("The configure json in EMAIL_VERIFY_CONFIG_JSON is invalid.")
+        } else {
+            let path = std::env::var(EMAIL_VERIFY_CONFIG_ENV).expect("You must set the configure file path to EMAIL_VERIFY_CONFIG.");
+            serde_json::from_reader(File::open(path.as_str()).expect(&format!("{} does not exist.", path))).expect("File is found but invalid.")
+        };
+        if let Err(err) = params.check_capacity() {
+            panic!("The configure exceeds the capacity of the circuit: {}", err);
+        }
+        params
+    }
+}
+
+/// Errors of [`EmailVerifyConfigParams::check_capacity`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum CapacityError {
+    /// A lookup table does not fit in the rows.
+    #[error("{name} = {bits} must be less than degree = {degree}, since the lookup table has 2^{bits} rows")]
+    LookupBits { name: &'static str, bits: usize, degree: u32 },
+    /// The bytes do not fit in the rows.
+    #[error("{name} = {size} must be less than 2^{degree} rows; increase degree or decrease {name}")]
+    ByteSize { name: &'static str, size: usize, degree: u32 },
+    /// The body hash size differs from that of SHA256 computed by the circuit.
+    #[error("body_config.hash_len = {hash_len} is not supported, since the circuit hashes the body with SHA256 into 32 bytes")]
+    HashLen { hash_len: usize },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_params_from_json_env() {
+        let config_json = std::fs::read_to_string("./configs/test1_email_verify.config").unwrap();
+        temp_env::with_vars(
+            [(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config_json.as_str())), (EMAIL_VERIFY_CONFIG_ENV, Some("./configs/not_exist.config"))],
+            || {
+                let params = EmailVerifyConfigParams::get_from_env();
+                let expected: EmailVerifyConfigParams = serde_json::from_str(&config_json).unwrap();
+                assert_eq!(serde_json::to_string(&params).unwrap(), serde_json::to_string(&expected).unwrap());
+            },
+        );
+    }
+
+    #[test]
+    fn test_check_capacity() {
+        let mut params: EmailVerifyConfigParams = serde_json::from_reader(File::open("./configs/test1_email_verify.config").unwrap()).unwrap();
+        assert_eq!(params.check_capacity(), Ok(()));
+        params.degree = 10;
+        assert_eq!(
+            params.check_capacity(),
+            Err(CapacityError::LookupBits {
+                name: "range_lookup_bits",
+                bits: 16,
+                degree: 10
+            })
+        );
+        params.range_lookup_bits = 8;
+        params.sha256_config.as_mut().unwrap().num_bits_lookup = 8;
+        let err = params.check_capacity().unwrap_err();
+        assert_eq!(
+            err,
+            CapacityError::ByteSize {
+                name: "header_config.max_variable_byte_size",
+                size: 1024,
+                degree: 10
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "header_config.max_variable_byte_size = 1024 must be less than 2^10 rows; increase degree or decrease header_config.max_variable_byte_size"
+        );
+        params.body_config.as_mut().unwrap().hash_len = Some(20);
+        let err = params.check_capacity().unwrap_err();
+        assert_eq!(err, CapacityError::HashLen { hash_len: 20 });
+        assert_eq!(
+            err.to_string(),
+            "body_config.hash_len = 20 is not supported, since the circuit hashes the body with SHA256 into 32 bytes"
+        );
     }
 }
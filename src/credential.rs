@@ -0,0 +1,232 @@
+use crate::helpers::{gen_proof_bundle, verify_proof_bundle, ProofBundle, VerificationOutcome};
+use crate::{DefaultEmailVerifyPublicInput, EMAIL_VERIFY_CONFIG_ENV};
+use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use halo2_base::halo2_proofs::plonk::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use snark_verifier_sdk::CircuitExt;
+use std::env::set_var;
+use std::fs::File;
+use std::io::BufWriter;
+
+/// The json-ld context of the W3C Verifiable Credentials Data Model v1.1.
+pub const CREDENTIALS_V1_CONTEXT: &'static str = "https://www.w3.org/2018/credentials/v1";
+/// The type of the credential, which is listed after `VerifiableCredential`.
+pub const EMAIL_CREDENTIAL_TYPE: &'static str = "EmailCredential";
+/// The type of the proof embedded in the credential.
+pub const EMAIL_PROOF_TYPE: &'static str = "Halo2ZkEmailProof";
+
+/// A W3C Verifiable Credential whose claims are the revealed and committed fields of an email, and whose proof embeds a [`ProofBundle`] of the email verification circuit.
+/// The credential is not signed by the DKIM signer: its `issuer` only identifies the DKIM public key by `public_key_hash`, and the proof of the circuit attests the claims instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiableCredential {
+    /// Always [`CREDENTIALS_V1_CONTEXT`].
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    /// `VerifiableCredential` and [`EMAIL_CREDENTIAL_TYPE`].
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    /// A URN of `public_key_hash` of the public input, i.e., `urn:halo2-zk-email:dkim-key:{public_key_hash}`.
+    pub issuer: String,
+    /// The UTC time at which the credential was generated in RFC 3339.
+    pub issuance_date: String,
+    /// The claims given by [`credential_subject`].
+    pub credential_subject: Map<String, Value>,
+    /// The proof of the claims.
+    pub proof: CredentialProof,
+}
+
+/// The proof of a [`VerifiableCredential`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialProof {
+    /// Always [`EMAIL_PROOF_TYPE`].
+    #[serde(rename = "type")]
+    pub proof_type: String,
+    /// The same time as `issuanceDate` of the credential.
+    pub created: String,
+    /// Always `assertionMethod`.
+    pub proof_purpose: String,
+    /// A URN of the hash of the verifying key, i.e., `urn:halo2-zk-email:vk:{vk_hash}`.
+    pub verification_method: String,
+    /// The proof with its public input, from which the claims are derived.
+    pub bundle: ProofBundle,
+}
+
+impl VerifiableCredential {
+    /// Wrap a [`ProofBundle`] into a credential.
+    /// The email verification circuit configuration must be set to derive the field names of the claims.
+    ///
+    /// # Arguments
+    /// * `bundle` - the proof bundle.
+    /// * `issued_at` - the UNIX time in seconds at which the credential is generated.
+    /// # Return values
+    /// Return a new [`VerifiableCredential`].
+    pub fn from_bundle(bundle: ProofBundle, issued_at: u64) -> Self {
+        let issuance_date = rfc3339_utc(issued_at);
+        Self {
+            context: vec![CREDENTIALS_V1_CONTEXT.to_string()],
+            types: vec!["VerifiableCredential".to_string(), EMAIL_CREDENTIAL_TYPE.to_string()],
+            issuer: format!("urn:halo2-zk-email:dkim-key:{}", bundle.public_input.public_key_hash),
+            issuance_date: issuance_date.clone(),
+            credential_subject: credential_subject(&bundle.public_input),
+            proof: CredentialProof {
+                proof_type: EMAIL_PROOF_TYPE.to_string(),
+                created: issuance_date,
+                proof_purpose: "assertionMethod".to_string(),
+                verification_method: format!("urn:halo2-zk-email:vk:{}", bundle.vk_hash),
+                bundle,
+            },
+        }
+    }
+
+    /// Read [`VerifiableCredential`] from a json file.
+    ///
+    /// # Arguments
+    /// * `credential_path` - a file path of the credential.
+    pub fn read_file(credential_path: &str) -> Self {
+        let file = File::open(credential_path).expect("credential_path cannot be opened");
+        serde_json::from_reader(file).unwrap_or_else(|err| panic!("invalid credential {}: {}", credential_path, err))
+    }
+
+    /// Output [`VerifiableCredential`] to a json file.
+    ///
+    /// # Arguments
+    /// * `credential_path` - a file path of the output json file.
+    pub fn write_file(&self, credential_path: &str) {
+        let file = File::create(credential_path).expect("credential_path creation failed");
+        serde_json::to_writer_pretty(BufWriter::new(file), self).unwrap();
+    }
+}
+
+/// Return the claims of a credential from the public input.
+/// Each revealed field of [`DefaultEmailVerifyPublicInput::public_fields`] is a claim named after the field, whose value is its UTF-8 string.
+/// The commitments of the hidden fields are in the `commitments` claim, which maps the names of the non-empty commitment lists of the public input to them.
+///
+/// # Arguments
+/// * `public_input` - the public input of the proof.
+/// # Return values
+/// Return the claims as a json object.
+pub fn credential_subject(public_input: &DefaultEmailVerifyPublicInput) -> Map<String, Value> {
+    let mut subject = Map::new();
+    for (name, value) in public_input.public_fields() {
+        subject.insert(name, Value::from(String::from_utf8_lossy(&value).to_string()));
+    }
+    let commits = [
+        ("sign_commit", vec![public_input.sign_commit.clone()]),
+        ("public_key_hash", vec![public_input.public_key_hash.clone()]),
+        ("body_hidden_commits", public_input.body_hidden_commits.clone()),
+        ("header_recipient_commits", public_input.header_recipient_commits.clone()),
+        ("body_decoded_commits", public_input.body_decoded_commits.clone()),
+        ("header_hidden_commits", public_input.header_hidden_commits.clone()),
+        ("header_bodyhash_commits", public_input.header_bodyhash_commits.clone()),
+    ];
+    let commitments = commits
+        .into_iter()
+        .filter(|(_, commits)| !commits.is_empty())
+        .map(|(name, commits)| (name.to_string(), Value::from(commits)))
+        .collect::<Map<_, _>>();
+    subject.insert("commitments".to_string(), Value::Object(commitments));
+    subject
+}
+
+/// Generate a proof for the email verification circuit and write it as a [`VerifiableCredential`].
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `pk_path` - a file path of the proving key.
+/// * `credential_path` - a file path of the output credential.
+/// * `circuit` - an email verification circuit.
+/// * `public_input` - the public input of `circuit`, e.g., [`crate::DefaultEmailVerifyCircuit::gen_default_public_input`].
+/// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+pub fn prove_credential<C: CircuitExt<Fr>>(
+    params_path: &str,
+    circuit_config_path: &str,
+    pk_path: &str,
+    credential_path: &str,
+    circuit: C,
+    public_input: &DefaultEmailVerifyPublicInput,
+    deterministic: bool,
+) -> Result<(), Error> {
+    let bundle = gen_proof_bundle(params_path, circuit_config_path, pk_path, circuit, public_input, deterministic)?;
+    let issued_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    VerifiableCredential::from_bundle(bundle, issued_at).write_file(credential_path);
+    Ok(())
+}
+
+/// Verify a [`VerifiableCredential`] for the email verification circuit.
+/// The credential is invalid without verifying the proof if its claims differ from those derived from the public input of the embedded bundle.
+/// Otherwise, the embedded bundle is verified as [`crate::helpers::verify_bundle`].
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// * `credential_path` - a file path of the credential.
+/// # Return values
+/// Return a [`VerificationOutcome`] that contains whether the proof is valid and the revealed fields.
+pub fn verify_credential<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, credential_path: &str) -> Result<VerificationOutcome, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let credential = VerifiableCredential::read_file(credential_path);
+    let bundle = &credential.proof.bundle;
+    if credential.proof.proof_type != EMAIL_PROOF_TYPE {
+        log::warn!("the proof of the credential is not {}", EMAIL_PROOF_TYPE);
+        return Ok(VerificationOutcome {
+            valid: false,
+            public_fields: bundle.public_input.public_fields(),
+        });
+    }
+    if credential.credential_subject != credential_subject(&bundle.public_input) {
+        log::warn!("the claims of the credential differ from those derived from the public input of its proof");
+        return Ok(VerificationOutcome {
+            valid: false,
+            public_fields: bundle.public_input.public_fields(),
+        });
+    }
+    verify_proof_bundle::<C>(params_path, circuit_config_path, vk_path, bundle)
+}
+
+/// Format a UNIX time in seconds as an RFC 3339 UTC time, e.g., `2023-11-14T22:13:20Z`.
+fn rfc3339_utc(secs: u64) -> String {
+    // The civil date of the days since 1970-01-01 in the proleptic Gregorian calendar, counted in 400-year eras from 0000-03-01.
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let secs_of_day = secs % 86400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rfc3339_utc() {
+        assert_eq!(rfc3339_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339_utc(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339_utc(1700000000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_credential_subject() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
+            let public_input = DefaultEmailVerifyPublicInput::new::<Fr>(Fr::from(1u64), Fr::from(2u64), vec![Some((0, "alice@zkemail.com".to_string()))], vec![]);
+            let subject = credential_subject(&public_input);
+            for (name, value) in public_input.public_fields() {
+                assert_eq!(subject[&name], Value::from(String::from_utf8(value).unwrap()));
+            }
+            assert_eq!(subject["commitments"]["sign_commit"], serde_json::json!(["1"]));
+            assert_eq!(subject["commitments"]["public_key_hash"], serde_json::json!(["2"]));
+            assert!(subject["commitments"].get("body_hidden_commits").is_none());
+        });
+    }
+}
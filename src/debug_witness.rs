@@ -0,0 +1,92 @@
+use crate::regex_sha2::RegexSha2Result;
+use crate::regex_sha2_base64::RegexSha2Base64Result;
+use base64::{engine::general_purpose, Engine as _};
+use halo2_base::{utils::PrimeField, AssignedValue};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
+
+/// Intermediate witnesses assigned in the email verification circuit, together with the corresponding values computed in the native code.
+/// Hash values are written as hex strings so that the in-circuit and native values can be diffed directly.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EmailDebugWitness {
+    /// The SHA256 hash of the canonicalized header assigned in the circuit.
+    pub header_hash: String,
+    /// The SHA256 hash of the canonicalized header computed natively.
+    pub native_header_hash: String,
+    /// The SHA256 hash of the canonicalized body assigned in the circuit.
+    pub body_hash: String,
+    /// The SHA256 hash of the canonicalized body computed natively.
+    pub native_body_hash: String,
+    /// The base64 encoded body hash assigned in the circuit.
+    pub body_hash_base64: String,
+    /// The base64 encoded body hash computed natively.
+    pub native_body_hash_base64: String,
+    /// The `bh=` value extracted from the header in the circuit.
+    pub extracted_bodyhash: String,
+    /// The substring id of each character in the header, where 0 means that the character is not in any substring.
+    pub header_substr_ids: Vec<u64>,
+    /// The substring id of each character in the body, where 0 means that the character is not in any substring.
+    pub body_substr_ids: Vec<u64>,
+}
+
+impl EmailDebugWitness {
+    /// Collect the intermediate witnesses of one email.
+    ///
+    /// # Arguments
+    /// * `header_bytes` - the canonicalized email header bytes.
+    /// * `body_bytes` - the canonicalized email body bytes.
+    /// * `header_result` - the output of [`RegexSha2Config`](crate::regex_sha2::RegexSha2Config) for the header.
+    /// * `body_result` - the output of [`RegexSha2Base64Config`](crate::regex_sha2_base64::RegexSha2Base64Config) for the body.
+    /// * `extracted_bodyhash` - the assigned bodyhash characters extracted from the header.
+    /// # Return values
+    /// Return `None` if some witnesses are unknown, e.g., during the key generation.
+    pub fn new<'a, F: PrimeField>(
+        header_bytes: &[u8],
+        body_bytes: &[u8],
+        header_result: &RegexSha2Result<'a, F>,
+        body_result: &RegexSha2Base64Result<'a, F>,
+        extracted_bodyhash: &[AssignedValue<'a, F>],
+    ) -> Option<Self> {
+        let native_body_hash = Sha256::digest(body_bytes).to_vec();
+        Some(Self {
+            header_hash: hex::encode(assigned_bytes(&header_result.hash_bytes)?),
+            native_header_hash: hex::encode(Sha256::digest(header_bytes)),
+            body_hash: hex::encode(assigned_bytes(&body_result.hash_bytes)?),
+            native_body_hash: hex::encode(&native_body_hash),
+            body_hash_base64: String::from_utf8_lossy(&assigned_bytes(&body_result.encoded_hash)?).to_string(),
+            native_body_hash_base64: general_purpose::STANDARD.encode(&native_body_hash),
+            extracted_bodyhash: String::from_utf8_lossy(&assigned_bytes(extracted_bodyhash)?).to_string(),
+            header_substr_ids: assigned_u64s(&header_result.regex.all_substr_ids)?,
+            body_substr_ids: assigned_u64s(&body_result.regex.all_substr_ids)?,
+        })
+    }
+
+    /// Write the witnesses to `{dir}/{native_header_hash}.json`.
+    ///
+    /// # Arguments
+    /// * `dir` - a directory to which the json file is written.
+    /// # Return values
+    /// Return the path of the written file.
+    pub fn write_file(&self, dir: &str) -> String {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = Path::new(dir).join(format!("{}.json", self.native_header_hash)).to_string_lossy().to_string();
+        serde_json::to_writer_pretty(File::create(&path).unwrap(), self).unwrap();
+        path
+    }
+}
+
+fn assigned_u64s<'a, F: PrimeField>(values: &[AssignedValue<'a, F>]) -> Option<Vec<u64>> {
+    values
+        .iter()
+        .map(|value| {
+            let mut known = None;
+            value.value().map(|v| known = Some(v.get_lower_32() as u64));
+            known
+        })
+        .collect()
+}
+
+fn assigned_bytes<'a, F: PrimeField>(values: &[AssignedValue<'a, F>]) -> Option<Vec<u8>> {
+    Some(assigned_u64s(values)?.into_iter().map(|v| v as u8).collect())
+}
@@ -0,0 +1,1171 @@
+use base64::{engine::general_purpose, Engine as _};
+use mailparse::{parse_headers, MailHeaderMap};
+use num_bigint::BigUint;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::{Pkcs1v15Sign, Pss, RsaPublicKey};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// The name of the DKIM signature header.
+pub const DKIM_SIGNATURE_HEADER: &str = "DKIM-Signature";
+/// The DKIM signature algorithms, i.e., the values of the `a=` tag, supported by the email verification circuit.
+pub const SUPPORTED_ALGORITHMS: &[&str] = &["rsa-sha256"];
+/// The query methods to retrieve the DKIM public key, i.e., the values of the `q=` tag, supported by [`crate::DefaultEmailVerifyCircuit::gen_circuit_from_email_bytes`].
+pub const SUPPORTED_QUERY_METHODS: &[&str] = &["dns/txt"];
+/// The name of the header that embeds the DKIM public key record resolved when the email was archived, e.g., `v=DKIM1; k=rsa; p=MIIB...`.
+pub const EMBEDDED_PUBLIC_KEY_HEADER: &str = "X-DKIM-Public-Key";
+/// The default DNS-over-HTTPS resolver that validates DNSSEC, whose `AD` flag in the response tells whether the record is authenticated.
+pub const DEFAULT_DNSSEC_RESOLVER_URL: &str = "https://dns.google/resolve";
+
+/// Options of selecting the DKIM signature of an email and resolving its public key from DNS.
+/// See [`crate::DefaultEmailVerifyCircuit::try_gen_circuit_from_email_bytes_with_pinned_keys`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DkimOptions {
+    /// The index of the DKIM signature to be proven among the DKIM signature headers, which counts from zero in the order of the headers.
+    /// The first signature is proven if it is `None`. See [`select_dkim_signature`].
+    pub signature_index: Option<usize>,
+    /// The path to the audit log to which every DKIM public key resolved from DNS is appended. See [`DkimAuditRecord`].
+    pub audit_log_path: Option<String>,
+    /// Whether the DKIM key records resolved from DNS must be authenticated by DNSSEC. See [`resolve_dnssec_public_key_n`].
+    pub require_dnssec: bool,
+    /// The URL of the DNS-over-HTTPS resolver queried if `require_dnssec` is set, which must validate DNSSEC and answer in the json format.
+    /// [`DEFAULT_DNSSEC_RESOLVER_URL`] is used if it is `None`.
+    pub dnssec_resolver_url: Option<String>,
+}
+
+impl DkimOptions {
+    /// Select the DKIM signature of [`DkimOptions::signature_index`] with [`select_dkim_signature`] if it is set.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// # Return values
+    /// Return the email bytes whose first DKIM signature header is the selected one, which are the given bytes if the index is not set.
+    pub fn select_signature(&self, email_bytes: Vec<u8>) -> Result<Vec<u8>, DkimError> {
+        match self.signature_index {
+            Some(index) => select_dkim_signature(&email_bytes, index),
+            None => Ok(email_bytes),
+        }
+    }
+}
+
+/// Errors of checking the DKIM signature header before proving.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DkimError {
+    /// The DKIM signature header or its tag is not found.
+    #[error("the email does not have a DKIM signature with the {0}= tag")]
+    MissingTag(String),
+    /// The signature algorithm is not supported by the circuit.
+    #[error("the DKIM signature algorithm {0} is not supported; the supported algorithms are {}", SUPPORTED_ALGORITHMS.join(", "))]
+    UnsupportedAlgorithm(String),
+    /// The email does not have any DKIM signature header.
+    #[error("the email does not have a {DKIM_SIGNATURE_HEADER} header")]
+    UnsignedEmail,
+    /// The selected DKIM signature does not exist.
+    #[error("the DKIM signature {index} is selected, but the email has {num_signatures} DKIM signatures")]
+    MissingSignature { index: usize, num_signatures: usize },
+    /// None of the query methods to retrieve the public key is supported.
+    #[error("the DKIM public key query method {0} is not supported; the supported methods are {}", SUPPORTED_QUERY_METHODS.join(", "))]
+    UnsupportedQueryMethod(String),
+    /// The signing domain is not allowed.
+    #[error("the signing domain {domain} is not in the allowed domains {allowed:?}")]
+    DisallowedDomain { domain: String, allowed: Vec<String> },
+    /// The embedded public key cannot be parsed as an RSA public key.
+    #[error("the public key embedded in the {EMBEDDED_PUBLIC_KEY_HEADER} header is invalid: {0}")]
+    InvalidEmbeddedPublicKey(String),
+    /// The pinned public key cannot be parsed as an RSA public key.
+    #[error("the pinned public key is invalid: {0}")]
+    InvalidPinnedPublicKey(String),
+    /// The email cannot be canonicalized.
+    #[error("the email cannot be canonicalized: {0}")]
+    InvalidEmail(String),
+    /// The `l=` tag is not a decimal number or exceeds the length of the canonicalized body.
+    #[error("the body length l={length} is invalid for the canonicalized body of {body_len} bytes")]
+    InvalidBodyLength { length: String, body_len: usize },
+    /// The `bh=` tag does not match the hash of the canonicalized body, e.g., the body is corrupted.
+    #[error("body hash mismatch: header says {header}, computed {computed}")]
+    BodyHashMismatch { header: String, computed: String },
+    /// None of the candidate public keys verifies the DKIM signature.
+    #[error("none of the {0} candidate public keys verifies the DKIM signature")]
+    NoMatchingPublicKey(usize),
+    /// The canonicalized header does not contain the signed header fields selected by the `h=` tag.
+    #[error("the canonicalized header has the fields {actual:?}, but the h= tag selects {expected:?}")]
+    SignedHeaderMismatch { expected: Vec<String>, actual: Vec<String> },
+    /// The `From` header is not signed, so that anyone could replace it without invalidating the DKIM signature.
+    #[error("the From header is not signed; the h= tag selects {0:?}")]
+    UnsignedFrom(Vec<String>),
+    /// The public key cannot be resolved from DNS, e.g., the selector record was removed.
+    #[error("the DKIM public key cannot be resolved: {0}")]
+    UnresolvablePublicKey(String),
+    /// The resolved public key cannot be appended to the audit log of [`DkimOptions::audit_log_path`].
+    #[error("failed to append to the DKIM audit log {path}: {message}")]
+    AuditLog { path: String, message: String },
+    /// The DKIM key record is not authenticated by DNSSEC although [`DkimOptions::require_dnssec`] is set.
+    #[error("the DKIM key record {0} is not authenticated by DNSSEC")]
+    UnauthenticatedKeyRecord(String),
+    /// The ARC headers do not form a valid chain, e.g., an instance is missing or a seal has `cv=fail`.
+    #[error("invalid ARC chain: {0}")]
+    InvalidArcChain(String),
+    /// The outermost ARC seal does not verify with the public key of the sealer.
+    #[error("the ARC seal of the instance {0} does not verify")]
+    InvalidArcSeal(u32),
+    /// The resolved public key is not an RSA key.
+    #[error("only RSA keys are supported")]
+    UnsupportedKeyType,
+    /// The email does not fit in the circuit of the configuration.
+    #[error(transparent)]
+    InvalidCircuitInput(#[from] crate::builder::EmailCircuitBuilderError),
+}
+
+/// The source of the public key that verified the DKIM signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicKeySource {
+    /// The key embedded in the [`EMBEDDED_PUBLIC_KEY_HEADER`] header.
+    Embedded,
+    /// The key resolved from DNS.
+    Dns,
+    /// The pinned key, which is tried only if the key resolved from DNS does not verify the signature.
+    Pinned,
+}
+
+impl std::fmt::Display for PublicKeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Embedded => write!(f, "the key embedded in the email"),
+            Self::Dns => write!(f, "the key resolved from DNS"),
+            Self::Pinned => write!(f, "the pinned key"),
+        }
+    }
+}
+
+/// A record of a DKIM public key resolved from DNS, which is written as a json line to the audit log of [`DkimOptions::audit_log_path`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DkimAuditRecord {
+    /// The signing domain, i.e., the `d=` tag.
+    pub domain: String,
+    /// The selector, i.e., the `s=` tag.
+    pub selector: String,
+    /// The hex string of the SHA256 hash of the big-endian bytes of the `n` parameter of the resolved RSA public key.
+    pub key_fingerprint: String,
+    /// The UNIX time in seconds when the key was resolved.
+    pub timestamp: u64,
+}
+
+impl DkimAuditRecord {
+    /// Record the public key resolved for the given email at the current time.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `public_key_n` - the `n` parameter of the resolved RSA public key.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(email_bytes: &[u8], public_key_n: &BigUint) -> Self {
+        Self {
+            domain: get_signing_domain(email_bytes).unwrap_or_default(),
+            selector: get_dkim_tag(email_bytes, "s").unwrap_or_default(),
+            key_fingerprint: public_key_fingerprint(public_key_n),
+            timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+        }
+    }
+
+    /// Append the record as a json line to the audit log, which is created if it does not exist.
+    ///
+    /// # Arguments
+    /// * `audit_log_path` - a file path of the audit log.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn append_to(&self, audit_log_path: &str) -> std::io::Result<()> {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(audit_log_path)?;
+        writeln!(file, "{}", serde_json::to_string(self).unwrap())
+    }
+
+    /// Read all the records of the audit log.
+    ///
+    /// # Arguments
+    /// * `audit_log_path` - a file path of the audit log.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_log(audit_log_path: &str) -> std::io::Result<Vec<Self>> {
+        std::fs::read_to_string(audit_log_path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+            .collect()
+    }
+}
+
+/// Compute the fingerprint of an RSA public key as in [`DkimAuditRecord::key_fingerprint`].
+///
+/// # Arguments
+/// * `public_key_n` - the `n` parameter of the RSA public key.
+pub fn public_key_fingerprint(public_key_n: &BigUint) -> String {
+    hex::encode(Sha256::digest(public_key_n.to_bytes_be()))
+}
+
+/// Append the public key resolved from DNS for the given email to the audit log if it is given.
+///
+/// # Arguments
+/// * `audit_log_path` - the path to the audit log, or `None` not to record the key.
+/// * `email_bytes` - email bytes.
+/// * `public_key_n` - the `n` parameter of the resolved RSA public key.
+/// # Return values
+/// Return [`DkimError::AuditLog`] if the record cannot be appended, e.g., the directory of the audit log does not exist.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn audit_public_key_resolution(audit_log_path: Option<&str>, email_bytes: &[u8], public_key_n: &BigUint) -> Result<(), DkimError> {
+    match audit_log_path {
+        Some(audit_log_path) => DkimAuditRecord::new(email_bytes, public_key_n)
+            .append_to(audit_log_path)
+            .map_err(|err| DkimError::AuditLog {
+                path: audit_log_path.to_string(),
+                message: err.to_string(),
+            }),
+        None => Ok(()),
+    }
+}
+
+/// Parse the RSA public key modulus from a json response of the DNSSEC-validating resolver for the DKIM key record.
+///
+/// # Arguments
+/// * `record_name` - the queried name, e.g., `default._domainkey.zkemail.com`.
+/// * `response` - the json response.
+/// # Return values
+/// Return the `n` parameter of the public key in the `p=` tag of the TXT answer for `record_name` if the query succeeded and the response has the `AD` flag,
+/// [`DkimError::UnauthenticatedKeyRecord`] if it does not have the flag, and [`DkimError::UnresolvablePublicKey`] if the query failed or no answer has a valid key.
+pub fn parse_dnssec_response(record_name: &str, response: &str) -> Result<BigUint, DkimError> {
+    let response: serde_json::Value =
+        serde_json::from_str(response).map_err(|err| DkimError::UnresolvablePublicKey(format!("invalid DNS response for {}: {}", record_name, err)))?;
+    // 0 is NOERROR.
+    if response["Status"].as_u64() != Some(0) {
+        return Err(DkimError::UnresolvablePublicKey(format!("the DNS query of {} failed with the status {}", record_name, response["Status"])));
+    }
+    if response["AD"].as_bool() != Some(true) {
+        return Err(DkimError::UnauthenticatedKeyRecord(record_name.to_string()));
+    }
+    let answers = response["Answer"].as_array().map(|answers| answers.as_slice()).unwrap_or_default();
+    answers
+        .iter()
+        // 16 is TXT. The answers may also include the CNAME records followed to the key record.
+        .filter(|answer| answer["type"].as_u64() == Some(16))
+        .filter_map(|answer| answer["data"].as_str())
+        .find_map(|data| parse_public_key_record(&join_txt_strings(data)).ok())
+        .ok_or_else(|| DkimError::UnresolvablePublicKey(format!("the authenticated answer for {} has no valid DKIM public key", record_name)))
+}
+
+/// Join the character strings of a TXT record, which some resolvers return quoted as `"v=DKIM1; k=rsa; " "p=MIIB..."` and others return already joined.
+fn join_txt_strings(data: &str) -> String {
+    if !data.starts_with('"') {
+        return data.to_string();
+    }
+    let mut joined = String::new();
+    let mut in_quote = false;
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '\\' if in_quote => joined.extend(chars.next()),
+            c if in_quote => joined.push(c),
+            _ => {}
+        }
+    }
+    joined
+}
+
+/// Resolve the RSA public key modulus of the given domain and selector from the DKIM key record authenticated by DNSSEC if [`DkimOptions::require_dnssec`] is set.
+/// The record is queried to the DNSSEC-validating resolver of [`DkimOptions::dnssec_resolver_url`], and the key is taken from its authenticated answer,
+/// so that the key resolved without DNSSEC is never used for proving.
+///
+/// # Arguments
+/// * `options` - the options of resolving the public key.
+/// * `domain` - the signing domain, i.e., the `d=` tag.
+/// * `selector` - the selector, i.e., the `s=` tag.
+/// # Return values
+/// Return `Ok(Some(public_key_n))` if [`DkimOptions::require_dnssec`] is set and the key record is authenticated, `Ok(None)` if it is not set, otherwise [`DkimError`].
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn resolve_dnssec_public_key_n(options: &DkimOptions, domain: &str, selector: &str) -> Result<Option<BigUint>, DkimError> {
+    if !options.require_dnssec {
+        return Ok(None);
+    }
+    let resolver_url = options.dnssec_resolver_url.as_deref().unwrap_or(DEFAULT_DNSSEC_RESOLVER_URL);
+    let record_name = format!("{}._domainkey.{}", selector, domain);
+    let to_err = |err: reqwest::Error| DkimError::UnresolvablePublicKey(format!("fail to query {}: {}", record_name, err));
+    let response = reqwest::Client::new()
+        .get(resolver_url)
+        .header("accept", "application/dns-json")
+        .query(&[("name", record_name.as_str()), ("type", "TXT"), ("do", "1")])
+        .send()
+        .await
+        .map_err(to_err)?
+        .text()
+        .await
+        .map_err(to_err)?;
+    parse_dnssec_response(&record_name, &response).map(Some)
+}
+
+/// Parse the tags of the first DKIM signature header in the given email.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return a list of (tag name, tag value) if the DKIM signature header exists, otherwise `None`.
+pub fn get_dkim_tags(email_bytes: &[u8]) -> Option<Vec<(String, String)>> {
+    let (headers, _) = parse_headers(email_bytes).ok()?;
+    let dkim_header = headers.get_first_value(DKIM_SIGNATURE_HEADER)?;
+    let tags = dkim_header
+        .split(';')
+        .filter_map(|tag| {
+            let (name, value) = tag.split_once('=')?;
+            let value = value.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+            Some((name.trim().to_string(), value))
+        })
+        .collect();
+    Some(tags)
+}
+
+/// Get the value of the given tag in the DKIM signature header.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `tag_name` - the tag name, e.g., `d` and `s`.
+/// # Return values
+/// Return the tag value if it exists, otherwise `None`.
+pub fn get_dkim_tag(email_bytes: &[u8], tag_name: &str) -> Option<String> {
+    get_dkim_tags(email_bytes)?.into_iter().find(|(name, _)| name == tag_name).map(|(_, value)| value)
+}
+
+/// Get the signing domain, i.e., the `d=` tag, in the DKIM signature header.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return the signing domain if it exists, otherwise `None`.
+pub fn get_signing_domain(email_bytes: &[u8]) -> Option<String> {
+    get_dkim_tag(email_bytes, "d")
+}
+
+/// Check whether the signing domain of the signature to be proven is in the allowed domains.
+/// It is the DKIM signature selected by [`DkimOptions::signature_index`], or the outermost ARC message signature if `arc` is `true`.
+/// The domains are compared case-insensitively after converting internationalized domains to punycode, so `例え.jp` is allowed for `d=xn--r8jz45g.jp`.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `allowed_domains` - a list of the allowed signing domains.
+/// * `options` - the options that select the DKIM signature to be proven.
+/// * `arc` - whether the outermost ARC message signature is proven instead of the DKIM signature.
+/// # Return values
+/// Return `Ok(domain)` if the signing domain is allowed, otherwise [`DkimError`].
+pub fn check_allowed_domain(email_bytes: &[u8], allowed_domains: &[String], options: &DkimOptions, arc: bool) -> Result<String, DkimError> {
+    let domain = if arc {
+        crate::arc::get_arc_sets(email_bytes)?.last().and_then(|set| set.message_signature_tag("d"))
+    } else {
+        get_signing_domain(&options.select_signature(email_bytes.to_vec())?)
+    }
+    .ok_or_else(|| DkimError::MissingTag("d".to_string()))?;
+    let ascii_domain = to_ascii_domain(&domain);
+    if allowed_domains.iter().any(|allowed| to_ascii_domain(allowed).eq_ignore_ascii_case(&ascii_domain)) {
+        Ok(domain)
+    } else {
+        Err(DkimError::DisallowedDomain {
+            domain,
+            allowed: allowed_domains.to_vec(),
+        })
+    }
+}
+
+/// Convert an internationalized domain to its punycode form, or return it as it is if it is not a valid domain.
+fn to_ascii_domain(domain: &str) -> String {
+    idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_string())
+}
+
+/// Check whether the signature algorithm, i.e., the `a=` tag, of the given email is supported by the circuit.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return `Ok(algorithm)` if the algorithm is one of [`SUPPORTED_ALGORITHMS`], otherwise [`DkimError`].
+pub fn check_signature_algorithm(email_bytes: &[u8]) -> Result<String, DkimError> {
+    let algorithm = get_dkim_tag(email_bytes, "a").ok_or_else(|| DkimError::MissingTag("a".to_string()))?;
+    if SUPPORTED_ALGORITHMS.iter().any(|supported| supported.eq_ignore_ascii_case(&algorithm)) {
+        Ok(algorithm)
+    } else {
+        Err(DkimError::UnsupportedAlgorithm(algorithm))
+    }
+}
+
+/// A summary of a DKIM signature header of an email listed by [`list_dkim_signatures`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkimSignatureSummary {
+    /// The signature algorithm, i.e., the `a=` tag.
+    pub algorithm: String,
+    /// The signing domain, i.e., the `d=` tag.
+    pub domain: String,
+    /// The selector, i.e., the `s=` tag.
+    pub selector: String,
+}
+
+impl DkimSignatureSummary {
+    /// Return `true` if the signature algorithm is one of [`SUPPORTED_ALGORITHMS`], i.e., the signature can be proven.
+    pub fn is_supported(&self) -> bool {
+        SUPPORTED_ALGORITHMS.iter().any(|supported| supported.eq_ignore_ascii_case(&self.algorithm))
+    }
+}
+
+// Split the header of the email into its fields, each of which includes its folded lines and line endings, and return them with the rest of the email, i.e., the blank line and the body.
+fn split_header_fields(email_bytes: &[u8]) -> (Vec<&[u8]>, &[u8]) {
+    let mut fields = vec![];
+    let mut field_start = 0;
+    let mut pos = 0;
+    while pos < email_bytes.len() {
+        let line_end = email_bytes[pos..].iter().position(|&b| b == b'\n').map(|idx| pos + idx + 1).unwrap_or(email_bytes.len());
+        let line = &email_bytes[pos..line_end];
+        if line == b"\r\n" || line == b"\n" {
+            break;
+        }
+        if pos > field_start && !line.starts_with(b" ") && !line.starts_with(b"\t") {
+            fields.push(&email_bytes[field_start..pos]);
+            field_start = pos;
+        }
+        pos = line_end;
+    }
+    if pos > field_start {
+        fields.push(&email_bytes[field_start..pos]);
+    }
+    (fields, &email_bytes[pos..])
+}
+
+// Whether the header field is a DKIM signature header, whose name is case-insensitive.
+fn is_dkim_signature_field(field: &[u8]) -> bool {
+    match field.iter().position(|&b| b == b':') {
+        Some(idx) => String::from_utf8_lossy(&field[..idx]).trim().eq_ignore_ascii_case(DKIM_SIGNATURE_HEADER),
+        None => false,
+    }
+}
+
+/// List the DKIM signature headers of the given email in their order, e.g., to choose the signature to be proven with [`select_dkim_signature`].
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return a list of [`DkimSignatureSummary`], whose missing tags are empty strings.
+pub fn list_dkim_signatures(email_bytes: &[u8]) -> Vec<DkimSignatureSummary> {
+    let (fields, _) = split_header_fields(email_bytes);
+    fields
+        .into_iter()
+        .filter(|field| is_dkim_signature_field(field))
+        .map(|field| {
+            let header = [field, b"\r\n"].concat();
+            DkimSignatureSummary {
+                algorithm: get_dkim_tag(&header, "a").unwrap_or_default(),
+                domain: get_dkim_tag(&header, "d").unwrap_or_default(),
+                selector: get_dkim_tag(&header, "s").unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Select the DKIM signature to be proven by moving its header to the top of the email, since the first DKIM signature header is the one verified and proven.
+/// The other header fields keep their order, so the selected signature still verifies unless it signs the DKIM signature headers themselves.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `index` - the index of the DKIM signature header, which counts from zero in the order of [`list_dkim_signatures`].
+/// # Return values
+/// Return the email bytes whose first DKIM signature header is the selected one, or [`DkimError::MissingSignature`] if the index is out of range.
+pub fn select_dkim_signature(email_bytes: &[u8], index: usize) -> Result<Vec<u8>, DkimError> {
+    let (fields, rest) = split_header_fields(email_bytes);
+    let signature_positions = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| is_dkim_signature_field(field))
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    let selected = *signature_positions.get(index).ok_or(DkimError::MissingSignature {
+        index,
+        num_signatures: signature_positions.len(),
+    })?;
+    let mut selected_bytes = fields[selected].to_vec();
+    for (_, field) in fields.iter().enumerate().filter(|(pos, _)| *pos != selected) {
+        selected_bytes.extend_from_slice(field);
+    }
+    selected_bytes.extend_from_slice(rest);
+    Ok(selected_bytes)
+}
+
+/// Check whether the public key of the given email can be retrieved with one of the query methods, i.e., the colon-separated list in the `q=` tag.
+/// The query method defaults to `dns/txt` if the tag is omitted.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return `Ok(query_method)` of the first query method in [`SUPPORTED_QUERY_METHODS`], otherwise [`DkimError`].
+pub fn check_query_method(email_bytes: &[u8]) -> Result<String, DkimError> {
+    let query_methods = match get_dkim_tag(email_bytes, "q") {
+        Some(query_methods) => query_methods,
+        None => return Ok(SUPPORTED_QUERY_METHODS[0].to_string()),
+    };
+    let supported_method = query_methods
+        .split(':')
+        .find(|method| SUPPORTED_QUERY_METHODS.iter().any(|supported| supported.eq_ignore_ascii_case(method)))
+        .map(|method| method.to_string());
+    supported_method.ok_or(DkimError::UnsupportedQueryMethod(query_methods))
+}
+
+/// Canonicalize the email, and then truncate the canonicalized body to the body length, i.e., the `l=` tag, if any.
+/// Only the first `l` bytes of the canonicalized body are hashed in `bh=`, e.g., none of them if `l=0`, so the rest is ignored by the circuit.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return the canonicalized header, the signed part of the canonicalized body, and the signature bytes, otherwise [`DkimError`].
+pub fn canonicalize_signed_email_with_body_length(email_bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), DkimError> {
+    let (header_bytes, mut body_bytes, signature_bytes) = cfdkim::canonicalize_signed_email(email_bytes).map_err(|err| DkimError::InvalidEmail(format!("{:?}", err)))?;
+    truncate_body_length(get_dkim_tag(email_bytes, "l"), &mut body_bytes)?;
+    Ok((header_bytes, body_bytes, signature_bytes))
+}
+
+// Truncate the canonicalized body to the body length, i.e., the value of the `l=` tag, if any.
+fn truncate_body_length(length: Option<String>, body_bytes: &mut Vec<u8>) -> Result<(), DkimError> {
+    if let Some(length) = length {
+        let invalid = || DkimError::InvalidBodyLength {
+            length: length.clone(),
+            body_len: body_bytes.len(),
+        };
+        let body_len = length.parse::<usize>().map_err(|_| invalid())?;
+        if body_len > body_bytes.len() {
+            return Err(invalid());
+        }
+        body_bytes.truncate(body_len);
+    }
+    Ok(())
+}
+
+/// Canonicalize an email already parsed by `mailparse` in the same way as [`canonicalize_signed_email_with_body_length`].
+/// The parsed headers are only used to check that the email has a DKIM signature header, and the raw bytes of the email are canonicalized by `cfdkim`,
+/// so that the header and the body are split and canonicalized exactly as for the raw bytes, e.g., for an email whose lines end with LF only.
+///
+/// # Arguments
+/// * `parsed` - the parsed email, e.g., by `mailparse::parse_mail`.
+/// # Return values
+/// Return the canonicalized header, the signed part of the canonicalized body, and the signature bytes, otherwise [`DkimError`].
+pub fn canonicalize_parsed_email(parsed: &mailparse::ParsedMail) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), DkimError> {
+    if parsed.headers.get_first_header(DKIM_SIGNATURE_HEADER).is_none() {
+        return Err(DkimError::UnsignedEmail);
+    }
+    canonicalize_signed_email_with_body_length(parsed.raw_bytes)
+}
+
+/// Check whether the body hash, i.e., the `bh=` tag, of the given email matches the base64 encoded SHA256 hash of its canonicalized body truncated to the body length, i.e., the `l=` tag, if any.
+/// Otherwise, the email verification circuit is unsatisfiable.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return `Ok(body_hash)` if the body hash matches, otherwise [`DkimError`].
+pub fn check_body_hash(email_bytes: &[u8]) -> Result<String, DkimError> {
+    let header_body_hash = get_dkim_tag(email_bytes, "bh").ok_or_else(|| DkimError::MissingTag("bh".to_string()))?;
+    let (_, canonicalized_body, _) = canonicalize_signed_email_with_body_length(email_bytes)?;
+    let computed_body_hash = general_purpose::STANDARD.encode(Sha256::digest(&canonicalized_body));
+    if header_body_hash == computed_body_hash {
+        Ok(computed_body_hash)
+    } else {
+        Err(DkimError::BodyHashMismatch {
+            header: header_body_hash,
+            computed: computed_body_hash,
+        })
+    }
+}
+
+/// Get the names of the signed header fields, i.e., the `h=` tag, in the DKIM signature header.
+/// A name listed multiple times, e.g., `from:from` to oversign the `From` header, is kept as many times as listed.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return the lowercase names in the listed order if the tag exists, otherwise `None`.
+pub fn get_signed_header_names(email_bytes: &[u8]) -> Option<Vec<String>> {
+    let names = get_dkim_tag(email_bytes, "h")?;
+    Some(names.split(':').filter(|name| !name.is_empty()).map(|name| name.to_ascii_lowercase()).collect())
+}
+
+/// Select the header fields signed by the DKIM signature as specified in RFC 6376 section 5.4.2.
+/// Each occurrence of a name in the `h=` tag selects the last header field of that name not yet selected, from the bottom up,
+/// and an occurrence without such a header field, e.g., the second `from` of an oversigned `From` header or a `bcc` listed to prevent adding a `Bcc` header, selects nothing.
+/// Such an occurrence is hashed as the null string, so it adds no line to the canonicalized header.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return a list of (lowercase name, value) in the order in which they are hashed, otherwise [`DkimError`].
+pub fn select_signed_headers(email_bytes: &[u8]) -> Result<Vec<(String, String)>, DkimError> {
+    let names = get_signed_header_names(email_bytes).ok_or_else(|| DkimError::MissingTag("h".to_string()))?;
+    let (headers, _) = parse_headers(email_bytes).map_err(|err| DkimError::InvalidEmail(err.to_string()))?;
+    let mut is_selected = vec![false; headers.len()];
+    let mut selected = vec![];
+    for name in names {
+        if let Some(idx) = (0..headers.len()).rev().find(|idx| !is_selected[*idx] && headers[*idx].get_key().eq_ignore_ascii_case(&name)) {
+            is_selected[idx] = true;
+            selected.push((name, headers[idx].get_value()));
+        }
+    }
+    Ok(selected)
+}
+
+/// Check whether the canonicalized header to be proven contains each signed header field as many times as selected by [`select_signed_headers`].
+/// Otherwise, e.g., if an oversigned header were included twice, the header hash would not match the DKIM signature.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return `Ok(names)` of the signed header fields in the hashed order if they match, otherwise [`DkimError`].
+pub fn check_signed_headers(email_bytes: &[u8]) -> Result<Vec<String>, DkimError> {
+    let expected = select_signed_headers(email_bytes)?.into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let (canonicalized_header, _, _) = cfdkim::canonicalize_signed_email(email_bytes).map_err(|err| DkimError::InvalidEmail(format!("{:?}", err)))?;
+    let mut actual = String::from_utf8_lossy(&canonicalized_header)
+        .split("\r\n")
+        .filter(|line| !line.is_empty() && !line.starts_with(|c: char| c == ' ' || c == '\t'))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, _)| name.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    // The last field is the DKIM signature header itself.
+    actual.pop();
+    if actual == expected {
+        Ok(expected)
+    } else {
+        Err(DkimError::SignedHeaderMismatch { expected, actual })
+    }
+}
+
+/// Check whether the `From` header is listed in the `h=` tag of the DKIM signature header.
+/// Otherwise, the `From` header is not covered by the signature and can be spoofed, so that any proof about the sender is meaningless.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return `Ok(())` if the `From` header is signed, otherwise [`DkimError`].
+pub fn check_from_signed(email_bytes: &[u8]) -> Result<(), DkimError> {
+    let names = get_signed_header_names(email_bytes).ok_or_else(|| DkimError::MissingTag("h".to_string()))?;
+    if names.iter().any(|name| name == "from") {
+        Ok(())
+    } else {
+        Err(DkimError::UnsignedFrom(names))
+    }
+}
+
+/// Get the RSA public key modulus embedded in the [`EMBEDDED_PUBLIC_KEY_HEADER`] header of the given email.
+/// The header value is a DKIM key record whose `p=` tag is a base64-encoded DER public key in either the SubjectPublicKeyInfo or PKCS#1 format.
+///
+/// Note that the embedded key is not authenticated by the DKIM signature.
+/// The verifier must still check the public key hash exposed by the circuit against a trusted key.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return `Ok(Some(public_key_n))` if the header exists, `Ok(None)` if it does not exist, otherwise [`DkimError`].
+pub fn get_embedded_public_key_n(email_bytes: &[u8]) -> Result<Option<BigUint>, DkimError> {
+    Ok(get_embedded_public_key_ns(email_bytes)?.into_iter().next())
+}
+
+/// Get the RSA public key moduli embedded in all the [`EMBEDDED_PUBLIC_KEY_HEADER`] headers of the given email, e.g., when the selector had multiple key records.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// # Return values
+/// Return the public key moduli in the order of the headers, otherwise [`DkimError`] if any of them is invalid.
+pub fn get_embedded_public_key_ns(email_bytes: &[u8]) -> Result<Vec<BigUint>, DkimError> {
+    let headers = match parse_headers(email_bytes) {
+        Ok((headers, _)) => headers.get_all_values(EMBEDDED_PUBLIC_KEY_HEADER),
+        Err(_) => return Ok(vec![]),
+    };
+    headers.iter().map(|header| parse_public_key_record(header)).collect()
+}
+
+/// Select the public key that verifies the DKIM signature of the given email among the candidate keys.
+/// The signature is verified natively with the public exponent 65537, as in the circuit.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `candidates` - the `n` parameters of the candidate RSA public keys.
+/// # Return values
+/// Return the first candidate that verifies the signature, otherwise [`DkimError`].
+pub fn select_public_key_n(email_bytes: &[u8], candidates: &[BigUint]) -> Result<BigUint, DkimError> {
+    select_public_key_n_with_padding(email_bytes, candidates, None)
+}
+
+/// Select the public key that verifies the DKIM signature of the given email among the candidate keys in the same way as [`select_public_key_n`],
+/// but with the RSASSA-PSS padding if `pss_salt_len` is given, as `sign_verify_config.pss_salt_len` of the circuit.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `candidates` - the `n` parameters of the candidate RSA public keys.
+/// * `pss_salt_len` - the salt length in bytes of the RSASSA-PSS padding, or `None` for the PKCS#1 v1.5 padding.
+/// # Return values
+/// Return the first candidate that verifies the signature, otherwise [`DkimError`].
+pub fn select_public_key_n_with_padding(email_bytes: &[u8], candidates: &[BigUint], pss_salt_len: Option<usize>) -> Result<BigUint, DkimError> {
+    let (canonicalized_header, _, signature_bytes) = cfdkim::canonicalize_signed_email(email_bytes).map_err(|err| DkimError::InvalidEmail(format!("{:?}", err)))?;
+    let header_hash = Sha256::digest(&canonicalized_header);
+    candidates
+        .iter()
+        .find(|candidate| {
+            RsaPublicKey::new(rsa::BigUint::from_bytes_be(&candidate.to_bytes_be()), rsa::BigUint::from(65537u64))
+                .map(|public_key| match pss_salt_len {
+                    Some(salt_len) => public_key.verify(Pss::new_with_salt::<Sha256>(salt_len), &header_hash, &signature_bytes).is_ok(),
+                    None => public_key.verify(Pkcs1v15Sign::new::<Sha256>(), &header_hash, &signature_bytes).is_ok(),
+                })
+                .unwrap_or(false)
+        })
+        .cloned()
+        .ok_or(DkimError::NoMatchingPublicKey(candidates.len()))
+}
+
+/// Parse a pinned RSA public key given as a DKIM key record, e.g., `v=DKIM1; k=rsa; p=MIIB...` formerly published in DNS.
+///
+/// # Arguments
+/// * `record` - the DKIM key record, whose `p=` tag is a base64-encoded DER public key in either the SubjectPublicKeyInfo or PKCS#1 format.
+/// # Return values
+/// Return the `n` parameter of the public key, otherwise [`DkimError`].
+pub fn parse_pinned_public_key(record: &str) -> Result<BigUint, DkimError> {
+    parse_public_key_record(record).map_err(|err| match err {
+        DkimError::InvalidEmbeddedPublicKey(message) => DkimError::InvalidPinnedPublicKey(message),
+        err => err,
+    })
+}
+
+fn parse_public_key_record(record: &str) -> Result<BigUint, DkimError> {
+    let encoded = record
+        .split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .find(|(name, _)| name.trim() == "p")
+        .map(|(_, value)| value.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+        .ok_or_else(|| DkimError::MissingTag("p".to_string()))?;
+    let der = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|err| DkimError::InvalidEmbeddedPublicKey(err.to_string()))?;
+    let public_key = RsaPublicKey::from_public_key_der(&der)
+        .or_else(|_| RsaPublicKey::from_pkcs1_der(&der))
+        .map_err(|err| DkimError::InvalidEmbeddedPublicKey(err.to_string()))?;
+    Ok(BigUint::from_bytes_be(&public_key.n().to_bytes_be()))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EMAIL: &[u8] =
+        b"DKIM-Signature: v=1; a=rsa-sha256; d=zkemail.com; s=default;\r\n c=relaxed/relaxed; h=from; bh=AAAA; b=BBBB\r\nFrom: alice@zkemail.com\r\n\r\nbody";
+
+    #[test]
+    fn test_dkim_audit_log() {
+        let audit_log_path = "./build/test_dkim_audit.log";
+        std::fs::create_dir_all("./build").unwrap();
+        let _ = std::fs::remove_file(audit_log_path);
+        let public_key_ns = [BigUint::from(65537u64), BigUint::from(3u64)];
+        for public_key_n in public_key_ns.iter() {
+            audit_public_key_resolution(Some(audit_log_path), EMAIL, public_key_n).unwrap();
+        }
+        audit_public_key_resolution(None, EMAIL, &public_key_ns[0]).unwrap();
+        let missing_dir_path = "./build/test_dkim_audit_missing/dkim_audit.log";
+        assert!(matches!(
+            audit_public_key_resolution(Some(missing_dir_path), EMAIL, &public_key_ns[0]),
+            Err(DkimError::AuditLog { path, .. }) if path == missing_dir_path
+        ));
+        let records = DkimAuditRecord::read_log(audit_log_path).unwrap();
+        assert_eq!(records.len(), 2);
+        for (record, public_key_n) in records.iter().zip(public_key_ns.iter()) {
+            assert_eq!(record.domain, "zkemail.com");
+            assert_eq!(record.selector, "default");
+            assert_eq!(record.key_fingerprint, public_key_fingerprint(public_key_n));
+            assert!(record.timestamp > 0);
+        }
+    }
+
+
+    #[test]
+    fn test_parse_dnssec_response() {
+        let record_name = "default._domainkey.zkemail.com";
+        use rsa::pkcs8::EncodePublicKey;
+        let public_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap().to_public_key();
+        let encoded = general_purpose::STANDARD.encode(public_key.to_public_key_der().unwrap().as_bytes());
+        let public_key_n = BigUint::from_bytes_be(&public_key.n().to_bytes_be());
+        let response = |ad: bool, data: &str| {
+            let answers = serde_json::json!([
+                {"name": "default._domainkey.zkemail.com.", "type": 5, "TTL": 300, "data": "alias.zkemail.com."},
+                {"name": "alias.zkemail.com.", "type": 16, "TTL": 300, "data": data},
+            ]);
+            serde_json::json!({"Status": 0, "TC": false, "RD": true, "RA": true, "AD": ad, "CD": false, "Answer": answers}).to_string()
+        };
+        let record = format!("v=DKIM1; k=rsa; p={}", encoded);
+        assert_eq!(parse_dnssec_response(record_name, &response(true, &record)), Ok(public_key_n.clone()));
+        let (first, second) = encoded.split_at(100);
+        let quoted = format!(r#""v=DKIM1; k=rsa; p={}" "{}""#, first, second);
+        assert_eq!(parse_dnssec_response(record_name, &response(true, &quoted)), Ok(public_key_n));
+        assert_eq!(
+            parse_dnssec_response(record_name, &response(false, &record)),
+            Err(DkimError::UnauthenticatedKeyRecord(record_name.to_string()))
+        );
+        assert!(matches!(
+            parse_dnssec_response(record_name, &response(true, "v=spf1 -all")),
+            Err(DkimError::UnresolvablePublicKey(_))
+        ));
+        let nxdomain = r#"{"Status":3,"TC":false,"RD":true,"RA":true,"AD":true,"CD":false}"#;
+        assert!(matches!(parse_dnssec_response(record_name, nxdomain), Err(DkimError::UnresolvablePublicKey(_))));
+    }
+
+    #[test]
+    fn test_get_signing_domain() {
+        assert_eq!(get_signing_domain(EMAIL), Some("zkemail.com".to_string()));
+        assert_eq!(get_dkim_tag(EMAIL, "s"), Some("default".to_string()));
+        assert_eq!(get_signing_domain(b"From: alice@zkemail.com\r\n\r\nbody"), None);
+    }
+
+    #[test]
+    fn test_check_allowed_domain() {
+        let options = DkimOptions::default();
+        assert!(check_allowed_domain(EMAIL, &["ZKEmail.com".to_string()], &options, false).is_ok());
+        assert!(check_allowed_domain(EMAIL, &["gmail.com".to_string()], &options, false).is_err());
+        let idn_email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("d=zkemail.com", "d=xn--r8jz45g.jp");
+        assert_eq!(
+            check_allowed_domain(idn_email.as_bytes(), &["例え.jp".to_string()], &options, false),
+            Ok("xn--r8jz45g.jp".to_string())
+        );
+        assert!(check_allowed_domain(idn_email.as_bytes(), &["xn--r8jz45g.jp".to_string()], &options, false).is_ok());
+        assert_eq!(
+            check_allowed_domain(EMAIL, &["zkemail.com".to_string()], &options, true),
+            Err(DkimError::MissingTag("d".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_check_allowed_domain_of_selected_signature() {
+        // Only the domain of the first signature is allowed, so the second one must not be proven under the allowlist.
+        let second_signature = b"DKIM-Signature: v=1; a=rsa-sha256; d=mallory.com; s=default;\r\n c=relaxed/relaxed; h=from; bh=AAAA; b=BBBB\r\n";
+        let (first_signature, rest) = EMAIL.split_at(EMAIL.windows(6).position(|window| window == b"From: ").unwrap());
+        let email_bytes = vec![first_signature, &second_signature[..], rest].concat();
+        let allowed_domains = ["zkemail.com".to_string()];
+        assert_eq!(
+            check_allowed_domain(&email_bytes, &allowed_domains, &DkimOptions::default(), false),
+            Ok("zkemail.com".to_string())
+        );
+        let options = DkimOptions {
+            signature_index: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_allowed_domain(&email_bytes, &allowed_domains, &options, false),
+            Err(DkimError::DisallowedDomain {
+                domain: "mallory.com".to_string(),
+                allowed: allowed_domains.to_vec(),
+            })
+        );
+        assert!(check_allowed_domain(&email_bytes, &["mallory.com".to_string()], &options, false).is_ok());
+        // The ARC message signature is proven with --arc instead of the DKIM signature.
+        let arc_headers = b"ARC-Seal: i=1; a=rsa-sha256; cv=none; d=google.com; s=arc; b=\r\n\
+            ARC-Message-Signature: i=1; a=rsa-sha256; d=mallory.com; s=arc; h=from; bh=AAAA; b=BBBB\r\n\
+            ARC-Authentication-Results: i=1; mx.google.com\r\n";
+        let arc_email = vec![&arc_headers[..], EMAIL].concat();
+        assert!(check_allowed_domain(&arc_email, &allowed_domains, &DkimOptions::default(), false).is_ok());
+        assert!(matches!(
+            check_allowed_domain(&arc_email, &allowed_domains, &DkimOptions::default(), true),
+            Err(DkimError::DisallowedDomain { .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_signature_algorithm() {
+        assert_eq!(check_signature_algorithm(EMAIL), Ok("rsa-sha256".to_string()));
+        let email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("a=rsa-sha256", "a=ed25519-sha256");
+        let err = check_signature_algorithm(email.as_bytes()).unwrap_err();
+        assert_eq!(err, DkimError::UnsupportedAlgorithm("ed25519-sha256".to_string()));
+        assert_eq!(
+            err.to_string(),
+            "the DKIM signature algorithm ed25519-sha256 is not supported; the supported algorithms are rsa-sha256"
+        );
+    }
+
+    #[test]
+    fn test_check_query_method() {
+        assert_eq!(check_query_method(EMAIL), Ok("dns/txt".to_string()));
+        let email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("s=default;", "s=default; q=DNS/TXT;");
+        assert_eq!(check_query_method(email.as_bytes()), Ok("DNS/TXT".to_string()));
+        let email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("s=default;", "s=default; q=http/well-known;");
+        let err = check_query_method(email.as_bytes()).unwrap_err();
+        assert_eq!(err, DkimError::UnsupportedQueryMethod("http/well-known".to_string()));
+        assert_eq!(
+            err.to_string(),
+            "the DKIM public key query method http/well-known is not supported; the supported methods are dns/txt"
+        );
+        let email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("s=default;", "s=default; q=http/well-known:dns/txt;");
+        assert_eq!(check_query_method(email.as_bytes()), Ok("dns/txt".to_string()));
+    }
+
+    #[test]
+    fn test_check_from_signed() {
+        assert_eq!(check_from_signed(EMAIL), Ok(()));
+        let email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("h=from;", "h=To:FROM;");
+        assert_eq!(check_from_signed(email.as_bytes()), Ok(()));
+        let email = String::from_utf8(EMAIL.to_vec()).unwrap().replace("h=from;", "h=to:subject;");
+        let err = check_from_signed(email.as_bytes()).unwrap_err();
+        assert_eq!(err, DkimError::UnsignedFrom(vec!["to".to_string(), "subject".to_string()]));
+        assert_eq!(err.to_string(), "the From header is not signed; the h= tag selects [\"to\", \"subject\"]");
+    }
+
+    #[test]
+    fn test_check_body_hash() {
+        use cfdkim::SignerBuilder;
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let message = b"From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+        let body_hash = check_body_hash(&email_bytes).unwrap();
+        let tampered = String::from_utf8(email_bytes).unwrap().replace("@zkemailverify", "@zkemailverifz");
+        let err = check_body_hash(tampered.as_bytes()).unwrap_err();
+        assert!(matches!(&err, DkimError::BodyHashMismatch { header, .. } if header == &body_hash));
+        assert!(err.to_string().starts_with(&format!("body hash mismatch: header says {}, computed ", body_hash)));
+    }
+
+    #[test]
+    fn test_body_length() {
+        use cfdkim::SignerBuilder;
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let message = b"From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+        let (_, body_bytes, _) = canonicalize_signed_email_with_body_length(&email_bytes).unwrap();
+        let with_length = |length: &str| String::from_utf8(email_bytes.clone()).unwrap().replacen("v=1;", &format!("v=1; l={};", length), 1).into_bytes();
+        let (_, truncated_body, _) = canonicalize_signed_email_with_body_length(&with_length("5")).unwrap();
+        assert_eq!(truncated_body, body_bytes[..5].to_vec());
+        let (_, empty_body, _) = canonicalize_signed_email_with_body_length(&with_length("0")).unwrap();
+        assert!(empty_body.is_empty());
+        assert_eq!(
+            canonicalize_signed_email_with_body_length(&with_length("1000")).unwrap_err(),
+            DkimError::InvalidBodyLength {
+                length: "1000".to_string(),
+                body_len: body_bytes.len()
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_public_key_n() {
+        use cfdkim::SignerBuilder;
+        let private_keys = (0..2).map(|_| rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap()).collect::<Vec<_>>();
+        let candidates = private_keys
+            .iter()
+            .map(|private_key| BigUint::from_bytes_be(&RsaPublicKey::from(private_key).n().to_bytes_be()))
+            .collect::<Vec<_>>();
+        let message = b"From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_keys[1].clone()))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+        assert_eq!(select_public_key_n(&email_bytes, &candidates), Ok(candidates[1].clone()));
+        assert_eq!(select_public_key_n(&email_bytes, &candidates[..1]), Err(DkimError::NoMatchingPublicKey(1)));
+    }
+
+    #[test]
+    fn test_select_signed_headers() {
+        let email = String::from_utf8(EMAIL.to_vec())
+            .unwrap()
+            .replace("h=from;", "h=from:subject:from:from;")
+            .replace("From: alice@zkemail.com\r\n", "From: alice@zkemail.com\r\nSubject: hi\r\nFrom: bob@zkemail.com\r\n");
+        assert_eq!(
+            get_signed_header_names(email.as_bytes()),
+            Some(vec!["from".to_string(), "subject".to_string(), "from".to_string(), "from".to_string()])
+        );
+        assert_eq!(
+            select_signed_headers(email.as_bytes()),
+            Ok(vec![
+                ("from".to_string(), "bob@zkemail.com".to_string()),
+                ("subject".to_string(), "hi".to_string()),
+                ("from".to_string(), "alice@zkemail.com".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_oversigned_from() {
+        use cfdkim::SignerBuilder;
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let public_key_n = BigUint::from_bytes_be(&RsaPublicKey::from(&private_key).n().to_bytes_be());
+        let message = b"From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From", "From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+        assert_eq!(get_signed_header_names(&email_bytes), Some(vec!["from".to_string(), "from".to_string()]));
+        // The second `from` selects nothing, so the canonicalized header has only one From field.
+        assert_eq!(check_signed_headers(&email_bytes), Ok(vec!["from".to_string()]));
+        assert_eq!(select_public_key_n(&email_bytes, &[public_key_n.clone()]), Ok(public_key_n.clone()));
+        // A From header injected after signing is selected by the oversigned `from` and breaks the signature.
+        let injected = vec![signature.as_bytes(), b"\r\nFrom: mallory@zkemail.com\r\n", message].concat();
+        assert_eq!(check_signed_headers(&injected), Ok(vec!["from".to_string(), "from".to_string()]));
+        assert_eq!(select_public_key_n(&injected, &[public_key_n]), Err(DkimError::NoMatchingPublicKey(1)));
+    }
+
+    #[test]
+    fn test_oversigned_absent_bcc() {
+        use cfdkim::SignerBuilder;
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let public_key_n = BigUint::from_bytes_be(&RsaPublicKey::from(&private_key).n().to_bytes_be());
+        let message = b"From: alice@zkemail.com\r\nTo: bob@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From", "To", "Bcc"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+        assert_eq!(get_signed_header_names(&email_bytes), Some(vec!["from".to_string(), "to".to_string(), "bcc".to_string()]));
+        // The absent Bcc header is hashed as the null string, so the canonicalized header has no Bcc field.
+        assert_eq!(check_signed_headers(&email_bytes), Ok(vec!["from".to_string(), "to".to_string()]));
+        let (canonicalized_header, _, _) = canonicalize_signed_email_with_body_length(&email_bytes).unwrap();
+        assert!(!String::from_utf8_lossy(&canonicalized_header).split("\r\n").any(|line| line.starts_with("bcc:")));
+        assert_eq!(select_public_key_n(&email_bytes, &[public_key_n.clone()]), Ok(public_key_n.clone()));
+        // A Bcc header added after signing is selected by the oversigned `bcc` and breaks the signature.
+        let injected = vec![signature.as_bytes(), b"\r\nBcc: mallory@zkemail.com\r\n", message].concat();
+        assert_eq!(check_signed_headers(&injected), Ok(vec!["from".to_string(), "to".to_string(), "bcc".to_string()]));
+        assert_eq!(select_public_key_n(&injected, &[public_key_n]), Err(DkimError::NoMatchingPublicKey(1)));
+    }
+
+    #[test]
+    fn test_select_dkim_signature() {
+        use cfdkim::SignerBuilder;
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let public_key_n = BigUint::from_bytes_be(&RsaPublicKey::from(&private_key).n().to_bytes_be());
+        let message = b"From: alice@zkemail.com\r\nTo: bob@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From", "To"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let ed25519_signature = b"DKIM-Signature: v=1; a=ed25519-sha256; d=zkemail.com; s=ed;\r\n c=relaxed/relaxed; h=from:to; bh=AAAA; b=BBBB\r\n";
+        let email_bytes = vec![&ed25519_signature[..], signature.as_bytes(), b"\r\n", message].concat();
+        let summaries = list_dkim_signatures(&email_bytes);
+        assert_eq!(
+            summaries.iter().map(|summary| (summary.algorithm.as_str(), summary.selector.as_str(), summary.is_supported())).collect::<Vec<_>>(),
+            vec![("ed25519-sha256", "ed", false), ("rsa-sha256", "default", true)]
+        );
+        let first = select_dkim_signature(&email_bytes, 0).unwrap();
+        assert_eq!(first, email_bytes);
+        assert_eq!(check_signature_algorithm(&first), Err(DkimError::UnsupportedAlgorithm("ed25519-sha256".to_string())));
+        let second = select_dkim_signature(&email_bytes, 1).unwrap();
+        assert_eq!(second, vec![signature.as_bytes(), b"\r\n", &ed25519_signature[..], message].concat());
+        assert_eq!(check_signature_algorithm(&second), Ok("rsa-sha256".to_string()));
+        assert_eq!(select_public_key_n(&second, &[public_key_n.clone()]), Ok(public_key_n));
+        assert_eq!(
+            select_dkim_signature(&email_bytes, 2),
+            Err(DkimError::MissingSignature { index: 2, num_signatures: 2 })
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_parsed_email() {
+        use cfdkim::canonicalization::Type;
+        use cfdkim::SignerBuilder;
+        let message = b"From: Alice  <alice@zkemail.com>\r\nSubject: Re:  your\r\n\tcode \r\nTo: bob@zkemail.com\r\n\r\nemail  was meant\tfor @zkemailverify. \r\n\r\n\r\n";
+        let email = mailparse::parse_mail(message).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        for (header_canonicalization, body_canonicalization) in [(Type::Relaxed, Type::Relaxed), (Type::Relaxed, Type::Simple), (Type::Simple, Type::Simple)] {
+            let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["From", "Subject", "To", "Cc"])
+                .unwrap()
+                .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(header_canonicalization)
+                .with_body_canonicalization(body_canonicalization)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&email).unwrap();
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+            let parsed = mailparse::parse_mail(&email_bytes).unwrap();
+            assert_eq!(canonicalize_parsed_email(&parsed), canonicalize_signed_email_with_body_length(&email_bytes));
+            // The lines of the header and the body may end with LF only.
+            let lf_email_bytes = String::from_utf8(email_bytes.clone()).unwrap().replace("\r\n", "\n").into_bytes();
+            let parsed = mailparse::parse_mail(&lf_email_bytes).unwrap();
+            assert_eq!(canonicalize_parsed_email(&parsed), canonicalize_signed_email_with_body_length(&lf_email_bytes));
+        }
+        assert_eq!(canonicalize_parsed_email(&email), Err(DkimError::UnsignedEmail));
+    }
+
+    #[test]
+    fn test_get_embedded_public_key_n() {
+        use rsa::pkcs8::EncodePublicKey;
+        assert_eq!(get_embedded_public_key_n(EMAIL), Ok(None));
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let public_key = RsaPublicKey::from(&private_key);
+        let der = public_key.to_public_key_der().unwrap();
+        let header = format!("{}: v=DKIM1; k=rsa; p={}\r\n", EMBEDDED_PUBLIC_KEY_HEADER, general_purpose::STANDARD.encode(der.as_bytes()));
+        let email = vec![header.as_bytes(), EMAIL].concat();
+        let expected = BigUint::from_bytes_be(&public_key.n().to_bytes_be());
+        assert_eq!(get_embedded_public_key_n(&email), Ok(Some(expected)));
+        let header = format!("{}: v=DKIM1; k=rsa; p=AAAA\r\n", EMBEDDED_PUBLIC_KEY_HEADER);
+        let email = vec![header.as_bytes(), EMAIL].concat();
+        assert!(matches!(get_embedded_public_key_n(&email), Err(DkimError::InvalidEmbeddedPublicKey(_))));
+    }
+}
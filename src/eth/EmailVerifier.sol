@@ -1,5 +1,5 @@
 // SPDX-License-Identifier: MIT
-pragma solidity ^0.8.17;
+pragma solidity <%solidity_version%>;
 import "./VerifierBase.sol";
 
 contract EmailVerifier {
@@ -8,6 +8,7 @@ contract EmailVerifier {
     address public verifierBase;
     uint public maxHeaderBytes;
     uint public maxBodyBytes;
+<%verified_event_decl%>
 
     constructor(
         address _verifierBase,
@@ -22,17 +23,17 @@ contract EmailVerifier {
     function verifyEmail(
         bytes memory instance,
         bytes memory proof
-    ) public view {
+    ) public<%verify_email_mutability%> {
         (
             uint headerHashCommit,
             uint publicKeyHash,
             string[] memory headerSubstrs,
             uint[] memory headerSubstrStarts,
             string[] memory bodySubstrs,
-            uint[] memory bodySubstrStarts
+            uint[] memory bodySubstrStarts<%block_number_decl%>
         ) = abi.decode(
                 instance,
-                (uint, uint, string[], uint[], string[], uint[])
+                (uint, uint, string[], uint[], string[], uint[]<%block_number_type%>)
             );
         uint rlc = 0;
         uint coeff = headerHashCommit;
@@ -54,11 +55,13 @@ contract EmailVerifier {
         (rlc, coeff) = computeRLC(rlc, coeff, headerHashCommit, substrIds);
 
         VerifierBase verifier = VerifierBase(verifierBase);
-        uint[] memory pubInputs = new uint[](3);
+        uint[] memory pubInputs = new uint[](<%num_instances%>);
         pubInputs[0] = headerHashCommit;
         pubInputs[1] = publicKeyHash;
         pubInputs[2] = rlc;
+<%block_hash_instances%>
         require(verifier.verify(pubInputs, proof), "invalid proof");
+<%verified_event_emit%>
     }
 
     function getMaskedCharsAndIds(
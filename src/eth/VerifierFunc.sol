@@ -1,5 +1,5 @@
 // SPDX-License-Identifier: MIT
-pragma solidity ^0.8.17;
+pragma solidity <%solidity_version%>;
 import "./VerifierFuncAbst.sol";
 
 contract VerifierFunc<%ID%> is VerifierFuncAbst {
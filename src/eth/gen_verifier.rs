@@ -19,14 +19,25 @@ use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::PathBuf;
 use std::rc::Rc;
 
-pub fn gen_sol_verifiers(params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>, max_line_size_per_file: usize, sols_dir: &PathBuf) {
-    let yul = gen_evm_verifier_yul::<DefaultEmailVerifyCircuit<Fr>>(params, vk, vec![3usize]);
-    let (sols, max_transcript_addr) = gen_evm_verifier_sols_from_yul(&yul, max_line_size_per_file).unwrap();
+/// The default version pragma of the generated Solidity codes.
+pub const DEFAULT_SOLIDITY_VERSION: &'static str = "^0.8.17";
+
+pub fn gen_sol_verifiers(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    max_line_size_per_file: usize,
+    sols_dir: &PathBuf,
+    solidity_version: &str,
+    emit_verified_event: bool,
+    pretty: bool,
+) {
+    let yul = gen_evm_verifier_yul::<DefaultEmailVerifyCircuit<Fr>>(params, vk, vec![num_instances()]);
+    let (sols, max_transcript_addr) = gen_evm_verifier_sols_from_yul(&yul, max_line_size_per_file, pretty).unwrap();
     {
         fs::create_dir_all(&sols_dir).unwrap();
         for (idx, sol) in sols.iter().enumerate() {
             let mut file = File::create(sols_dir.join(format!("VerifierFunc{}.sol", idx))).unwrap();
-            file.write_all(sol.as_bytes()).unwrap();
+            file.write_all(sol.replace("<%solidity_version%>", solidity_version).as_bytes()).unwrap();
         }
         let deploy_params = DeployParamsJson {
             max_transcript_addr,
@@ -35,14 +46,143 @@ pub fn gen_sol_verifiers(params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>,
         let mut json_file = File::create(sols_dir.join("deploy_params.json")).unwrap();
         json_file.write_all(serde_json::to_string_pretty(&deploy_params).unwrap().as_bytes()).unwrap();
     }
-    let email_verifier_sol = include_str!("./EmailVerifier.sol");
+    let bind_block_hash = default_config_params().bind_block_hash.unwrap_or(false);
+    write_sol_templates(sols_dir, max_transcript_addr, solidity_version, emit_verified_event, bind_block_hash);
+    if pretty {
+        for name in ["EmailVerifier", "VerifierBase", "VerifierFuncAbst"] {
+            let path = sols_dir.join(format!("{}.sol", name));
+            let sol = fs::read_to_string(&path).unwrap();
+            fs::write(&path, format_solidity(&sol)).unwrap();
+        }
+    }
+}
+
+/// Re-indent Solidity codes so that the output is stable and diffable.
+/// Each line is indented by four spaces per nesting level of `{`, `(`, and `[`, trailing whitespaces are removed, and consecutive blank lines are merged into one.
+/// Brackets in comments and string literals are ignored.
+///
+/// # Arguments
+/// * `code` - the Solidity codes to be formatted.
+///
+/// # Return values
+/// Return the formatted codes.
+pub fn format_solidity(code: &str) -> String {
+    let mut formatted = String::new();
+    let mut depth = 0usize;
+    let mut in_block_comment = false;
+    let mut prev_blank = true;
+    for line in code.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            if !prev_blank {
+                formatted.push('\n');
+            }
+            prev_blank = true;
+            continue;
+        }
+        prev_blank = false;
+        let is_comment_body = in_block_comment && line.starts_with('*');
+        let chars = line.chars().collect::<Vec<char>>();
+        let mut opens = 0usize;
+        let mut closes = 0usize;
+        let mut leading_closes = 0usize;
+        let mut is_leading = true;
+        let mut in_str: Option<char> = None;
+        let mut idx = 0;
+        while idx < chars.len() {
+            let c = chars[idx];
+            let next = chars.get(idx + 1).copied();
+            if in_block_comment {
+                if c == '*' && next == Some('/') {
+                    in_block_comment = false;
+                    idx += 1;
+                }
+            } else if let Some(quote) = in_str {
+                if c == '\\' {
+                    idx += 1;
+                } else if c == quote {
+                    in_str = None;
+                }
+            } else if c == '/' && next == Some('/') {
+                break;
+            } else if c == '/' && next == Some('*') {
+                in_block_comment = true;
+                idx += 1;
+            } else if c == '"' || c == '\'' {
+                in_str = Some(c);
+            } else if c == '{' || c == '(' || c == '[' {
+                opens += 1;
+            } else if c == '}' || c == ')' || c == ']' {
+                closes += 1;
+                if is_leading {
+                    leading_closes += 1;
+                }
+            }
+            if !(c == '}' || c == ')' || c == ']') {
+                is_leading = false;
+            }
+            idx += 1;
+        }
+        let indent = depth.saturating_sub(leading_closes);
+        formatted += &" ".repeat(4 * indent);
+        if is_comment_body {
+            formatted.push(' ');
+        }
+        formatted += line;
+        formatted.push('\n');
+        depth = (depth + opens).saturating_sub(closes);
+    }
+    while formatted.ends_with("\n\n") {
+        formatted.pop();
+    }
+    formatted
+}
+
+/// Write `EmailVerifier.sol`, `VerifierBase.sol`, and `VerifierFuncAbst.sol` to `sols_dir`.
+///
+/// # Arguments
+/// * `sols_dir` - a directory path of the output Solidity codes.
+/// * `max_transcript_addr` - the maximum address of the transcript used in the verifier functions.
+/// * `solidity_version` - the version in the `pragma solidity` line, e.g., `^0.8.17`.
+/// * `emit_verified_event` - if true, `verifyEmail` emits `Verified(bytes32 publicInputsHash)` when the proof is valid. Note that it makes `verifyEmail` non-view.
+/// * `bind_block_hash` - if true, `instance` ends with the number of the block bound to the proof, and `verifyEmail` requires it to be one of the last 256 blocks and passes its hash as the last two instances.
+pub fn write_sol_templates(sols_dir: &PathBuf, max_transcript_addr: u32, solidity_version: &str, emit_verified_event: bool, bind_block_hash: bool) {
+    let (event_decl, mutability, event_emit) = if emit_verified_event {
+        ("    event Verified(bytes32 publicInputsHash);\n", "", "        emit Verified(keccak256(instance));\n")
+    } else {
+        ("", " view", "")
+    };
+    let (block_number_decl, block_number_type, num_instances, block_hash_instances) = if bind_block_hash {
+        (
+            ",\n            uint blockNumber",
+            ", uint",
+            "5",
+            concat!(
+                "        require(blockNumber < block.number && block.number - blockNumber <= 256, \"stale block\");\n",
+                "        uint blockHash = uint(blockhash(blockNumber));\n",
+                "        pubInputs[3] = blockHash >> 128;\n",
+                "        pubInputs[4] = blockHash & ((1 << 128) - 1);\n",
+            ),
+        )
+    } else {
+        ("", "", "3", "")
+    };
+    let email_verifier_sol = include_str!("./EmailVerifier.sol")
+        .replace("<%solidity_version%>", solidity_version)
+        .replace("<%verified_event_decl%>\n", event_decl)
+        .replace("<%verify_email_mutability%>", mutability)
+        .replace("<%verified_event_emit%>\n", event_emit)
+        .replace("<%block_number_decl%>", block_number_decl)
+        .replace("<%block_number_type%>", block_number_type)
+        .replace("<%num_instances%>", num_instances)
+        .replace("<%block_hash_instances%>\n", block_hash_instances);
     fs::write(sols_dir.join("EmailVerifier.sol"), email_verifier_sol).unwrap();
-    let mut verifier_base_sol = include_str!("./VerifierBase.sol").to_string();
-    verifier_base_sol = verifier_base_sol.replace("<%max_transcript_addr%>", &format!("{}", max_transcript_addr));
-    fs::write(sols_dir.join("VerifierBase.sol"), verifier_base_sol).unwrap();
-    let mut verifier_func_abst_sol = include_str!("./VerifierFuncAbst.sol").to_string();
-    verifier_func_abst_sol = verifier_func_abst_sol.replace("<%max_transcript_addr%>", &format!("{}", max_transcript_addr));
-    fs::write(sols_dir.join("VerifierFuncAbst.sol"), verifier_func_abst_sol).unwrap();
+    for (name, sol) in [("VerifierBase", include_str!("./VerifierBase.sol")), ("VerifierFuncAbst", include_str!("./VerifierFuncAbst.sol"))] {
+        let sol = sol
+            .replace("<%solidity_version%>", solidity_version)
+            .replace("<%max_transcript_addr%>", &format!("{}", max_transcript_addr));
+        fs::write(sols_dir.join(format!("{}.sol", name)), sol).unwrap();
+    }
 }
 
 pub fn gen_evm_verifier_yul<C>(params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>, num_instance: Vec<usize>) -> String
@@ -70,7 +210,8 @@ where
 }
 
 // original: https://github.com/zkonduit/ezkl/blob/main/src/eth.rs#L326-L602
-pub fn gen_evm_verifier_sols_from_yul(yul: &str, max_line_size_per_file: usize) -> Result<(Vec<String>, u32), Box<dyn std::error::Error>> {
+// If `pretty` is true, each line of the nested blocks is kept on its own line and the output codes are formatted with `format_solidity`.
+pub fn gen_evm_verifier_sols_from_yul(yul: &str, max_line_size_per_file: usize, pretty: bool) -> Result<(Vec<String>, u32), Box<dyn std::error::Error>> {
     // let file = File::open(input_file.clone())?;
     let reader = BufReader::new(yul.as_bytes());
 
@@ -269,6 +410,7 @@ pub fn gen_evm_verifier_sols_from_yul(yul: &str, max_line_size_per_file: usize)
     // get the max transcript addr
     let max_transcript_addr = transcript_addrs.iter().max().unwrap() / 32;
 
+    let line_sep = if pretty { "\n" } else { "" };
     let mut blocks = vec![];
     let mut is_nest = false;
     let mut cur_block = String::new();
@@ -278,6 +420,7 @@ pub fn gen_evm_verifier_sols_from_yul(yul: &str, max_line_size_per_file: usize)
             debug_assert_eq!(cur_block.len(), 0, "cur_block is not empty");
             is_nest = true;
             cur_block += line;
+            cur_block += line_sep;
         } else if line.trim() == "}" {
             debug_assert!(is_nest, "there is no opening brace");
             is_nest = false;
@@ -287,6 +430,7 @@ pub fn gen_evm_verifier_sols_from_yul(yul: &str, max_line_size_per_file: usize)
         } else {
             if is_nest {
                 cur_block += line;
+                cur_block += line_sep;
             } else {
                 blocks.push(line.to_string());
             }
@@ -366,5 +510,71 @@ pub fn gen_evm_verifier_sols_from_yul(yul: &str, max_line_size_per_file: usize)
         template = template.replace("<%ASSEMBLY%>", &codes);
         outputs.push(template);
     }
+    if pretty {
+        outputs = outputs.iter().map(|sol| format_solidity(sol)).collect();
+    }
     Ok((outputs, max_transcript_addr))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eth::get_contract_artifacts;
+
+    #[test]
+    fn test_sol_templates_with_verified_event() {
+        let sols_dir = PathBuf::new().join("./build/test_sol_templates");
+        fs::create_dir_all(&sols_dir).unwrap();
+        write_sol_templates(&sols_dir, 32, ">=0.8.17 <0.9.0", true, false);
+        let email_verifier_sol = fs::read_to_string(sols_dir.join("EmailVerifier.sol")).unwrap();
+        assert!(email_verifier_sol.contains("pragma solidity >=0.8.17 <0.9.0;"));
+        assert!(!email_verifier_sol.contains("<%"));
+        let (abi, _, _) = get_contract_artifacts(&sols_dir.join("EmailVerifier.sol"), "EmailVerifier", None);
+        let event = abi.event("Verified").unwrap();
+        assert_eq!(event.inputs.len(), 1);
+        assert_eq!(event.inputs[0].name, "publicInputsHash");
+    }
+
+    #[test]
+    fn test_sol_templates_without_verified_event() {
+        let sols_dir = PathBuf::new().join("./build/test_sol_templates_no_event");
+        fs::create_dir_all(&sols_dir).unwrap();
+        write_sol_templates(&sols_dir, 32, DEFAULT_SOLIDITY_VERSION, false, false);
+        let (abi, _, _) = get_contract_artifacts(&sols_dir.join("EmailVerifier.sol"), "EmailVerifier", None);
+        assert!(abi.event("Verified").is_err());
+    }
+
+    #[test]
+    fn test_sol_templates_with_block_hash() {
+        let sols_dir = PathBuf::new().join("./build/test_sol_templates_block_hash");
+        fs::create_dir_all(&sols_dir).unwrap();
+        write_sol_templates(&sols_dir, 32, DEFAULT_SOLIDITY_VERSION, false, true);
+        let email_verifier_sol = fs::read_to_string(sols_dir.join("EmailVerifier.sol")).unwrap();
+        assert!(!email_verifier_sol.contains("<%"));
+        assert!(email_verifier_sol.contains("new uint[](5)"));
+        assert!(email_verifier_sol.contains("blockhash(blockNumber)"));
+        get_contract_artifacts(&sols_dir.join("EmailVerifier.sol"), "EmailVerifier", None);
+    }
+
+    #[test]
+    fn test_format_solidity() {
+        let code = "contract A {\n  function f(uint x) public {\nif (x > 0) { // {\n   x = g(\n1,\n  2);\n} else {\nrevert(\"}\");\n}   \n\n\n  }\n}\n";
+        let expected = "contract A {\n    function f(uint x) public {\n        if (x > 0) { // {\n            x = g(\n                1,\n                2);\n        } else {\n            revert(\"}\");\n        }\n\n    }\n}\n";
+        let formatted = format_solidity(code);
+        assert_eq!(formatted, expected);
+        assert_eq!(format_solidity(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_sol_templates_pretty() {
+        let sols_dir = PathBuf::new().join("./build/test_sol_templates_pretty");
+        fs::create_dir_all(&sols_dir).unwrap();
+        write_sol_templates(&sols_dir, 32, DEFAULT_SOLIDITY_VERSION, true, false);
+        let email_verifier_sol = fs::read_to_string(sols_dir.join("EmailVerifier.sol")).unwrap();
+        let formatted = format_solidity(&email_verifier_sol);
+        assert_eq!(format_solidity(&formatted), formatted);
+        fs::write(sols_dir.join("EmailVerifier.sol"), formatted).unwrap();
+        let (abi, _, _) = get_contract_artifacts(&sols_dir.join("EmailVerifier.sol"), "EmailVerifier", None);
+        assert!(abi.event("Verified").is_ok());
+    }
+}
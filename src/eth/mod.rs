@@ -111,26 +111,53 @@ pub async fn deploy_and_call_verifiers(sols_dir: &PathBuf, runs: Option<usize>,
     )
     .await;
     gas_sum += gas;
-    println!("total deploy gas {}", gas_sum);
-    println!("address {:?}", Address::from(email_verifier));
+    log::info!("total deploy gas {}", gas_sum);
+    log::info!("address {:?}", Address::from(email_verifier));
 
     let verifier = EmailVerifier::new(email_verifier, client.clone());
-    let instance = encode(&[
-        Token::Uint(U256::from_str_radix(&instance.sign_commit, 10).unwrap()),
-        Token::Uint(U256::from_str_radix(&instance.public_key_hash, 10).unwrap()),
-        Token::Array(instance.header_substrs.iter().map(|s| Token::String(s.clone())).collect_vec()),
-        Token::Array(instance.header_starts.iter().map(|idx| Token::Uint(U256::from(idx.clone()))).collect_vec()),
-        Token::Array(instance.body_substrs.iter().map(|s| Token::String(s.clone())).collect_vec()),
-        Token::Array(instance.body_starts.iter().map(|idx| Token::Uint(U256::from(idx.clone()))).collect_vec()),
-    ]);
+    let instance = encode_public_input(instance);
     let proof = Bytes::from(proof.to_vec());
     verifier.verify_email(Bytes::from(instance.clone()), proof.clone()).call().await.unwrap();
-    println!("verification passed");
+    log::info!("verification passed");
     let call = verifier.method::<_, ()>("verifyEmail", (Bytes::from(instance.clone()), proof.clone())).unwrap();
-    println!("estimated gas {:?}", call.estimate_gas().await.unwrap());
+    log::info!("estimated gas {:?}", call.estimate_gas().await.unwrap());
     // drop(anvil);
 }
 
+/// Encode the public input as the `instance` argument of `EmailVerifier.verifyEmail`.
+/// If `bind_block_hash` is set in the configuration, the number of the bound block is appended.
+///
+/// # Arguments
+/// * `public_input` - a public input of the email verification circuit.
+/// # Return values
+/// Return the ABI encoded bytes of the public input.
+pub fn encode_public_input(public_input: &DefaultEmailVerifyPublicInput) -> Vec<u8> {
+    let mut tokens = vec![
+        Token::Uint(U256::from_str_radix(&public_input.sign_commit, 10).unwrap()),
+        Token::Uint(U256::from_str_radix(&public_input.public_key_hash, 10).unwrap()),
+        Token::Array(public_input.header_substrs.iter().map(|s| Token::String(s.clone())).collect_vec()),
+        Token::Array(public_input.header_starts.iter().map(|idx| Token::Uint(U256::from(idx.clone()))).collect_vec()),
+        Token::Array(public_input.body_substrs.iter().map(|s| Token::String(s.clone())).collect_vec()),
+        Token::Array(public_input.body_starts.iter().map(|idx| Token::Uint(U256::from(idx.clone()))).collect_vec()),
+    ];
+    if default_config_params().bind_block_hash.unwrap_or(false) {
+        let block_number = public_input.block_number.expect("the public input has no bound block");
+        tokens.push(Token::Uint(U256::from(block_number)));
+    }
+    encode(&tokens)
+}
+
+/// Compute the keccak256 digest of the public input encoded by [`encode_public_input`].
+/// It is equal to `publicInputsHash` of the `Verified` event emitted by `EmailVerifier.verifyEmail`.
+///
+/// # Arguments
+/// * `public_input` - a public input of the email verification circuit.
+/// # Return values
+/// Return the 32 bytes digest.
+pub fn public_input_digest(public_input: &DefaultEmailVerifyPublicInput) -> [u8; 32] {
+    ethers::utils::keccak256(encode_public_input(public_input))
+}
+
 async fn deploy_verifier_base_and_funcs(client: &EthersClient, sols_dir: &PathBuf, runs: usize) -> (Address, U256) {
     let deploy_params =
         serde_json::from_reader::<_, DeployParamsJson>(File::open(&sols_dir.join("deploy_params.json")).expect(&format!("deploy_params.json in {:?} cannot open", sols_dir)))
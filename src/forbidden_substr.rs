@@ -0,0 +1,251 @@
+use crate::*;
+use halo2_base::{gates::flex_gate::FlexGateConfig, utils::PrimeField, AssignedValue, Context, QuantumCell};
+
+/// Configuration to assert that a forbidden substring does not appear anywhere in the input characters.
+///
+/// The forbidden substring is a literal string rather than a regex, and can be compared case-insensitively for ASCII letters.
+/// The input characters are compared as they are, so a substring hidden by an encoding, e.g., quoted-printable or base64 in the email body, is not detected.
+#[derive(Debug, Clone)]
+pub struct ForbiddenSubstrConfig<F: PrimeField> {
+    /// The bytes of the forbidden substring.
+    pub substr: Vec<u8>,
+    /// A flag whether ASCII letters are compared case-insensitively.
+    pub case_insensitive: bool,
+    _f: PhantomData<F>,
+}
+
+impl<F: PrimeField> ForbiddenSubstrConfig<F> {
+    /// Construct a new [`ForbiddenSubstrConfig`].
+    ///
+    /// # Arguments
+    /// * `substr` - the forbidden substring.
+    /// * `case_insensitive` - a flag whether ASCII letters are compared case-insensitively.
+    /// # Return values
+    /// Return a new [`ForbiddenSubstrConfig`].
+    pub fn configure(substr: &str, case_insensitive: bool) -> Self {
+        assert!(!substr.is_empty(), "the forbidden substring must not be empty");
+        Self {
+            substr: substr.as_bytes().to_vec(),
+            case_insensitive,
+            _f: PhantomData,
+        }
+    }
+
+    /// Assert that the forbidden substring does not appear in the given characters.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `assigned_chars` - a list of the assigned characters.
+    pub fn assert_absent<'a, 'b: 'a>(&self, ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>]) {
+        if assigned_chars.len() < self.substr.len() {
+            return;
+        }
+        for start in 0..=(assigned_chars.len() - self.substr.len()) {
+            self.assert_no_match_at(ctx, gate, assigned_chars, start, &self.substr);
+        }
+    }
+
+    /// Check whether the forbidden substring appears in the given characters natively.
+    ///
+    /// # Arguments
+    /// * `chars` - the input characters.
+    /// # Return values
+    /// Return `true` if the forbidden substring appears in `chars`.
+    pub fn contains(&self, chars: &[u8]) -> bool {
+        chars.windows(self.substr.len()).any(|window| {
+            if self.case_insensitive {
+                window.eq_ignore_ascii_case(&self.substr)
+            } else {
+                window == self.substr.as_slice()
+            }
+        })
+    }
+
+    /// Assert that `substr` does not appear at `start` of the given characters.
+    fn assert_no_match_at<'a, 'b: 'a>(&self, ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>], start: usize, substr: &[u8]) {
+        let is_match = self.is_match_at(ctx, gate, assigned_chars, start, substr);
+        gate.assert_equal(ctx, QuantumCell::Existing(&is_match), QuantumCell::Constant(F::zero()));
+    }
+
+    /// Return a boolean whether `substr` appears at `start` of the given characters.
+    fn is_match_at<'a, 'b: 'a>(
+        &self,
+        ctx: &mut Context<'b, F>,
+        gate: &FlexGateConfig<F>,
+        assigned_chars: &[AssignedValue<'a, F>],
+        start: usize,
+        substr: &[u8],
+    ) -> AssignedValue<'a, F> {
+        let mut is_match = None;
+        for (offset, byte) in substr.iter().enumerate() {
+            let is_equal = self.is_equal_char(ctx, gate, &assigned_chars[start + offset], *byte);
+            is_match = Some(match is_match {
+                None => is_equal,
+                Some(prev) => gate.mul(ctx, QuantumCell::Existing(&prev), QuantumCell::Existing(&is_equal)),
+            });
+        }
+        is_match.expect("the forbidden substring is not empty")
+    }
+
+    fn is_equal_char<'a, 'b: 'a>(&self, ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_char: &AssignedValue<'a, F>, byte: u8) -> AssignedValue<'a, F> {
+        let is_equal = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(byte as u64)));
+        let other_case = if byte.is_ascii_lowercase() {
+            byte.to_ascii_uppercase()
+        } else {
+            byte.to_ascii_lowercase()
+        };
+        if !self.case_insensitive || other_case == byte {
+            return is_equal;
+        }
+        // At most one of the two cases is equal to the character, so their sum is a boolean.
+        let is_equal_other = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(other_case as u64)));
+        gate.add(ctx, QuantumCell::Existing(&is_equal), QuantumCell::Existing(&is_equal_other))
+    }
+}
+
+/// Configuration to assert that a header field, e.g., `List-Unsubscribe`, does not appear in the canonicalized signed headers.
+///
+/// A header field is found by its name followed by `:` at the start of the headers or after `\r\n`.
+/// Spaces and tabs between the name and `:`, which the simple header canonicalization keeps, are allowed.
+/// The field names are compared case-insensitively, which covers both the relaxed and simple header canonicalizations.
+#[derive(Debug, Clone)]
+pub struct ForbiddenHeaderFieldConfig<F: PrimeField> {
+    /// The name of the forbidden header field.
+    pub name: String,
+    substr_config: ForbiddenSubstrConfig<F>,
+}
+
+impl<F: PrimeField> ForbiddenHeaderFieldConfig<F> {
+    /// Construct a new [`ForbiddenHeaderFieldConfig`].
+    ///
+    /// # Arguments
+    /// * `name` - the name of the forbidden header field without `:`.
+    /// # Return values
+    /// Return a new [`ForbiddenHeaderFieldConfig`].
+    pub fn configure(name: &str) -> Self {
+        assert!(
+            !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_graphic() && byte != b':'),
+            "invalid forbidden header field name {:?}",
+            name
+        );
+        Self {
+            name: name.to_string(),
+            substr_config: ForbiddenSubstrConfig::configure(&format!("\r\n{}", name), true),
+        }
+    }
+
+    /// Assert that the forbidden header field does not appear in the given header characters.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `assigned_chars` - a list of the assigned characters of the header, which must start from the first header field.
+    pub fn assert_absent<'a, 'b: 'a>(&self, ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>]) {
+        for is_match in self.matches(ctx, gate, assigned_chars) {
+            gate.assert_equal(ctx, QuantumCell::Existing(&is_match), QuantumCell::Constant(F::zero()));
+        }
+    }
+
+    /// Return a boolean whether the header field appears in the given header characters, without asserting anything.
+    /// It allows the same configuration to require a header field, e.g., `DKIM-Signature`.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `gate` - a configuration of [`FlexGateConfig`].
+    /// * `assigned_chars` - a list of the assigned characters of the header, which must start from the first header field.
+    /// # Return values
+    /// Return the assigned boolean, which is 1 if the header field appears.
+    pub fn is_present<'a, 'b: 'a>(&self, ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>]) -> AssignedValue<'a, F> {
+        let mut num_matches = gate.load_zero(ctx);
+        for is_match in self.matches(ctx, gate, assigned_chars) {
+            num_matches = gate.add(ctx, QuantumCell::Existing(&num_matches), QuantumCell::Existing(&is_match));
+        }
+        let is_absent = gate.is_equal(ctx, QuantumCell::Existing(&num_matches), QuantumCell::Constant(F::zero()));
+        gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(&is_absent))
+    }
+
+    /// Check whether the forbidden header field appears in the given header characters natively.
+    ///
+    /// # Arguments
+    /// * `chars` - the canonicalized header characters.
+    /// # Return values
+    /// Return `true` if the forbidden header field appears in `chars`.
+    pub fn contains(&self, chars: &[u8]) -> bool {
+        let substr = &self.substr_config.substr;
+        let first_field = &substr[2..];
+        let is_colon_after = |end: usize| chars[end..].iter().find(|byte| **byte != b' ' && **byte != b'\t').map_or(false, |byte| *byte == b':');
+        if chars.len() > first_field.len() && chars[..first_field.len()].eq_ignore_ascii_case(first_field) && is_colon_after(first_field.len()) {
+            return true;
+        }
+        chars
+            .windows(substr.len())
+            .enumerate()
+            .any(|(start, window)| window.eq_ignore_ascii_case(substr) && is_colon_after(start + substr.len()))
+    }
+
+    /// Return booleans whether the header field starts at the head of the headers and at each position of `\r\n`.
+    fn matches<'a, 'b: 'a>(&self, ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+        let substr = &self.substr_config.substr;
+        let first_field = &substr[2..];
+        if assigned_chars.len() <= first_field.len() {
+            return vec![];
+        }
+        let colon_flags = Self::colon_flags(ctx, gate, assigned_chars);
+        let mut matches = vec![];
+        let is_name_match = self.substr_config.is_match_at(ctx, gate, assigned_chars, 0, first_field);
+        matches.push(gate.mul(ctx, QuantumCell::Existing(&is_name_match), QuantumCell::Existing(&colon_flags[first_field.len()])));
+        if assigned_chars.len() > substr.len() {
+            for start in 0..(assigned_chars.len() - substr.len()) {
+                let is_name_match = self.substr_config.is_match_at(ctx, gate, assigned_chars, start, substr);
+                matches.push(gate.mul(ctx, QuantumCell::Existing(&is_name_match), QuantumCell::Existing(&colon_flags[start + substr.len()])));
+            }
+        }
+        matches
+    }
+
+    /// Return booleans whether the characters from each position are spaces or tabs followed by `:`.
+    fn colon_flags<'a, 'b: 'a>(ctx: &mut Context<'b, F>, gate: &FlexGateConfig<F>, assigned_chars: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+        let mut flags = Vec::with_capacity(assigned_chars.len());
+        let mut next_flag = gate.load_zero(ctx);
+        for assigned_char in assigned_chars.iter().rev() {
+            let is_colon = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(b':' as u64)));
+            let is_space = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(b' ' as u64)));
+            let is_tab = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(b'\t' as u64)));
+            let is_wsp = gate.add(ctx, QuantumCell::Existing(&is_space), QuantumCell::Existing(&is_tab));
+            // A character is either `:` or a space or tab, so the sum is a boolean.
+            let flag = gate.mul_add(ctx, QuantumCell::Existing(&is_wsp), QuantumCell::Existing(&next_flag), QuantumCell::Existing(&is_colon));
+            flags.push(flag.clone());
+            next_flag = flag;
+        }
+        flags.reverse();
+        flags
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_contains() {
+        let config = ForbiddenSubstrConfig::<Fr>::configure("javascript:", true);
+        assert!(config.contains(b"click JavaScript:alert(1)"));
+        assert!(!config.contains(b"email was meant for @zkemailverify."));
+        let config = ForbiddenSubstrConfig::<Fr>::configure("javascript:", false);
+        assert!(!config.contains(b"click JavaScript:alert(1)"));
+    }
+
+    #[test]
+    fn test_header_field_contains() {
+        let config = ForbiddenHeaderFieldConfig::<Fr>::configure("List-Unsubscribe");
+        assert!(config.contains(b"from:alice@zkemail.com\r\nlist-unsubscribe:<mailto:u@zkemail.com>\r\n"));
+        assert!(config.contains(b"List-Unsubscribe: <mailto:u@zkemail.com>\r\nfrom:alice@zkemail.com\r\n"));
+        assert!(!config.contains(b"from:alice@zkemail.com\r\ndkim-signature:v=1; h=from:list-unsubscribe; b="));
+        assert!(config.contains(b"from:alice@zkemail.com\r\nList-Unsubscribe :<mailto:u@zkemail.com>\r\n"));
+        assert!(config.contains(b"List-Unsubscribe\t: <mailto:u@zkemail.com>\r\n"));
+        assert!(!config.contains(b"from:alice@zkemail.com\r\nList-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n"));
+    }
+}
@@ -1,30 +1,42 @@
-use crate::eth::deploy_and_call_verifiers;
+use crate::eth::{deploy_and_call_verifiers, public_input_digest};
 // use crate::snark_verifier_sdk::*;
-use crate::eth::gen_verifier::gen_sol_verifiers;
-use crate::{default_config_params, DefaultEmailVerifyPublicInput};
+use crate::eth::gen_verifier::{gen_sol_verifiers, DEFAULT_SOLIDITY_VERSION};
+use crate::circuit_description::{DKIM_TIMESTAMP_INSTANCE_NAME, INSTANCE_NAMES};
+use crate::params;
+use crate::{default_config_params, num_instance_columns, num_instances, DefaultEmailVerifyPublicInput};
 // use crate::eth::{gen_evm_verifier_sols, gen_evm_verifier_yul};
-use crate::utils::get_email_substrs;
+use crate::utils::{decompose_named_regex, get_email_substrs, json_path_regex_parts, json_path_substr_regexes, split_instances, substr_field_names, EmailEncoding, JsonValueType};
 use crate::vrm::DecomposedRegexConfig;
 use crate::EMAIL_VERIFY_CONFIG_ENV;
 use ark_std::{end_timer, start_timer};
-use cfdkim::{canonicalize_signed_email, resolve_public_key};
+use cfdkim::resolve_public_key;
 use ethereum_types::Address;
-use halo2_base::halo2_proofs::circuit::Value;
-use halo2_base::halo2_proofs::halo2curves::bn256::{Bn256, Fq, Fr, G1Affine};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use halo2_base::halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+use halo2_base::halo2_proofs::dev::MockProver;
+use halo2_base::halo2_proofs::halo2curves::bn256::{Bn256, Fq, Fr, G1Affine, G2Affine};
+use halo2_base::halo2_proofs::halo2curves::group::prime::PrimeCurveAffine;
+use halo2_base::halo2_proofs::halo2curves::pairing::Engine;
 use halo2_base::halo2_proofs::halo2curves::FieldExt;
-use halo2_base::halo2_proofs::plonk::{verify_proof, Error, ProvingKey, VerifyingKey};
+use halo2_base::halo2_proofs::plonk::{
+    create_proof, keygen_pk, keygen_vk, verify_proof, Advice, Circuit, Column, ConstraintSystem, Error, Instance, ProvingKey, Selector, VerifyingKey,
+};
+use halo2_base::halo2_proofs::poly::Rotation;
 use halo2_base::halo2_proofs::poly::commitment::{Params, ParamsProver};
-use halo2_base::halo2_proofs::poly::kzg::commitment::ParamsKZG;
-use halo2_base::halo2_proofs::poly::kzg::multiopen::VerifierSHPLONK;
+use halo2_base::halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use halo2_base::halo2_proofs::poly::kzg::multiopen::{ProverSHPLONK, VerifierSHPLONK};
 use halo2_base::halo2_proofs::poly::kzg::strategy::AccumulatorStrategy;
 use halo2_base::halo2_proofs::poly::VerificationStrategy;
 use halo2_base::halo2_proofs::SerdeFormat;
+use halo2_base::utils::fe_to_biguint;
 use halo2_rsa::{RSAPubE, RSAPublicKey, RSASignature};
 use hex;
 use itertools::Itertools;
 use num_bigint::BigUint;
-use rand::rngs::OsRng;
-use rand::thread_rng;
+use rand::rngs::{OsRng, StdRng};
+use rand::{thread_rng, SeedableRng};
 use regex_simple::Regex;
 use rsa::traits::PublicKeyParts;
 use serde::{Deserialize, Serialize};
@@ -38,50 +50,493 @@ use snark_verifier::verifier::PlonkVerifier;
 use snark_verifier_sdk::evm::gen_evm_proof_shplonk;
 // use snark_verifier_sdk::evm::{encode_calldata, gen_evm_proof_shplonk};
 use snark_verifier_sdk::halo2::aggregation::PublicAggregationCircuit;
-use snark_verifier_sdk::halo2::{gen_proof_shplonk, gen_snark_shplonk, PoseidonTranscript};
-use snark_verifier_sdk::{gen_pk, CircuitExt, LIMBS};
+use snark_verifier_sdk::halo2::{gen_proof_shplonk, gen_snark_shplonk, read_snark, PoseidonTranscript};
+use snark_verifier_sdk::{gen_pk, CircuitExt, Snark, LIMBS};
 use snark_verifier_sdk::{NativeLoader, Plonk};
 use std::env::set_var;
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::panic::AssertUnwindSafe;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
-// /// The number of limbs of the accumulator in the aggregation circuit.
-// pub const NUM_ACC_INSTANCES: usize = 4 * LIMBS;
-// /// The name of env variable for the path to the configuration json of the aggregation circuit.
-// pub const VERIFY_CONFIG_KEY: &'static str = "VERIFY_CONFIG";
+/// The number of limbs of the accumulator in the aggregation circuit.
+pub const NUM_ACC_INSTANCES: usize = 4 * LIMBS;
+/// The name of env variable for the path to the configuration json of the aggregation circuit.
+pub const VERIFY_CONFIG_KEY: &'static str = "VERIFY_CONFIG";
+
+/// Public input definition of the aggregation circuit.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AggregationPublicInput {
+    /// Decimal strings of the limbs of the accumulator shared by all the aggregated proofs.
+    pub accumulator: Vec<String>,
+    /// The instances of each aggregated email verification proof in the order of aggregation.
+    pub app_instances: Vec<AppInstances>,
+}
+
+/// Instances of one email verification proof aggregated in the aggregation circuit.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AppInstances {
+    /// The index of the email verification proof in the aggregation.
+    pub index: usize,
+    /// Decimal strings of the instances of the email verification proof.
+    pub instances: Vec<String>,
+}
+
+impl AggregationPublicInput {
+    /// Split the instances of the aggregation circuit into the accumulator and the instances of each email verification proof.
+    ///
+    /// # Arguments
+    /// * `agg_instances` - the instances of the aggregation circuit.
+    /// * `num_app_instances` - the numbers of the instances of each email verification proof.
+    /// # Return values
+    /// Return a new [`AggregationPublicInput`].
+    pub fn new(agg_instances: &[Fr], num_app_instances: &[usize]) -> Self {
+        assert_eq!(agg_instances.len(), NUM_ACC_INSTANCES + num_app_instances.iter().sum::<usize>());
+        let field2string = |val: &Fr| fe_to_biguint(val).to_str_radix(10);
+        let accumulator = agg_instances[0..NUM_ACC_INSTANCES].iter().map(field2string).collect_vec();
+        let mut offset = NUM_ACC_INSTANCES;
+        let app_instances = num_app_instances
+            .iter()
+            .enumerate()
+            .map(|(index, num)| {
+                let instances = agg_instances[offset..offset + num].iter().map(field2string).collect_vec();
+                offset += num;
+                AppInstances { index, instances }
+            })
+            .collect_vec();
+        Self { accumulator, app_instances }
+    }
+
+    /// Output [`AggregationPublicInput`] to a json file.
+    ///
+    /// # Arguments
+    /// * `public_input_path` - a file path of the output json file.
+    pub fn write_file(&self, public_input_path: &str) {
+        serde_json::to_writer_pretty(File::create(public_input_path).expect("public_input_path creation failed"), &self).unwrap();
+    }
+
+    /// Read [`AggregationPublicInput`] from a json file written by [`AggregationPublicInput::write_file`].
+    ///
+    /// # Arguments
+    /// * `public_input_path` - a file path of the json file.
+    pub fn read_file(public_input_path: &str) -> Self {
+        serde_json::from_reader(File::open(public_input_path).expect("public_input_path open failed")).expect("fail to parse the aggregation public input")
+    }
+
+    /// Format the limbs of the accumulator into the 32-byte words of the calldata expected by the aggregation verifier contract.
+    /// Each limb is a big-endian word, and the words come first in the calldata in the order of the limbs.
+    ///
+    /// # Return values
+    /// Return [`NUM_ACC_INSTANCES`] words.
+    pub fn accumulator_words(&self) -> Vec<[u8; 32]> {
+        self.accumulator.iter().map(|limb| decimal_to_word(limb)).collect_vec()
+    }
+
+    /// Encode the calldata of the aggregation verifier contract, i.e., the accumulator, the instances of each aggregated proof, and then the proof.
+    /// It is the same as `snark_verifier_sdk::evm::encode_calldata` for the instances of the aggregation circuit.
+    ///
+    /// # Arguments
+    /// * `proof` - the proof bytes output by [`evm_prove_agg`].
+    /// # Return values
+    /// Return the calldata bytes.
+    pub fn encode_calldata(&self, proof: &[u8]) -> Vec<u8> {
+        let app_words = self.app_instances.iter().flat_map(|app| app.instances.iter().map(|instance| decimal_to_word(instance)));
+        self.accumulator_words().into_iter().chain(app_words).flatten().chain(proof.iter().cloned()).collect_vec()
+    }
+}
+
+/// Convert a decimal string of a field element into its big-endian 32-byte word.
+fn decimal_to_word(decimal: &str) -> [u8; 32] {
+    let bytes = BigUint::parse_bytes(decimal.as_bytes(), 10).expect("invalid decimal string").to_bytes_be();
+    assert!(bytes.len() <= 32, "the value {} exceeds 32 bytes", decimal);
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    word
+}
+
+/// The magic bytes at the head of a gzip-compressed file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Open a file for reading, transparently decompressing it if it is gzip-compressed.
+///
+/// # Arguments
+/// * `path` - a file path.
+/// # Return values
+/// Return a reader of the (decompressed) file contents.
+pub fn open_maybe_compressed(path: &str) -> std::io::Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(File::open(Path::new(path))?);
+    let is_gzip = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if is_gzip {
+        Ok(Box::new(BufReader::new(GzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Create a file and write its contents, optionally compressing them with gzip.
+///
+/// # Arguments
+/// * `path` - a file path.
+/// * `compress` - if `true`, the contents are gzip-compressed.
+/// * `write_fn` - a function to write the contents to the given writer.
+pub fn create_maybe_compressed<W: FnOnce(&mut dyn Write) -> std::io::Result<()>>(path: &str, compress: bool, write_fn: W) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    if compress {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        write_fn(&mut encoder)?;
+        encoder.finish()?.flush()
+    } else {
+        write_fn(&mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Errors of validating SRS parameters with [`check_params`].
+#[derive(thiserror::Error, Debug)]
+pub enum ParamsCheckError {
+    /// The file cannot be opened or read.
+    #[error("fail to read the params file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file ends before the expected structure.
+    #[error("the params file is truncated at byte offset {offset} while reading {item}")]
+    Truncated { offset: u64, item: String },
+    /// A point is not a valid point on the curve.
+    #[error("invalid curve point at byte offset {offset}: {item}")]
+    InvalidPoint { offset: u64, item: String },
+    /// The points are valid but inconsistent with each other, e.g., they are not powers of the same secret.
+    #[error("the params are inconsistent: {0}")]
+    Inconsistent(String),
+    /// The file has extra bytes after the structure.
+    #[error("the params file has trailing bytes after byte offset {offset}")]
+    TrailingBytes { offset: u64 },
+}
+
+/// The maximum SRS size accepted by [`check_params`].
+pub const MAX_PARAMS_K: u32 = 28;
+
+/// Validate SRS parameters in either serialization format without keeping them in memory.
+/// It checks that the file is not truncated, all the points are on the curve, the first points are the generators,
+/// and `e(g[1], g2) = e(g[0], s_g2)`, i.e., `g[1]` and `s_g2` share the same secret.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters, which may be gzip-compressed.
+/// # Return values
+/// Return the SRS size `k` if the parameters are valid, otherwise [`ParamsCheckError`].
+pub fn check_params(params_path: &str) -> Result<u32, ParamsCheckError> {
+    // A file of an unknown format is checked as raw bytes to report where it breaks.
+    let format = params::detect_format(params_path).unwrap_or(SerdeFormat::RawBytes);
+    let mut reader = open_maybe_compressed(params_path)?;
+    let mut offset = 0u64;
+    let k_bytes = read_params_chunk(&mut reader, &mut offset, 4, "k")?;
+    let k = u32::from_le_bytes(k_bytes.try_into().unwrap());
+    if k == 0 || k > MAX_PARAMS_K {
+        return Err(ParamsCheckError::Inconsistent(format!("k = {} is out of range [1, {}]", k, MAX_PARAMS_K)));
+    }
+    let (g1_size, g2_size) = params::point_sizes(format);
+    let mut first_g = vec![];
+    for (name, num_points) in [("g", 1u64 << k), ("g_lagrange", 1u64 << k)] {
+        for idx in 0..num_points {
+            let item = format!("{}[{}]", name, idx);
+            let point_offset = offset;
+            let bytes = read_params_chunk(&mut reader, &mut offset, g1_size, &item)?;
+            let point = params::read_point::<G1Affine>(&bytes, format).ok_or(ParamsCheckError::InvalidPoint { offset: point_offset, item })?;
+            if name == "g" && idx < 2 {
+                first_g.push(point);
+            }
+        }
+    }
+    let mut g2s = vec![];
+    for item in ["g2", "s_g2"] {
+        let point_offset = offset;
+        let bytes = read_params_chunk(&mut reader, &mut offset, g2_size, item)?;
+        let point = params::read_point::<G2Affine>(&bytes, format).ok_or_else(|| ParamsCheckError::InvalidPoint {
+            offset: point_offset,
+            item: item.to_string(),
+        })?;
+        g2s.push(point);
+    }
+    if reader.read(&mut [0u8; 1])? != 0 {
+        return Err(ParamsCheckError::TrailingBytes { offset });
+    }
+    if first_g[0] != G1Affine::generator() || g2s[0] != G2Affine::generator() {
+        return Err(ParamsCheckError::Inconsistent("g[0] or g2 is not the generator".to_string()));
+    }
+    if Bn256::pairing(&first_g[1], &g2s[0]) != Bn256::pairing(&first_g[0], &g2s[1]) {
+        return Err(ParamsCheckError::Inconsistent("e(g[1], g2) != e(g[0], s_g2)".to_string()));
+    }
+    Ok(k)
+}
+
+/// Read exactly `size` bytes, reporting the offset where the file ends if it is truncated.
+fn read_params_chunk(reader: &mut Box<dyn Read>, offset: &mut u64, size: usize, item: &str) -> Result<Vec<u8>, ParamsCheckError> {
+    let mut buf = vec![0u8; size];
+    let mut filled = 0;
+    while filled < size {
+        let read = reader.read(&mut buf[filled..])?;
+        if read == 0 {
+            return Err(ParamsCheckError::Truncated {
+                offset: *offset + filled as u64,
+                item: item.to_string(),
+            });
+        }
+        filled += read;
+    }
+    *offset += size as u64;
+    Ok(buf)
+}
+
+fn read_proof(proof_path: &str) -> Vec<u8> {
+    let mut reader = open_maybe_compressed(proof_path).unwrap();
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).unwrap();
+    if proof_path.ends_with(".hex") {
+        let hex = std::str::from_utf8(&buf).expect("the hex proof is not UTF-8").trim();
+        return hex::decode(hex.strip_prefix("0x").unwrap_or(hex)).expect("invalid hex proof");
+    }
+    buf
+}
+
+/// The number of bytes hex-encoded at once by [`write_hex_chunked`].
+const HEX_CHUNK_SIZE: usize = 4096;
+
+/// Write bytes as a `0x`-prefixed hex string.
+/// The bytes are encoded in chunks of [`HEX_CHUNK_SIZE`] bytes into a fixed buffer, so the whole hex string is never held in memory.
+///
+/// # Arguments
+/// * `writer` - a writer of the hex string.
+/// * `bytes` - the bytes to be encoded.
+pub fn write_hex_chunked<W: Write + ?Sized>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(b"0x")?;
+    let mut buf = [0u8; 2 * HEX_CHUNK_SIZE];
+    for chunk in bytes.chunks(HEX_CHUNK_SIZE) {
+        let encoded = &mut buf[..2 * chunk.len()];
+        hex::encode_to_slice(chunk, encoded).expect("the buffer is twice as long as the chunk");
+        writer.write_all(encoded)?;
+    }
+    Ok(())
+}
+
+/// Write an EVM proof to a file.
+/// If `proof_path` ends with `.hex`, the proof is written as a `0x`-prefixed hex string by [`write_hex_chunked`]; otherwise, it is written as raw bytes.
+fn write_evm_proof(proof_path: &str, proof: &[u8]) {
+    if proof_path.ends_with(".hex") {
+        create_maybe_compressed(proof_path, false, |writer| write_hex_chunked(writer, proof)).unwrap();
+    } else {
+        create_maybe_compressed(proof_path, false, |writer| writer.write_all(proof)).unwrap();
+    }
+}
 
 /// Generate SRS parameters.
 ///
 /// # Arguments
 /// * `params_path` - a file path of the SRS parameters.
 /// * `k` - the SRS size.
-pub fn gen_params(params_path: &str, k: u32) -> Result<(), Error> {
+/// * `format` - the serialization format. See [`params::store`].
+/// * `compress` - if `true`, the output file is gzip-compressed.
+pub fn gen_params(params_path: &str, k: u32, format: SerdeFormat, compress: bool) -> Result<(), Error> {
     let rng = thread_rng();
     let params = ParamsKZG::<Bn256>::setup(k, rng);
-    let f = File::create(params_path).unwrap();
-    let mut writer = BufWriter::new(f);
-    params.write(&mut writer).unwrap();
-    writer.flush().unwrap();
+    params::store(params_path, &params, format, compress).unwrap();
     Ok(())
 }
 
+/// Generate SRS parameters of the minimal size for the email verification circuit, i.e., `2^degree` of its configuration.
+/// The configuration is validated when it is loaded, so an oversized configuration fails before the expensive setup.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `format` - the serialization format. See [`params::store`].
+/// * `compress` - if `true`, the output file is gzip-compressed.
+/// # Return values
+/// Return the SRS size `k`.
+pub fn gen_params_for_circuit(params_path: &str, circuit_config_path: &str, format: SerdeFormat, compress: bool) -> Result<u32, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let k = default_config_params().degree;
+    gen_params(params_path, k, format, compress)?;
+    Ok(k)
+}
+
 /// Reduce the size of the given SRS parameters.
 ///
 /// # Arguments
 /// * `original_params_path` - a file path of the original SRS parameters.
 /// * `new_params_path` - a file path of the new SRS parameters.
 /// * `k` - the reduced SRS size.
-pub fn downsize_params(original_params_path: &str, new_params_path: &str, k: u32) -> Result<(), Error> {
-    let f = File::open(Path::new(original_params_path)).unwrap();
-    let mut reader = BufReader::new(f);
-    let mut params = ParamsKZG::<Bn256>::read(&mut reader).unwrap();
-    params.downsize(k);
-    let f = File::create(new_params_path).unwrap();
-    let mut writer = BufWriter::new(f);
-    params.write(&mut writer).unwrap();
-    writer.flush().unwrap();
+/// * `format` - the serialization format of the new SRS parameters, which may differ from that of the original ones.
+/// * `compress` - if `true`, the output file is gzip-compressed.
+pub fn downsize_params(original_params_path: &str, new_params_path: &str, k: u32, format: SerdeFormat, compress: bool) -> Result<(), Error> {
+    let params = params::load_downsized(original_params_path, k).unwrap();
+    params::store(new_params_path, &params, format, compress).unwrap();
+    Ok(())
+}
+
+/// Downsize SRS parameters as [`downsize_params`] does, and then check that the new parameters work with [`verify_params_by_proving`].
+///
+/// # Arguments
+/// * `original_params_path` - a file path of the original SRS parameters.
+/// * `new_params_path` - a file path of the new (downsized) SRS parameters.
+/// * `k` - the SRS size of the new parameters.
+/// * `format` - the serialization format of the new parameters.
+/// * `compress` - if `true`, the new parameters are gzip-compressed.
+/// # Return values
+/// Return `true` if the proof with the new parameters is valid, otherwise `false`.
+/// Return [`Error::NotEnoughRowsAvailable`] if `k` is larger than that of the original parameters or too small for any circuit.
+pub fn downsize_and_verify_params(original_params_path: &str, new_params_path: &str, k: u32, format: SerdeFormat, compress: bool) -> Result<bool, Error> {
+    downsize_params(original_params_path, new_params_path, k, format, compress)?;
+    verify_params_by_proving(new_params_path, k)
+}
+
+/// Check that SRS parameters work by generating the keys, a proof, and verifying it for a trivial circuit that proves the square of a number.
+/// Unlike [`check_params`], it takes only a few seconds for small `k` but catches parameters that cannot prove anything.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `k` - the SRS size expected for the parameters.
+/// # Return values
+/// Return `true` if the proof is valid, otherwise `false`.
+/// Return [`Error::NotEnoughRowsAvailable`] if the SRS size of the parameters is not `k` or `k` is too small for the trivial circuit.
+pub fn verify_params_by_proving(params_path: &str, k: u32) -> Result<bool, Error> {
+    let params = params::load_downsized(params_path, k).unwrap();
+    if params.k() != k {
+        return Err(Error::NotEnoughRowsAvailable { current_k: params.k() });
+    }
+    let circuit = SquareCircuit { x: Fr::from(3) };
+    let vk = keygen_vk(&params, &circuit)?;
+    let pk = keygen_pk(&params, vk, &circuit)?;
+    let instances = vec![vec![circuit.x * circuit.x]];
+    let proof = gen_proof_shplonk(&params, &pk, circuit, instances.clone(), &mut OsRng, None);
+    verify_proof_with_vk(&params, pk.get_vk(), &proof, &instances)
+}
+
+/// A trivial circuit for [`verify_params_by_proving`], which exposes the square of `x` as an instance.
+#[derive(Debug, Clone)]
+struct SquareCircuit {
+    x: Fr,
+}
+
+impl Circuit<Fr> for SquareCircuit {
+    type Config = (Column<Advice>, Column<Instance>, Selector);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let advice = meta.advice_column();
+        let instance = meta.instance_column();
+        let selector = meta.selector();
+        meta.enable_equality(advice);
+        meta.enable_equality(instance);
+        meta.create_gate("square", |meta| {
+            let selector = meta.query_selector(selector);
+            let x = meta.query_advice(advice, Rotation::cur());
+            let square = meta.query_advice(advice, Rotation::next());
+            vec![selector * (x.clone() * x - square)]
+        });
+        (advice, instance, selector)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fr>) -> Result<(), Error> {
+        let (advice, instance, selector) = config;
+        let square = layouter.assign_region(
+            || "square",
+            |mut region| {
+                selector.enable(&mut region, 0)?;
+                region.assign_advice(|| "x", advice, 0, || Value::known(self.x))?;
+                region.assign_advice(|| "square", advice, 1, || Value::known(self.x * self.x))
+            },
+        )?;
+        layouter.constrain_instance(square.cell(), instance, 0)
+    }
+}
+
+/// The suffix of the metadata file that [`gen_keys`] writes next to the proving key, e.g., `app.pk.meta.json` for `app.pk`.
+pub const KEY_METADATA_SUFFIX: &'static str = ".meta.json";
+
+/// Metadata of a proving key, which records the circuit configuration that the key was generated for.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyMetadata {
+    /// The hex string of [`config_hash`] when the key was generated.
+    pub config_hash: String,
+}
+
+impl KeyMetadata {
+    /// Record the current circuit configuration, i.e., [`default_config_params`].
+    pub fn current() -> Self {
+        Self {
+            config_hash: hex::encode(config_hash()),
+        }
+    }
+
+    /// Write the metadata to `{pk_path}{KEY_METADATA_SUFFIX}`.
+    ///
+    /// # Arguments
+    /// * `pk_path` - a file path of the proving key.
+    pub fn write_file(&self, pk_path: &str) {
+        let path = format!("{}{}", pk_path, KEY_METADATA_SUFFIX);
+        serde_json::to_writer_pretty(File::create(&path).expect("key metadata creation failed"), self).unwrap();
+    }
+}
+
+/// Compute the SHA256 hash of the current circuit configuration, i.e., [`default_config_params`], and the contents of its regex files.
+/// The contents are included since the lookup tables of the circuit, and thus its keys, depend on them.
+pub fn config_hash() -> [u8; 32] {
+    let config_params = default_config_params();
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&config_params).unwrap());
+    let mut regex_filepathes = vec![];
+    if let Some(header_config) = config_params.header_config.as_ref() {
+        regex_filepathes.extend(header_config.allstr_filepathes.iter().chain(header_config.substr_filepathes.iter().flatten()).cloned());
+    }
+    if let Some(body_config) = config_params.body_config.as_ref() {
+        regex_filepathes.extend(body_config.allstr_filepathes.iter().chain(body_config.substr_filepathes.iter().flatten()).cloned());
+    }
+    for path in regex_filepathes {
+        hasher.update(fs::read(&path).unwrap_or_default());
+    }
+    hasher.finalize().into()
+}
+
+/// An error of a proving key generated for another circuit configuration, found by [`check_key_config`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeyConfigError {
+    /// The metadata file exists but cannot be read.
+    #[error("fail to read the key metadata {path}: {message}")]
+    Metadata { path: String, message: String },
+    /// The configuration differs from the one that the key was generated for.
+    #[error("the proving key {pk_path} was generated for another circuit configuration (config hash {key_hash}) than the given one (config hash {config_hash}); pass the configuration used by gen-keys or regenerate the keys")]
+    Mismatch { pk_path: String, key_hash: String, config_hash: String },
+}
+
+/// Check that the proving key was generated for the current circuit configuration, i.e., [`default_config_params`], with the metadata written by [`gen_keys`].
+/// A key without the metadata, e.g., one generated by an older version, is not checked.
+///
+/// # Arguments
+/// * `pk_path` - a file path of the proving key.
+/// # Return values
+/// Return `Ok(())` if the configuration matches or the metadata is missing, otherwise [`KeyConfigError`].
+pub fn check_key_config(pk_path: &str) -> Result<(), KeyConfigError> {
+    let path = format!("{}{}", pk_path, KEY_METADATA_SUFFIX);
+    if !Path::new(&path).exists() {
+        return Ok(());
+    }
+    let metadata_error = |message: String| KeyConfigError::Metadata { path: path.clone(), message };
+    let file = File::open(&path).map_err(|err| metadata_error(err.to_string()))?;
+    let metadata = serde_json::from_reader::<_, KeyMetadata>(file).map_err(|err| metadata_error(err.to_string()))?;
+    let config_hash = hex::encode(config_hash());
+    if metadata.config_hash != config_hash {
+        return Err(KeyConfigError::Mismatch {
+            pk_path: pk_path.to_string(),
+            key_hash: metadata.config_hash,
+            config_hash,
+        });
+    }
     Ok(())
 }
 
@@ -93,26 +548,29 @@ pub fn downsize_params(original_params_path: &str, new_params_path: &str, k: u32
 /// * `pk_path` - a file path of the output proving key.
 /// * `vk_path` - a file path of the output verifying key.
 /// * `circuit` - an email verification circuit.
-pub fn gen_keys<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, vk_path: &str, circuit: C) -> Result<(), Error> {
+/// * `verify_keys` - if `true`, a proof for `circuit` is generated and verified with the generated keys before they are written.
+pub fn gen_keys<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, vk_path: &str, circuit: C, verify_keys: bool) -> Result<(), Error> {
     set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
 
-    let mut params = {
-        let f = File::open(Path::new(params_path)).unwrap();
-        let mut reader = BufReader::new(f);
-        ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-    };
     let app_config = default_config_params();
-    if params.k() > app_config.degree {
-        params.downsize(app_config.degree);
+    let params = timed("load params", || params::load_downsized(params_path, app_config.degree).unwrap());
+    let pk = timed("generate pk", || gen_pk::<C>(&params, &circuit, None));
+    log::info!("app pk generated");
+    if verify_keys {
+        let instances = circuit.instances();
+        let proof = gen_proof_shplonk(&params, &pk, circuit, instances.clone(), &mut OsRng, None);
+        if !verify_proof_with_vk(&params, pk.get_vk(), &proof, &instances)? {
+            return Err(Error::ConstraintSystemFailure);
+        }
+        log::info!("app keys verified");
     }
-    let pk = gen_pk::<C>(&params, &circuit, None);
-    println!("app pk generated");
     {
         let f = File::create(pk_path).unwrap();
         let mut writer = BufWriter::new(f);
         pk.write(&mut writer, SerdeFormat::RawBytesUnchecked).unwrap();
         writer.flush().unwrap();
     }
+    KeyMetadata::current().write_file(pk_path);
 
     let vk = pk.get_vk();
     {
@@ -124,67 +582,349 @@ pub fn gen_keys<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str,
     Ok(())
 }
 
-// /// Generate proving and verifying keys for the aggregation circuit.
-// ///
-// /// # Arguments
-// /// * `app_params_path` - a file path of the SRS parameters for the email verification circuit.
-// /// * `agg_params_path` - a file path of the SRS parameters for the aggregation circuit.
-// /// * `app_circuit_config_path` - a file path of the configuration of the email verification circuit.
-// /// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit.
-// /// * `app_pk_path` - a file path of the proving key for the email verification circuit.
-// /// * `agg_pk_path` - a file path of the output proving key for the aggregation circuit.
-// /// * `agg_vk_path` - a file path of the output verifying key for the aggregation circuit.
-// /// * `app_circuit` - an email verification circuit.
-// pub fn gen_agg_key<C: CircuitExt<Fr>>(
-//     app_params_path: &str,
-//     agg_params_path: &str,
-//     app_circuit_config_path: &str,
-//     agg_circuit_config_path: &str,
-//     app_pk_path: &str,
-//     agg_pk_path: &str,
-//     agg_vk_path: &str,
-//     app_circuit: C,
-// ) -> Result<(), Error> {
-//     set_var(EMAIL_VERIFY_CONFIG_ENV, app_circuit_config_path);
-//     set_var(VERIFY_CONFIG_KEY, agg_circuit_config_path);
-//     let agg_params = {
-//         let f = File::open(Path::new(agg_params_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-//     };
-//     let app_params = {
-//         let f = File::open(Path::new(app_params_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-//     };
-//     let app_pk = {
-//         let f = File::open(Path::new(app_pk_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ProvingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
-//     };
-//     let snark = gen_snark_shplonk(&app_params, &app_pk, app_circuit, &mut OsRng, None::<&str>);
-//     println!("snark generated");
-//     let agg_circuit = PublicAggregationCircuit::new(&agg_params, vec![snark], false, &mut OsRng);
-//     let agg_pk = gen_pk::<PublicAggregationCircuit>(&agg_params, &agg_circuit, None);
-//     println!("agg pk generated");
-//     {
-//         let f = File::create(agg_pk_path).unwrap();
-//         let mut writer = BufWriter::new(f);
-//         agg_pk.write(&mut writer, SerdeFormat::RawBytesUnchecked).unwrap();
-//         writer.flush().unwrap();
-//     }
+/// Generate proving and verifying keys for the aggregation circuit.
+///
+/// # Arguments
+/// * `app_params_path` - a file path of the SRS parameters for the email verification circuit.
+/// * `agg_params_path` - a file path of the SRS parameters for the aggregation circuit.
+/// * `app_circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit.
+/// * `app_pk_path` - a file path of the proving key for the email verification circuit.
+/// * `agg_pk_path` - a file path of the output proving key for the aggregation circuit.
+/// * `agg_vk_path` - a file path of the output verifying key for the aggregation circuit.
+/// * `acc_path` - a file path of the running aggregation to which the proofs are appended. See [`evm_prove_agg`].
+/// * `app_circuits` - email verification circuits whose proofs are aggregated.
+/// If `num_snarks` is set in the aggregation circuit configuration, the last circuit is repeated up to `num_snarks` circuits because the keys depend only on the number of the aggregated proofs.
+/// * `verify_keys` - if `true`, an aggregation proof is generated and verified with the generated keys before they are written.
+pub fn gen_agg_key<C: CircuitExt<Fr> + Clone>(
+    app_params_path: &str,
+    agg_params_path: &str,
+    app_circuit_config_path: &str,
+    agg_circuit_config_path: &str,
+    app_pk_path: &str,
+    agg_pk_path: &str,
+    agg_vk_path: &str,
+    acc_path: Option<&str>,
+    app_circuits: Vec<C>,
+    verify_keys: bool,
+) -> Result<(), Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, app_circuit_config_path);
+    set_var(VERIFY_CONFIG_KEY, agg_circuit_config_path);
+    let app_circuits = pad_app_circuits(app_circuits, agg_num_snarks(agg_circuit_config_path));
+    let agg_params = params::load(agg_params_path).unwrap();
+    let agg_circuit = gen_agg_circuit(app_params_path, app_pk_path, &agg_params, read_prev_agg_snark(acc_path), app_circuits, false);
+    let agg_pk = gen_pk::<PublicAggregationCircuit>(&agg_params, &agg_circuit, None);
+    log::info!("agg pk generated");
+    if verify_keys {
+        let instances = agg_circuit.instances();
+        let proof = gen_proof_shplonk(&agg_params, &agg_pk, agg_circuit, instances.clone(), &mut OsRng, None);
+        if !verify_proof_with_vk(&agg_params, agg_pk.get_vk(), &proof, &instances)? {
+            return Err(Error::ConstraintSystemFailure);
+        }
+        log::info!("agg keys verified");
+    }
+    {
+        let f = File::create(agg_pk_path).unwrap();
+        let mut writer = BufWriter::new(f);
+        agg_pk.write(&mut writer, SerdeFormat::RawBytesUnchecked).unwrap();
+        writer.flush().unwrap();
+    }
 
-//     let vk = agg_pk.get_vk();
-//     {
-//         let f = File::create(agg_vk_path).unwrap();
-//         let mut writer = BufWriter::new(f);
-//         vk.write(&mut writer, SerdeFormat::RawBytesUnchecked).unwrap();
-//         writer.flush().unwrap();
-//     }
-//     Ok(())
-// }
+    let vk = agg_pk.get_vk();
+    {
+        let f = File::create(agg_vk_path).unwrap();
+        let mut writer = BufWriter::new(f);
+        vk.write(&mut writer, SerdeFormat::RawBytesUnchecked).unwrap();
+        writer.flush().unwrap();
+    }
+    Ok(())
+}
+
+/// The smallest SRS size of the aggregation circuit found by [`estimate_agg_cost`] and the rows it provides.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggCost {
+    /// The number of the aggregated proofs.
+    pub num_snarks: usize,
+    /// The number of the advice columns in the first phase of the aggregation circuit.
+    pub num_advice: usize,
+    /// The minimal SRS size `k` with which the aggregation circuit is satisfied.
+    pub k: u32,
+    /// The rows usable in each advice column for `k`, which bounds the row usage from above.
+    pub usable_rows: usize,
+    /// The rows usable in each advice column for `k - 1`, which the row usage exceeds.
+    /// It is `0` if `k - 1` is too small for the lookup table of `2^lookup_bits` rows, i.e., the row usage is not bounded from below.
+    pub exceeded_rows: usize,
+}
+
+/// Find the minimal SRS size `k` of the aggregation circuit for the given number of proofs by synthesizing it with [`MockProver`] for candidate sizes.
+/// The search starts from `degree` of the aggregation circuit configuration and only the degree is replaced, so the other parameters of the configuration are kept.
+/// The app proofs are generated once, but each candidate requires as much memory as a mock proving of the aggregation circuit.
+///
+/// # Arguments
+/// * `app_params_path` - a file path of the SRS parameters for the email verification circuit.
+/// * `app_circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit.
+/// * `app_pk_path` - a file path of the proving key for the email verification circuit.
+/// * `app_circuits` - email verification circuits whose proofs are aggregated.
+/// * `num_snarks` - the number of the aggregated proofs, to which the last circuit is repeated. `num_snarks` of the configuration or the number of the circuits is used if `None`.
+/// # Return values
+/// Return [`AggCost`] for the minimal `k`.
+/// Return [`Error::NotEnoughRowsAvailable`] if the circuit is not satisfied even for [`MAX_PARAMS_K`].
+pub fn estimate_agg_cost<C: CircuitExt<Fr> + Clone>(
+    app_params_path: &str,
+    app_circuit_config_path: &str,
+    agg_circuit_config_path: &str,
+    app_pk_path: &str,
+    app_circuits: Vec<C>,
+    num_snarks: Option<usize>,
+) -> Result<AggCost, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, app_circuit_config_path);
+    let num_snarks = num_snarks.or_else(|| agg_num_snarks(agg_circuit_config_path));
+    let app_circuits = pad_app_circuits(app_circuits, num_snarks);
+    let snarks = gen_app_snarks(app_params_path, app_pk_path, app_circuits, false);
+    let mut agg_config: serde_json::Value = serde_json::from_reader(File::open(agg_circuit_config_path).unwrap()).unwrap();
+    let lookup_bits = agg_config["lookup_bits"].as_u64().expect("lookup_bits is not set") as u32;
+    let num_advice = agg_config["num_advice"][0].as_u64().expect("num_advice is not set") as usize;
+    // The lookup table of `2^lookup_bits` rows must fit in the usable rows.
+    let min_k = lookup_bits + 1;
+    // Only `g[0]` of the SRS parameters, which is the generator for any setup, is used to construct the aggregation circuit.
+    let agg_params = ParamsKZG::<Bn256>::setup(1, OsRng);
+    let candidate_config_path = std::env::temp_dir().join(format!("agg_cost_{}.config", std::process::id()));
+    let mut is_satisfied = |k: u32| -> bool {
+        agg_config["degree"] = serde_json::json!(k);
+        fs::write(&candidate_config_path, agg_config.to_string()).unwrap();
+        set_var(VERIFY_CONFIG_KEY, &candidate_config_path);
+        let agg_circuit = PublicAggregationCircuit::new(&agg_params, snarks.clone(), false, &mut OsRng);
+        // The gates panic when the assigned cells overflow the advice columns, which is reported in the same way as unsatisfied constraints.
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| MockProver::run(k, &agg_circuit, agg_circuit.instances())));
+        std::panic::set_hook(hook);
+        let satisfied = matches!(result, Ok(Ok(prover)) if prover.verify().is_ok());
+        log::info!("k = {}: {}", k, if satisfied { "satisfied" } else { "not satisfied" });
+        satisfied
+    };
+    let mut k = agg_config["degree"].as_u64().map_or(min_k, |degree| (degree as u32).clamp(min_k, MAX_PARAMS_K));
+    let mut exceeded_k = None;
+    if is_satisfied(k) {
+        while k > min_k {
+            if !is_satisfied(k - 1) {
+                exceeded_k = Some(k - 1);
+                break;
+            }
+            k -= 1;
+        }
+    } else {
+        exceeded_k = Some(k);
+        loop {
+            if k == MAX_PARAMS_K {
+                fs::remove_file(&candidate_config_path).ok();
+                set_var(VERIFY_CONFIG_KEY, agg_circuit_config_path);
+                return Err(Error::NotEnoughRowsAvailable { current_k: k });
+            }
+            k += 1;
+            if is_satisfied(k) {
+                break;
+            }
+            exceeded_k = Some(k);
+        }
+    }
+    fs::remove_file(&candidate_config_path).ok();
+    set_var(VERIFY_CONFIG_KEY, agg_circuit_config_path);
+    // The blinding rows do not depend on the degree.
+    let mut cs = ConstraintSystem::<Fr>::default();
+    PublicAggregationCircuit::configure(&mut cs);
+    let usable_rows_of = |k: u32| (1usize << k) - cs.minimum_rows();
+    Ok(AggCost {
+        num_snarks: snarks.len(),
+        num_advice,
+        k,
+        usable_rows: usable_rows_of(k),
+        exceeded_rows: exceeded_k.map_or(0, usable_rows_of),
+    })
+}
+
+/// Validate the configuration of the email verification circuit and optionally that of the aggregation circuit without generating any key.
+/// It checks that the required sections are present, the sizes fit in `2^degree` rows, the byte sizes are multiples of the SHA256 block size,
+/// every referenced regex file exists and is consistent with the others, and every substring regex compiles.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit, if any.
+/// # Return values
+/// Return the messages of all the problems found, which is empty if the configurations are valid.
+pub fn validate_config(circuit_config_path: &str, agg_circuit_config_path: Option<&str>) -> Vec<String> {
+    let mut problems = vec![];
+    let params = fs::read_to_string(circuit_config_path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str::<crate::EmailVerifyConfigParams>(&json).map_err(|err| err.to_string()));
+    match params {
+        Ok(params) => validate_app_config(&params, &mut problems),
+        Err(err) => problems.push(format!("{}: {}", circuit_config_path, err)),
+    }
+    if let Some(agg_circuit_config_path) = agg_circuit_config_path {
+        let config = fs::read_to_string(agg_circuit_config_path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).map_err(|err| err.to_string()));
+        match config {
+            Ok(config) => {
+                let number = |name: &str| config.get(name).and_then(|value| value.as_u64());
+                match (number("degree"), number("lookup_bits")) {
+                    (Some(degree), Some(lookup_bits)) if lookup_bits >= degree => {
+                        problems.push(format!("{}: lookup_bits = {} must be less than degree = {}", agg_circuit_config_path, lookup_bits, degree))
+                    }
+                    (Some(_), Some(_)) => {}
+                    _ => problems.push(format!("{}: degree and lookup_bits are required", agg_circuit_config_path)),
+                }
+                if number("num_snarks") == Some(0) {
+                    problems.push(format!("{}: num_snarks must be positive", agg_circuit_config_path));
+                }
+            }
+            Err(err) => problems.push(format!("{}: {}", agg_circuit_config_path, err)),
+        }
+    }
+    problems
+}
+
+fn validate_app_config(params: &crate::EmailVerifyConfigParams, problems: &mut Vec<String>) {
+    if let Err(err) = params.check_capacity() {
+        problems.push(err.to_string());
+    }
+    for (name, is_set) in [
+        ("sha256_config", params.sha256_config.is_some()),
+        ("sign_verify_config", params.sign_verify_config.is_some()),
+        ("header_config", params.header_config.is_some()),
+        ("body_config", params.body_config.is_some()),
+    ] {
+        if !is_set {
+            problems.push(format!("{} is required", name));
+        }
+    }
+    let mut parts = vec![];
+    if let Some(header_params) = params.header_config.as_ref() {
+        parts.push((
+            "header_config",
+            header_params.max_variable_byte_size,
+            header_params.skip_prefix_bytes_size,
+            &header_params.allstr_filepathes,
+            &header_params.substr_filepathes,
+            &header_params.substr_regexes,
+        ));
+        for (name, path) in [
+            ("header_config.bodyhash_allstr_filepath", &header_params.bodyhash_allstr_filepath),
+            ("header_config.bodyhash_substr_filepath", &header_params.bodyhash_substr_filepath),
+        ] {
+            if !Path::new(path).exists() {
+                problems.push(format!("{}: {} does not exist", name, path));
+            }
+        }
+    }
+    if let Some(body_params) = params.body_config.as_ref() {
+        parts.push((
+            "body_config",
+            body_params.max_variable_byte_size,
+            body_params.skip_prefix_bytes_size,
+            &body_params.allstr_filepathes,
+            &body_params.substr_filepathes,
+            &body_params.substr_regexes,
+        ));
+    }
+    for (part, max_size, skip_prefix_size, allstr_pathes, substr_pathes, substr_regexes) in parts {
+        if max_size % 64 != 0 {
+            problems.push(format!("{}.max_variable_byte_size = {} must be a multiple of 64", part, max_size));
+        }
+        if let Some(skip_prefix_size) = skip_prefix_size {
+            if skip_prefix_size % 64 != 0 || skip_prefix_size >= max_size {
+                problems.push(format!(
+                    "{}.skip_prefix_bytes_size = {} must be a multiple of 64 and less than max_variable_byte_size",
+                    part, skip_prefix_size
+                ));
+            }
+        }
+        if allstr_pathes.len() != substr_pathes.len() || allstr_pathes.len() != substr_regexes.len() {
+            problems.push(format!(
+                "{} has {} allstr_filepathes, {} substr_filepathes, and {} substr_regexes, which must be the same number",
+                part,
+                allstr_pathes.len(),
+                substr_pathes.len(),
+                substr_regexes.len()
+            ));
+        }
+        for (idx, (allstr_path, substr_pathes)) in allstr_pathes.iter().zip(substr_pathes.iter()).enumerate() {
+            let mut missing = false;
+            for (name, path) in std::iter::once((format!("{}.allstr_filepathes[{}]", part, idx), allstr_path))
+                .chain(substr_pathes.iter().enumerate().map(|(sub_idx, path)| (format!("{}.substr_filepathes[{}][{}]", part, idx, sub_idx), path)))
+            {
+                if !Path::new(path).exists() {
+                    problems.push(format!("{}: {} does not exist", name, path));
+                    missing = true;
+                }
+            }
+            if !missing {
+                if let Err(err) = crate::utils::check_regex_files(allstr_path, substr_pathes) {
+                    problems.push(err.to_string());
+                }
+                if let Err(err) = crate::utils::check_regex_capacity(allstr_path, params.degree) {
+                    problems.push(err.to_string());
+                }
+            }
+        }
+        for (idx, regexes) in substr_regexes.iter().enumerate() {
+            for (sub_idx, regex) in regexes.iter().enumerate() {
+                if let Err(err) = fancy_regex::Regex::new(regex) {
+                    problems.push(format!("{}.substr_regexes[{}][{}]: {}", part, idx, sub_idx, err));
+                }
+            }
+        }
+    }
+}
+
+/// Read `num_snarks`, i.e., the number of the email verification proofs aggregated at once, from the configuration of the aggregation circuit.
+///
+/// # Arguments
+/// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit.
+/// # Return values
+/// Return `num_snarks` if it is set, otherwise `None`, i.e., the number of the given emails is used.
+pub fn agg_num_snarks(agg_circuit_config_path: &str) -> Option<usize> {
+    let config: serde_json::Value = serde_json::from_reader(File::open(agg_circuit_config_path).unwrap()).unwrap();
+    config.get("num_snarks").and_then(|num_snarks| num_snarks.as_u64()).map(|num_snarks| num_snarks as usize)
+}
+
+/// Repeat the last circuit so that `num_snarks` circuits are given to the key generation.
+fn pad_app_circuits<C: Clone>(mut app_circuits: Vec<C>, num_snarks: Option<usize>) -> Vec<C> {
+    if let Some(num_snarks) = num_snarks {
+        assert!(
+            !app_circuits.is_empty() && app_circuits.len() <= num_snarks,
+            "{} emails are given, but the aggregation circuit aggregates {} proofs",
+            app_circuits.len(),
+            num_snarks
+        );
+        let last = app_circuits.last().unwrap().clone();
+        app_circuits.resize(num_snarks, last);
+    }
+    app_circuits
+}
+
+/// The seed of the randomness for deterministic proofs.
+pub const DETERMINISTIC_PROOF_SEED: u64 = 0;
+
+/// Return the randomness used to blind a proof.
+/// If `deterministic` or `disable_blinding` of the circuit configuration is true, it is seeded with [`DETERMINISTIC_PROOF_SEED`] so that the same inputs always produce the same proof bytes.
+/// Such proofs are only for tests, e.g., comparing with golden files, because the fixed blinding factors break zero-knowledge.
+fn proof_rng(deterministic: bool) -> StdRng {
+    if default_config_params().disable_blinding.unwrap_or(false) {
+        log::warn!("disable_blinding is set in the circuit configuration, so the proof is generated with a fixed seed, which breaks zero-knowledge. Use it only for tests.");
+        StdRng::seed_from_u64(DETERMINISTIC_PROOF_SEED)
+    } else if deterministic {
+        log::warn!("the proof is generated with a fixed seed, which breaks zero-knowledge. Use it only for tests.");
+        StdRng::seed_from_u64(DETERMINISTIC_PROOF_SEED)
+    } else {
+        StdRng::from_rng(OsRng).unwrap()
+    }
+}
 
 /// Generate a proof for the email verification circuit.
+/// The proof uses the Poseidon transcript, which [`verify`] and the aggregation circuit can verify. Use [`evm_prove`] for the Solidity verifier.
 ///
 /// # Arguments
 /// * `params_path` - a file path of the SRS parameters.
@@ -192,32 +932,357 @@ pub fn gen_keys<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str,
 /// * `pk_path` - a file path of the proving key.
 /// * `proof_path` - a file path of the output proof.
 /// * `circuit` - an email verification circuit.
-pub fn prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, proof_path: &str, circuit: C) -> Result<(), Error> {
+/// * `compress` - if `true`, the output proof file is gzip-compressed.
+/// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+///
+/// The [`ProofMetadata`] of the verifying key and the SRS parameters is also written to `{proof_path}{PROOF_METADATA_SUFFIX}`.
+pub fn prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, proof_path: &str, circuit: C, compress: bool, deterministic: bool) -> Result<(), Error> {
+    let context = ProverContext::new::<C>(params_path, circuit_config_path, pk_path);
+    // let (circuit, headerhash, public_key_n, header_substrs, body_substrs) = gen_circuit_from_email_path(email_path).await;
+    let proof = context.try_prove(&circuit, deterministic)?;
+    create_maybe_compressed(proof_path, compress, |writer| writer.write_all(&proof)).unwrap();
+    context.proof_metadata().write_file(proof_path);
+    Ok(())
+}
+
+/// The suffix of the metadata file that [`prove`] writes next to the proof, e.g., `app.proof.meta.json` for `app.proof`.
+pub const PROOF_METADATA_SUFFIX: &'static str = ".meta.json";
+
+/// Metadata of a proof, which records the verifying key and the SRS parameters that the proof was generated with.
+/// [`verify`] checks them against its own verifying key and parameters, so that mismatched artifacts are reported instead of only an invalid proof.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProofMetadata {
+    /// The hex string of the keccak256 hash of the verifying key, which is equal to [`vk_hash`].
+    pub vk_hash: String,
+    /// The hex string of [`params::fingerprint`] of the SRS parameters.
+    pub params_fingerprint: String,
+}
+
+impl ProofMetadata {
+    /// Write the metadata to `{proof_path}{PROOF_METADATA_SUFFIX}`.
+    ///
+    /// # Arguments
+    /// * `proof_path` - a file path of the proof.
+    pub fn write_file(&self, proof_path: &str) {
+        let path = format!("{}{}", proof_path, PROOF_METADATA_SUFFIX);
+        serde_json::to_writer_pretty(File::create(&path).expect("proof metadata creation failed"), self).unwrap();
+    }
+
+    /// Read the metadata written next to the proof.
+    ///
+    /// # Arguments
+    /// * `proof_path` - a file path of the proof.
+    /// # Return values
+    /// Return the metadata, or `None` if it does not exist, e.g., for a proof generated by an older version.
+    pub fn read_file(proof_path: &str) -> Option<Self> {
+        let path = format!("{}{}", proof_path, PROOF_METADATA_SUFFIX);
+        if !Path::new(&path).exists() {
+            return None;
+        }
+        let file = File::open(&path).expect("proof metadata cannot be opened");
+        Some(serde_json::from_reader(file).unwrap_or_else(|err| panic!("invalid proof metadata {}: {}", path, err)))
+    }
+
+    /// Compute the metadata of the given verifying key and SRS parameters.
+    ///
+    /// # Arguments
+    /// * `vk` - the verifying key.
+    /// * `params` - the SRS parameters.
+    pub fn new(vk: &VerifyingKey<G1Affine>, params: &ParamsKZG<Bn256>) -> Self {
+        Self {
+            vk_hash: hex::encode(ethers::utils::keccak256(vk.to_bytes(SerdeFormat::RawBytesUnchecked))),
+            params_fingerprint: hex::encode(params::fingerprint(params)),
+        }
+    }
+
+    /// Find a difference from the metadata of the artifacts used for verification.
+    ///
+    /// # Arguments
+    /// * `other` - the metadata of the verifying key and the SRS parameters used for verification.
+    /// # Return values
+    /// Return a message describing the mismatch, or `None` if both fingerprints match.
+    pub fn mismatch(&self, other: &Self) -> Option<String> {
+        if self.vk_hash != other.vk_hash {
+            Some(format!("the proof was generated for another verifying key (hash {}) than the given one (hash {})", self.vk_hash, other.vk_hash))
+        } else if self.params_fingerprint != other.params_fingerprint {
+            Some(format!(
+                "the proof was generated with other SRS parameters (fingerprint {}) than the given ones (fingerprint {})",
+                self.params_fingerprint, other.params_fingerprint
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// The SRS parameters and the proving key of the email verification circuit, which are loaded once to generate many proofs, e.g., in [`crate::server`].
+pub struct ProverContext {
+    params: ParamsKZG<Bn256>,
+    pk: ProvingKey<G1Affine>,
+}
+
+impl ProverContext {
+    /// Load the SRS parameters and the proving key.
+    ///
+    /// # Arguments
+    /// * `params_path` - a file path of the SRS parameters.
+    /// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+    /// * `pk_path` - a file path of the proving key.
+    /// # Return values
+    /// Return a new [`ProverContext`].
+    pub fn new<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str) -> Self {
+        set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+        check_key_config(pk_path).unwrap_or_else(|err| panic!("{}", err));
+        let app_config = default_config_params();
+        let params = timed("load params", || params::load_downsized(params_path, app_config.degree).unwrap());
+        let pk = timed("load pk", || {
+            let f = File::open(Path::new(pk_path)).unwrap();
+            let mut reader = BufReader::new(f);
+            ProvingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
+        });
+        Self { params, pk }
+    }
+
+    /// Generate a proof in the same way as [`prove`] without writing it to a file.
+    ///
+    /// # Arguments
+    /// * `circuit` - an email verification circuit.
+    /// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+    /// # Return values
+    /// Return the proof bytes.
+    pub fn prove<C: CircuitExt<Fr>>(&self, circuit: C, deterministic: bool) -> Vec<u8> {
+        self.try_prove(&circuit, deterministic)
+            .unwrap_or_else(|err| panic!("failed to generate a proof: {:?}", err))
+    }
+
+    /// Generate a proof as [`ProverContext::prove`], returning the error of the proving backend instead of panicking.
+    ///
+    /// # Arguments
+    /// * `circuit` - an email verification circuit, which is only borrowed so that the same circuit can be proven again.
+    /// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+    /// # Return values
+    /// Return the proof bytes, or the [`Error`] of `create_proof`.
+    pub fn try_prove<C: CircuitExt<Fr>>(&self, circuit: &C, deterministic: bool) -> Result<Vec<u8>, Error> {
+        let instances = circuit.instances();
+        let instances = instances.iter().map(|column| column.as_slice()).collect_vec();
+        timed("generate proof", || {
+            let mut transcript = PoseidonTranscript::<NativeLoader, Vec<u8>>::new(Vec::new());
+            create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+                &self.params,
+                &self.pk,
+                std::slice::from_ref(circuit),
+                &[&instances],
+                proof_rng(deterministic),
+                &mut transcript,
+            )?;
+            Ok(transcript.finalize())
+        })
+    }
+
+    /// Verify a proof generated by [`ProverContext::prove`] with the verifying key of the loaded proving key.
+    ///
+    /// # Arguments
+    /// * `proof` - the proof bytes.
+    /// * `instances` - the instances of the proven circuit.
+    /// # Return values
+    /// Return `true` if the proof is valid, otherwise `false`.
+    pub fn verify(&self, proof: &[u8], instances: &[Vec<Fr>]) -> Result<bool, Error> {
+        verify_proof_with_vk(&self.params, self.pk.get_vk(), proof, instances)
+    }
+
+    /// Return the [`ProofMetadata`] of the loaded verifying key and SRS parameters, which [`prove`] writes next to the proof.
+    pub fn proof_metadata(&self) -> ProofMetadata {
+        ProofMetadata::new(self.pk.get_vk(), &self.params)
+    }
+
+    /// Generate the proofs of independent circuits concurrently with at most `num_threads` threads, each of which proves one circuit at a time.
+    /// A circuit that panics, e.g., because its email does not fit in the configured sizes, fails only its own proof.
+    ///
+    /// # Arguments
+    /// * `circuits` - email verification circuits.
+    /// * `num_threads` - the maximum number of the circuits proven at once, which is at least 1.
+    /// * `deterministic` - if `true`, the proofs are generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+    /// # Return values
+    /// Return the proof bytes or [`BatchProveError::Proving`] of each circuit in the given order.
+    pub fn prove_concurrently<C: CircuitExt<Fr> + Send>(&self, circuits: Vec<C>, num_threads: usize, deterministic: bool) -> Vec<Result<Vec<u8>, BatchProveError>> {
+        let num_circuits = circuits.len();
+        let circuits = circuits.into_iter().map(|circuit| Mutex::new(Some(circuit))).collect_vec();
+        let results = (0..num_circuits).map(|_| Mutex::new(None)).collect_vec();
+        let next_idx = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..num_threads.max(1).min(num_circuits) {
+                scope.spawn(|| loop {
+                    let idx = next_idx.fetch_add(1, Ordering::SeqCst);
+                    if idx >= num_circuits {
+                        break;
+                    }
+                    let circuit = circuits[idx].lock().unwrap().take().expect("each circuit is proven once");
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(|| self.prove(circuit, deterministic))).map_err(|_| BatchProveError::Proving);
+                    log::info!("email {} of {} {}", idx + 1, num_circuits, if result.is_ok() { "proven" } else { "failed" });
+                    *results[idx].lock().unwrap() = Some(result);
+                });
+            }
+        });
+        results.into_iter().map(|result| result.into_inner().unwrap().expect("every circuit is proven")).collect()
+    }
+
+    /// Generate the proofs and public inputs of independent emails concurrently as [`ProverContext::prove_concurrently`].
+    /// The DKIM public keys are resolved before the proving, which blocks the current thread until all the proofs are generated.
+    ///
+    /// # Arguments
+    /// * `emails` - the bytes of the emails.
+    /// * `use_embedded_key` - if `true`, the public key embedded in the email is used instead of the one resolved from DNS.
+    /// * `dkim_options` - the options of selecting the DKIM signature and resolving its public key from DNS.
+    /// * `num_threads` - the maximum number of the emails proven at once, which is at least 1.
+    /// * `deterministic` - if `true`, the proofs are generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+    /// # Return values
+    /// Return the proof bytes and the public input, or [`BatchProveError`] of each email in the given order.
+    /// An error of one email does not abort the others.
+    pub async fn prove_batch(
+        &self,
+        emails: &[Vec<u8>],
+        use_embedded_key: bool,
+        dkim_options: &crate::dkim::DkimOptions,
+        num_threads: usize,
+        deterministic: bool,
+    ) -> Vec<Result<(Vec<u8>, DefaultEmailVerifyPublicInput), BatchProveError>> {
+        let mut results = vec![];
+        let mut circuits = vec![];
+        for email_bytes in emails.iter() {
+            let generated =
+                crate::DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(email_bytes.clone(), use_embedded_key, &[], dkim_options).await;
+            let circuit = match generated {
+                Ok((circuit, _)) => circuit,
+                Err(err) => {
+                    results.push(Err(BatchProveError::Email(err)));
+                    continue;
+                }
+            };
+            // The public input is computed natively, which panics in the same cases as the proving.
+            match std::panic::catch_unwind(AssertUnwindSafe(|| circuit.gen_default_public_input())) {
+                Ok(public_input) => {
+                    results.push(Ok(public_input));
+                    circuits.push(circuit);
+                }
+                Err(_) => results.push(Err(BatchProveError::Proving)),
+            }
+        }
+        let mut proofs = self.prove_concurrently(circuits, num_threads, deterministic).into_iter();
+        results
+            .into_iter()
+            .map(|result| -> Result<(Vec<u8>, DefaultEmailVerifyPublicInput), BatchProveError> {
+                let public_input = result?;
+                let proof = proofs.next().expect("every circuit is proven")?;
+                Ok((proof, public_input))
+            })
+            .collect()
+    }
+}
+
+/// An error of one email proven by [`ProverContext::prove_batch`], which does not abort the other emails.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum BatchProveError {
+    /// The circuit cannot be generated from the email, e.g., its DKIM public key is not resolved.
+    #[error("the email cannot be proven: {0}")]
+    Email(crate::dkim::DkimError),
+    /// The proof generation panicked, e.g., the email does not fit in the configured sizes.
+    #[error("failed to generate a proof for the email")]
+    Proving,
+}
+
+/// Synthesize the email verification circuit with [`MockProver`] and write its intermediate witnesses to json files.
+/// See [`EmailDebugWitness`](crate::debug_witness::EmailDebugWitness) for the dumped values.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `debug_witness_dir` - a directory to which the intermediate witnesses are written.
+/// * `circuit` - an email verification circuit.
+/// # Return values
+/// Return `true` if all constraints of the circuit are satisfied, otherwise `false`.
+pub fn dump_debug_witness(circuit_config_path: &str, debug_witness_dir: &str, circuit: &crate::DefaultEmailVerifyCircuit<Fr>) -> Result<bool, Error> {
     set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
-    let mut params = {
-        let f = File::open(Path::new(params_path)).unwrap();
-        let mut reader = BufReader::new(f);
-        ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-    };
+    let circuit = circuit.clone().with_debug_witness_dir(debug_witness_dir);
     let app_config = default_config_params();
-    if params.k() > app_config.degree {
-        params.downsize(app_config.degree);
+    let result = MockProver::run(app_config.degree, &circuit, circuit.instances())?.verify();
+    if let Err(failures) = result.as_ref() {
+        for failure in failures.iter() {
+            log::info!("{}", failure);
+        }
     }
-    let pk = {
-        let f = File::open(Path::new(pk_path)).unwrap();
-        let mut reader = BufReader::new(f);
-        ProvingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
-    };
-    // let (circuit, headerhash, public_key_n, header_substrs, body_substrs) = gen_circuit_from_email_path(email_path).await;
-    let instances = circuit.instances();
-    let proof = gen_proof_shplonk(&params, &pk, circuit, instances, &mut OsRng, None);
-    {
-        let f = File::create(proof_path).unwrap();
-        let mut writer = BufWriter::new(f);
-        writer.write_all(&proof).unwrap();
-        writer.flush().unwrap();
-    };
-    Ok(())
+    Ok(result.is_ok())
+}
+
+/// A mismatch between an instance computed from the email and the one in the public input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceMismatch {
+    /// The name of the instance, e.g., `public_key_hash`. See [`INSTANCE_NAMES`].
+    pub name: String,
+    /// The index of the instance column.
+    pub column: usize,
+    /// The row index in the instance column.
+    pub row: usize,
+    /// The instance computed from the email.
+    pub expected: Fr,
+    /// The instance in the public input.
+    pub given: Fr,
+}
+
+/// The reasons why a proof for the email verification circuit fails to verify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationFailureExplanation {
+    /// The instances in the public input that differ from those computed from the email.
+    pub instance_mismatches: Vec<InstanceMismatch>,
+    /// The failures reported by [`MockProver`] when the circuit is synthesized with the instances in the public input.
+    pub failures: Vec<String>,
+}
+
+/// Explain why a proof fails to verify by synthesizing the circuit of the proven email with [`MockProver`] and the instances in the public input.
+/// Since the proof itself is not used, it requires the email from which the proof was generated.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `circuit` - an email verification circuit of the proven email.
+/// * `public_input_path` - a file path of the public input.
+/// # Return values
+/// Return a [`VerificationFailureExplanation`], whose fields are both empty if the email and the public input are consistent.
+pub fn explain_verification_failure<C: CircuitExt<Fr>>(circuit_config_path: &str, circuit: &C, public_input_path: &str) -> Result<VerificationFailureExplanation, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let app_config = default_config_params();
+    let public_input = DefaultEmailVerifyPublicInput::read_file(public_input_path);
+    let given_instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+    let num_columns = given_instances.len();
+    let mut instance_mismatches = vec![];
+    for (column, (expected_column, given_column)) in circuit.instances().iter().zip(given_instances.iter()).enumerate() {
+        for (row, (expected, given)) in expected_column.iter().zip(given_column.iter()).enumerate() {
+            if expected != given {
+                let idx = row * num_columns + column;
+                instance_mismatches.push(InstanceMismatch {
+                    name: INSTANCE_NAMES
+                        .get(idx)
+                        .copied()
+                        .or((idx == INSTANCE_NAMES.len()).then_some(DKIM_TIMESTAMP_INSTANCE_NAME))
+                        .map(|name| name.to_string())
+                        .unwrap_or(format!("instance_{}", idx)),
+                    column,
+                    row,
+                    expected: *expected,
+                    given: *given,
+                });
+            }
+        }
+    }
+    let prover = MockProver::run(app_config.degree, circuit, given_instances)?;
+    let failures = prover.verify().err().unwrap_or_default().iter().map(|failure| failure.to_string()).collect_vec();
+    Ok(VerificationFailureExplanation { instance_mismatches, failures })
+}
+
+/// The result of verifying a proof for the email verification circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationOutcome {
+    /// `true` if the proof is valid, otherwise `false`.
+    pub valid: bool,
+    /// The revealed substrings in the public input with their field names. See [`DefaultEmailVerifyPublicInput::public_fields`].
+    pub public_fields: Vec<(String, Vec<u8>)>,
 }
 
 /// Verify a proof for the email verification circuit.
@@ -229,15 +1294,268 @@ pub fn prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk
 /// * `proof_path` - a file path of the proof.
 /// * `public_input_path` - a file path of the public input.
 /// # Return values
-/// Return `true` if the proof is valid, otherwise `false`.
-pub fn verify<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, proof_path: &str, public_input_path: &str) -> Result<bool, Error> {
-    let proof = {
-        let mut f = File::open(&proof_path).unwrap();
-        let mut buf = Vec::new();
-        f.read_to_end(&mut buf).unwrap();
-        buf
+/// Return a [`VerificationOutcome`] that contains whether the proof is valid and the revealed fields.
+/// If the [`ProofMetadata`] written by [`prove`] exists and differs from that of the given verifying key or parameters, the outcome is invalid without verifying the proof.
+pub fn verify<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, proof_path: &str, public_input_path: &str) -> Result<VerificationOutcome, Error> {
+    let proof = read_proof(proof_path);
+    let metadata = ProofMetadata::read_file(proof_path);
+    verify_util::<C>(params_path, circuit_config_path, vk_path, proof, public_input_path, metadata.as_ref())
+}
+
+/// Verify a proof for the email verification circuit after checking that its public input hashes to the expected digest.
+/// It lets a caller that stores only the digest, e.g., `publicInputsHash` of the `Verified` event of `EmailVerifier`, check the proof against it.
+/// The public input file is still required because the instances of the proof are computed from it.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// * `proof_path` - a file path of the proof.
+/// * `public_input_path` - a file path of the public input.
+/// * `expected_digest` - a hex string of the expected [`public_input_digest`].
+/// # Return values
+/// Return a [`VerificationOutcome`], which is invalid if the digest does not match without verifying the proof.
+pub fn verify_with_digest<C: CircuitExt<Fr>>(
+    params_path: &str,
+    circuit_config_path: &str,
+    vk_path: &str,
+    proof_path: &str,
+    public_input_path: &str,
+    expected_digest: &str,
+) -> Result<VerificationOutcome, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let public_input = DefaultEmailVerifyPublicInput::read_file(public_input_path);
+    let digest = hex::encode(public_input_digest(&public_input));
+    if !digest.eq_ignore_ascii_case(expected_digest.trim_start_matches("0x")) {
+        log::warn!("public input digest 0x{} does not match the expected digest {}", digest, expected_digest);
+        return Ok(VerificationOutcome {
+            valid: false,
+            public_fields: public_input.public_fields(),
+        });
+    }
+    verify::<C>(params_path, circuit_config_path, vk_path, proof_path, public_input_path)
+}
+
+/// Verify a proof for the email verification circuit after checking that its public input has exactly the documented instance order with [`DefaultEmailVerifyPublicInput::check_instance_order`].
+/// It rejects a public input whose commitments are moved between its lists, which [`verify`] accepts because the instances are the same.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// * `proof_path` - a file path of the proof.
+/// * `public_input_path` - a file path of the public input.
+/// # Return values
+/// Return a [`VerificationOutcome`], which is invalid if the instance order does not match without verifying the proof.
+pub fn verify_strict<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, proof_path: &str, public_input_path: &str) -> Result<VerificationOutcome, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let public_input = DefaultEmailVerifyPublicInput::read_file(public_input_path);
+    if let Err(err) = public_input.check_instance_order() {
+        log::warn!("the public input does not follow the instance order: {}", err);
+        return Ok(VerificationOutcome {
+            valid: false,
+            public_fields: public_input.public_fields(),
+        });
+    }
+    verify::<C>(params_path, circuit_config_path, vk_path, proof_path, public_input_path)
+}
+
+/// The format tag of [`ProofBundle`], which is changed when its fields are changed incompatibly.
+pub const PROOF_BUNDLE_FORMAT: &'static str = "halo2-zk-email-proof-bundle/1";
+
+/// A single self-describing file of a proof for the email verification circuit, written by [`prove_bundle`] and verified by [`verify_bundle`].
+/// It records the circuit configuration and the verifying key that the proof is for, so that a bundle for another circuit is rejected before verifying the proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofBundle {
+    /// Always [`PROOF_BUNDLE_FORMAT`].
+    pub format: String,
+    /// The hex string of [`config_hash`] of the proven circuit.
+    pub config_hash: String,
+    /// The hex string of the keccak256 hash of the verifying key, which is equal to [`vk_hash`].
+    pub vk_hash: String,
+    /// The hex string of [`params::fingerprint`] of the SRS parameters used for proving. It is empty in bundles written by an older version, which are not checked.
+    #[serde(default)]
+    pub params_fingerprint: String,
+    /// The public input of the proof.
+    pub public_input: DefaultEmailVerifyPublicInput,
+    /// Decimal strings of the instances of each instance column, which are computed from `public_input`.
+    pub instances: Vec<Vec<String>>,
+    /// The hex string of the proof with the Poseidon transcript as [`prove`] generates.
+    pub proof: String,
+}
+
+impl ProofBundle {
+    /// Read [`ProofBundle`] from a json file.
+    ///
+    /// # Arguments
+    /// * `bundle_path` - a file path of the bundle.
+    pub fn read_file(bundle_path: &str) -> Self {
+        let bundle: Self = serde_json::from_reader(File::open(bundle_path).expect("bundle_path cannot be opened")).unwrap_or_else(|err| panic!("invalid proof bundle {}: {}", bundle_path, err));
+        assert_eq!(bundle.format, PROOF_BUNDLE_FORMAT, "{} is not a proof bundle of the supported format", bundle_path);
+        bundle
+    }
+
+    /// Output [`ProofBundle`] to a json file.
+    ///
+    /// # Arguments
+    /// * `bundle_path` - a file path of the output json file.
+    pub fn write_file(&self, bundle_path: &str) {
+        let file = File::create(bundle_path).expect("bundle_path creation failed");
+        serde_json::to_writer_pretty(BufWriter::new(file), self).unwrap();
+    }
+}
+
+/// Generate a proof for the email verification circuit and write it with its public input as a [`ProofBundle`].
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `pk_path` - a file path of the proving key.
+/// * `bundle_path` - a file path of the output bundle.
+/// * `circuit` - an email verification circuit.
+/// * `public_input` - the public input of `circuit`, e.g., [`crate::DefaultEmailVerifyCircuit::gen_default_public_input`].
+/// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+pub fn prove_bundle<C: CircuitExt<Fr>>(
+    params_path: &str,
+    circuit_config_path: &str,
+    pk_path: &str,
+    bundle_path: &str,
+    circuit: C,
+    public_input: &DefaultEmailVerifyPublicInput,
+    deterministic: bool,
+) -> Result<(), Error> {
+    let bundle = gen_proof_bundle(params_path, circuit_config_path, pk_path, circuit, public_input, deterministic)?;
+    bundle.write_file(bundle_path);
+    Ok(())
+}
+
+/// Generate a proof for the email verification circuit as a [`ProofBundle`] in the same way as [`prove_bundle`] without writing it to a file.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `pk_path` - a file path of the proving key.
+/// * `circuit` - an email verification circuit.
+/// * `public_input` - the public input of `circuit`, e.g., [`crate::DefaultEmailVerifyCircuit::gen_default_public_input`].
+/// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+/// # Return values
+/// Return the [`ProofBundle`].
+pub fn gen_proof_bundle<C: CircuitExt<Fr>>(
+    params_path: &str,
+    circuit_config_path: &str,
+    pk_path: &str,
+    circuit: C,
+    public_input: &DefaultEmailVerifyPublicInput,
+    deterministic: bool,
+) -> Result<ProofBundle, Error> {
+    let context = ProverContext::new::<C>(params_path, circuit_config_path, pk_path);
+    let instances = circuit.instances();
+    let proof = context.try_prove(&circuit, deterministic)?;
+    let metadata = context.proof_metadata();
+    let bundle = ProofBundle {
+        format: PROOF_BUNDLE_FORMAT.to_string(),
+        config_hash: hex::encode(config_hash()),
+        vk_hash: metadata.vk_hash,
+        params_fingerprint: metadata.params_fingerprint,
+        public_input: public_input.clone(),
+        instances: instances
+            .iter()
+            .map(|column| column.iter().map(|instance| fe_to_biguint(instance).to_str_radix(10)).collect_vec())
+            .collect_vec(),
+        proof: hex::encode(&proof),
+    };
+    Ok(bundle)
+}
+
+/// Verify a [`ProofBundle`] for the email verification circuit.
+/// The bundle is invalid without verifying the proof if its configuration hash, verifying key hash, or instances differ from the given configuration, the given verifying key, or its public input, respectively.
+/// So is it if its parameters fingerprint, when recorded, differs from that of the given parameters.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// * `bundle_path` - a file path of the bundle.
+/// # Return values
+/// Return a [`VerificationOutcome`] that contains whether the proof is valid and the revealed fields.
+pub fn verify_bundle<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, bundle_path: &str) -> Result<VerificationOutcome, Error> {
+    verify_proof_bundle::<C>(params_path, circuit_config_path, vk_path, &ProofBundle::read_file(bundle_path))
+}
+
+/// Verify a [`ProofBundle`] in the same way as [`verify_bundle`] without reading it from a file, e.g., one embedded in a [`crate::credential::VerifiableCredential`].
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// * `bundle` - the bundle.
+/// # Return values
+/// Return a [`VerificationOutcome`] that contains whether the proof is valid and the revealed fields.
+pub fn verify_proof_bundle<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, bundle: &ProofBundle) -> Result<VerificationOutcome, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let invalid = |reason: String| {
+        log::warn!("{}", reason);
+        Ok(VerificationOutcome {
+            valid: false,
+            public_fields: bundle.public_input.public_fields(),
+        })
+    };
+    let config_hash = hex::encode(config_hash());
+    if bundle.config_hash != config_hash {
+        return invalid(format!("the bundle is for another circuit configuration (config hash {}) than the given one (config hash {})", bundle.config_hash, config_hash));
+    }
+    let vk_hash = hex::encode(vk_hash::<C>(circuit_config_path, vk_path)?);
+    if bundle.vk_hash != vk_hash {
+        return invalid(format!("the bundle is for another verifying key (hash {}) than the given one (hash {})", bundle.vk_hash, vk_hash));
+    }
+    let instances = split_instances(&bundle.public_input.instances::<Fr>(), num_instance_columns());
+    let expected_instances = instances
+        .iter()
+        .map(|column| column.iter().map(|instance| fe_to_biguint(instance).to_str_radix(10)).collect_vec())
+        .collect_vec();
+    if bundle.instances != expected_instances {
+        return invalid("the instances of the bundle differ from those computed from its public input".to_string());
+    }
+    let proof = match hex::decode(&bundle.proof) {
+        Ok(proof) => proof,
+        Err(_) => return invalid("the proof of the bundle is not a hex string".to_string()),
+    };
+    let params = params::load(params_path).unwrap();
+    let params_fingerprint = hex::encode(params::fingerprint(&params));
+    if !bundle.params_fingerprint.is_empty() && bundle.params_fingerprint != params_fingerprint {
+        return invalid(format!(
+            "the bundle was generated with other SRS parameters (fingerprint {}) than the given ones (fingerprint {})",
+            bundle.params_fingerprint, params_fingerprint
+        ));
+    }
+    let vk = {
+        let f = File::open(Path::new(vk_path)).unwrap();
+        let mut reader = BufReader::new(f);
+        VerifyingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
     };
-    verify_util::<C>(params_path, circuit_config_path, vk_path, proof, public_input_path)
+    let valid = verify_proof_with_vk(&params, &vk, &proof, &instances)?;
+    Ok(VerificationOutcome {
+        valid,
+        public_fields: bundle.public_input.public_fields(),
+    })
+}
+
+/// Generate a proof for the email verification circuit and verify it natively without writing any file.
+/// It is a shortcut of [`prove`] followed by [`verify`] for end-to-end testing.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `pk_path` - a file path of the proving key.
+/// * `circuit` - an email verification circuit.
+/// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+/// # Return values
+/// Return `true` if the generated proof is valid for the instances of the circuit, otherwise `false`.
+pub fn prove_and_verify<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, circuit: C, deterministic: bool) -> Result<bool, Error> {
+    let context = ProverContext::new::<C>(params_path, circuit_config_path, pk_path);
+    let instances = circuit.instances();
+    let proof = context.prove(circuit, deterministic);
+    context.verify(&proof, &instances)
 }
 
 /// Verify a proof for the email verification circuit generated in wasm.
@@ -249,61 +1567,278 @@ pub fn verify<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, v
 /// * `proof_hex_path` - a file path of the proof hex string.
 /// * `public_input_path` - a file path of the public input.
 /// # Return values
-/// Return `true` if the proof is valid, otherwise `false`.
-pub fn verify_wasm<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, proof_hex_path: &str, public_input_path: &str) -> Result<bool, Error> {
-    let proof = {
-        let hex = fs::read_to_string(proof_hex_path).unwrap();
-        hex::decode(hex.trim_start_matches("0x")).unwrap()
+/// Return a [`VerificationOutcome`] that contains whether the proof is valid and the revealed fields.
+pub fn verify_wasm<C: CircuitExt<Fr>>(
+    params_path: &str,
+    circuit_config_path: &str,
+    vk_path: &str,
+    proof_hex_path: &str,
+    public_input_path: &str,
+) -> Result<VerificationOutcome, Error> {
+    let proof = {
+        let hex = fs::read_to_string(proof_hex_path).unwrap();
+        hex::decode(hex.trim_start_matches("0x")).unwrap()
+    };
+    verify_util::<C>(params_path, circuit_config_path, vk_path, proof, public_input_path, None)
+}
+
+/// Compute the keccak256 hash of the verifying key, which identifies the circuit, e.g., when a contract accepts proofs of multiple circuits.
+/// The verifying key is serialized in the [`SerdeFormat::RawBytesUnchecked`] format, i.e., the same bytes as the file written by [`gen_keys`],
+/// so a contract can compute the same hash from those bytes.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// # Return values
+/// Return the keccak256 hash of the serialized verifying key.
+pub fn vk_hash<C: CircuitExt<Fr>>(circuit_config_path: &str, vk_path: &str) -> Result<[u8; 32], Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let vk = {
+        let f = File::open(Path::new(vk_path)).unwrap();
+        let mut reader = BufReader::new(f);
+        VerifyingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
+    };
+    Ok(ethers::utils::keccak256(vk.to_bytes(SerdeFormat::RawBytesUnchecked)))
+}
+
+/// The multiopen scheme of the KZG proofs, which determines the commitments of the opening proof.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiopenScheme {
+    /// SHPLONK used by [`prove`] and [`evm_prove`], whose opening proof consists of two commitments.
+    #[default]
+    Shplonk,
+    /// GWC, whose opening proof consists of a commitment for each distinct rotation of the queries.
+    Gwc,
+}
+
+/// Estimate the byte size of a proof for the circuit without proving, from the numbers of the commitments and the evaluations that the prover writes to the transcript.
+/// Each commitment is a compressed 32-byte point of G1, and each evaluation is a 32-byte scalar. The instances are not included since they are not written in the proof.
+///
+/// # Arguments
+/// * `cs` - the constraint system of the verifying key, whose selectors are already compressed into the fixed columns.
+/// * `scheme` - the multiopen scheme of the proof.
+/// # Return values
+/// Return the estimated proof size in bytes.
+pub fn estimate_proof_size(cs: &ConstraintSystem<Fr>, scheme: MultiopenScheme) -> usize {
+    const POINT_SIZE: usize = 32;
+    const SCALAR_SIZE: usize = 32;
+    let num_lookups = cs.lookups().len();
+    let num_permutation_columns = cs.permutation().get_columns().len();
+    // The permutation product is split into chunks of `degree - 2` columns.
+    let chunk_len = cs.degree() - 2;
+    let num_permutation_chunks = (num_permutation_columns + chunk_len - 1) / chunk_len;
+    // The advice, the permuted lookup input and table, the permutation products, the lookup products, the random polynomial of the vanishing argument, and the pieces of the quotient.
+    let num_commitments = cs.num_advice_columns() + 2 * num_lookups + num_permutation_chunks + num_lookups + 1 + (cs.degree() - 1);
+    // The advice and fixed queries, the random polynomial, the permuted columns, each permutation product at the current, next, and last rows except the last row of the last chunk,
+    // and each lookup product at the current and next rows, its permuted input at the current and previous rows, and its permuted table at the current row.
+    let num_evaluations = cs.advice_queries().len() + cs.fixed_queries().len() + 1 + num_permutation_columns + (3 * num_permutation_chunks).saturating_sub(1) + 5 * num_lookups;
+    let num_opening_commitments = match scheme {
+        MultiopenScheme::Shplonk => 2,
+        MultiopenScheme::Gwc => {
+            let mut rotations = cs.advice_queries().iter().map(|(_, rotation)| rotation.0).chain(cs.fixed_queries().iter().map(|(_, rotation)| rotation.0)).collect_vec();
+            rotations.push(0);
+            if num_permutation_chunks > 0 {
+                rotations.push(1);
+            }
+            if num_permutation_chunks > 1 {
+                rotations.push(-((cs.blinding_factors() + 1) as i32));
+            }
+            if num_lookups > 0 {
+                rotations.extend([1, -1]);
+            }
+            rotations.into_iter().unique().count()
+        }
+    };
+    (num_commitments + num_opening_commitments) * POINT_SIZE + num_evaluations * SCALAR_SIZE
+}
+
+/// Estimate the byte size of a proof for the email verification circuit without proving. See [`estimate_proof_size`].
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `vk_path` - a file path of the verifying key.
+/// * `scheme` - the multiopen scheme of the proof.
+/// # Return values
+/// Return the estimated proof size in bytes, which is the size of the proof written by [`prove`] without compression for [`MultiopenScheme::Shplonk`].
+pub fn estimate_app_proof_size<C: CircuitExt<Fr>>(circuit_config_path: &str, vk_path: &str, scheme: MultiopenScheme) -> usize {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let vk = {
+        let f = File::open(Path::new(vk_path)).unwrap();
+        let mut reader = BufReader::new(f);
+        VerifyingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
+    };
+    estimate_proof_size(vk.cs(), scheme)
+}
+
+/// A difference between an old verifying key and the one of the current circuit, found by [`check_vk_compat`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VkDiff {
+    /// The differing item of the verifying keys, e.g., `k` or `fixed_commitments[3]`.
+    pub item: String,
+    /// The item of the old verifying key.
+    pub old: String,
+    /// The item of the verifying key of the current circuit.
+    pub new: String,
+}
+
+/// The result of [`check_vk_compat`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VkCompatibility {
+    /// Whether the proofs generated with the old verifying key are still valid for the current circuit.
+    pub compatible: bool,
+    /// The differences, which are empty if `compatible` is `true`.
+    pub diffs: Vec<VkDiff>,
+}
+
+/// Check whether the proofs generated for an old verifying key, e.g., one generated before an upgrade, remain verifiable with the current circuit.
+/// The old key is compatible if its domain, i.e., `k`, its fixed commitments, and its permutation commitments are equal to those of the verifying key generated for the current circuit.
+/// The constraint system is not serialized in the key and is reconstructed from the current circuit configuration, so a changed layout of the columns is reported as a changed size of the key.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters.
+/// * `circuit_config_path` - a file path of the current configuration of the email verification circuit.
+/// * `old_vk_path` - a file path of the old verifying key.
+/// * `circuit` - an email verification circuit.
+/// # Return values
+/// Return [`VkCompatibility`] with the differences of the keys.
+pub fn check_vk_compat<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, old_vk_path: &str, circuit: C) -> Result<VkCompatibility, Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let app_config = default_config_params();
+    let params = params::load_downsized(params_path, app_config.degree).unwrap();
+    let new_vk = keygen_vk(&params, &circuit)?;
+    let old_vk_bytes = fs::read(old_vk_path).unwrap();
+    let diffs = diff_vks::<C>(&old_vk_bytes, &new_vk);
+    Ok(VkCompatibility {
+        compatible: diffs.is_empty(),
+        diffs,
+    })
+}
+
+/// List the differences between the serialized old verifying key and the new one as [`check_vk_compat`] does.
+///
+/// # Arguments
+/// * `old_vk_bytes` - the old verifying key serialized with [`SerdeFormat::RawBytesUnchecked`].
+/// * `new_vk` - the verifying key of the current circuit.
+/// # Return values
+/// Return the differences, which are empty if the proofs for the old key are valid for the new one.
+pub fn diff_vks<C: Circuit<Fr>>(old_vk_bytes: &[u8], new_vk: &VerifyingKey<G1Affine>) -> Vec<VkDiff> {
+    let new_vk_size = new_vk.to_bytes(SerdeFormat::RawBytesUnchecked).len();
+    let size_diff = |old: String| {
+        vec![VkDiff {
+            item: "size".to_string(),
+            old,
+            new: format!("{} bytes", new_vk_size),
+        }]
+    };
+    if old_vk_bytes.len() != new_vk_size {
+        return size_diff(format!("{} bytes", old_vk_bytes.len()));
+    }
+    let old_vk = match VerifyingKey::<G1Affine>::read::<_, C>(&mut &old_vk_bytes[..], SerdeFormat::RawBytesUnchecked) {
+        Ok(old_vk) => old_vk,
+        Err(err) => return size_diff(format!("unreadable with the current circuit: {}", err)),
     };
-    verify_util::<C>(params_path, circuit_config_path, vk_path, proof, public_input_path)
+    let mut diffs = vec![];
+    let (old_k, new_k) = (old_vk.get_domain().k(), new_vk.get_domain().k());
+    if old_k != new_k {
+        diffs.push(VkDiff {
+            item: "k".to_string(),
+            old: old_k.to_string(),
+            new: new_k.to_string(),
+        });
+    }
+    for (name, old_commitments, new_commitments) in [
+        ("fixed_commitments", old_vk.fixed_commitments(), new_vk.fixed_commitments()),
+        ("permutation_commitments", old_vk.permutation().commitments(), new_vk.permutation().commitments()),
+    ] {
+        if old_commitments.len() != new_commitments.len() {
+            diffs.push(VkDiff {
+                item: format!("{}.len()", name),
+                old: old_commitments.len().to_string(),
+                new: new_commitments.len().to_string(),
+            });
+            continue;
+        }
+        for (idx, (old_commitment, new_commitment)) in old_commitments.iter().zip(new_commitments.iter()).enumerate() {
+            if old_commitment != new_commitment {
+                diffs.push(VkDiff {
+                    item: format!("{}[{}]", name, idx),
+                    old: format!("{:?}", old_commitment),
+                    new: format!("{:?}", new_commitment),
+                });
+            }
+        }
+    }
+    diffs
 }
 
-fn verify_util<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, vk_path: &str, proof: Vec<u8>, public_input_path: &str) -> Result<bool, Error> {
+fn verify_util<C: CircuitExt<Fr>>(
+    params_path: &str,
+    circuit_config_path: &str,
+    vk_path: &str,
+    proof: Vec<u8>,
+    public_input_path: &str,
+    metadata: Option<&ProofMetadata>,
+) -> Result<VerificationOutcome, Error> {
     set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
-    let params = {
-        let f = File::open(Path::new(params_path)).unwrap();
-        let mut reader = BufReader::new(f);
-        ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-    };
-    let vk = {
+    let params = timed("load params", || params::load(params_path).unwrap());
+    let vk = timed("load vk", || {
         let f = File::open(Path::new(vk_path)).unwrap();
         let mut reader = BufReader::new(f);
         VerifyingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
-    };
-    let public_input = serde_json::from_reader::<_, DefaultEmailVerifyPublicInput>(File::open(public_input_path).unwrap()).unwrap();
-    let instances = public_input.instances::<Fr>();
-    let result = {
-        let mut transcript_read = PoseidonTranscript::<NativeLoader, &[u8]>::new(&proof);
-        VerificationStrategy::<_, VerifierSHPLONK<Bn256>>::finalize(verify_proof::<_, VerifierSHPLONK<Bn256>, _, _, _>(
-            params.verifier_params(),
-            &vk,
-            AccumulatorStrategy::new(params.verifier_params()),
-            &[&[instances.as_slice()]],
-            &mut transcript_read,
-        )?)
-    };
+    });
+    let public_input = DefaultEmailVerifyPublicInput::read_file(public_input_path);
+    if let Some(reason) = metadata.and_then(|metadata| metadata.mismatch(&ProofMetadata::new(&vk, &params))) {
+        log::warn!("{}", reason);
+        return Ok(VerificationOutcome {
+            valid: false,
+            public_fields: public_input.public_fields(),
+        });
+    }
+    let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+    let valid = timed("verify proof", || verify_proof_with_vk(&params, &vk, &proof, &instances))?;
+    Ok(VerificationOutcome {
+        valid,
+        public_fields: public_input.public_fields(),
+    })
+}
+
+/// Run a step of a command, logging its elapsed time at the debug level, e.g., with `--log-level debug`.
+fn timed<T>(step: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    log::debug!("{}: {:.3} s", step, start.elapsed().as_secs_f64());
+    result
+}
+
+fn verify_proof_with_vk(params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>, proof: &[u8], instances: &[Vec<Fr>]) -> Result<bool, Error> {
+    let instances = instances.iter().map(|instance| instance.as_slice()).collect_vec();
+    let mut transcript_read = PoseidonTranscript::<NativeLoader, &[u8]>::new(proof);
+    let result = VerificationStrategy::<_, VerifierSHPLONK<Bn256>>::finalize(verify_proof::<_, VerifierSHPLONK<Bn256>, _, _, _>(
+        params.verifier_params(),
+        vk,
+        AccumulatorStrategy::new(params.verifier_params()),
+        &[instances.as_slice()],
+        &mut transcript_read,
+    )?);
     Ok(result)
 }
 
 /// Generate a proof for the email verification circuit verifiable on EVM.
+/// The proof uses the Keccak256 transcript, which the Solidity verifier generated by [`gen_evm_verifier`] expects.
 ///
 /// # Arguments
 /// * `params_path` - a file path of the SRS parameters.
 /// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
 /// * `pk_path` - a file path of the proving key.
-/// * `proof_path` - a file path of the output proof.
+/// * `proof_path` - a file path of the output proof. If it ends with `.hex`, the proof is written as a `0x`-prefixed hex string.
 /// * `circuit` - an email verification circuit.
-pub fn evm_prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, proof_path: &str, circuit: C) -> Result<(), Error> {
+/// * `deterministic` - if `true`, the proof is generated with a fixed seed. It breaks zero-knowledge, so use it only for tests.
+pub fn evm_prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str, pk_path: &str, proof_path: &str, circuit: C, deterministic: bool) -> Result<(), Error> {
     set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
-    let mut params = {
-        let f = File::open(Path::new(params_path)).unwrap();
-        let mut reader = BufReader::new(f);
-        ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-    };
+    check_key_config(pk_path).unwrap_or_else(|err| panic!("{}", err));
     let app_config = default_config_params();
-    if params.k() > app_config.degree {
-        params.downsize(app_config.degree);
-    }
+    let params = params::load_downsized(params_path, app_config.degree).unwrap();
     let pk = {
         let f = File::open(Path::new(pk_path)).unwrap();
         let mut reader = BufReader::new(f);
@@ -312,91 +1847,156 @@ pub fn evm_prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str
     // let (circuit, headerhash, public_key_n, header_substrs, body_substrs) = gen_circuit_from_email_path(email_path).await;
     let timer = start_timer!(|| "generate evm proof");
     let instances = circuit.instances();
-    let proof = gen_evm_proof_shplonk(&params, &pk, circuit, instances, &mut OsRng);
+    let proof = timed("generate evm proof", || gen_evm_proof_shplonk(&params, &pk, circuit, instances, &mut proof_rng(deterministic)));
     end_timer!(timer);
-    {
-        // let proof_hex = hex::encode(&proof);
-        // let mut file = File::create(proof_path)?;
-        // write!(file, "0x{}", proof_hex).unwrap();
-        // file.flush().unwrap();
-        let f = File::create(proof_path).unwrap();
-        let mut writer = BufWriter::new(f);
-        writer.write_all(&proof).unwrap();
-        writer.flush().unwrap();
+    write_evm_proof(proof_path, &proof);
+    Ok(())
+}
+
+/// Generate an aggregation proof of the email verification proofs verifiable on EVM.
+///
+/// # Arguments
+/// * `app_params_path` - a file path of the SRS parameters for the email verification circuit.
+/// * `agg_params_path` - a file path of the SRS parameters for the aggregation circuit.
+/// * `app_circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit.
+/// * `app_pk_path` - a file path of the proving key for the email verification circuit.
+/// * `agg_pk_path` - a file path of the proving key for the aggregation circuit.
+/// * `proof_path` - a file path of the output proof for the aggregation circuit. If it ends with `.hex`, the proof is written as a `0x`-prefixed hex string.
+/// * `public_input_path` - a file path of the output public input, i.e., [`AggregationPublicInput`].
+/// * `acc_path` - a file path of the running aggregation.
+/// If it is given and exists, the proofs are appended to the previous aggregation stored in the file, whose accumulator is folded into the new one.
+/// The new aggregation is then written to the file so that more proofs can be appended later.
+/// Since the aggregation circuit depends on the previous aggregation, `agg_pk_path` must be generated by [`gen_agg_key`] with the same `acc_path`.
+/// All the aggregated proofs must be of the same email verification circuit.
+/// * `app_circuits` - email verification circuits whose proofs are aggregated. If `num_snarks` is set in the aggregation circuit configuration, exactly `num_snarks` circuits must be given.
+/// * `fail_fast` - if `true`, each app proof is verified before the aggregation, which panics with [`InvalidAppProofError`] naming the index of the first invalid proof in `app_circuits`.
+pub fn evm_prove_agg<C: CircuitExt<Fr>>(
+    app_params_path: &str,
+    agg_params_path: &str,
+    app_circuit_config_path: &str,
+    agg_circuit_config_path: &str,
+    app_pk_path: &str,
+    agg_pk_path: &str,
+    proof_path: &str,
+    public_input_path: &str,
+    acc_path: Option<&str>,
+    app_circuits: Vec<C>,
+    fail_fast: bool,
+) -> Result<(), Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, app_circuit_config_path);
+    set_var(VERIFY_CONFIG_KEY, agg_circuit_config_path);
+    if let Some(num_snarks) = agg_num_snarks(agg_circuit_config_path) {
+        assert_eq!(
+            app_circuits.len(),
+            num_snarks,
+            "the aggregation circuit aggregates {} proofs, but {} emails are given",
+            num_snarks,
+            app_circuits.len()
+        );
+    }
+    let agg_params = params::load(agg_params_path).unwrap();
+    let mut num_app_instances = app_circuits.iter().map(|circuit| circuit.num_instance().iter().sum::<usize>()).collect_vec();
+    let prev_snark = read_prev_agg_snark(acc_path);
+    if let Some(prev_snark) = prev_snark.as_ref() {
+        // The instances of the previous aggregation except for its accumulator are those of the previously aggregated proofs.
+        let num_prev_instances = prev_snark.instances[0].len() - NUM_ACC_INSTANCES;
+        let num_instances = num_app_instances[0];
+        assert_eq!(num_prev_instances % num_instances, 0, "the aggregated proofs must be of the same circuit");
+        num_app_instances = vec![vec![num_instances; num_prev_instances / num_instances], num_app_instances].concat();
+    }
+    let agg_circuit = gen_agg_circuit(app_params_path, app_pk_path, &agg_params, prev_snark, app_circuits, fail_fast);
+    let agg_pk = {
+        let f = File::open(Path::new(agg_pk_path)).unwrap();
+        let mut reader = BufReader::new(f);
+        ProvingKey::<G1Affine>::read::<_, PublicAggregationCircuit>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
     };
+    let instances = agg_circuit.instances();
+    let public_input = AggregationPublicInput::new(&instances[0], &num_app_instances);
+    let timer = start_timer!(|| "generate agg evm proof");
+    let proof = gen_evm_proof_shplonk(&agg_params, &agg_pk, agg_circuit.clone(), instances, &mut OsRng);
+    end_timer!(timer);
+    write_evm_proof(proof_path, &proof);
+    public_input.write_file(public_input_path);
+    if let Some(acc_path) = acc_path {
+        // `gen_snark_shplonk` returns the snark in the given path as it is if the file exists, so the previous aggregation must be removed first.
+        if Path::new(acc_path).exists() {
+            fs::remove_file(acc_path).unwrap();
+        }
+        gen_snark_shplonk(&agg_params, &agg_pk, agg_circuit, &mut OsRng, Some(acc_path));
+        log::info!("the running aggregation is written to {}", acc_path);
+    }
     Ok(())
 }
 
-// /// Generate proving and verifying keys of the aggregation circuit verifiable on EVM..
-// ///
-// /// # Arguments
-// /// * `app_params_path` - a file path of the SRS parameters for the email verification circuit.
-// /// * `agg_params_path` - a file path of the SRS parameters for the aggregation circuit.
-// /// * `app_circuit_config_path` - a file path of the configuration of the email verification circuit.
-// /// * `agg_circuit_config_path` - a file path of the configuration of the aggregation circuit.
-// /// * `app_pk_path` - a file path of the proving key for the email verification circuit.
-// /// * `agg_pk_path` - a file path of the proving key for the aggregation circuit.
-// /// * `acc_path` - a file path of the output accumulator for the aggregation circuit.
-// /// * `proof_path` - a file path of the output proof for the aggregation circuit.
-// /// * `app_circuit` - an email verification circuit.
-// pub fn evm_prove_agg<C: CircuitExt<Fr>>(
-//     app_params_path: &str,
-//     agg_params_path: &str,
-//     app_circuit_config_path: &str,
-//     agg_circuit_config_path: &str,
-//     app_pk_path: &str,
-//     agg_pk_path: &str,
-//     acc_path: &str,
-//     proof_path: &str,
-//     app_circuit: C,
-//     // public_input_path: &str,
-// ) -> Result<(), Error> {
-//     set_var(EMAIL_VERIFY_CONFIG_ENV, app_circuit_config_path);
-//     set_var(VERIFY_CONFIG_KEY, agg_circuit_config_path);
-//     let agg_params = {
-//         let f = File::open(Path::new(agg_params_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-//     };
-//     let app_params = {
-//         let f = File::open(Path::new(app_params_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-//     };
-//     // let (app_circuit, headerhash, public_key_n, header_substrs, body_substrs) = gen_circuit_from_email_path(email_path).await;
-//     let app_pk = {
-//         let f = File::open(Path::new(app_pk_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ProvingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
-//     };
-//     let snark = gen_snark_shplonk(&app_params, &app_pk, app_circuit, &mut OsRng, None::<&str>);
-//     println!("snark generated");
-//     let agg_circuit = PublicAggregationCircuit::new(&agg_params, vec![snark], false, &mut OsRng);
-//     let agg_pk = {
-//         let f = File::open(Path::new(agg_pk_path)).unwrap();
-//         let mut reader = BufReader::new(f);
-//         ProvingKey::<G1Affine>::read::<_, PublicAggregationCircuit>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
-//     };
-//     let instances = agg_circuit.instances();
-//     println!("instances {:?}", instances[0]);
+/// An error of an app proof that fails the verification before being aggregated.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("the app proof at index {index} is invalid, so the proofs are not aggregated")]
+pub struct InvalidAppProofError {
+    /// The index of the first invalid proof among the aggregated app proofs, excluding the running aggregation.
+    pub index: usize,
+}
 
-//     let acc = encode_calldata(&[instances[0][0..NUM_ACC_INSTANCES].to_vec()], &[]);
-//     {
-//         let acc_hex = hex::encode(&acc);
-//         let mut file = File::create(acc_path)?;
-//         write!(file, "0x{}", acc_hex).unwrap();
-//         file.flush().unwrap();
-//     };
-//     let proof = gen_evm_proof_shplonk(&agg_params, &agg_pk, agg_circuit, instances, &mut OsRng);
-//     println!("proof generated");
-//     {
-//         let proof_hex = hex::encode(&proof);
-//         let mut file = File::create(proof_path)?;
-//         write!(file, "0x{}", proof_hex).unwrap();
-//         file.flush().unwrap();
-//     };
-//     Ok(())
-// }
+/// Verify each app proof individually, so that an invalid proof is named before the aggregation fails as a whole.
+///
+/// # Arguments
+/// * `params` - the SRS parameters for the app circuit.
+/// * `vk` - the verifying key of the app circuit.
+/// * `snarks` - the app proofs to be aggregated.
+/// # Return values
+/// Return [`InvalidAppProofError`] with the index of the first invalid proof if any.
+pub fn verify_app_snarks(params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>, snarks: &[Snark]) -> Result<(), InvalidAppProofError> {
+    for (index, snark) in snarks.iter().enumerate() {
+        // A malformed proof makes the verifier return an error, which is also invalid.
+        if !verify_proof_with_vk(params, vk, &snark.proof, &snark.instances).unwrap_or(false) {
+            return Err(InvalidAppProofError { index });
+        }
+    }
+    Ok(())
+}
+
+/// Read the running aggregation from `acc_path` if it is given and exists.
+fn read_prev_agg_snark(acc_path: Option<&str>) -> Option<Snark> {
+    acc_path
+        .filter(|acc_path| Path::new(acc_path).exists())
+        .map(|acc_path| read_snark(acc_path).expect("fail to read the running aggregation"))
+}
+
+fn gen_agg_circuit<C: CircuitExt<Fr>>(
+    app_params_path: &str,
+    app_pk_path: &str,
+    agg_params: &ParamsKZG<Bn256>,
+    prev_snark: Option<Snark>,
+    app_circuits: Vec<C>,
+    fail_fast: bool,
+) -> PublicAggregationCircuit {
+    let snarks = gen_app_snarks(app_params_path, app_pk_path, app_circuits, fail_fast);
+    let has_prev_accumulator = prev_snark.is_some();
+    let snarks = prev_snark.into_iter().chain(snarks).collect_vec();
+    PublicAggregationCircuit::new(agg_params, snarks, has_prev_accumulator, &mut OsRng)
+}
+
+/// Generate the proofs of the email verification circuits to be aggregated.
+fn gen_app_snarks<C: CircuitExt<Fr>>(app_params_path: &str, app_pk_path: &str, app_circuits: Vec<C>, fail_fast: bool) -> Vec<Snark> {
+    check_key_config(app_pk_path).unwrap_or_else(|err| panic!("{}", err));
+    let app_config = default_config_params();
+    let app_params = params::load_downsized(app_params_path, app_config.degree).unwrap();
+    let app_pk = {
+        let f = File::open(Path::new(app_pk_path)).unwrap();
+        let mut reader = BufReader::new(f);
+        ProvingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytesUnchecked).unwrap()
+    };
+    let snarks = app_circuits
+        .into_iter()
+        .map(|app_circuit| gen_snark_shplonk(&app_params, &app_pk, app_circuit, &mut OsRng, None::<&str>))
+        .collect_vec();
+    log::info!("{} snarks generated", snarks.len());
+    if fail_fast {
+        verify_app_snarks(&app_params, app_pk.get_vk(), &snarks).unwrap_or_else(|err| panic!("{}", err));
+        log::info!("{} app proofs verified", snarks.len());
+    }
+    snarks
+}
 
 /// Generate yul and Solidity code of the verifier contract for the email verification circuit.
 ///
@@ -406,23 +2006,36 @@ pub fn evm_prove<C: CircuitExt<Fr>>(params_path: &str, circuit_config_path: &str
 /// * `vk_path` - a file path of the verifying key.
 /// * `sols_path` - a directory path of the output Solidity codes.
 /// * `max_line_size_per_file` - the maximum bytes size of each output Solidity code.
+/// * `solidity_version` - the version in the `pragma solidity` line of the output Solidity codes. [`DEFAULT_SOLIDITY_VERSION`] is used if `None`.
+/// * `emit_verified_event` - if true, the email verifier contract emits `Verified(bytes32 publicInputsHash)` on successful verification.
+/// * `pretty` - if true, the output Solidity codes are re-indented by [`crate::eth::gen_verifier::format_solidity`] so that they are diffable across regenerations.
 pub fn gen_evm_verifier<C: CircuitExt<Fr>>(
     params_path: &str,
     circuit_config_path: &str,
     vk_path: &str,
     sols_dir: &str,
     max_line_size_per_file: Option<usize>,
+    solidity_version: Option<&str>,
+    emit_verified_event: bool,
+    pretty: bool,
 ) -> Result<(), Error> {
     set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
-    let mut params = {
-        let f = File::open(Path::new(params_path)).unwrap();
-        let mut reader = BufReader::new(f);
-        ParamsKZG::<Bn256>::read(&mut reader).unwrap()
-    };
     let app_config = default_config_params();
-    if params.k() > app_config.degree {
-        params.downsize(app_config.degree);
+    if num_instance_columns() != 1 {
+        log::error!("The EVM verifier supports only one instance column.");
+        return Err(Error::Synthesis);
+    }
+    let num_block_hash_instances = if app_config.bind_block_hash.unwrap_or(false) { 2 } else { 0 };
+    if num_instances() != INSTANCE_NAMES.len() + num_block_hash_instances {
+        log::error!("The EVM verifier does not support the integer instances, i.e., the DKIM timestamp and the predicate thresholds.");
+        return Err(Error::Synthesis);
+    }
+    let solidity_version = solidity_version.unwrap_or(DEFAULT_SOLIDITY_VERSION);
+    if !Regex::new(r"^[0-9\.\^~<>= ]+$").unwrap().is_match(solidity_version) {
+        log::error!("Invalid Solidity version {}.", solidity_version);
+        return Err(Error::Synthesis);
     }
+    let params = params::load_downsized(params_path, app_config.degree).unwrap();
     let vk = {
         let f = File::open(vk_path).unwrap();
         let mut reader = BufReader::new(f);
@@ -432,7 +2045,7 @@ pub fn gen_evm_verifier<C: CircuitExt<Fr>>(
     if PathBuf::new().join(sols_dir).exists() {
         fs::remove_dir_all(sols_dir).unwrap();
     }
-    gen_sol_verifiers(&params, &vk, max_line_size_per_file, &PathBuf::new().join(sols_dir));
+    gen_sol_verifiers(&params, &vk, max_line_size_per_file, &PathBuf::new().join(sols_dir), solidity_version, emit_verified_event, pretty);
     Ok(())
 }
 
@@ -505,15 +2118,10 @@ pub fn gen_evm_verifier<C: CircuitExt<Fr>>(
 /// Therefore, your verifier contract may violate that limitation even if it passes the verification here.
 pub async fn evm_verify(circuit_config_path: &str, sols_dir: &str, proof_path: &str, public_input_path: &str, gas_limit: Option<u64>) -> Result<(), Error> {
     set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
-    let proof = {
-        let mut f = File::open(&proof_path).unwrap();
-        let mut buf = Vec::new();
-        f.read_to_end(&mut buf).unwrap();
-        buf
-    };
-    println!("proof {}", hex::encode(&proof));
-    let public_input = serde_json::from_reader(File::open(public_input_path).unwrap()).unwrap();
-    println!("public_input {:?}", public_input);
+    let proof = read_proof(proof_path);
+    log::debug!("proof {}", hex::encode(&proof));
+    let public_input = DefaultEmailVerifyPublicInput::read_file(public_input_path);
+    log::debug!("public_input {:?}", public_input);
     let gas_limit = gas_limit.unwrap_or(100000000);
     deploy_and_call_verifiers(&PathBuf::new().join(sols_dir), None, &proof, &public_input, gas_limit).await;
     Ok(())
@@ -552,22 +2160,53 @@ pub async fn evm_verify(circuit_config_path: &str, sols_dir: &str, proof_path: &
 
 /// Generate regex-definition text files from the given decomposed regex json file.
 ///
+/// Instead of the `parts` of the decomposed regex, the json may give a single `regex` with named capture groups, e.g., `{"max_byte_size": 1024, "regex": "code: (?P<code>(0|1|2|3|4|5|6|7|8|9)+)"}`.
+/// It is decomposed by [`decompose_named_regex`](crate::utils::decompose_named_regex), and the substring file of each group is named `{regex_files_prefix}_substr_{name}.txt`,
+/// from which [`substr_field_names`](crate::utils::substr_field_names) derives the field name.
+/// Likewise, the json may give a `json_path`, e.g., `{"max_byte_size": 1024, "json_path": "data.order.total", "value_type": "number"}`, which is converted by [`json_path_regex_parts`],
+/// and the matching `substr_regexes` are logged.
+///
 /// # Arguments
 /// * `decomposed_regex_config_path` - a file path pf the decomposed regex json.
 /// * `regex_dir_path` - a directory path in which the output text files are stored.
 /// * `regex_files_prefix` - a prefix used for the output text files.
 pub fn gen_regex_files(decomposed_regex_config_path: &str, regex_dir_path: &str, regex_files_prefix: &str) -> Result<(), Error> {
-    let decomposed_regex_config = serde_json::from_reader::<File, DecomposedRegexConfig>(File::open(decomposed_regex_config_path).unwrap()).unwrap();
+    let config_json = serde_json::from_reader::<File, serde_json::Value>(File::open(decomposed_regex_config_path).unwrap()).unwrap();
     let regex_dir_path = PathBuf::new().join(regex_dir_path);
     let allstr_file_path = regex_dir_path.join(format!("{}_allstr.txt", regex_files_prefix));
-    let mut num_public_parts = 0usize;
-    for part in decomposed_regex_config.parts.iter() {
-        if part.is_public {
-            num_public_parts += 1;
+    let named_parts = if let Some(regex) = config_json.get("regex").and_then(|regex| regex.as_str()) {
+        Some(decompose_named_regex(regex).unwrap_or_else(|err| panic!("invalid named regex: {}", err)))
+    } else if let Some(json_path) = config_json.get("json_path").and_then(|json_path| json_path.as_str()) {
+        let value_type = config_json
+            .get("value_type")
+            .map(|value_type| serde_json::from_value::<JsonValueType>(value_type.clone()).expect("value_type must be number or string"))
+            .unwrap_or_default();
+        let substr_regexes = json_path_substr_regexes(json_path, value_type).unwrap_or_else(|err| panic!("invalid json path: {}", err));
+        log::info!("substr_regexes of {}: {}", json_path, serde_json::to_string(&substr_regexes).unwrap());
+        Some(json_path_regex_parts(json_path, value_type).unwrap())
+    } else {
+        None
+    };
+    let (decomposed_regex_config, substr_names) = match named_parts {
+        Some(parts) => {
+            let max_byte_size = config_json["max_byte_size"].as_u64().expect("max_byte_size is required");
+            let substr_names = parts.iter().filter_map(|(name, _)| name.clone()).collect_vec();
+            let parts_json = parts
+                .into_iter()
+                .map(|(name, regex_def)| serde_json::json!({ "is_public": name.is_some(), "regex_def": regex_def, "max_size": max_byte_size }))
+                .collect_vec();
+            let config = serde_json::from_value::<DecomposedRegexConfig>(serde_json::json!({ "max_byte_size": max_byte_size, "parts": parts_json })).unwrap();
+            (config, substr_names)
         }
-    }
-    let substr_file_pathes = (0..num_public_parts)
-        .map(|idx| regex_dir_path.join(format!("{}_substr_{}.txt", regex_files_prefix, idx)))
+        None => {
+            let config = serde_json::from_value::<DecomposedRegexConfig>(config_json).unwrap();
+            let num_public_parts = config.parts.iter().filter(|part| part.is_public).count();
+            (config, (0..num_public_parts).map(|idx| idx.to_string()).collect_vec())
+        }
+    };
+    let substr_file_pathes = substr_names
+        .iter()
+        .map(|name| regex_dir_path.join(format!("{}_substr_{}.txt", regex_files_prefix, name)))
         .collect_vec();
     decomposed_regex_config
         .gen_regex_files(&allstr_file_path, &substr_file_pathes)
@@ -575,10 +2214,143 @@ pub fn gen_regex_files(decomposed_regex_config_path: &str, regex_dir_path: &str,
     Ok(())
 }
 
+/// Read the raw bytes of the email at the given path.
+/// If `email_path` starts with `http://` or `https://`, the email is fetched from the URL and its response body is returned as is, i.e., the bytes are not decoded nor normalized.
+/// Otherwise, it is read from the local file.
+///
+/// # Arguments
+/// * `email_path` - a file path or an http(s) URL of the email.
+/// # Return values
+/// Return the email bytes, or the error of reading the file or fetching the URL.
+pub async fn read_email_bytes(email_path: &str) -> Result<Vec<u8>, std::io::Error> {
+    if !email_path.starts_with("http://") && !email_path.starts_with("https://") {
+        return fs::read(email_path);
+    }
+    let to_io_err = |err: reqwest::Error| std::io::Error::new(std::io::ErrorKind::Other, format!("fail to fetch {}: {}", email_path, err));
+    let response = reqwest::get(email_path).await.map_err(to_io_err)?.error_for_status().map_err(to_io_err)?;
+    let email_bytes = response.bytes().await.map_err(to_io_err)?;
+    Ok(email_bytes.to_vec())
+}
+
+/// Generate the source of a standalone Rust test that checks the email verification circuit for the given email with `MockProver` and asserts the substrings extracted from it.
+/// The email bytes and the RSA public key are embedded in the source, so the test needs neither the email file nor any DNS query.
+/// It depends on `halo2-zk-email`, `halo2-base`, `num-bigint`, and `snark-verifier-sdk`, and reads the configuration and its regex files from the given paths relative to the directory where the test runs.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `test_name` - the name of the test function, which must be a Rust identifier.
+/// * `circuit` - an email verification circuit.
+/// # Return values
+/// Return the source of the test file.
+pub fn gen_mock_prover_test(circuit_config_path: &str, test_name: &str, circuit: &crate::DefaultEmailVerifyCircuit<Fr>) -> String {
+    assert!(
+        test_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && test_name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'),
+        "the test name {} is not a Rust identifier",
+        test_name
+    );
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let degree = default_config_params().degree;
+    let public_input = circuit.gen_default_public_input();
+    let string_vec = |substrs: &[String]| format!("vec![{}]", substrs.iter().map(|substr| format!("{:?}.to_string()", substr)).join(", "));
+    format!(
+        r#"//! Generated by `zkemail gen-test` for the configuration {circuit_config_path}.
+use halo2_base::halo2_proofs::dev::MockProver;
+use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use halo2_zk_email::{{DefaultEmailVerifyCircuit, EMAIL_VERIFY_CONFIG_ENV}};
+use num_bigint::BigUint;
+use snark_verifier_sdk::CircuitExt;
+
+const EMAIL: &[u8] = b"{email}";
+const PUBLIC_KEY_N: &str = "{public_key_n}";
+
+#[test]
+fn {test_name}() {{
+    std::env::set_var(EMAIL_VERIFY_CONFIG_ENV, {circuit_config_path:?});
+    let public_key_n = BigUint::parse_bytes(PUBLIC_KEY_N.as_bytes(), 16).unwrap();
+    let circuit = DefaultEmailVerifyCircuit::<Fr>::new(EMAIL.to_vec(), public_key_n);
+    let public_input = circuit.gen_default_public_input();
+    assert_eq!(public_input.header_substrs, {header_substrs});
+    assert_eq!(public_input.body_substrs, {body_substrs});
+    let prover = MockProver::run({degree}, &circuit, circuit.instances()).unwrap();
+    assert_eq!(prover.verify(), Ok(()));
+}}
+"#,
+        circuit_config_path = circuit_config_path,
+        email = circuit.email_bytes.escape_ascii(),
+        public_key_n = circuit.public_key_n.to_str_radix(16),
+        test_name = test_name,
+        header_substrs = string_vec(&public_input.header_substrs),
+        body_substrs = string_vec(&public_input.body_substrs),
+        degree = degree,
+    )
+}
+
+/// Extract the substrings that the email verification circuit would expose from the given email.
+/// It only runs the substring regexes natively on the canonicalized email, i.e., neither circuit nor proof is generated.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `email_bytes` - email bytes, e.g., read by [`read_email_bytes`].
+/// * `encoding` - the encoding to decode the canonicalized email, which panics if it is [`EmailEncoding::Strict`] and the email is not valid UTF-8.
+/// * `dkim_options` - the options that select the DKIM signature whose canonicalized email is searched.
+/// # Return values
+/// Return a tuple of the (start position, substring) in the email header and those in the email body.
+pub fn extract_substrs(
+    circuit_config_path: &str,
+    email_bytes: &[u8],
+    encoding: EmailEncoding,
+    dkim_options: &crate::dkim::DkimOptions,
+) -> Result<(Vec<Option<(usize, String)>>, Vec<Option<(usize, String)>>), Error> {
+    set_var(EMAIL_VERIFY_CONFIG_ENV, circuit_config_path);
+    let email_bytes = dkim_options.select_signature(email_bytes.to_vec()).unwrap_or_else(|err| panic!("{}", err));
+    let (canonicalized_header, canonicalized_body, _) = crate::dkim::canonicalize_signed_email_with_body_length(&email_bytes).unwrap();
+    let header_str = encoding.decode(&canonicalized_header);
+    let body_str = encoding.decode(&canonicalized_body);
+    let config_params = default_config_params();
+    let header_config = config_params.header_config.as_ref().expect("header_config is required");
+    let body_config = config_params.body_config.as_ref().expect("body_config is required");
+    // The positions are relative to the whole body, including the skipped prefix.
+    let body_str = if body_config.smime_signed_content.unwrap_or(false) {
+        let skip_prefix_bytes_size = body_config.skip_prefix_bytes_size.unwrap_or(0);
+        "\0".repeat(skip_prefix_bytes_size) + &body_config.searched_str(&canonicalized_body)
+    } else {
+        body_str
+    };
+    Ok(get_email_substrs(&header_str, &body_str, header_config.substr_regexes.clone(), body_config.substr_regexes.clone()))
+}
+
+/// Extract the substrings as [`extract_substrs`] does, paired with their field names derived by [`substr_field_names`].
+/// The fields of the substring files generated from named capture groups are named after the groups.
+///
+/// # Arguments
+/// * `circuit_config_path` - a file path of the configuration of the email verification circuit.
+/// * `email_bytes` - email bytes, e.g., read by [`read_email_bytes`].
+/// * `encoding` - the encoding to decode the canonicalized email.
+/// * `dkim_options` - the options that select the DKIM signature whose canonicalized email is searched.
+/// # Return values
+/// Return a tuple of the (field name, (start position, substring)) in the email header and those in the email body.
+pub fn extract_named_substrs(
+    circuit_config_path: &str,
+    email_bytes: &[u8],
+    encoding: EmailEncoding,
+    dkim_options: &crate::dkim::DkimOptions,
+) -> Result<(Vec<(String, Option<(usize, String)>)>, Vec<(String, Option<(usize, String)>)>), Error> {
+    let (header_substrs, body_substrs) = extract_substrs(circuit_config_path, email_bytes, encoding, dkim_options)?;
+    let config_params = default_config_params();
+    let header_config = config_params.header_config.as_ref().expect("header_config is required");
+    let body_config = config_params.body_config.as_ref().expect("body_config is required");
+    let header_names = substr_field_names(&header_config.allstr_filepathes, &header_config.substr_filepathes);
+    let body_names = substr_field_names(&body_config.allstr_filepathes, &body_config.substr_filepathes);
+    Ok((
+        header_names.into_iter().zip(header_substrs.into_iter()).collect_vec(),
+        body_names.into_iter().zip(body_substrs.into_iter()).collect_vec(),
+    ))
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod test {
-    use crate::{DefaultEmailVerifyCircuit, DefaultEmailVerifyPublicInput};
+    use crate::{DefaultEmailVerifyCircuit, DefaultEmailVerifyPublicInput, InstanceOrderError, EMAIL_VERIFY_CONFIG_JSON_ENV};
 
     use super::*;
     use cfdkim::{canonicalize_signed_email, resolve_public_key};
@@ -592,9 +2364,604 @@ mod test {
     use std::{fs::File, io::Read, path::Path};
     use temp_env;
 
+    /// The golden file of [`test_golden_proof`], which is regenerated if [`UPDATE_GOLDEN_ENV`] is set.
+    const GOLDEN_PROOF_PATH: &str = "./test_data/golden/demo_proof.json";
+    /// The environment variable to regenerate the golden file after an intentional change of the circuit.
+    const UPDATE_GOLDEN_ENV: &str = "EMAIL_VERIFY_UPDATE_GOLDEN";
+
+    /// A proof of `./examples/demo.eml` under `./configs/default_app.config` with the SRS, the keys, and the blinding factors seeded with [`DETERMINISTIC_PROOF_SEED`].
+    #[derive(Serialize, Deserialize)]
+    struct GoldenProof {
+        /// The hex string of the `n` parameter of the RSA public key, which is stored so that a rotated DKIM key does not change the proof.
+        public_key_n: String,
+        public_input: serde_json::Value,
+        /// The hex string of the proof bytes.
+        proof: String,
+    }
+
+    #[test]
+    fn test_aggregation_public_input() {
+        let agg_instances = (0..NUM_ACC_INSTANCES + 6).map(|idx| Fr::from(idx as u64)).collect_vec();
+        let public_input = AggregationPublicInput::new(&agg_instances, &[3, 3]);
+        assert_eq!(public_input.accumulator.len(), NUM_ACC_INSTANCES);
+        assert_eq!(public_input.app_instances.len(), 2);
+        for (idx, app_instances) in public_input.app_instances.iter().enumerate() {
+            assert_eq!(app_instances.index, idx);
+            let expected = (0..3).map(|j| (NUM_ACC_INSTANCES + 3 * idx + j).to_string()).collect_vec();
+            assert_eq!(app_instances.instances, expected);
+        }
+    }
+
+    #[test]
+    fn test_aggregation_calldata() {
+        let agg_instances = (0..NUM_ACC_INSTANCES + 6).map(|idx| Fr::from(idx as u64) * Fr::from(u64::MAX)).collect_vec();
+        let public_input = AggregationPublicInput::new(&agg_instances, &[3, 3]);
+        let words = public_input.accumulator_words();
+        assert_eq!(words.len(), NUM_ACC_INSTANCES);
+        for (word, limb) in words.iter().zip(agg_instances.iter()) {
+            assert_eq!(word.iter().rev().cloned().collect_vec(), limb.to_bytes().to_vec());
+        }
+        let proof = vec![0xab; 64];
+        let calldata = public_input.encode_calldata(&proof);
+        assert_eq!(calldata, snark_verifier_sdk::evm::encode_calldata(&[agg_instances], &proof));
+    }
+
+    #[test]
+    fn test_public_fields() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_ex1_email_verify.config"), || {
+            let public_input = DefaultEmailVerifyPublicInput {
+                sign_commit: "0".to_string(),
+                public_key_hash: "0".to_string(),
+                header_starts: vec![10, 40, 80],
+                header_substrs: vec!["alice@zkemail.com".to_string(), "".to_string(), "Hello".to_string()],
+                body_starts: vec![],
+                body_substrs: vec![],
+                body_hidden_commits: vec![],
+                header_recipient_commits: vec![],
+                body_decoded_commits: vec![],
+                header_hidden_commits: vec![],
+                header_bodyhash_commits: vec![],
+                dkim_timestamp: None,
+                predicate_thresholds: vec![],
+                block_number: None,
+                block_hash: None,
+            };
+            let public_fields = public_input.public_fields();
+            assert_eq!(
+                public_fields,
+                vec![("from".to_string(), b"alice@zkemail.com".to_vec()), ("subject".to_string(), b"Hello".to_vec())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_public_fields_decode_encoded_words() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_ex1_email_verify.config").unwrap()).unwrap();
+        config["header_config"]["decode_encoded_words"] = serde_json::Value::Bool(true);
+        let config_json = config.to_string();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config_json.as_str()), || {
+            let public_input = DefaultEmailVerifyPublicInput {
+                sign_commit: "0".to_string(),
+                public_key_hash: "0".to_string(),
+                header_starts: vec![10, 40, 80],
+                header_substrs: vec!["alice@zkemail.com".to_string(), "".to_string(), "=?UTF-8?B?44GT44KT44Gr44Gh44Gv?=".to_string()],
+                body_starts: vec![],
+                body_substrs: vec![],
+                body_hidden_commits: vec![],
+                header_recipient_commits: vec![],
+                body_decoded_commits: vec![],
+                header_hidden_commits: vec![],
+                header_bodyhash_commits: vec![],
+                dkim_timestamp: None,
+                predicate_thresholds: vec![],
+                block_number: None,
+                block_hash: None,
+            };
+            assert_eq!(
+                public_input.public_fields(),
+                vec![("from".to_string(), b"alice@zkemail.com".to_vec()), ("subject".to_string(), "こんにちは".as_bytes().to_vec())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_compressed_params() {
+        let params_path = "./build/test_uncompressed.params";
+        let compressed_params_path = "./build/test_compressed.params";
+        fs::create_dir_all("./build").unwrap();
+        gen_params(params_path, 8, SerdeFormat::RawBytes, false).unwrap();
+        let params = params::load(params_path).unwrap();
+        create_maybe_compressed(compressed_params_path, true, |mut writer| params.write(&mut writer)).unwrap();
+        let compressed_params = params::load(compressed_params_path).unwrap();
+        let mut expected = vec![];
+        params.write(&mut expected).unwrap();
+        let mut actual = vec![];
+        compressed_params.write(&mut actual).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_check_params() {
+        let params_path = "./build/test_check.params";
+        let truncated_params_path = "./build/test_check_truncated.params";
+        fs::create_dir_all("./build").unwrap();
+        gen_params(params_path, 4, SerdeFormat::RawBytes, false).unwrap();
+        assert_eq!(check_params(params_path).unwrap(), 4);
+        let bytes = fs::read(params_path).unwrap();
+        fs::write(truncated_params_path, &bytes[0..100]).unwrap();
+        let err = check_params(truncated_params_path).unwrap_err();
+        assert!(matches!(err, ParamsCheckError::Truncated { offset: 100, .. }));
+        assert!(err.to_string().contains("byte offset 100"));
+    }
+
+    #[test]
+    fn test_downsize_and_verify_params() {
+        let params_path = "./build/test_downsize_and_verify.params";
+        let new_params_path = "./build/test_downsize_and_verify_new.params";
+        fs::create_dir_all("./build").unwrap();
+        gen_params(params_path, 6, SerdeFormat::RawBytes, false).unwrap();
+        assert!(downsize_and_verify_params(params_path, new_params_path, 5, SerdeFormat::RawBytes, false).unwrap());
+        assert!(matches!(
+            downsize_and_verify_params(params_path, new_params_path, 7, SerdeFormat::RawBytes, false),
+            Err(Error::NotEnoughRowsAvailable { current_k: 6 })
+        ));
+        assert!(matches!(downsize_and_verify_params(params_path, new_params_path, 2, SerdeFormat::RawBytes, false), Err(Error::NotEnoughRowsAvailable { .. })));
+    }
+
+    impl CircuitExt<Fr> for SquareCircuit {
+        fn num_instance(&self) -> Vec<usize> {
+            vec![1]
+        }
+
+        fn instances(&self) -> Vec<Vec<Fr>> {
+            vec![vec![self.x * self.x]]
+        }
+    }
+
+    #[test]
+    fn test_verify_app_snarks() {
+        let params = ParamsKZG::<Bn256>::setup(5, OsRng);
+        let pk = gen_pk(&params, &SquareCircuit { x: Fr::from(1) }, None);
+        let mut snarks = (2..5).map(|x| gen_snark_shplonk(&params, &pk, SquareCircuit { x: Fr::from(x) }, &mut OsRng, None::<&str>)).collect_vec();
+        assert_eq!(verify_app_snarks(&params, pk.get_vk(), &snarks), Ok(()));
+        snarks[1].instances[0][0] += Fr::from(1);
+        assert_eq!(verify_app_snarks(&params, pk.get_vk(), &snarks), Err(InvalidAppProofError { index: 1 }));
+        snarks[2].proof.truncate(32);
+        assert_eq!(verify_app_snarks(&params, pk.get_vk(), &snarks), Err(InvalidAppProofError { index: 1 }));
+        snarks.swap(0, 2);
+        assert_eq!(verify_app_snarks(&params, pk.get_vk(), &snarks), Err(InvalidAppProofError { index: 0 }));
+    }
+
+    #[test]
+    fn test_estimate_proof_size() {
+        let params = ParamsKZG::<Bn256>::setup(5, OsRng);
+        let circuit = SquareCircuit { x: Fr::from(3) };
+        let pk = gen_pk(&params, &circuit, None);
+        let proof = gen_proof_shplonk(&params, &pk, circuit.clone(), vec![vec![circuit.x * circuit.x]], &mut OsRng, None);
+        assert_eq!(estimate_proof_size(pk.get_vk().cs(), MultiopenScheme::Shplonk), proof.len());
+    }
+
+    #[test]
+    fn test_gen_mock_prover_test() {
+        let circuit_config_path = "./configs/test_forbidden_substr_email_verify.config";
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+            let public_key = rsa::RsaPublicKey::from(&private_key);
+            let message = "From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["From"])
+                .unwrap()
+                .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&mailparse::parse_mail(message.as_bytes()).unwrap()).unwrap();
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message.as_bytes()].concat();
+            let public_key_n = BigUint::from_bytes_be(&public_key.n().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes.clone(), public_key_n.clone());
+            let public_input = circuit.gen_default_public_input();
+            let source = gen_mock_prover_test(circuit_config_path, "test_demo_email", &circuit);
+            assert!(source.contains("fn test_demo_email() {"));
+            assert!(source.contains(&format!("const PUBLIC_KEY_N: &str = \"{}\";", public_key_n.to_str_radix(16))));
+            assert!(source.contains(&format!("const EMAIL: &[u8] = b\"{}\";", email_bytes.escape_ascii())));
+            // The line endings are escaped in the byte string literal.
+            assert!(source.contains("From: alice@zkemail.com\\r\\n"));
+            let body_substrs = public_input.body_substrs.iter().map(|substr| format!("{:?}.to_string()", substr)).join(", ");
+            assert!(source.contains(&format!("assert_eq!(public_input.body_substrs, vec![{}]);", body_substrs)));
+            assert!(source.contains(&format!("MockProver::run({}, &circuit", default_config_params().degree)));
+        });
+    }
+
+    #[test]
+    fn test_diff_vks() {
+        let params = ParamsKZG::<Bn256>::setup(6, OsRng);
+        let vk = keygen_vk(&params, &SquareCircuit { x: Fr::from(1) }).unwrap();
+        let vk_bytes = vk.to_bytes(SerdeFormat::RawBytesUnchecked);
+        assert_eq!(diff_vks::<SquareCircuit>(&vk_bytes, &vk), vec![]);
+        let mut small_params = params.clone();
+        small_params.downsize(5);
+        let small_vk = keygen_vk(&small_params, &SquareCircuit { x: Fr::from(1) }).unwrap();
+        let diffs = diff_vks::<SquareCircuit>(&small_vk.to_bytes(SerdeFormat::RawBytesUnchecked), &vk);
+        assert_eq!(diffs[0].item, "k");
+        assert_eq!((diffs[0].old.as_str(), diffs[0].new.as_str()), ("5", "6"));
+        let diffs = diff_vks::<SquareCircuit>(&vk_bytes[..vk_bytes.len() - 1], &vk);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].item, "size");
+    }
+
+    #[test]
+    fn test_read_params_downsized() {
+        let params_path = "./build/test_downsized.params";
+        fs::create_dir_all("./build").unwrap();
+        gen_params(params_path, 10, SerdeFormat::RawBytes, false).unwrap();
+        let mut params = params::load(params_path).unwrap();
+        params.downsize(8);
+        let downsized_params = params::load_downsized(params_path, 8).unwrap();
+        let mut expected = vec![];
+        params.write(&mut expected).unwrap();
+        let mut actual = vec![];
+        downsized_params.write(&mut actual).unwrap();
+        assert_eq!(expected, actual);
+        let full_params = params::load_downsized(params_path, 12).unwrap();
+        assert_eq!(full_params.k(), 10);
+    }
+
+    #[test]
+    fn test_extract_substrs() {
+        let circuit_config_path = "./configs/test_ex1_email_verify.config";
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let email_bytes = fs::read("./test_data/test_email1.eml").unwrap();
+            let (header_substrs, body_substrs) = extract_substrs(circuit_config_path, &email_bytes, EmailEncoding::Lossy, &crate::dkim::DkimOptions::default()).unwrap();
+            let config_params = default_config_params();
+            assert_eq!(header_substrs.len(), config_params.header_config.as_ref().unwrap().substr_regexes.len());
+            assert_eq!(body_substrs.len(), config_params.body_config.as_ref().unwrap().substr_regexes.len());
+            assert!(header_substrs.iter().all(|substr| substr.is_some()));
+        });
+    }
+
+    #[test]
+    fn test_validate_config() {
+        assert_eq!(validate_config("./configs/test1_email_verify.config", Some("./configs/agg_bench.config")), Vec::<String>::new());
+        let circuit_config_path = "./build/test_validate_config.config";
+        fs::create_dir_all("./build").unwrap();
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test1_email_verify.config").unwrap()).unwrap();
+        config["header_config"]["allstr_filepathes"][0] = serde_json::Value::from("./test_data/not_exist_allstr.txt");
+        config["body_config"]["substr_regexes"][0][0] = serde_json::Value::from("(?<=@[a-z]+)x");
+        fs::write(circuit_config_path, config.to_string()).unwrap();
+        let problems = validate_config(circuit_config_path, None);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0], "header_config.allstr_filepathes[0]: ./test_data/not_exist_allstr.txt does not exist");
+        assert!(problems[1].starts_with("body_config.substr_regexes[0][0]: "));
+    }
+
+    #[test]
+    fn test_proof_rng_disable_blinding() {
+        use rand::RngCore;
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test1_email_verify.config").unwrap()).unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string()), || {
+            assert_ne!(proof_rng(false).next_u64(), proof_rng(false).next_u64());
+            assert_eq!(proof_rng(true).next_u64(), proof_rng(true).next_u64());
+        });
+        config["disable_blinding"] = serde_json::Value::from(true);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string()), || {
+            assert_eq!(proof_rng(false).next_u64(), proof_rng(false).next_u64());
+        });
+    }
+
+    #[test]
+    fn test_write_hex_chunked() {
+        let proof = (0..3 * HEX_CHUNK_SIZE + 17).map(|i| (i * 7) as u8).collect_vec();
+        let mut hex = Vec::new();
+        write_hex_chunked(&mut hex, &proof).unwrap();
+        assert_eq!(String::from_utf8(hex).unwrap(), format!("0x{}", hex::encode(&proof)));
+        let proof_path = "./build/test_write_hex_chunked.hex";
+        fs::create_dir_all("./build").unwrap();
+        write_evm_proof(proof_path, &proof);
+        assert_eq!(read_proof(proof_path), proof);
+        fs::remove_file(proof_path).unwrap();
+    }
+
+    #[test]
+    fn test_check_key_config() {
+        let pk_path = "./build/test_key_config.pk";
+        fs::create_dir_all("./build").unwrap();
+        let _ = fs::remove_file(format!("{}{}", pk_path, KEY_METADATA_SUFFIX));
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_ex1_email_verify.config"), || {
+            assert_eq!(check_key_config(pk_path), Ok(()));
+            KeyMetadata::current().write_file(pk_path);
+            assert_eq!(check_key_config(pk_path), Ok(()));
+        });
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/default_app.config"), || {
+            let err = check_key_config(pk_path).unwrap_err();
+            assert!(matches!(err, KeyConfigError::Mismatch { .. }));
+            assert!(err.to_string().contains("another circuit configuration"));
+        });
+    }
+
+    #[test]
+    fn test_agg_num_snarks() {
+        let agg_circuit_config_path = "./build/test_agg_num_snarks.config";
+        fs::create_dir_all("./build").unwrap();
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/agg_bench.config").unwrap()).unwrap();
+        assert_eq!(agg_num_snarks("./configs/agg_bench.config"), None);
+        config["num_snarks"] = serde_json::Value::from(4);
+        fs::write(agg_circuit_config_path, config.to_string()).unwrap();
+        assert_eq!(agg_num_snarks(agg_circuit_config_path), Some(4));
+        assert_eq!(pad_app_circuits(vec![1, 2], Some(4)), vec![1, 2, 2, 2]);
+        assert_eq!(pad_app_circuits(vec![1, 2], None), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_gen_named_regex_files() {
+        let regex_config_path = "./build/test_named_regex_defs.json";
+        fs::create_dir_all("./build").unwrap();
+        let digit = "(0|1|2|3|4|5|6|7|8|9)";
+        let config = serde_json::json!({ "max_byte_size": 64, "regex": format!("code: (?P<code>{}{}{}{}{}{})\r\n", digit, digit, digit, digit, digit, digit) });
+        fs::write(regex_config_path, config.to_string()).unwrap();
+        gen_regex_files(regex_config_path, "./build", "test_named").unwrap();
+        let allstr_path = "./build/test_named_allstr.txt".to_string();
+        let substr_path = "./build/test_named_substr_code.txt".to_string();
+        assert!(Path::new(&substr_path).exists());
+        crate::utils::check_regex_files(&allstr_path, &[substr_path.clone()]).unwrap();
+        assert_eq!(substr_field_names(&[allstr_path], &[vec![substr_path]]), vec!["code"]);
+    }
+
+    #[test]
+    fn test_verify_with_digest_mismatch() {
+        let circuit_config_path = "./configs/test_forbidden_substr_email_verify.config";
+        let public_input_path = "./build/test_digest_public_input.json";
+        fs::create_dir_all("./build").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let public_input = DefaultEmailVerifyPublicInput {
+                sign_commit: "1".to_string(),
+                public_key_hash: "2".to_string(),
+                header_starts: vec![5],
+                header_substrs: vec!["alice@zkemail.com".to_string()],
+                body_starts: vec![20],
+                body_substrs: vec!["zkemailverify".to_string()],
+                body_hidden_commits: vec![],
+                header_recipient_commits: vec![],
+                body_decoded_commits: vec![],
+                header_hidden_commits: vec![],
+                header_bodyhash_commits: vec![],
+                dkim_timestamp: None,
+                predicate_thresholds: vec![],
+                block_number: None,
+                block_hash: None,
+            };
+            public_input.write_file(public_input_path);
+            let mut tampered = public_input.clone();
+            tampered.body_substrs = vec!["zkemailverifz".to_string()];
+            assert_ne!(public_input_digest(&tampered), public_input_digest(&public_input));
+            // The proof is not read if the digest does not match.
+            let outcome = verify_with_digest::<DefaultEmailVerifyCircuit<Fr>>(
+                "./build/missing_params.bin",
+                circuit_config_path,
+                "./build/missing.vk",
+                "./build/missing.proof",
+                public_input_path,
+                &format!("0x{}", hex::encode(public_input_digest(&tampered))),
+            )
+            .unwrap();
+            assert!(!outcome.valid);
+            assert_eq!(
+                outcome.public_fields,
+                vec![("from".to_string(), b"alice@zkemail.com".to_vec()), ("test1_email_body".to_string(), b"zkemailverify".to_vec())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_verify_strict_instance_order() {
+        let circuit_config_path = "./configs/test_json_path_email_verify.config";
+        let public_input_path = "./build/test_strict_public_input.json";
+        fs::create_dir_all("./build").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let public_input = DefaultEmailVerifyPublicInput {
+                sign_commit: "1".to_string(),
+                public_key_hash: "2".to_string(),
+                header_starts: vec![22],
+                header_substrs: vec!["123456".to_string()],
+                body_starts: vec![0],
+                body_substrs: vec!["".to_string()],
+                body_hidden_commits: vec!["3".to_string()],
+                header_recipient_commits: vec![],
+                body_decoded_commits: vec![],
+                header_hidden_commits: vec![],
+                header_bodyhash_commits: vec![],
+                dkim_timestamp: None,
+                predicate_thresholds: vec![],
+                block_number: None,
+                block_hash: None,
+            };
+            assert_eq!(public_input.check_instance_order(), Ok(()));
+            // The commitment moved to the next list has the same instances, so only the strict check rejects it.
+            let mut reordered = public_input.clone();
+            reordered.header_recipient_commits = std::mem::take(&mut reordered.body_hidden_commits);
+            assert_eq!(reordered.instances::<Fr>(), public_input.instances::<Fr>());
+            assert_eq!(
+                reordered.check_instance_order(),
+                Err(InstanceOrderError {
+                    field: "body_hidden_commits".to_string(),
+                    expected: 1,
+                    actual: 0,
+                })
+            );
+            reordered.write_file(public_input_path);
+            // The proof is not read if the instance order does not match.
+            let outcome = verify_strict::<DefaultEmailVerifyCircuit<Fr>>(
+                "./build/missing_params.bin",
+                circuit_config_path,
+                "./build/missing.vk",
+                "./build/missing.proof",
+                public_input_path,
+            )
+            .unwrap();
+            assert!(!outcome.valid);
+        });
+    }
+
+    #[test]
+    fn test_verify_bundle_mismatch() {
+        let circuit_config_path = "./configs/test_forbidden_substr_email_verify.config";
+        let bundle_path = "./build/test_mismatch_bundle.json";
+        fs::create_dir_all("./build").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let public_input = DefaultEmailVerifyPublicInput {
+                sign_commit: "1".to_string(),
+                public_key_hash: "2".to_string(),
+                header_starts: vec![5],
+                header_substrs: vec!["alice@zkemail.com".to_string()],
+                body_starts: vec![20],
+                body_substrs: vec!["zkemailverify".to_string()],
+                body_hidden_commits: vec![],
+                header_recipient_commits: vec![],
+                body_decoded_commits: vec![],
+                header_hidden_commits: vec![],
+                header_bodyhash_commits: vec![],
+                dkim_timestamp: None,
+                predicate_thresholds: vec![],
+                block_number: None,
+                block_hash: None,
+            };
+            let bundle = ProofBundle {
+                format: PROOF_BUNDLE_FORMAT.to_string(),
+                config_hash: hex::encode([0u8; 32]),
+                vk_hash: hex::encode([0u8; 32]),
+                params_fingerprint: hex::encode([0u8; 32]),
+                public_input,
+                instances: vec![],
+                proof: "".to_string(),
+            };
+            bundle.write_file(bundle_path);
+            let read_bundle = ProofBundle::read_file(bundle_path);
+            assert_eq!(read_bundle.public_input.body_substrs, bundle.public_input.body_substrs);
+            // Neither the parameters nor the verifying key is read if the configuration hash does not match.
+            let outcome = verify_bundle::<DefaultEmailVerifyCircuit<Fr>>("./build/missing_params.bin", circuit_config_path, "./build/missing.vk", bundle_path).unwrap();
+            assert!(!outcome.valid);
+            assert_eq!(
+                outcome.public_fields,
+                vec![("from".to_string(), b"alice@zkemail.com".to_vec()), ("test1_email_body".to_string(), b"zkemailverify".to_vec())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_explain_verification_failure() {
+        use cfdkim::SignerBuilder;
+        use rsa::RsaPrivateKey;
+        let circuit_config_path = "./configs/test_forbidden_substr_email_verify.config";
+        let public_input_path = "./build/test_explain_public_input.json";
+        fs::create_dir_all("./build").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+            let public_key = rsa::RsaPublicKey::from(&private_key);
+            let message = "From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["From"])
+                .unwrap()
+                .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&mailparse::parse_mail(message.as_bytes()).unwrap()).unwrap();
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message.as_bytes()].concat();
+            let public_key_n = BigUint::from_bytes_be(&public_key.n().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+            let mut public_input = circuit.gen_default_public_input();
+            public_input.write_file(public_input_path);
+            let explanation = explain_verification_failure(circuit_config_path, &circuit, public_input_path).unwrap();
+            assert!(explanation.instance_mismatches.is_empty());
+            assert!(explanation.failures.is_empty());
+
+            let expected = public_input.instances::<Fr>()[1];
+            public_input.public_key_hash = "1".to_string();
+            public_input.write_file(public_input_path);
+            let explanation = explain_verification_failure(circuit_config_path, &circuit, public_input_path).unwrap();
+            assert_eq!(
+                explanation.instance_mismatches,
+                vec![InstanceMismatch {
+                    name: "public_key_hash".to_string(),
+                    column: 0,
+                    row: 1,
+                    expected,
+                    given: Fr::from(1),
+                }]
+            );
+            assert!(!explanation.failures.is_empty());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_read_email_bytes() {
+        let email_path = "./test_data/test_email1.eml";
+        assert_eq!(read_email_bytes(email_path).await.unwrap(), fs::read(email_path).unwrap());
+        assert!(read_email_bytes("./test_data/not_found.eml").await.is_err());
+        assert!(read_email_bytes("http://127.0.0.1:1/demo.eml").await.is_err());
+    }
+
     #[ignore]
     #[tokio::test]
-    async fn test_helper_app_circuit() {
+    async fn test_golden_proof() {
+        let circuit_config_path = "./configs/default_app.config";
+        let email_bytes = fs::read("./examples/demo.eml").unwrap();
+        let golden = if std::env::var(UPDATE_GOLDEN_ENV).is_ok() {
+            None
+        } else {
+            let file = File::open(GOLDEN_PROOF_PATH).unwrap_or_else(|_| panic!("{} is not found; run this test with {}=1 to generate it", GOLDEN_PROOF_PATH, UPDATE_GOLDEN_ENV));
+            Some(serde_json::from_reader::<_, GoldenProof>(file).unwrap())
+        };
+        let public_key_n = match golden.as_ref() {
+            Some(golden) => BigUint::parse_bytes(golden.public_key_n.as_bytes(), 16).unwrap(),
+            None => {
+                let logger = slog::Logger::root(slog::Discard, slog::o!());
+                match resolve_public_key(&logger, &email_bytes).await.unwrap() {
+                    cfdkim::DkimPublicKey::Rsa(_pk) => BigUint::from_radix_le(&_pk.n().clone().to_radix_le(16), 16).unwrap(),
+                    _ => panic!("Only RSA keys are supported."),
+                }
+            }
+        };
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), move || {
+            let config_params = default_config_params();
+            let circuit = DefaultEmailVerifyCircuit::new(email_bytes, public_key_n.clone());
+            let public_input = serde_json::to_value(circuit.gen_default_public_input()).unwrap();
+            // The SRS is also seeded, so that the keys and thus the proof bytes are reproducible.
+            let params = ParamsKZG::<Bn256>::setup(config_params.degree, StdRng::seed_from_u64(DETERMINISTIC_PROOF_SEED));
+            let pk = gen_pk(&params, &circuit, None);
+            let instances = circuit.instances();
+            let proof = gen_proof_shplonk(&params, &pk, circuit, instances.clone(), &mut proof_rng(true), None);
+            assert!(verify_proof_with_vk(&params, pk.get_vk(), &proof, &instances).unwrap());
+            match golden {
+                Some(golden) => {
+                    let message = format!("the circuit changed; if it is intentional, run this test with {}=1 and commit {}", UPDATE_GOLDEN_ENV, GOLDEN_PROOF_PATH);
+                    assert_eq!(public_input, golden.public_input, "{}", message);
+                    assert!(hex::encode(&proof) == golden.proof, "{}", message);
+                }
+                None => {
+                    let golden = GoldenProof {
+                        public_key_n: public_key_n.to_str_radix(16),
+                        public_input,
+                        proof: hex::encode(&proof),
+                    };
+                    fs::create_dir_all(Path::new(GOLDEN_PROOF_PATH).parent().unwrap()).unwrap();
+                    fs::write(GOLDEN_PROOF_PATH, serde_json::to_string_pretty(&golden).unwrap()).unwrap();
+                }
+            }
+        });
+    }
+
+    /// Generate the regex files of `./configs/app_bench.config`, and read `./test_data/test_email1.eml` and the modulus of its DKIM public key from DNS.
+    async fn load_app_bench_email() -> (Vec<u8>, BigUint) {
         gen_regex_files("./test_data/bodyhash_defs.json", "./test_data", "body_hash").unwrap();
         gen_regex_files("./test_data/from_defs.json", "./test_data", "from").unwrap();
         gen_regex_files("./test_data/to_defs.json", "./test_data", "to").unwrap();
@@ -607,7 +2974,6 @@ mod test {
             f.read_to_end(&mut buf).unwrap();
             buf
         };
-        println!("email {}", String::from_utf8(email_bytes.clone()).unwrap());
         let public_key_n = {
             let logger = slog::Logger::root(slog::Discard, slog::o!());
             match resolve_public_key(&logger, &email_bytes).await.unwrap() {
@@ -617,6 +2983,77 @@ mod test {
                 }
             }
         };
+        (email_bytes, public_key_n)
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_prove_concurrently() {
+        let (email_bytes, public_key_n) = load_app_bench_email().await;
+        let circuit_config_path = "./configs/app_bench.config";
+        let params_path = "./build/test_concurrent.params";
+        let pk_path = "./build/test_concurrent.pk";
+        let vk_path = "./build/test_concurrent.vk";
+        fs::create_dir_all("./build").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), move || {
+            let config_params = default_config_params();
+            let circuit = DefaultEmailVerifyCircuit::new(email_bytes.clone(), public_key_n.clone());
+            gen_params(params_path, config_params.degree, SerdeFormat::RawBytes, false).unwrap();
+            gen_keys(params_path, circuit_config_path, pk_path, vk_path, circuit.clone(), true).unwrap();
+            // Every concurrently generated proof verifies, and a failing circuit does not abort the others.
+            let context = ProverContext::new::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, pk_path);
+            let proofs = context.prove_concurrently(vec![circuit.clone(); 4], 2, false);
+            for proof in proofs.iter() {
+                assert!(context.verify(proof.as_ref().unwrap(), &circuit.instances()).unwrap());
+            }
+            let invalid_circuit = DefaultEmailVerifyCircuit::new(b"not an email".to_vec(), public_key_n.clone());
+            let proofs = context.prove_concurrently(vec![circuit.clone(), invalid_circuit, circuit.clone()], 2, false);
+            assert_eq!(proofs[1], Err(BatchProveError::Proving));
+            assert!(proofs[0].is_ok() && proofs[2].is_ok());
+        });
+    }
+
+    #[ignore]
+    #[test]
+    fn test_prove_batch() {
+        let circuit_config_path = "./configs/app_bench.config";
+        let params_path = "./build/test_batch.params";
+        let pk_path = "./build/test_batch.pk";
+        let vk_path = "./build/test_batch.vk";
+        fs::create_dir_all("./build").unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (email_bytes, public_key_n) = runtime.block_on(load_app_bench_email());
+        // The selector of the DKIM signature is renamed, so that its public key cannot be resolved from DNS.
+        let unresolvable_email = String::from_utf8(email_bytes.clone())
+            .unwrap()
+            .replacen("d=gmail.com; s=20230601", "d=gmail.com; s=unresolvable", 1)
+            .into_bytes();
+        assert_ne!(unresolvable_email, email_bytes);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            let config_params = default_config_params();
+            let circuit = DefaultEmailVerifyCircuit::new(email_bytes.clone(), public_key_n.clone());
+            gen_params(params_path, config_params.degree, SerdeFormat::RawBytes, false).unwrap();
+            gen_keys(params_path, circuit_config_path, pk_path, vk_path, circuit.clone(), false).unwrap();
+            let context = ProverContext::new::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, pk_path);
+            let emails = vec![email_bytes.clone(), unresolvable_email, email_bytes.clone(), b"not an email".to_vec()];
+            let results = runtime.block_on(context.prove_batch(&emails, false, &crate::dkim::DkimOptions::default(), 2, false));
+            // Each failed email is reported at its own position, and the others are still proven.
+            assert_eq!(results.len(), emails.len());
+            assert!(matches!(results[1], Err(BatchProveError::Email(crate::dkim::DkimError::UnresolvablePublicKey(_)))));
+            assert!(matches!(&results[3], Err(BatchProveError::Email(crate::dkim::DkimError::MissingTag(tag))) if tag == "a"));
+            for idx in [0, 2] {
+                let (proof, public_input) = results[idx].as_ref().unwrap();
+                assert_eq!(public_input.instances::<Fr>(), circuit.gen_default_public_input().instances::<Fr>());
+                assert!(context.verify(proof, &circuit.instances()).unwrap());
+            }
+        });
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_helper_app_circuit() {
+        let (email_bytes, public_key_n) = load_app_bench_email().await;
+        println!("email {}", String::from_utf8(email_bytes.clone()).unwrap());
         // let header_str = String::from_utf8(canonicalized_header.clone()).unwrap();
         // let body_str = String::from_utf8(canonicalized_body.clone()).unwrap();
         let circuit_config_path = "./configs/app_bench.config";
@@ -625,6 +3062,8 @@ mod test {
         let pk_path = "./build/test.pk";
         let vk_path = "./build/test.vk";
         let proof_path = "./build/test.proof";
+        let deterministic_proof_path = "./build/test_deterministic.proof";
+        let bundle_path = "./build/test_bundle.json";
         let evm_proof_path = "./build/test_evm.proof";
         let sols_dir = "./build/test_sols";
         temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), move || {
@@ -633,13 +3072,46 @@ mod test {
             let circuit = DefaultEmailVerifyCircuit::new(email_bytes.clone(), public_key_n.clone());
             let public_input = circuit.gen_default_public_input();
             public_input.write_file(&public_input_path);
-            gen_params(params_path, config_params.degree).unwrap();
-            gen_keys(params_path, circuit_config_path, pk_path, vk_path, circuit.clone()).unwrap();
-            prove(params_path, circuit_config_path, pk_path, proof_path, circuit.clone()).unwrap();
+            gen_params(params_path, config_params.degree, SerdeFormat::RawBytes, false).unwrap();
+            gen_keys(params_path, circuit_config_path, pk_path, vk_path, circuit.clone(), true).unwrap();
+            let expected_vk_hash = ethers::utils::keccak256(fs::read(vk_path).unwrap());
+            assert_eq!(vk_hash::<DefaultEmailVerifyCircuit<Fr>>(circuit_config_path, vk_path).unwrap(), expected_vk_hash);
+            prove(params_path, circuit_config_path, pk_path, proof_path, circuit.clone(), false, false).unwrap();
             let result = verify::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, proof_path, public_input_path).unwrap();
-            assert!(result);
-            evm_prove(params_path, circuit_config_path, pk_path, evm_proof_path, circuit.clone()).unwrap();
-            gen_evm_verifier::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, sols_dir, None).unwrap();
+            assert!(result.valid);
+            let metadata = ProofMetadata::read_file(proof_path).unwrap();
+            assert_eq!(metadata.vk_hash, hex::encode(expected_vk_hash));
+            assert_eq!(metadata.params_fingerprint, hex::encode(params::fingerprint(&params::load(params_path).unwrap())));
+            // The proof is rejected before verification with parameters of another setup.
+            let other_params_path = "./build/test_other.params";
+            gen_params(other_params_path, config_params.degree, SerdeFormat::RawBytes, false).unwrap();
+            let result = verify::<DefaultEmailVerifyCircuit<Fr>>(other_params_path, circuit_config_path, vk_path, proof_path, public_input_path).unwrap();
+            assert!(!result.valid);
+            prove(params_path, circuit_config_path, pk_path, deterministic_proof_path, circuit.clone(), false, true).unwrap();
+            let deterministic_proof = fs::read(deterministic_proof_path).unwrap();
+            prove(params_path, circuit_config_path, pk_path, deterministic_proof_path, circuit.clone(), false, true).unwrap();
+            assert_eq!(fs::read(deterministic_proof_path).unwrap(), deterministic_proof);
+            let result = verify::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, deterministic_proof_path, public_input_path).unwrap();
+            assert!(result.valid);
+            prove_bundle(params_path, circuit_config_path, pk_path, bundle_path, circuit.clone(), &public_input, false).unwrap();
+            let result = verify_bundle::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, bundle_path).unwrap();
+            assert!(result.valid);
+            // The claims of the credential are the extracted fields, and its embedded proof verifies.
+            let credential_path = "./build/test_credential.json";
+            crate::credential::prove_credential(params_path, circuit_config_path, pk_path, credential_path, circuit.clone(), &public_input, false).unwrap();
+            let result = crate::credential::verify_credential::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, credential_path).unwrap();
+            assert!(result.valid);
+            let mut credential = crate::credential::VerifiableCredential::read_file(credential_path);
+            for (name, value) in public_input.public_fields() {
+                assert_eq!(credential.credential_subject[&name], serde_json::Value::from(String::from_utf8(value).unwrap()));
+            }
+            // A tampered claim is rejected.
+            credential.credential_subject.insert("from".to_string(), serde_json::Value::from("mallory@zkemail.com"));
+            credential.write_file(credential_path);
+            let result = crate::credential::verify_credential::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, credential_path).unwrap();
+            assert!(!result.valid);
+            evm_prove(params_path, circuit_config_path, pk_path, evm_proof_path, circuit.clone(), false).unwrap();
+            gen_evm_verifier::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, vk_path, sols_dir, None, None, true, false).unwrap();
         });
         evm_verify(circuit_config_path, sols_dir, evm_proof_path, public_input_path, None).await.unwrap();
     }
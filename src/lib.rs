@@ -19,27 +19,68 @@
 //! The [`RegexSha2Config`], [`RegexSha2Base64Config`], [`SignVerifyConfig`] are used for the email header, email body, and RSA signature, respectively.
 //! If you want to omit some verification in our circuit, you can build your own circuit with these chips.  
 
+/// ARC chain parsing and verification for forwarded emails.
+pub mod arc;
+/// Builder of the email verification circuit that checks its inputs.
+pub mod builder;
+/// Build configuration for bug reports.
+pub mod build_info;
 pub mod chars_shift;
+/// Self-description of the circuit for verifier tooling.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod circuit_description;
+/// Statistics of the cells used in the circuit.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod circuit_stats;
 pub mod config_params;
+/// W3C Verifiable Credentials of the email fields with an embedded proof.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod credential;
+/// Dump of the intermediate witnesses for debugging.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod debug_witness;
+/// DKIM signature header parsing.
+pub mod dkim;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod eth;
+/// Assertion of the absence of forbidden substrings.
+pub mod forbidden_substr;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod helpers;
+/// Reading and writing SRS parameters in either serialization format.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod params;
 /// Regex verification + SHA256 computation.
 pub mod regex_sha2;
 /// Regex verification + SHA256 computation + base64 encoding.
 pub mod regex_sha2_base64;
+/// Verification of two emails signed by the same sender.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod same_sender;
+/// HTTP server to generate proofs as a service.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+/// Verification of only the DKIM signature of the email header.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sign_only;
 /// RSA signature verification.
 pub mod sign_verify;
+/// Proof of only the presence of a DKIM signature, which does not authenticate the email.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod signature_presence;
 /// Util functions.
 pub mod utils;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
+/// Pre-computed witness assignment in json for the interoperability with circom tooling.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod witness_json;
 pub mod wtns_commit;
 use std::fs::File;
 use std::marker::PhantomData;
 
 use crate::chars_shift::CharsShiftConfig;
+use crate::forbidden_substr::{ForbiddenHeaderFieldConfig, ForbiddenSubstrConfig};
 #[cfg(not(target_arch = "wasm32"))]
 pub use crate::helpers::*;
 use crate::regex_sha2::RegexSha2Config;
@@ -47,6 +88,7 @@ use crate::sign_verify::*;
 use crate::utils::*;
 use crate::wtns_commit::poseidon_circuit::*;
 use crate::wtns_commit::*;
+use base64::{engine::general_purpose, Engine as _};
 use cfdkim::canonicalize_signed_email;
 #[cfg(not(target_arch = "wasm32"))]
 use cfdkim::resolve_public_key;
@@ -55,9 +97,10 @@ use halo2_base::halo2_proofs::circuit;
 use halo2_base::halo2_proofs::circuit::{SimpleFloorPlanner, Value};
 use halo2_base::halo2_proofs::plonk::{Circuit, Column, ConstraintSystem, Instance};
 use halo2_base::halo2_proofs::{circuit::Layouter, plonk::Error};
-use halo2_base::utils::fe_to_biguint;
+use halo2_base::utils::{biguint_to_fe, fe_to_biguint};
 use halo2_base::utils::{decompose_fe_to_u64_limbs, value_to_option};
 use halo2_base::QuantumCell;
+use halo2_base::{AssignedValue, Context};
 use halo2_base::{gates::range::RangeStrategy::Vertical, SKIP_FIRST_PASS};
 use halo2_base::{
     gates::{flex_gate::FlexGateConfig, range::RangeConfig, GateInstructions, RangeInstructions},
@@ -77,17 +120,148 @@ pub use halo2_rsa;
 use halo2_rsa::*;
 use itertools::Itertools;
 use num_bigint::BigUint;
-use regex_sha2_base64::RegexSha2Base64Config;
+use regex_sha2_base64::{base64_encoded_len, RegexSha2Base64Config};
 use rsa::traits::PublicKeyParts;
 use sha2::{Digest, Sha256};
 #[cfg(not(target_arch = "wasm32"))]
 use snark_verifier::loader::LoadedScalar;
 use snark_verifier_sdk::CircuitExt;
-use std::io::{Read, Write};
+use std::io::Write;
 
 /// The name of env variable for the path to the email configuration json.
 pub const EMAIL_VERIFY_CONFIG_ENV: &'static str = "EMAIL_VERIFY_CONFIG";
 
+/// The folding whitespace characters ignored in the `bh=` value of the DKIM signature header.
+pub const FOLDING_WHITESPACE_CHARS: [u8; 4] = [b' ', b'\t', b'\r', b'\n'];
+/// The maximum number of the folding whitespace characters in the `bh=` value.
+pub const MAX_BODYHASH_FOLDING_BYTES: usize = 20;
+
+/// Get the `bh=` value of the DKIM signature header in the canonicalized header without folding whitespace.
+/// The value ends at `;`, at a line break not followed by whitespace, i.e., the end of the header field, or at the end of the header.
+fn get_bodyhash_value(header_bytes: &[u8]) -> Option<String> {
+    let (_, bodyhash_value) = get_substr(&String::from_utf8_lossy(header_bytes), &[r"(?<=bh=)(?:[^;\r]|\r\n[ \t])*".to_string()])?;
+    Some(bodyhash_value.chars().filter(|c| !FOLDING_WHITESPACE_CHARS.contains(&(*c as u8))).collect())
+}
+
+/// The names of the fields of [`DefaultEmailVerifyPublicInput`] holding field elements, which are serialized by [`PublicInputFormat`].
+const PUBLIC_INPUT_FIELD_ELEMENT_KEYS: [&str; 7] = [
+    "sign_commit",
+    "public_key_hash",
+    "body_hidden_commits",
+    "header_recipient_commits",
+    "body_decoded_commits",
+    "header_hidden_commits",
+    "header_bodyhash_commits",
+];
+
+/// How the field elements in [`DefaultEmailVerifyPublicInput`], e.g., `sign_commit`, are serialized in its json file.
+/// [`DefaultEmailVerifyPublicInput::read_file`] accepts every format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PublicInputFormat {
+    /// A decimal string, e.g., `"255"`.
+    #[default]
+    Decimal,
+    /// A `0x`-prefixed big-endian hex string of 32 bytes, e.g., `"0x00...ff"`.
+    Hex,
+    /// An array of 32 little-endian bytes, e.g., `[255, 0, ..., 0]`, which is the byte representation of the field element.
+    LeBytes,
+}
+
+impl PublicInputFormat {
+    /// Return the format of the given name, i.e., `decimal`, `hex`, or `le-bytes`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "decimal" => Some(Self::Decimal),
+            "hex" => Some(Self::Hex),
+            "le-bytes" => Some(Self::LeBytes),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, decimal: &str) -> serde_json::Value {
+        let value = BigUint::parse_bytes(decimal.as_bytes(), 10).unwrap_or_else(|| panic!("{} is not a decimal string", decimal));
+        match self {
+            Self::Decimal => serde_json::Value::String(decimal.to_string()),
+            Self::Hex => serde_json::Value::String(format!("0x{:064x}", value)),
+            Self::LeBytes => {
+                let mut bytes = value.to_bytes_le();
+                bytes.resize(32, 0);
+                serde_json::json!(bytes)
+            }
+        }
+    }
+
+    /// Decode a field element serialized in any format into a decimal string.
+    fn decode(value: &serde_json::Value) -> Result<String, String> {
+        let decoded = match value {
+            serde_json::Value::String(string) => match string.strip_prefix("0x") {
+                Some(hex) => BigUint::parse_bytes(hex.as_bytes(), 16),
+                None => BigUint::parse_bytes(string.as_bytes(), 10),
+            },
+            serde_json::Value::Array(bytes) => bytes
+                .iter()
+                .map(|byte| byte.as_u64().filter(|byte| *byte < 256).map(|byte| byte as u8))
+                .collect::<Option<Vec<u8>>>()
+                .map(|bytes| BigUint::from_bytes_le(&bytes)),
+            _ => None,
+        };
+        decoded.map(|value| value.to_str_radix(10)).ok_or_else(|| format!("{} is not a field element", value))
+    }
+}
+
+/// An error of [`DefaultEmailVerifyPublicInput::check_instance_order`], where a field of the public input does not have the number of the entries expected by the circuit configuration.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("{field} has {actual} entries, but the circuit configuration expects {expected}")]
+pub struct InstanceOrderError {
+    /// The name of the field in the public input, e.g., `body_hidden_commits`.
+    pub field: String,
+    /// The number of the entries expected by the circuit configuration.
+    pub expected: usize,
+    /// The number of the entries in the public input.
+    pub actual: usize,
+}
+
+/// An error of [`DefaultEmailVerifyPublicInput::from_parts`], where the email does not satisfy the circuit configuration, so that its proof would fail.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum PublicInputError {
+    /// The email cannot be canonicalized.
+    #[error(transparent)]
+    Dkim(#[from] dkim::DkimError),
+    /// An extracted substring contains bytes that are not valid UTF-8, which the lossy email encoding replaced.
+    #[error("the substring at byte {0} contains bytes that are not valid UTF-8")]
+    InvalidUtf8(usize),
+    /// A substring required by the configuration is not found in the email.
+    #[error("field {name} is not found in the email {part}")]
+    NotFound { name: String, part: &'static str },
+    /// A substring exceeds its size limit in the configuration, e.g., `max_substr_size`.
+    #[error("field {name} exceeds {limit} {max_size}: it has {size} bytes")]
+    TooLarge { name: String, limit: &'static str, max_size: usize, size: usize },
+    /// A `Received` hop is not the expected host.
+    #[error("field {name} is not the expected host {host}")]
+    UnexpectedHost { name: String, host: String },
+    /// A body substring is not a valid hex or base64 token.
+    #[error("field {name} is not a valid {encoding} token: {substr}")]
+    InvalidToken { name: String, encoding: String, substr: String },
+    /// A reveal range splits a UTF-8 character of the body substring.
+    #[error("the revealed range {range:?} of the body substring {substr_id} splits a UTF-8 character")]
+    SplitCharacter { range: std::ops::Range<usize>, substr_id: usize },
+    /// A recipient list has more addresses than `max_recipients`.
+    #[error("field {name} has {num_addresses} addresses, but max_recipients is {max_recipients}")]
+    TooManyRecipients { name: String, num_addresses: usize, max_recipients: usize },
+    /// An address of a recipient list exceeds `max_address_size`.
+    #[error("the address {address} in field {name} exceeds max_address_size {max_address_size}")]
+    AddressTooLarge { name: String, address: String, max_address_size: usize },
+    /// A header substring is not in its membership set.
+    #[error("field {name} is not in the membership set {values:?}")]
+    NotMember { name: String, values: Vec<String> },
+    /// The `bh=` tag is not found in the email header.
+    #[error("the bodyhash is not found in the email header")]
+    MissingBodyhash,
+    /// The DKIM timestamp is not a decimal number.
+    #[error("the DKIM timestamp {0} is not a decimal number")]
+    InvalidTimestamp(String),
+}
+
 /// Public input definition of [`DefaultEmailVerifyCircuit`].
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DefaultEmailVerifyPublicInput {
@@ -102,7 +276,46 @@ pub struct DefaultEmailVerifyPublicInput {
     /// The start position of the substrings in the email body.
     pub body_starts: Vec<usize>,
     /// The substrings in the email body.
+    /// A substring with a reveal range in `body_config.reveal_ranges` is only its revealed bytes, and `body_starts` is the start position of those bytes.
     pub body_substrs: Vec<String>,
+    /// Decimal strings of the commitments of the body substrings with reveal ranges, in the order of `body_config.reveal_ranges` except for those whose `commit_hidden` is false.
+    /// Each commitment is [`value_commit_wtns_bytes_with_scheme`] with `commitment_scheme`, [`BODY_COMMIT_DOMAIN`], and `sign_commit` as the randomness,
+    /// of the whole substring placed at its position in `max_variable_byte_size` zero bytes.
+    #[serde(default)]
+    pub body_hidden_commits: Vec<String>,
+    /// Decimal strings of the commitments of the addresses in the lists of `header_config.recipients`, `max_recipients` commitments for each list in the order of its `substr_ids`.
+    /// Each commitment is [`value_commit_wtns_bytes_with_scheme`] with `commitment_scheme`, [`RECIPIENT_COMMIT_DOMAIN`], and `sign_commit` as the randomness,
+    /// of the address without whitespace padded with zero bytes to `max_address_size`. The lists themselves are not revealed in `header_substrs`.
+    #[serde(default)]
+    pub header_recipient_commits: Vec<String>,
+    /// Decimal strings of the commitments of the decoded body substrings, in the order of `body_config.decoded_tokens`.
+    /// Each commitment is [`value_commit_wtns_bytes_with_scheme`] with `commitment_scheme`, [`DECODED_COMMIT_DOMAIN`], and `sign_commit` as the randomness,
+    /// of the decoded bytes padded with zero bytes to the decoded size of `max_token_size`.
+    #[serde(default)]
+    pub body_decoded_commits: Vec<String>,
+    /// Decimal strings of the commitments of the header substrings in `header_config.committed_substrs`, in its order.
+    /// Each commitment is [`value_commit_wtns_bytes_with_scheme`] with `commitment_scheme`, [`HEADER_COMMIT_DOMAIN`], and `sign_commit` as the randomness,
+    /// of the substring padded with zero bytes to `max_substr_size`. The substrings themselves are not revealed in `header_substrs`.
+    #[serde(default)]
+    pub header_hidden_commits: Vec<String>,
+    /// A decimal string of the commitment of the `bh=` value in the DKIM signature header if `header_config.commit_bodyhash` is true, and empty otherwise.
+    /// The commitment is [`value_commit_wtns_bytes_with_scheme`] with `commitment_scheme`, [`BODYHASH_COMMIT_DOMAIN`], and `sign_commit` as the randomness,
+    /// of the base64 characters of the value without folding whitespace, which are [`base64_encoded_len`] of `body_config.hash_len` bytes.
+    #[serde(default)]
+    pub header_bodyhash_commits: Vec<String>,
+    /// The `t=` timestamp of the DKIM signature if `header_config.dkim_timestamp` is set, which is the last instance.
+    #[serde(default)]
+    pub dkim_timestamp: Option<u64>,
+    /// The thresholds of `body_config.numeric_predicates` in their order, which are the instances after the DKIM timestamp.
+    #[serde(default)]
+    pub predicate_thresholds: Vec<u64>,
+    /// The number of the block bound to the proof if `bind_block_hash` is set, with which the verifier contract looks up `block_hash`.
+    #[serde(default)]
+    pub block_number: Option<u64>,
+    /// A `0x`-prefixed hex string of the hash of the block bound to the proof if `bind_block_hash` is set.
+    /// Its upper and lower 128 bits are the last two instances.
+    #[serde(default)]
+    pub block_hash: Option<String>,
 }
 
 impl DefaultEmailVerifyPublicInput {
@@ -150,18 +363,375 @@ impl DefaultEmailVerifyPublicInput {
             header_substrs: header_substrs_vec,
             body_starts: body_starts_vec,
             body_substrs: body_substrs_vec,
+            body_hidden_commits: vec![],
+            header_recipient_commits: vec![],
+            body_decoded_commits: vec![],
+            header_hidden_commits: vec![],
+            header_bodyhash_commits: vec![],
+            dkim_timestamp: None,
+            predicate_thresholds: vec![],
+            block_number: None,
+            block_hash: None,
+        }
+    }
+
+    /// Compute [`DefaultEmailVerifyPublicInput`] natively from the email without constructing [`DefaultEmailVerifyCircuit`].
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `public_key_n` - `n` parameter of the RSA public key.
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyPublicInput`], or [`PublicInputError`] if the email cannot be canonicalized or does not satisfy the circuit configuration.
+    pub fn from_email<F: PrimeField>(email_bytes: &[u8], public_key_n: &BigUint) -> Result<Self, PublicInputError> {
+        let (header_bytes, body_bytes, signature_bytes) = dkim::canonicalize_signed_email_with_body_length(email_bytes)?;
+        Self::from_parts::<F>(&header_bytes, &body_bytes, &signature_bytes, public_key_n)
+    }
+
+    /// Compute [`DefaultEmailVerifyPublicInput`] natively from the already canonicalized email header and body and the signature.
+    ///
+    /// # Arguments
+    /// * `header_bytes` - the canonicalized email header bytes.
+    /// * `body_bytes` - the canonicalized email body bytes.
+    /// * `signature_bytes` - the RSA signature bytes.
+    /// * `public_key_n` - `n` parameter of the RSA public key.
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyPublicInput`], or [`PublicInputError`] if the email does not satisfy the circuit configuration, e.g., a substring exceeds its size limit.
+    pub fn from_parts<F: PrimeField>(header_bytes: &[u8], body_bytes: &[u8], signature_bytes: &[u8], public_key_n: &BigUint) -> Result<Self, PublicInputError> {
+        let signature = BigUint::from_bytes_be(signature_bytes);
+        let config_params = default_config_params();
+        let num_limbs = config_params.sign_verify_config.as_ref().unwrap().public_key_bits / LIMB_BITS;
+        let sign_commit: F = {
+            let limbs = decompose_biguint(&signature, num_limbs, LIMB_BITS);
+            poseidon_hash_fields(&limbs)
+        };
+        // let header_hash_commit = value_commit_wtns_bytes(&sign_rand, &header_hash);
+        let public_key_hash: F = {
+            let limbs = decompose_biguint(public_key_n, num_limbs, LIMB_BITS);
+            poseidon_hash_fields(&limbs)
+        };
+        let header_params: &HeaderConfigParams = config_params.header_config.as_ref().unwrap();
+        let body_params = config_params.body_config.as_ref().unwrap();
+        let header_bytes = &header_bytes[header_params.skip_prefix_bytes_size.unwrap_or(0)..];
+        let body_str = body_params.searched_str(body_bytes);
+        let body_bytes = &body_bytes[body_params.skip_prefix_bytes_size.unwrap_or(0)..];
+        let header_str = EmailEncoding::Lossy.decode(header_bytes);
+        let (header_substrs, mut body_substrs) = get_email_substrs(&header_str, &body_str, header_params.substr_regexes.clone(), body_params.substr_regexes.clone());
+        // The lossy encoding replaces invalid UTF-8 bytes, which cannot be revealed as a string.
+        for (bytes, substrs) in [(header_bytes, &header_substrs), (body_bytes, &body_substrs)] {
+            for (start, substr) in substrs.iter().flatten() {
+                if bytes[*start..*start + substr.len()] != *substr.as_bytes() {
+                    return Err(PublicInputError::InvalidUtf8(*start));
+                }
+            }
+        }
+        // A compared substring longer than its `max_substr_size` fails in the circuit, so report it with the field name before proving.
+        let mut size_limits = vec![];
+        for equality in header_params.substr_equalities.iter().flatten() {
+            size_limits.extend(equality.substr_ids.iter().map(|substr_id| (true, *substr_id, equality.max_substr_size)));
+        }
+        for alignment in header_params.dkim_domain_alignments.iter().flatten() {
+            size_limits.push((true, alignment.substr_id, alignment.max_substr_size));
+            size_limits.push((true, alignment.dkim_domain_substr_id, alignment.max_substr_size));
+        }
+        if let Some(timestamp) = header_params.dkim_timestamp.as_ref() {
+            size_limits.push((true, timestamp.substr_id, timestamp.max_substr_size));
+        }
+        for equality in config_params.header_body_substr_equalities.iter().flatten() {
+            size_limits.push((true, equality.header_substr_id, equality.max_substr_size));
+            size_limits.push((false, equality.body_substr_id, equality.max_substr_size));
+        }
+        let header_names = substr_field_names(&header_params.allstr_filepathes, &header_params.substr_filepathes);
+        let body_names = substr_field_names(&body_params.allstr_filepathes, &body_params.substr_filepathes);
+        for (is_header, substr_id, max_size) in size_limits {
+            let (names, substrs) = if is_header { (&header_names, &header_substrs) } else { (&body_names, &body_substrs) };
+            if let Some(Some((_, substr))) = substrs.get(substr_id - 1) {
+                if substr.len() > max_size {
+                    return Err(PublicInputError::TooLarge {
+                        name: names[substr_id - 1].clone(),
+                        limit: "max_size",
+                        max_size,
+                        size: substr.len(),
+                    });
+                }
+            }
+        }
+        for hop in header_params.received_hops.iter().flatten() {
+            let found = header_substrs[hop.substr_id - 1].as_ref().map(|(_, substr)| substr.as_str());
+            if found != Some(hop.host.as_str()) {
+                return Err(PublicInputError::UnexpectedHost {
+                    name: header_names[hop.substr_id - 1].clone(),
+                    host: hop.host.clone(),
+                });
+            }
+        }
+        let commitment_scheme = config_params.commitment_scheme.unwrap_or_default();
+        // Commit the decoded bytes of the hex or base64 tokens before their substrings are narrowed to the reveal ranges.
+        let body_decoded_commits = body_params
+            .decoded_tokens
+            .iter()
+            .flatten()
+            .map(|token| {
+                let name = &body_names[token.substr_id - 1];
+                let (_, substr) = body_substrs[token.substr_id - 1].as_ref().ok_or_else(|| PublicInputError::NotFound { name: name.clone(), part: "body" })?;
+                if substr.len() > token.max_token_size {
+                    return Err(PublicInputError::TooLarge {
+                        name: name.clone(),
+                        limit: "max_token_size",
+                        max_size: token.max_token_size,
+                        size: substr.len(),
+                    });
+                }
+                let decoded = token.encoding.decode(substr).ok_or_else(|| PublicInputError::InvalidToken {
+                    name: name.clone(),
+                    encoding: format!("{:?}", token.encoding),
+                    substr: substr.clone(),
+                })?;
+                let mut decoded_bytes = vec![0u8; token.encoding.max_decoded_size(token.max_token_size)];
+                decoded_bytes[0..decoded.len()].copy_from_slice(&decoded);
+                Ok(value_commit_wtns_bytes_with_scheme(commitment_scheme, Some(DECODED_COMMIT_DOMAIN), &sign_commit, &decoded_bytes))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Reveal only the requested ranges of the body substrings and commit the whole substrings instead.
+        let mut body_hidden_commits = vec![];
+        for reveal_range in body_params.reveal_ranges.iter().flatten() {
+            let mut hidden_bytes = vec![0u8; body_params.max_variable_byte_size];
+            if let Some((start, substr)) = body_substrs[reveal_range.substr_id - 1].as_mut() {
+                hidden_bytes[*start..*start + substr.len()].copy_from_slice(substr.as_bytes());
+                let range = reveal_range.byte_range(substr.len());
+                let revealed = substr
+                    .get(range.clone())
+                    .ok_or_else(|| PublicInputError::SplitCharacter {
+                        range: range.clone(),
+                        substr_id: reveal_range.substr_id,
+                    })?
+                    .to_string();
+                *start += range.start;
+                *substr = revealed;
+            }
+            if reveal_range.commit_hidden.unwrap_or(true) {
+                body_hidden_commits.push(value_commit_wtns_bytes_with_scheme(commitment_scheme, Some(BODY_COMMIT_DOMAIN), &sign_commit, &hidden_bytes));
+            }
+        }
+        // Commit each address of the recipient lists, and hide the lists instead.
+        let mut header_substrs = header_substrs;
+        let mut header_recipient_commits = vec![];
+        if let Some(recipients) = header_params.recipients.as_ref() {
+            for substr_id in recipients.substr_ids.iter() {
+                let name = &header_names[substr_id - 1];
+                let (_, list) = header_substrs[substr_id - 1].take().ok_or_else(|| PublicInputError::NotFound { name: name.clone(), part: "header" })?;
+                if list.len() > recipients.max_list_size {
+                    return Err(PublicInputError::TooLarge {
+                        name: name.clone(),
+                        limit: "max_list_size",
+                        max_size: recipients.max_list_size,
+                        size: list.len(),
+                    });
+                }
+                let addresses = split_address_list(&list);
+                if addresses.len() > recipients.max_recipients {
+                    return Err(PublicInputError::TooManyRecipients {
+                        name: name.clone(),
+                        num_addresses: addresses.len(),
+                        max_recipients: recipients.max_recipients,
+                    });
+                }
+                for idx in 0..recipients.max_recipients {
+                    let mut address_bytes = vec![0u8; recipients.max_address_size];
+                    if let Some(address) = addresses.get(idx) {
+                        if address.len() > recipients.max_address_size {
+                            return Err(PublicInputError::AddressTooLarge {
+                                name: name.clone(),
+                                address: address.to_string(),
+                                max_address_size: recipients.max_address_size,
+                            });
+                        }
+                        address_bytes[0..address.len()].copy_from_slice(address.as_bytes());
+                    }
+                    header_recipient_commits.push(value_commit_wtns_bytes_with_scheme(commitment_scheme, Some(RECIPIENT_COMMIT_DOMAIN), &sign_commit, &address_bytes));
+                }
+            }
+        }
+        // Commit the requested header substrings, and hide them instead.
+        let mut header_hidden_commits = vec![];
+        for committed in header_params.committed_substrs.iter().flatten() {
+            let name = &header_names[committed.substr_id - 1];
+            let (_, substr) = header_substrs[committed.substr_id - 1].take().ok_or_else(|| PublicInputError::NotFound { name: name.clone(), part: "header" })?;
+            if substr.len() > committed.max_substr_size {
+                return Err(PublicInputError::TooLarge {
+                    name: name.clone(),
+                    limit: "max_substr_size",
+                    max_size: committed.max_substr_size,
+                    size: substr.len(),
+                });
+            }
+            let mut substr_bytes = vec![0u8; committed.max_substr_size];
+            substr_bytes[0..substr.len()].copy_from_slice(substr.as_bytes());
+            if committed.lowercase.unwrap_or(false) {
+                substr_bytes.make_ascii_lowercase();
+            }
+            header_hidden_commits.push(value_commit_wtns_bytes_with_scheme(commitment_scheme, Some(HEADER_COMMIT_DOMAIN), &sign_commit, &substr_bytes));
+        }
+        // Hide the header substrings asserted to be in the membership sets.
+        for set in header_params.membership_sets.iter().flatten() {
+            let name = &header_names[set.substr_id - 1];
+            let (_, substr) = header_substrs[set.substr_id - 1].take().ok_or_else(|| PublicInputError::NotFound { name: name.clone(), part: "header" })?;
+            if !set.values.contains(&substr) {
+                return Err(PublicInputError::NotMember {
+                    name: name.clone(),
+                    values: set.values.clone(),
+                });
+            }
+        }
+        let header_bodyhash_commits = if header_params.commit_bodyhash.unwrap_or(false) {
+            let bodyhash_value = get_bodyhash_value(header_bytes).ok_or(PublicInputError::MissingBodyhash)?;
+            vec![value_commit_wtns_bytes_with_scheme(commitment_scheme, Some(BODYHASH_COMMIT_DOMAIN), &sign_commit, bodyhash_value.as_bytes())]
+        } else {
+            vec![]
+        };
+        let dkim_timestamp = match header_params.dkim_timestamp.as_ref() {
+            Some(timestamp) => {
+                let name = &header_names[timestamp.substr_id - 1];
+                let (_, substr) = header_substrs[timestamp.substr_id - 1].as_ref().ok_or_else(|| PublicInputError::NotFound { name: name.clone(), part: "header" })?;
+                Some(substr.parse::<u64>().map_err(|_| PublicInputError::InvalidTimestamp(substr.clone()))?)
+            }
+            None => None,
+        };
+        // Hide the body substrings compared by the numeric predicates.
+        let mut predicate_thresholds = vec![];
+        for predicate in body_params.numeric_predicates.iter().flatten() {
+            let name = &body_names[predicate.substr_id - 1];
+            let (_, substr) = body_substrs[predicate.substr_id - 1].take().ok_or_else(|| PublicInputError::NotFound { name: name.clone(), part: "body" })?;
+            if substr.len() > predicate.max_substr_size {
+                return Err(PublicInputError::TooLarge {
+                    name: name.clone(),
+                    limit: "max_substr_size",
+                    max_size: predicate.max_substr_size,
+                    size: substr.len(),
+                });
+            }
+            predicate_thresholds.extend_from_slice(&predicate.thresholds);
         }
+        let mut public_input = Self::new(sign_commit, public_key_hash, header_substrs, body_substrs);
+        public_input.body_hidden_commits = body_hidden_commits.iter().map(|commit| fe_to_biguint(commit).to_str_radix(10)).collect();
+        public_input.header_recipient_commits = header_recipient_commits.iter().map(|commit| fe_to_biguint(commit).to_str_radix(10)).collect();
+        public_input.body_decoded_commits = body_decoded_commits.iter().map(|commit| fe_to_biguint(commit).to_str_radix(10)).collect();
+        public_input.header_hidden_commits = header_hidden_commits.iter().map(|commit| fe_to_biguint(commit).to_str_radix(10)).collect();
+        public_input.header_bodyhash_commits = header_bodyhash_commits.iter().map(|commit| fe_to_biguint(commit).to_str_radix(10)).collect();
+        public_input.dkim_timestamp = dkim_timestamp;
+        public_input.predicate_thresholds = predicate_thresholds;
+        Ok(public_input)
     }
 
     /// Output [`DefaultEmailVerifyPublicInput`] to a json file.
+    /// The json is streamed to the file through a buffer instead of being built as a string in memory.
     ///
     /// # Arguments
     /// * `public_input_path` - a file path of the output json file.
     pub fn write_file(&self, public_input_path: &str) {
-        let public_input_str = serde_json::to_string(&self).unwrap();
-        let mut file = File::create(public_input_path).expect("public_input_path creation failed");
-        write!(file, "{}", public_input_str).unwrap();
-        file.flush().unwrap();
+        let file = File::create(public_input_path).expect("public_input_path creation failed");
+        let mut writer = std::io::BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &self).unwrap();
+        writer.flush().unwrap();
+    }
+
+    /// Convert [`DefaultEmailVerifyPublicInput`] to a json value whose field elements are serialized in the given format.
+    ///
+    /// # Arguments
+    /// * `format` - the serialization of the field elements.
+    /// # Return values
+    /// Return the json value.
+    pub fn to_json_with_format(&self, format: PublicInputFormat) -> serde_json::Value {
+        let mut json = serde_json::to_value(self).unwrap();
+        for key in PUBLIC_INPUT_FIELD_ELEMENT_KEYS.iter() {
+            let encoded = match &json[*key] {
+                serde_json::Value::Array(values) => serde_json::Value::Array(values.iter().map(|value| format.encode(value.as_str().unwrap())).collect()),
+                value => format.encode(value.as_str().unwrap()),
+            };
+            json[*key] = encoded;
+        }
+        json
+    }
+
+    /// Parse [`DefaultEmailVerifyPublicInput`] from a json value whose field elements are serialized in any [`PublicInputFormat`].
+    ///
+    /// # Arguments
+    /// * `json` - the json value.
+    /// # Return values
+    /// Return the parsed [`DefaultEmailVerifyPublicInput`], or an error message if it is invalid.
+    pub fn from_json(mut json: serde_json::Value) -> Result<Self, String> {
+        for key in PUBLIC_INPUT_FIELD_ELEMENT_KEYS.iter() {
+            let decoded = match &json[*key] {
+                serde_json::Value::Null => continue,
+                // An array of numbers is a single element in le-bytes, while the commitments are an array of elements.
+                serde_json::Value::Array(values) if key.ends_with("commits") => serde_json::Value::Array(
+                    values
+                        .iter()
+                        .map(|value| PublicInputFormat::decode(value).map(serde_json::Value::String))
+                        .collect::<Result<Vec<_>, _>>()?,
+                ),
+                value => serde_json::Value::String(PublicInputFormat::decode(value)?),
+            };
+            json[*key] = decoded;
+        }
+        serde_json::from_value(json).map_err(|err| err.to_string())
+    }
+
+    /// Read [`DefaultEmailVerifyPublicInput`] from a json file written in any [`PublicInputFormat`].
+    ///
+    /// # Arguments
+    /// * `public_input_path` - a file path of the json file.
+    pub fn read_file(public_input_path: &str) -> Self {
+        let json = serde_json::from_reader(File::open(public_input_path).expect("public_input_path cannot be opened")).expect("public_input_path is not a json file");
+        Self::from_json(json).unwrap_or_else(|err| panic!("invalid public input {}: {}", public_input_path, err))
+    }
+
+    /// Output [`DefaultEmailVerifyPublicInput`] to a json file whose field elements are serialized in the given format.
+    ///
+    /// # Arguments
+    /// * `public_input_path` - a file path of the output json file.
+    /// * `format` - the serialization of the field elements.
+    pub fn write_file_with_format(&self, public_input_path: &str, format: PublicInputFormat) {
+        let file = File::create(public_input_path).expect("public_input_path creation failed");
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &self.to_json_with_format(format)).unwrap();
+    }
+
+    /// Output the revealed substrings with their field names.
+    /// The name of each field is derived from the allstr file path of its regex, e.g., `subject` for `./test_data/subject_allstr.txt`.
+    /// The substrings that are not found in the email are omitted.
+    /// If `decode_encoded_words` is set in the header configuration, the RFC2047 encoded words in the header substrings are decoded.
+    /// If `decode_idn_domains` is set, the punycode labels of internationalized domains in the header substrings are decoded to Unicode.
+    pub fn public_fields(&self) -> Vec<(String, Vec<u8>)> {
+        let config_params = default_config_params();
+        let header_names = config_params
+            .header_config
+            .as_ref()
+            .map(|config| substr_field_names(&config.allstr_filepathes, &config.substr_filepathes))
+            .unwrap_or_default();
+        let body_names = config_params
+            .body_config
+            .as_ref()
+            .map(|config| substr_field_names(&config.allstr_filepathes, &config.substr_filepathes))
+            .unwrap_or_default();
+        let decode_header = config_params.header_config.as_ref().and_then(|config| config.decode_encoded_words).unwrap_or(false);
+        let decode_idn = config_params.header_config.as_ref().and_then(|config| config.decode_idn_domains).unwrap_or(false);
+        let field_name = |names: &[String], part: &str, idx: usize| names.get(idx).cloned().unwrap_or(format!("{}_substr_{}", part, idx));
+        let header_fields = self
+            .header_substrs
+            .iter()
+            .enumerate()
+            .filter(|(_, substr)| !substr.is_empty())
+            .map(|(idx, substr)| {
+                let value = if decode_header { decode_encoded_words(substr) } else { substr.clone() };
+                let value = if decode_idn { decode_idn_domain(&value) } else { value };
+                (field_name(&header_names, "header", idx), value.as_bytes().to_vec())
+            });
+        let body_fields = self
+            .body_substrs
+            .iter()
+            .enumerate()
+            .filter(|(_, substr)| !substr.is_empty())
+            .map(|(idx, substr)| (field_name(&body_names, "body", idx), substr.as_bytes().to_vec()));
+        header_fields.chain(body_fields).collect_vec()
     }
 
     /// Output a vector of field values in the instance column.
@@ -202,11 +772,153 @@ impl DefaultEmailVerifyPublicInput {
             rlc += coeff * F::from(input as u64);
             coeff *= sign_commit.clone();
         }
-        println!("rlc instance {:?}", rlc);
-        vec![sign_commit, public_key_hash, rlc]
+        for commit in self
+            .body_hidden_commits
+            .iter()
+            .chain(self.header_recipient_commits.iter())
+            .chain(self.body_decoded_commits.iter())
+            .chain(self.header_hidden_commits.iter())
+            .chain(self.header_bodyhash_commits.iter())
+        {
+            rlc += coeff * F::from_str_vartime(commit).unwrap();
+            coeff *= sign_commit.clone();
+        }
+        log::debug!("rlc instance {:?}", rlc);
+        let mut instances = vec![sign_commit, public_key_hash, rlc];
+        if config_params.header_config.as_ref().unwrap().dkim_timestamp.is_some() {
+            instances.push(F::from(self.dkim_timestamp.expect("the public input has no DKIM timestamp")));
+        }
+        instances.extend(self.predicate_thresholds.iter().map(|threshold| F::from(*threshold)));
+        if config_params.bind_block_hash.unwrap_or(false) {
+            instances.extend(self.bound_block().expect("the public input has no bound block").hash_instances::<F>());
+        }
+        instances
+    }
+
+    /// Check that each field of the public input has exactly the number of the entries documented for the circuit configuration,
+    /// i.e., one substring and start position for each substring regex, the commitments of each list in its order, the DKIM timestamp, and the predicate thresholds.
+    /// [`DefaultEmailVerifyPublicInput::instances`] chains the commitment lists, so a public input whose commitments are moved between adjacent lists has the same instances and its proof still verifies,
+    /// although the moved commitments are attributed to other fields.
+    ///
+    /// # Return values
+    /// Return `Ok(())` if every field has the expected number of the entries, otherwise [`InstanceOrderError`] of the first mismatched field.
+    pub fn check_instance_order(&self) -> Result<(), InstanceOrderError> {
+        let config_params = default_config_params();
+        let header_params = config_params.header_config.as_ref().expect("header_config is required");
+        let body_params = config_params.body_config.as_ref().expect("body_config is required");
+        let expected_lens = [
+            ("header_starts", header_params.substr_regexes.len(), self.header_starts.len()),
+            ("header_substrs", header_params.substr_regexes.len(), self.header_substrs.len()),
+            ("body_starts", body_params.substr_regexes.len(), self.body_starts.len()),
+            ("body_substrs", body_params.substr_regexes.len(), self.body_substrs.len()),
+            (
+                "body_hidden_commits",
+                body_params.reveal_ranges.iter().flatten().filter(|reveal_range| reveal_range.commit_hidden.unwrap_or(true)).count(),
+                self.body_hidden_commits.len(),
+            ),
+            (
+                "header_recipient_commits",
+                header_params.recipients.as_ref().map(|recipients| recipients.substr_ids.len() * recipients.max_recipients).unwrap_or(0),
+                self.header_recipient_commits.len(),
+            ),
+            ("body_decoded_commits", body_params.decoded_tokens.iter().flatten().count(), self.body_decoded_commits.len()),
+            ("header_hidden_commits", header_params.committed_substrs.iter().flatten().count(), self.header_hidden_commits.len()),
+            ("header_bodyhash_commits", header_params.commit_bodyhash.unwrap_or(false) as usize, self.header_bodyhash_commits.len()),
+            ("dkim_timestamp", header_params.dkim_timestamp.is_some() as usize, self.dkim_timestamp.is_some() as usize),
+            (
+                "predicate_thresholds",
+                body_params.numeric_predicates.iter().flatten().map(|predicate| predicate.thresholds.len()).sum::<usize>(),
+                self.predicate_thresholds.len(),
+            ),
+        ];
+        match expected_lens.into_iter().find(|(_, expected, actual)| expected != actual) {
+            Some((field, expected, actual)) => Err(InstanceOrderError {
+                field: field.to_string(),
+                expected,
+                actual,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the block bound to the proof, which is `None` if either `block_number` or `block_hash` is not set.
+    /// It panics if `block_hash` is not a hex string of 32 bytes.
+    pub fn bound_block(&self) -> Option<BoundBlock> {
+        let number = self.block_number?;
+        let hash = self.block_hash.as_ref()?;
+        Some(BoundBlock {
+            number,
+            hash: BoundBlock::parse_hash(hash).unwrap_or_else(|| panic!("the block hash {} is not a hex string of 32 bytes", hash)),
+        })
+    }
+}
+
+/// A recent block to which the proof is bound if `bind_block_hash` is set in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundBlock {
+    /// The block number, with which the verifier contract looks up the block hash.
+    pub number: u64,
+    /// The block hash.
+    pub hash: [u8; 32],
+}
+
+impl BoundBlock {
+    /// Parse a hex string of 32 bytes, optionally prefixed with `0x`, as a block hash.
+    pub fn parse_hash(hash: &str) -> Option<[u8; 32]> {
+        let hash = hash.strip_prefix("0x").unwrap_or(hash);
+        if hash.len() != 64 {
+            return None;
+        }
+        let bytes = BigUint::parse_bytes(hash.as_bytes(), 16)?.to_bytes_be();
+        let mut padded = [0u8; 32];
+        padded[32 - bytes.len()..].copy_from_slice(&bytes);
+        Some(padded)
+    }
+
+    /// Return the upper and lower 128 bits of the block hash, which are the instances of the block hash.
+    pub fn hash_instances<F: PrimeField>(&self) -> [F; 2] {
+        [
+            biguint_to_fe(&BigUint::from_bytes_be(&self.hash[..16])),
+            biguint_to_fe(&BigUint::from_bytes_be(&self.hash[16..])),
+        ]
     }
 }
 
+/// Return the number of the instances of [`DefaultEmailVerifyCircuit`], which is 3, plus 1 if `header_config.dkim_timestamp` is set, plus the number of the thresholds of `body_config.numeric_predicates`,
+/// plus 2 if `bind_block_hash` is set.
+pub fn num_instances() -> usize {
+    let params = default_config_params();
+    let has_timestamp = params.header_config.as_ref().map(|config| config.dkim_timestamp.is_some()).unwrap_or(false);
+    let num_thresholds = params
+        .body_config
+        .as_ref()
+        .and_then(|config| config.numeric_predicates.as_ref())
+        .map(|predicates| predicates.iter().map(|predicate| predicate.predicate.num_thresholds()).sum())
+        .unwrap_or(0);
+    let num_block_hash_instances = if params.bind_block_hash.unwrap_or(false) { 2 } else { 0 };
+    3 + has_timestamp as usize + num_thresholds + num_block_hash_instances
+}
+
+/// Return the number of instance columns specified in the configuration parameters, which defaults to 1.
+pub fn num_instance_columns() -> usize {
+    let num_columns = default_config_params().num_instance_columns.unwrap_or(1);
+    assert!(num_columns > 0, "num_instance_columns must be positive");
+    num_columns
+}
+
+/// Compute the instances of [`DefaultEmailVerifyCircuit`] natively, without constructing the circuit.
+/// The randomness of the RLC commitment is the signature commitment, so no other randomness is required.
+///
+/// # Arguments
+/// * `email_bytes` - email bytes.
+/// * `public_key_n` - `n` parameter of the RSA public key.
+/// # Return values
+/// Return the instances of each instance column, which are equal to `DefaultEmailVerifyCircuit::instances`.
+pub fn compute_expected_instances<F: PrimeField>(email_bytes: &[u8], public_key_n: &BigUint) -> Vec<Vec<F>> {
+    let public_input = DefaultEmailVerifyPublicInput::from_email::<F>(email_bytes, public_key_n).unwrap_or_else(|err| panic!("{}", err));
+    split_instances(&public_input.instances(), num_instance_columns())
+}
+
 /// Configuration for [`DefaultEmailVerifyCircuit`].
 #[derive(Debug, Clone)]
 pub struct DefaultEmailVerifyConfig<F: PrimeField> {
@@ -215,8 +927,32 @@ pub struct DefaultEmailVerifyConfig<F: PrimeField> {
     pub header_config: RegexSha2Config<F>,
     pub body_config: RegexSha2Base64Config<F>,
     pub chars_shift_config: CharsShiftConfig<F>,
+    /// Configurations to extract the header substrings whose values must be equal.
+    pub header_substr_equalities: Vec<Vec<CharsShiftConfig<F>>>,
+    /// Configurations to extract pairs of a header substring and a body substring whose values must be equal.
+    pub header_body_substr_equalities: Vec<(CharsShiftConfig<F>, CharsShiftConfig<F>)>,
+    /// Configurations of the substrings that must not appear in the email body.
+    pub body_forbidden_substrs: Vec<ForbiddenSubstrConfig<F>>,
+    /// Configurations of the header fields that must not appear in the signed headers.
+    pub header_forbidden_fields: Vec<ForbiddenHeaderFieldConfig<F>>,
+    /// Configurations to extract pairs of a header domain and the DKIM domain, which must be aligned, and the flags of the strict alignment.
+    pub header_dkim_domain_alignments: Vec<(CharsShiftConfig<F>, CharsShiftConfig<F>, bool)>,
+    /// A configuration to extract the DKIM `t=` timestamp exposed as an integer instance.
+    pub header_dkim_timestamp: Option<CharsShiftConfig<F>>,
+    /// Configurations to extract the header substrings that must be the given hosts, e.g., the hops in `Received` fields.
+    pub header_received_hops: Vec<(CharsShiftConfig<F>, String)>,
+    /// Configurations to extract the recipient lists, whose addresses are committed instead of revealing the lists.
+    pub header_recipient_lists: Vec<CharsShiftConfig<F>>,
+    /// Configurations to extract the hex or base64 body substrings, whose decoded bytes are committed, and their encodings.
+    pub body_decoded_tokens: Vec<(CharsShiftConfig<F>, TokenEncoding)>,
+    /// Configurations to extract the header substrings that are committed instead of being revealed.
+    pub header_committed_substrs: Vec<CharsShiftConfig<F>>,
+    /// Configurations to extract the header substrings that must be one of the values, and the values.
+    pub header_membership_sets: Vec<(CharsShiftConfig<F>, Vec<String>)>,
+    /// Configurations to extract the body substrings compared by the numeric predicates, and their predicates.
+    pub body_numeric_predicates: Vec<(CharsShiftConfig<F>, NumericPredicate)>,
     /// An instance column that contains a commitment of the email header, a hash of the public key `n` parameter, and a random linear combination of the masked characters and their substring ids in the email header and body.
-    pub instances: Column<Instance>,
+    pub instances: Vec<Column<Instance>>,
 }
 
 /// Default email verification circuit.
@@ -226,6 +962,14 @@ pub struct DefaultEmailVerifyCircuit<F: PrimeField> {
     pub email_bytes: Vec<u8>,
     /// A `n` parameter of the RSA public key.
     pub public_key_n: BigUint, // pub public_key: RSAPublicKey<F>,
+    /// The canonicalized header, body, and signature supplied by [`DefaultEmailVerifyCircuit::from_parts`], which are used instead of canonicalizing `email_bytes`.
+    canonicalized_parts: Option<(Vec<u8>, Vec<u8>, Vec<u8>)>,
+    /// The block bound to the proof if `bind_block_hash` is set. See [`DefaultEmailVerifyCircuit::with_bound_block`].
+    pub bound_block: Option<BoundBlock>,
+    /// The directory to which the intermediate witnesses are written when the circuit is synthesized. See [`DefaultEmailVerifyCircuit::with_debug_witness_dir`].
+    pub debug_witness_dir: Option<String>,
+    /// Whether the circuit statistics are printed when the circuit is synthesized. See [`DefaultEmailVerifyCircuit::with_stats`].
+    pub print_stats: bool,
     _f: PhantomData<F>,
 }
 
@@ -237,6 +981,10 @@ impl<F: PrimeField> Circuit<F> for DefaultEmailVerifyCircuit<F> {
         Self {
             email_bytes: vec![],
             public_key_n: self.public_key_n.clone(),
+            canonicalized_parts: None,
+            bound_block: self.bound_block,
+            debug_witness_dir: None,
+            print_stats: self.print_stats,
             _f: PhantomData,
         }
     }
@@ -260,9 +1008,9 @@ impl<F: PrimeField> Circuit<F> for DefaultEmailVerifyCircuit<F> {
         if let Some(sign_config) = params.sign_verify_config.as_ref() {
             assert_eq!(self.public_key_n.bits() as usize, sign_config.public_key_bits);
         }
-        let (header_bytes, body_bytes, signature_bytes) = canonicalize_signed_email(&self.email_bytes).unwrap();
-        println!("canonicalized_header:\n{}", String::from_utf8(header_bytes.clone()).unwrap());
-        println!("canonicalized_body:\n{}", String::from_utf8(body_bytes.clone()).unwrap());
+        let (header_bytes, body_bytes, signature_bytes) = self.canonicalized_email();
+        log::debug!("canonicalized_header:\n{}", String::from_utf8_lossy(&header_bytes));
+        log::debug!("canonicalized_body:\n{}", String::from_utf8_lossy(&body_bytes));
 
         layouter.assign_region(
             || "zkemail",
@@ -272,81 +1020,39 @@ impl<F: PrimeField> Circuit<F> for DefaultEmailVerifyCircuit<F> {
                     return Ok(());
                 }
                 let ctx = &mut config.sha256_config.new_context(region);
-                let header_params = params.header_config.as_ref().expect("header_config is required");
-
                 let range = config.sha256_config.range().clone();
-                let gate = range.gate.clone();
-
-                // 1. Extract sub strings in the body and compute the base64 encoded hash of the body.
-                let body_result = config.body_config.match_hash_and_base64(ctx, &mut config.sha256_config, &body_bytes)?;
-
-                // 2. Extract sub strings in the header, which includes the body hash, and compute the raw hash of the header.
-                let header_result = config.header_config.match_and_hash(ctx, &mut config.sha256_config, &header_bytes)?;
-
-                // 3. Verify the rsa signature.
-                let e = RSAPubE::Fix(BigUint::from(Self::DEFAULT_E));
-                let public_key = RSAPublicKey::<F>::new(Value::known(self.public_key_n.clone()), e);
-                let signature = RSASignature::<F>::new(Value::known(BigUint::from_bytes_be(&signature_bytes)));
-                let (assigned_public_key, assigned_signature) = config.sign_verify_config.verify_signature(ctx, &header_result.hash_bytes, public_key, signature.clone())?;
-
-                // 4. Assert that the bodyhash is included in the email header.
-                let (extracted_bodyhash, is_target_vec) = config
-                    .chars_shift_config
-                    .shift(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
-                // for (val, id) in header_result.regex.masked_characters.iter().zip(header_result.regex.all_substr_ids.iter()) {
-                //     println!("val {:?} id {:?}", val, id);
-                // }
-                for (a, b) in extracted_bodyhash.iter().zip(body_result.encoded_hash.iter()) {
-                    gate.assert_equal(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
-                }
-
-                // 5. Compute public input values.
-                let poseidon = PoseidonChipBn254_8_58::new(ctx, &gate);
-                let sign_commit = poseidon.hash_elements(ctx, &gate, &assigned_signature.c.limbs()).unwrap().0[0].clone();
-                // let header_hash_commit = assigned_commit_wtns_bytes(ctx, &gate, &poseidon, &sign_rand, &header_result.hash_bytes);
-                let public_key_n_hash = poseidon.hash_elements(ctx, &gate, &assigned_public_key.n.limbs()).unwrap().0[0].clone();
+                let (sign_commit, public_key_n_hash, rlc, dkim_timestamp, predicate_thresholds) =
+                    Self::assign_email(ctx, &mut config, &header_bytes, &body_bytes, &signature_bytes, &self.public_key_n, self.debug_witness_dir.as_deref())?;
                 public_hash_cell.push(sign_commit.cell());
                 public_hash_cell.push(public_key_n_hash.cell());
-                let mut rlc_inputs = vec![];
-                let mut bodyhash_masked_header_chars = vec![];
-                let mut bodyhash_masked_header_substr_ids = vec![];
-                for idx in 0..header_params.max_variable_byte_size {
-                    let is_target = &is_target_vec[idx];
-                    bodyhash_masked_header_chars.push(gate.select(
-                        ctx,
-                        QuantumCell::Constant(F::zero()),
-                        QuantumCell::Existing(&header_result.regex.masked_characters[idx]),
-                        QuantumCell::Existing(is_target),
-                    ));
-                    bodyhash_masked_header_substr_ids.push(gate.select(
-                        ctx,
-                        QuantumCell::Constant(F::zero()),
-                        QuantumCell::Existing(&header_result.regex.all_substr_ids[idx]),
-                        QuantumCell::Existing(is_target),
-                    ));
+                public_hash_cell.push(rlc.cell());
+                if let Some(dkim_timestamp) = dkim_timestamp {
+                    public_hash_cell.push(dkim_timestamp.cell());
                 }
-                // println!("bodyhash_masked_header_chars {:?}", bodyhash_masked_header_chars);
-                // for (idx, val) in bodyhash_masked_header_chars.iter().enumerate() {
-                //     println!("idx {} val {:?}", idx, val.value().map(|v| v.get_lower_32() as u8 as char));
-                // }
-                rlc_inputs.append(&mut bodyhash_masked_header_chars);
-                rlc_inputs.append(&mut bodyhash_masked_header_substr_ids);
-                rlc_inputs.append(&mut body_result.regex.masked_characters.clone());
-                rlc_inputs.append(&mut body_result.regex.all_substr_ids.clone());
-                let mut rlc = gate.load_zero(ctx);
-                let mut coeff = sign_commit.clone();
-                for input in rlc_inputs.into_iter() {
-                    rlc = gate.mul_add(ctx, QuantumCell::Existing(&input), QuantumCell::Existing(&coeff), QuantumCell::Existing(&rlc));
-                    coeff = gate.mul(ctx, QuantumCell::Existing(&sign_commit), QuantumCell::Existing(&coeff));
+                for threshold in predicate_thresholds.iter() {
+                    public_hash_cell.push(threshold.cell());
+                }
+                if params.bind_block_hash.unwrap_or(false) {
+                    // The block hash is only bound to the proof, so its halves are range checked but not constrained otherwise.
+                    let hash_instances = self.bound_block.map(|block| block.hash_instances::<F>()).unwrap_or([F::zero(), F::zero()]);
+                    let block_hash = range.gate().assign_witnesses(ctx, hash_instances.into_iter().map(Value::known).collect());
+                    for half in block_hash.iter() {
+                        range.range_check(ctx, half, 128);
+                        public_hash_cell.push(half.cell());
+                    }
                 }
-                public_hash_cell.push(rlc.cell());
 
+                #[cfg(not(target_arch = "wasm32"))]
+                if self.print_stats {
+                    circuit_stats::CircuitStats::new(ctx, params).print();
+                }
                 range.finalize(ctx);
                 Ok(())
             },
         )?;
         for (idx, cell) in public_hash_cell.into_iter().enumerate() {
-            layouter.constrain_instance(cell, config.instances, idx)?;
+            let (column, row) = instance_position(idx, config.instances.len());
+            layouter.constrain_instance(cell, config.instances[column], row)?;
         }
         Ok(())
     }
@@ -354,12 +1060,12 @@ impl<F: PrimeField> Circuit<F> for DefaultEmailVerifyCircuit<F> {
 
 impl<F: PrimeField> CircuitExt<F> for DefaultEmailVerifyCircuit<F> {
     fn num_instance(&self) -> Vec<usize> {
-        vec![3]
+        split_instances(&vec![(); num_instances()], num_instance_columns()).iter().map(|column| column.len()).collect()
     }
 
     fn instances(&self) -> Vec<Vec<F>> {
         let public_input = self.gen_default_public_input();
-        vec![public_input.instances()]
+        split_instances(&public_input.instances(), num_instance_columns())
     }
 }
 
@@ -377,124 +1083,819 @@ impl<F: PrimeField> DefaultEmailVerifyCircuit<F> {
         Self {
             email_bytes,
             public_key_n,
+            canonicalized_parts: None,
+            bound_block: None,
+            debug_witness_dir: None,
+            print_stats: false,
             _f: PhantomData,
         }
     }
 
-    /// Generate a new circuit from the given email file.
+    /// Create a new [`DefaultEmailVerifyCircuit`] from the already canonicalized email header and body and the signature.
+    /// The circuit does not canonicalize them again, so they must be canonicalized as specified in the DKIM signature.
+    /// The resulting proof is the same as that of [`DefaultEmailVerifyCircuit::new`] with the raw email.
     ///
     /// # Arguments
-    /// * `email_path` - a file path of the email file.
+    /// * `header_bytes` - the canonicalized email header bytes, which end with the DKIM-Signature header without its `b=` value.
+    /// * `body_bytes` - the canonicalized email body bytes.
+    /// * `signature_bytes` - the RSA signature bytes.
+    /// * `public_key_n` - `n` parameter of the RSA public key.
     ///
     /// # Return values
     /// Return a new [`DefaultEmailVerifyCircuit`].
-    #[cfg(not(target_arch = "wasm32"))]
-    pub async fn gen_circuit_from_email_path(email_path: &str) -> Self {
-        let email_bytes = {
-            let mut f = File::open(email_path).unwrap();
-            let mut buf = Vec::new();
-            f.read_to_end(&mut buf).unwrap();
-            buf
-        };
-        // println!("email {}", String::from_utf8(email_bytes.clone()).unwrap());
-        // let (canonicalized_header, canonicalized_body, signature_bytes) = canonicalize_signed_email(&email_bytes).unwrap();
-        // let headerhash = Sha256::digest(&canonicalized_header).to_vec();
-        let public_key_n = {
-            let logger = slog::Logger::root(slog::Discard, slog::o!());
-            match resolve_public_key(&logger, &email_bytes).await.unwrap() {
-                cfdkim::DkimPublicKey::Rsa(_pk) => BigUint::from_radix_le(&_pk.n().clone().to_radix_le(16), 16).unwrap(),
-                _ => {
-                    panic!("Only RSA keys are supported.");
-                }
-            }
-        };
-        let circuit = Self::new(email_bytes, public_key_n);
-        circuit
+    pub fn from_parts(header_bytes: Vec<u8>, body_bytes: Vec<u8>, signature_bytes: Vec<u8>, public_key_n: BigUint) -> Self {
+        Self {
+            email_bytes: vec![],
+            public_key_n,
+            canonicalized_parts: Some((header_bytes, body_bytes, signature_bytes)),
+            bound_block: None,
+            debug_witness_dir: None,
+            print_stats: false,
+            _f: PhantomData,
+        }
     }
 
-    /// Compute public input values as [`DefaultEmailVerifyPublicInput`] from the circuit.
-    pub fn gen_default_public_input(&self) -> DefaultEmailVerifyPublicInput {
-        let (header_bytes, body_bytes, signature_bytes) = canonicalize_signed_email(&self.email_bytes).unwrap();
-        let signature = BigUint::from_bytes_be(&signature_bytes);
-        let config_params = default_config_params();
-        let num_limbs = config_params.sign_verify_config.as_ref().unwrap().public_key_bits / LIMB_BITS;
-        let sign_commit: F = {
-            let limbs = decompose_biguint(&signature, num_limbs, LIMB_BITS);
-            poseidon_hash_fields(&limbs)
-        };
-        // let header_hash_commit = value_commit_wtns_bytes(&sign_rand, &header_hash);
-        let public_key_hash = {
-            let limbs = decompose_biguint(&self.public_key_n, num_limbs, LIMB_BITS);
-            poseidon_hash_fields(&limbs)
-        };
-        let header_params: &HeaderConfigParams = config_params.header_config.as_ref().unwrap();
-        let body_params = config_params.body_config.as_ref().unwrap();
-        let header_str = String::from_utf8(header_bytes[header_params.skip_prefix_bytes_size.unwrap_or(0)..].to_vec()).unwrap();
-        let body_str = String::from_utf8(body_bytes[body_params.skip_prefix_bytes_size.unwrap_or(0)..].to_vec()).unwrap();
-        let (header_substrs, body_substrs) = get_email_substrs(&header_str, &body_str, header_params.substr_regexes.clone(), body_params.substr_regexes.clone());
-        DefaultEmailVerifyPublicInput::new(sign_commit, public_key_hash, header_substrs, body_substrs)
+    /// Bind the proof to a recent block, whose hash is exposed as the instances if `bind_block_hash` is set in the configuration.
+    /// A proof bound to one block does not verify with the instances of another block.
+    ///
+    /// # Arguments
+    /// * `bound_block` - the number and hash of the block.
+    /// # Return values
+    /// Return the circuit bound to the block.
+    pub fn with_bound_block(mut self, bound_block: BoundBlock) -> Self {
+        self.bound_block = Some(bound_block);
+        self
     }
 
+    /// Write the intermediate witnesses to the given directory when the circuit is synthesized.
+    /// See [`debug_witness::EmailDebugWitness`] for the written values.
+    ///
+    /// # Arguments
+    /// * `debug_witness_dir` - a directory to which the intermediate witnesses are written.
+    /// # Return values
+    /// Return the circuit that dumps its intermediate witnesses.
     #[cfg(not(target_arch = "wasm32"))]
-    fn configure_native(meta: &mut ConstraintSystem<F>) -> DefaultEmailVerifyConfig<F> {
-        let params = default_config_params();
-        let range_config = RangeConfig::configure(
-            meta,
-            Vertical,
-            &[params.num_flex_advice],
-            &[params.num_range_lookup_advice],
-            params.num_flex_advice,
-            params.range_lookup_bits,
-            0,
-            params.degree as usize,
-        );
-        let header_params = params.header_config.as_ref().expect("header_config is required");
-        let body_params = params.body_config.as_ref().expect("body_config is required");
-        let sign_verify_params = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
-        let sha256_params = params.sha256_config.as_ref().expect("sha256_config is required");
-        assert_eq!(header_params.allstr_filepathes.len(), header_params.substr_filepathes.len());
-        assert_eq!(body_params.allstr_filepathes.len(), body_params.substr_filepathes.len());
+    pub fn with_debug_witness_dir(mut self, debug_witness_dir: &str) -> Self {
+        self.debug_witness_dir = Some(debug_witness_dir.to_string());
+        self
+    }
 
-        let sha256_config = Sha256DynamicConfig::configure(
-            meta,
-            vec![body_params.max_variable_byte_size, header_params.max_variable_byte_size],
-            range_config.clone(),
-            sha256_params.num_bits_lookup,
-            sha256_params.num_advice_columns,
-            false,
-        );
+    /// Print the numbers of used and available cells when the circuit is synthesized. See [`circuit_stats::CircuitStats`].
+    ///
+    /// # Return values
+    /// Return the circuit that prints its statistics.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stats(mut self) -> Self {
+        self.print_stats = true;
+        self
+    }
 
-        let sign_verify_config = SignVerifyConfig::configure(range_config.clone(), sign_verify_params.public_key_bits);
+    /// Return the canonicalized email header and body and the signature bytes.
+    /// They are the ones given to [`DefaultEmailVerifyCircuit::from_parts`] if any, or otherwise canonicalized from `email_bytes`.
+    pub fn canonicalized_email(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        match self.canonicalized_parts.as_ref() {
+            Some(parts) => parts.clone(),
+            None => dkim::canonicalize_signed_email_with_body_length(&self.email_bytes).unwrap(),
+        }
+    }
 
-        let bodyhash_allstr_def = AllstrRegexDef::read_from_text(&header_params.bodyhash_allstr_filepath);
-        let bodyhash_substr_def = SubstrRegexDef::read_from_text(&header_params.bodyhash_substr_filepath);
-        let bodyhash_defs = RegexDefs {
-            allstr: bodyhash_allstr_def,
-            substrs: vec![bodyhash_substr_def],
-        };
-        let mut bodyhash_substr_id = 1;
-        let header_regex_defs = header_params
-            .allstr_filepathes
-            .iter()
-            .zip(header_params.substr_filepathes.iter())
-            .map(|(allstr_path, substr_pathes)| {
-                let allstr = AllstrRegexDef::read_from_text(&allstr_path);
-                let substrs = substr_pathes.into_iter().map(|path| SubstrRegexDef::read_from_text(&path)).collect_vec();
-                bodyhash_substr_id += substrs.len();
-                RegexDefs { allstr, substrs }
-            })
-            .collect_vec();
-        let header_config = RegexSha2Config::configure(
-            meta,
-            header_params.max_variable_byte_size,
-            header_params.skip_prefix_bytes_size.unwrap_or(0),
-            range_config.clone(),
-            vec![header_regex_defs, vec![bodyhash_defs]].concat(),
-        );
+    /// Assign the email verification constraints of [`DefaultEmailVerifyCircuit`] for one email in the given context.
+    /// It does not finalize the context, so that multiple emails can be verified in the same region.
+    ///
+    /// # Arguments
+    /// * `ctx` - a region context.
+    /// * `config` - a configuration of [`DefaultEmailVerifyCircuit`].
+    /// * `header_bytes` - the canonicalized email header bytes.
+    /// * `body_bytes` - the canonicalized email body bytes.
+    /// * `signature_bytes` - the RSA signature bytes.
+    /// * `public_key_n` - `n` parameter of the RSA public key.
+    /// * `debug_witness_dir` - a directory to which the intermediate witnesses are written, or `None` not to write them.
+    /// # Return values
+    /// Return a tuple of the assigned signature commitment, hash of the public key, random linear combination of the masked characters and substring ids,
+    /// DKIM timestamp if `header_config.dkim_timestamp` is set, and thresholds of `body_config.numeric_predicates`.
+    pub fn assign_email<'v>(
+        ctx: &mut Context<'v, F>,
+        config: &mut DefaultEmailVerifyConfig<F>,
+        header_bytes: &[u8],
+        body_bytes: &[u8],
+        signature_bytes: &[u8],
+        public_key_n: &BigUint,
+        debug_witness_dir: Option<&str>,
+    ) -> Result<(AssignedValue<'v, F>, AssignedValue<'v, F>, AssignedValue<'v, F>, Option<AssignedValue<'v, F>>, Vec<AssignedValue<'v, F>>), Error> {
+        let config_params = default_config_params();
+        let header_params = config_params.header_config.as_ref().expect("header_config is required");
 
-        let body_regex_defs = body_params
-            .allstr_filepathes
-            .iter()
+        let gate = config.sha256_config.range().gate.clone();
+
+        // 1. Extract sub strings in the body and compute the base64 encoded hash of the body.
+        let body_result = config.body_config.match_hash_and_base64(ctx, &mut config.sha256_config, body_bytes)?;
+
+        // 1-2. Assert that the forbidden substrings do not appear in the body.
+        for forbidden_config in config.body_forbidden_substrs.iter() {
+            forbidden_config.assert_absent(ctx, &gate, &body_result.regex.all_characters);
+        }
+
+        // 2. Extract sub strings in the header, which includes the body hash, and compute the raw hash of the header.
+        let header_result = config.header_config.match_and_hash(ctx, &mut config.sha256_config, header_bytes)?;
+
+        // 2-2. Assert that the forbidden header fields do not appear in the signed headers.
+        for forbidden_config in config.header_forbidden_fields.iter() {
+            forbidden_config.assert_absent(ctx, &gate, &header_result.regex.all_characters);
+        }
+
+        let body_params = config_params.body_config.as_ref().expect("body_config is required");
+        // 2-3. Check that the regex circuits extract the substrings at the same positions as the public input, e.g., no ambiguous regex matches a field twice.
+        // Otherwise, the commitment of the substrings never matches the one computed from the public input.
+        // The assigned ids are unknown during the key generation, so the substring regexes are run natively only when they are known.
+        let mut is_known = false;
+        if let Some(substr_id) = header_result.regex.all_substr_ids.first() {
+            substr_id.value().map(|_| is_known = true);
+        }
+        if is_known {
+            let (header_substrs, body_substrs) = get_email_substrs(
+                &EmailEncoding::Lossy.decode(&header_bytes[header_params.skip_prefix_bytes_size.unwrap_or(0)..]),
+                &body_params.searched_str(body_bytes),
+                header_params.substr_regexes.clone(),
+                body_params.substr_regexes.clone(),
+            );
+            for (part, assigned_substr_ids, substrs) in [
+                ("header", &header_result.regex.all_substr_ids, &header_substrs),
+                ("body", &body_result.regex.all_substr_ids, &body_substrs),
+            ] {
+                if let Err(err) = check_substr_ids(part, assigned_substr_ids, substrs) {
+                    log::error!("{}", err);
+                    return Err(Error::Synthesis);
+                }
+            }
+        }
+
+        // 3. Verify the rsa signature.
+        let e = RSAPubE::Fix(BigUint::from(Self::DEFAULT_E));
+        let public_key = RSAPublicKey::<F>::new(Value::known(public_key_n.clone()), e);
+        let signature = RSASignature::<F>::new(Value::known(BigUint::from_bytes_be(signature_bytes)));
+        let (assigned_public_key, assigned_signature) = config
+            .sign_verify_config
+            .verify_signature(ctx, &mut config.sha256_config, &header_result.hash_bytes, public_key, signature.clone())?;
+
+        // 4. Assert that the bodyhash is included in the email header.
+        // The `bh=` value must be a base64 encoded SHA256 hash, otherwise the extracted bodyhash is not comparable with the computed one.
+        // It may contain folding whitespace, which DKIM verifiers ignore.
+        let bodyhash_value = get_bodyhash_value(header_bytes).ok_or(Error::Synthesis)?;
+        let decoded_bodyhash_len = general_purpose::STANDARD.decode(bodyhash_value).map_err(|_| Error::Synthesis)?.len();
+        if decoded_bodyhash_len != config.body_config.hash_len {
+            return Err(Error::Synthesis);
+        }
+        let (bodyhash_window, is_target_vec) = config
+            .chars_shift_config
+            .shift(ctx, &gate, &header_result.regex.all_characters, &header_result.regex.all_substr_ids);
+        // for (val, id) in header_result.regex.masked_characters.iter().zip(header_result.regex.all_substr_ids.iter()) {
+        //     println!("val {:?} id {:?}", val, id);
+        // }
+        // The `bh=` value ends at `;`, at `\r\n` not followed by whitespace, i.e., the end of the DKIM signature header when `bh=` is the last tag,
+        // or at the zero padding after the end of the header. The characters from the terminator are masked to zero.
+        let bodyhash_window = CharsShiftConfig::mask_from_tag_value_end(ctx, &gate, &bodyhash_window);
+        // The compacted `bh=` value must be followed by zero so that it has exactly as many characters as the base64 encoded hash of `hash_len` bytes.
+        let bodyhash_base64_len = base64_encoded_len(config.body_config.hash_len);
+        let mut extracted_bodyhash = CharsShiftConfig::remove_chars(ctx, &gate, &bodyhash_window, &FOLDING_WHITESPACE_CHARS, bodyhash_base64_len + 1);
+        let bodyhash_terminator = extracted_bodyhash.pop().expect("the extracted bodyhash is not empty");
+        gate.assert_equal(ctx, QuantumCell::Existing(&bodyhash_terminator), QuantumCell::Constant(F::zero()));
+        for (a, b) in extracted_bodyhash.iter().zip(body_result.encoded_hash.iter()) {
+            gate.assert_equal(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
+        }
+
+        // 4-2. Assert that the specified header substrings are equal, e.g., the domains of From and Reply-To.
+        for equality_configs in config.header_substr_equalities.iter() {
+            let extracted_substrs = equality_configs
+                .iter()
+                .map(|shift_config| shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids))
+                .collect_vec();
+            for other_substr in extracted_substrs[1..].iter() {
+                for (a, b) in extracted_substrs[0].iter().zip(other_substr.iter()) {
+                    gate.assert_equal(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
+                }
+            }
+        }
+
+        // 4-3. Assert that the specified header domains are aligned with the DKIM domain, e.g., the Return-Path domain for the SPF alignment.
+        for (domain_shift_config, dkim_domain_shift_config, strict) in config.header_dkim_domain_alignments.iter() {
+            let domain = domain_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+            let dkim_domain = dkim_domain_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+            CharsShiftConfig::assert_domain_aligned(ctx, &gate, &domain, &dkim_domain, *strict);
+        }
+
+        // 4-4. Assert that the specified header substrings are equal to the body substrings, e.g., the one-time codes in the subject and the body.
+        for (header_shift_config, body_shift_config) in config.header_body_substr_equalities.iter() {
+            let header_substr = header_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+            let body_substr = body_shift_config.extract(ctx, &gate, &body_result.regex.masked_characters, &body_result.regex.all_substr_ids);
+            for (a, b) in header_substr.iter().zip(body_substr.iter()) {
+                gate.assert_equal(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
+            }
+        }
+
+        // 4-5. Parse the DKIM `t=` timestamp from its digits.
+        let dkim_timestamp = config.header_dkim_timestamp.as_ref().map(|timestamp_shift_config| {
+            let digits = timestamp_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+            CharsShiftConfig::parse_decimal(ctx, &gate, &digits)
+        });
+
+        // 4-6. Assert that the specified header substrings are the expected hosts, e.g., the MTA in a signed `Received` field.
+        for (hop_shift_config, host) in config.header_received_hops.iter() {
+            let hop = hop_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+            for (assigned_char, expected_char) in hop.iter().zip(host.as_bytes().iter()) {
+                gate.assert_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(*expected_char as u64)));
+            }
+        }
+
+        // 4-7. Split the recipient lists into their addresses.
+        let mut recipient_addresses = vec![];
+        if let Some(recipients) = header_params.recipients.as_ref() {
+            for list_shift_config in config.header_recipient_lists.iter() {
+                let list = list_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+                recipient_addresses.append(&mut CharsShiftConfig::split_list(
+                    ctx,
+                    &gate,
+                    &list,
+                    b',',
+                    &FOLDING_WHITESPACE_CHARS,
+                    recipients.max_recipients,
+                    recipients.max_address_size,
+                ));
+            }
+        }
+
+        // 4-8. Decode the hex or base64 tokens in the body.
+        let mut body_decoded_bytes = vec![];
+        for (token_shift_config, encoding) in config.body_decoded_tokens.iter() {
+            let token = token_shift_config.extract(ctx, &gate, &body_result.regex.masked_characters, &body_result.regex.all_substr_ids);
+            body_decoded_bytes.push(match encoding {
+                TokenEncoding::Hex => CharsShiftConfig::decode_hex(ctx, &gate, &token),
+                TokenEncoding::Base64 => CharsShiftConfig::decode_base64(ctx, &gate, &token),
+            });
+        }
+
+        // 4-9. Extract the header substrings committed instead of being revealed, and lowercase them if requested.
+        let header_committed_chars = config
+            .header_committed_substrs
+            .iter()
+            .zip(header_params.committed_substrs.iter().flatten())
+            .map(|(shift_config, committed)| {
+                let chars = shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+                if committed.lowercase.unwrap_or(false) {
+                    CharsShiftConfig::to_ascii_lowercase(ctx, &gate, &chars)
+                } else {
+                    chars
+                }
+            })
+            .collect_vec();
+
+        // 4-10. Assert that the header substrings are one of the values in their membership sets without revealing which.
+        for (set_shift_config, values) in config.header_membership_sets.iter() {
+            let substr = set_shift_config.extract(ctx, &gate, &header_result.regex.masked_characters, &header_result.regex.all_substr_ids);
+            let mut num_matches = gate.load_zero(ctx);
+            for value in values.iter() {
+                // The substring is padded with zeros, so it matches a value only if it has the same length.
+                let mut is_match = gate.load_constant(ctx, F::one());
+                for (idx, assigned_char) in substr.iter().enumerate() {
+                    let expected_char = value.as_bytes().get(idx).copied().unwrap_or(0);
+                    let is_equal = gate.is_equal(ctx, QuantumCell::Existing(assigned_char), QuantumCell::Constant(F::from(expected_char as u64)));
+                    is_match = gate.mul(ctx, QuantumCell::Existing(&is_match), QuantumCell::Existing(&is_equal));
+                }
+                num_matches = gate.add(ctx, QuantumCell::Existing(&num_matches), QuantumCell::Existing(&is_match));
+            }
+            gate.assert_equal(ctx, QuantumCell::Existing(&num_matches), QuantumCell::Constant(F::one()));
+        }
+
+        // 4-11. Assert that the values parsed from the body substrings satisfy the numeric predicates with the thresholds, which are exposed as instances.
+        let range = config.sha256_config.range().clone();
+        let mut predicate_thresholds = vec![];
+        for ((predicate_shift_config, predicate), predicate_params) in config.body_numeric_predicates.iter().zip(body_params.numeric_predicates.iter().flatten()) {
+            let digits = predicate_shift_config.extract(ctx, &gate, &body_result.regex.masked_characters, &body_result.regex.all_substr_ids);
+            let value = CharsShiftConfig::parse_decimal(ctx, &gate, &digits);
+            // The thresholds must fit in 64 bits, like the value of at most 19 digits, for the comparisons below.
+            let thresholds = gate.assign_witnesses(ctx, predicate_params.thresholds.iter().map(|threshold| Value::known(F::from(*threshold))).collect());
+            for threshold in thresholds.iter() {
+                range.range_check(ctx, threshold, 64);
+            }
+            match predicate {
+                NumericPredicate::Gt => range.check_less_than(ctx, QuantumCell::Existing(&thresholds[0]), QuantumCell::Existing(&value), 64),
+                NumericPredicate::Lt => range.check_less_than(ctx, QuantumCell::Existing(&value), QuantumCell::Existing(&thresholds[0]), 64),
+                NumericPredicate::Eq => gate.assert_equal(ctx, QuantumCell::Existing(&value), QuantumCell::Existing(&thresholds[0])),
+                NumericPredicate::Range => {
+                    let is_below = range.is_less_than(ctx, QuantumCell::Existing(&value), QuantumCell::Existing(&thresholds[0]), 64);
+                    gate.assert_equal(ctx, QuantumCell::Existing(&is_below), QuantumCell::Constant(F::zero()));
+                    let is_above = range.is_less_than(ctx, QuantumCell::Existing(&thresholds[1]), QuantumCell::Existing(&value), 64);
+                    gate.assert_equal(ctx, QuantumCell::Existing(&is_above), QuantumCell::Constant(F::zero()));
+                }
+            }
+            predicate_thresholds.extend(thresholds);
+        }
+
+        // 5. Compute public input values.
+        let poseidon = PoseidonChipBn254_8_58::new(ctx, &gate);
+        let sign_commit = poseidon.hash_elements(ctx, &gate, &assigned_signature.c.limbs()).unwrap().0[0].clone();
+        // let header_hash_commit = assigned_commit_wtns_bytes(ctx, &gate, &poseidon, &sign_rand, &header_result.hash_bytes);
+        let public_key_n_hash = poseidon.hash_elements(ctx, &gate, &assigned_public_key.n.limbs()).unwrap().0[0].clone();
+        let mut rlc_inputs = vec![];
+        let mut bodyhash_masked_header_chars = vec![];
+        let mut bodyhash_masked_header_substr_ids = vec![];
+        for idx in 0..header_params.max_variable_byte_size {
+            let is_target = &is_target_vec[idx];
+            bodyhash_masked_header_chars.push(gate.select(
+                ctx,
+                QuantumCell::Constant(F::zero()),
+                QuantumCell::Existing(&header_result.regex.masked_characters[idx]),
+                QuantumCell::Existing(is_target),
+            ));
+            bodyhash_masked_header_substr_ids.push(gate.select(
+                ctx,
+                QuantumCell::Constant(F::zero()),
+                QuantumCell::Existing(&header_result.regex.all_substr_ids[idx]),
+                QuantumCell::Existing(is_target),
+            ));
+        }
+        // println!("bodyhash_masked_header_chars {:?}", bodyhash_masked_header_chars);
+        // for (idx, val) in bodyhash_masked_header_chars.iter().enumerate() {
+        //     println!("idx {} val {:?}", idx, val.value().map(|v| v.get_lower_32() as u8 as char));
+        // }
+        // 5-2. Mask the recipient lists and the committed header substrings, which are committed instead, and the substrings in the membership sets.
+        for list_shift_config in config
+            .header_recipient_lists
+            .iter()
+            .chain(config.header_committed_substrs.iter())
+            .chain(config.header_membership_sets.iter().map(|(shift_config, _)| shift_config))
+        {
+            for idx in 0..header_params.max_variable_byte_size {
+                let is_list = gate.is_equal(
+                    ctx,
+                    QuantumCell::Existing(&header_result.regex.all_substr_ids[idx]),
+                    QuantumCell::Constant(F::from(list_shift_config.target_substr_id)),
+                );
+                bodyhash_masked_header_chars[idx] = gate.select(
+                    ctx,
+                    QuantumCell::Constant(F::zero()),
+                    QuantumCell::Existing(&bodyhash_masked_header_chars[idx]),
+                    QuantumCell::Existing(&is_list),
+                );
+                bodyhash_masked_header_substr_ids[idx] = gate.select(
+                    ctx,
+                    QuantumCell::Constant(F::zero()),
+                    QuantumCell::Existing(&bodyhash_masked_header_substr_ids[idx]),
+                    QuantumCell::Existing(&is_list),
+                );
+            }
+        }
+        rlc_inputs.append(&mut bodyhash_masked_header_chars);
+        rlc_inputs.append(&mut bodyhash_masked_header_substr_ids);
+        // 5-3. Mask the body substrings outside their reveal ranges, and commit the whole substrings instead.
+        let mut body_masked_chars = body_result.regex.masked_characters.clone();
+        let mut body_substr_ids = body_result.regex.all_substr_ids.clone();
+        let mut body_hidden_chars = vec![];
+        for reveal_range in body_params.reveal_ranges.iter().flatten() {
+            let is_targets = body_substr_ids
+                .iter()
+                .map(|substr_id| gate.is_equal(ctx, QuantumCell::Existing(substr_id), QuantumCell::Constant(F::from(reveal_range.substr_id as u64))))
+                .collect_vec();
+            let mut substr_len = gate.load_zero(ctx);
+            for is_target in is_targets.iter() {
+                substr_len = gate.add(ctx, QuantumCell::Existing(&substr_len), QuantumCell::Existing(is_target));
+            }
+            // The number of the substring bytes before each position, i.e., the index of the byte in the substring if it is a target.
+            let mut num_prev_targets = gate.load_zero(ctx);
+            let mut hidden_chars = vec![];
+            for (idx, is_target) in is_targets.iter().enumerate() {
+                let substr_idx = if reveal_range.from_end.unwrap_or(false) {
+                    let rest = gate.sub(ctx, QuantumCell::Existing(&substr_len), QuantumCell::Existing(&num_prev_targets));
+                    gate.sub(ctx, QuantumCell::Existing(&rest), QuantumCell::Constant(F::one()))
+                } else {
+                    num_prev_targets.clone()
+                };
+                let mut is_revealed = gate.load_zero(ctx);
+                for revealed_idx in reveal_range.offset..reveal_range.offset + reveal_range.len {
+                    let is_equal = gate.is_equal(ctx, QuantumCell::Existing(&substr_idx), QuantumCell::Constant(F::from(revealed_idx as u64)));
+                    is_revealed = gate.add(ctx, QuantumCell::Existing(&is_revealed), QuantumCell::Existing(&is_equal));
+                }
+                // keep = 1 - is_target + is_target * is_revealed
+                let is_not_target = gate.sub(ctx, QuantumCell::Constant(F::one()), QuantumCell::Existing(is_target));
+                let keep = gate.mul_add(ctx, QuantumCell::Existing(is_target), QuantumCell::Existing(&is_revealed), QuantumCell::Existing(&is_not_target));
+                hidden_chars.push(gate.mul(ctx, QuantumCell::Existing(&body_result.regex.masked_characters[idx]), QuantumCell::Existing(is_target)));
+                body_masked_chars[idx] = gate.mul(ctx, QuantumCell::Existing(&body_masked_chars[idx]), QuantumCell::Existing(&keep));
+                body_substr_ids[idx] = gate.mul(ctx, QuantumCell::Existing(&body_substr_ids[idx]), QuantumCell::Existing(&keep));
+                num_prev_targets = gate.add(ctx, QuantumCell::Existing(&num_prev_targets), QuantumCell::Existing(is_target));
+            }
+            if reveal_range.commit_hidden.unwrap_or(true) {
+                body_hidden_chars.push(hidden_chars);
+            }
+        }
+        // 5-4. Mask the body substrings compared by the numeric predicates.
+        for (predicate_shift_config, _) in config.body_numeric_predicates.iter() {
+            for idx in 0..body_params.max_variable_byte_size {
+                let is_target = gate.is_equal(
+                    ctx,
+                    QuantumCell::Existing(&body_result.regex.all_substr_ids[idx]),
+                    QuantumCell::Constant(F::from(predicate_shift_config.target_substr_id)),
+                );
+                body_masked_chars[idx] = gate.select(ctx, QuantumCell::Constant(F::zero()), QuantumCell::Existing(&body_masked_chars[idx]), QuantumCell::Existing(&is_target));
+                body_substr_ids[idx] = gate.select(ctx, QuantumCell::Constant(F::zero()), QuantumCell::Existing(&body_substr_ids[idx]), QuantumCell::Existing(&is_target));
+            }
+        }
+        let commitment_scheme = config_params.commitment_scheme.unwrap_or_default();
+        let mut body_hidden_commits = vec![];
+        for hidden_chars in body_hidden_chars.iter() {
+            body_hidden_commits.push(assigned_commit_wtns_bytes_with_scheme(
+                ctx,
+                &gate,
+                &poseidon,
+                commitment_scheme,
+                Some(BODY_COMMIT_DOMAIN),
+                &sign_commit,
+                hidden_chars,
+            ));
+        }
+        rlc_inputs.append(&mut body_masked_chars);
+        rlc_inputs.append(&mut body_substr_ids);
+        rlc_inputs.append(&mut body_hidden_commits);
+        let mut header_recipient_commits = vec![];
+        for address in recipient_addresses.iter() {
+            header_recipient_commits.push(assigned_commit_wtns_bytes_with_scheme(
+                ctx,
+                &gate,
+                &poseidon,
+                commitment_scheme,
+                Some(RECIPIENT_COMMIT_DOMAIN),
+                &sign_commit,
+                address,
+            ));
+        }
+        rlc_inputs.append(&mut header_recipient_commits);
+        let mut body_decoded_commits = vec![];
+        for decoded_bytes in body_decoded_bytes.iter() {
+            body_decoded_commits.push(assigned_commit_wtns_bytes_with_scheme(
+                ctx,
+                &gate,
+                &poseidon,
+                commitment_scheme,
+                Some(DECODED_COMMIT_DOMAIN),
+                &sign_commit,
+                decoded_bytes,
+            ));
+        }
+        rlc_inputs.append(&mut body_decoded_commits);
+        let mut header_hidden_commits = vec![];
+        for committed_chars in header_committed_chars.iter() {
+            header_hidden_commits.push(assigned_commit_wtns_bytes_with_scheme(
+                ctx,
+                &gate,
+                &poseidon,
+                commitment_scheme,
+                Some(HEADER_COMMIT_DOMAIN),
+                &sign_commit,
+                committed_chars,
+            ));
+        }
+        rlc_inputs.append(&mut header_hidden_commits);
+        if header_params.commit_bodyhash.unwrap_or(false) {
+            rlc_inputs.push(assigned_commit_wtns_bytes_with_scheme(
+                ctx,
+                &gate,
+                &poseidon,
+                commitment_scheme,
+                Some(BODYHASH_COMMIT_DOMAIN),
+                &sign_commit,
+                &extracted_bodyhash,
+            ));
+        }
+        let mut rlc = gate.load_zero(ctx);
+        let mut coeff = sign_commit.clone();
+        for input in rlc_inputs.into_iter() {
+            rlc = gate.mul_add(ctx, QuantumCell::Existing(&input), QuantumCell::Existing(&coeff), QuantumCell::Existing(&rlc));
+            coeff = gate.mul(ctx, QuantumCell::Existing(&sign_commit), QuantumCell::Existing(&coeff));
+        }
+
+        // 6. Dump the intermediate witnesses if requested.
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = debug_witness_dir {
+            if let Some(witness) = debug_witness::EmailDebugWitness::new(header_bytes, body_bytes, &header_result, &body_result, &extracted_bodyhash) {
+                let path = witness.write_file(dir);
+                log::info!("intermediate witnesses are written to {}", path);
+            }
+        }
+        Ok((sign_commit, public_key_n_hash, rlc, dkim_timestamp, predicate_thresholds))
+    }
+
+    /// Generate a new circuit from the given email file.
+    ///
+    /// # Arguments
+    /// * `email_path` - a file path or an http(s) URL of the email file. See [`read_email_bytes`].
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn gen_circuit_from_email_path(email_path: &str) -> Self {
+        let email_bytes = read_email_bytes(email_path).await.expect("fail to read the email");
+        Self::gen_circuit_from_email_bytes(email_bytes).await
+    }
+
+    /// Generate a new circuit from the given email bytes, where the RSA public key is resolved from DNS.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn gen_circuit_from_email_bytes(email_bytes: Vec<u8>) -> Self {
+        Self::gen_circuit_from_email_bytes_with_embedded_key(email_bytes, false).await
+    }
+
+    /// Generate a new circuit from the given email bytes.
+    /// If `use_embedded_key` is true and the email has the [`dkim::EMBEDDED_PUBLIC_KEY_HEADER`] headers, the RSA public keys in those headers are tried first without any DNS query.
+    /// Otherwise, or if none of them verifies the signature, the key resolved from DNS is tried, which requires the `q=` tag to be omitted or include `dns/txt`.
+    /// The first candidate key that verifies the DKIM signature natively is used in the circuit.
+    /// The first DKIM signature header is proven. See [`DefaultEmailVerifyCircuit::try_gen_circuit_from_email_bytes_with_pinned_keys`] to select another one.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `use_embedded_key` - a flag whether the public keys embedded in the email are used if present.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn gen_circuit_from_email_bytes_with_embedded_key(email_bytes: Vec<u8>, use_embedded_key: bool) -> Self {
+        Self::try_gen_circuit_from_email_bytes_with_embedded_key(email_bytes, use_embedded_key)
+            .await
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Generate a new circuit from the given email bytes in the same way as [`DefaultEmailVerifyCircuit::gen_circuit_from_email_bytes_with_embedded_key`],
+    /// but return an error instead of panicking if the email cannot be proven, e.g., its public key cannot be resolved from DNS.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `use_embedded_key` - a flag whether the public keys embedded in the email are used if present.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`], or [`dkim::DkimError`] if the email cannot be proven.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn try_gen_circuit_from_email_bytes_with_embedded_key(email_bytes: Vec<u8>, use_embedded_key: bool) -> Result<Self, dkim::DkimError> {
+        Ok(Self::try_gen_circuit_from_email_bytes_with_pinned_keys(email_bytes, use_embedded_key, &[], &dkim::DkimOptions::default()).await?.0)
+    }
+
+    /// Generate a new circuit from the given email bytes in the same way as [`DefaultEmailVerifyCircuit::try_gen_circuit_from_email_bytes_with_embedded_key`],
+    /// but fall back to the pinned public keys if the key resolved from DNS cannot verify the DKIM signature, e.g., because it was rotated after the email was signed.
+    ///
+    /// Note that the pinned keys are trusted as given.
+    /// The verifier must still check the public key hash exposed by the circuit against a trusted key.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `use_embedded_key` - a flag whether the public keys embedded in the email are used if present.
+    /// * `pinned_key_ns` - the `n` parameters of the pinned RSA public keys, which are tried only if the DNS verification fails.
+    /// * `options` - the options of selecting the DKIM signature and resolving its public key from DNS.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`] and the source of the key that verified the signature,
+    /// or [`dkim::DkimError`] of the DNS verification if none of the pinned keys verifies it either.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn try_gen_circuit_from_email_bytes_with_pinned_keys(
+        email_bytes: Vec<u8>,
+        use_embedded_key: bool,
+        pinned_key_ns: &[BigUint],
+        options: &dkim::DkimOptions,
+    ) -> Result<(Self, dkim::PublicKeySource), dkim::DkimError> {
+        let email_bytes = options.select_signature(email_bytes)?;
+        dkim::check_signature_algorithm(&email_bytes)?;
+        dkim::check_body_hash(&email_bytes)?;
+        dkim::check_signed_headers(&email_bytes)?;
+        if !default_config_params().allow_unsigned_from.unwrap_or(false) {
+            dkim::check_from_signed(&email_bytes)?;
+        }
+        // println!("email {}", String::from_utf8(email_bytes.clone()).unwrap());
+        // let (canonicalized_header, canonicalized_body, signature_bytes) = canonicalize_signed_email(&email_bytes).unwrap();
+        // let headerhash = Sha256::digest(&canonicalized_header).to_vec();
+        let candidates = if use_embedded_key { dkim::get_embedded_public_key_ns(&email_bytes)? } else { vec![] };
+        let pss_salt_len = default_config_params().sign_verify_config.as_ref().and_then(|params| params.pss_salt_len);
+        let (public_key_n, source) = match dkim::select_public_key_n_with_padding(&email_bytes, &candidates, pss_salt_len) {
+            Ok(public_key_n) => (public_key_n, dkim::PublicKeySource::Embedded),
+            Err(_) => match Self::select_dns_public_key_n(options, &email_bytes, candidates, pss_salt_len).await {
+                Ok(public_key_n) => (public_key_n, dkim::PublicKeySource::Dns),
+                Err(err) if !pinned_key_ns.is_empty() => {
+                    let public_key_n = dkim::select_public_key_n_with_padding(&email_bytes, pinned_key_ns, pss_salt_len).map_err(|_| err)?;
+                    (public_key_n, dkim::PublicKeySource::Pinned)
+                }
+                Err(err) => return Err(err),
+            },
+        };
+        Ok((builder::EmailCircuitBuilder::new().email(email_bytes).public_key(public_key_n).build()?, source))
+    }
+
+    /// Generate a new circuit from an email forwarded with an ARC chain, e.g., by Gmail, whose original DKIM signature may be broken.
+    /// The outermost ARC seal and message signature are verified natively with the public keys resolved from DNS as in [`arc::verify_arc`],
+    /// and the circuit proves the outermost message signature in place of the DKIM signature.
+    /// The configuration must therefore match the ARC message signature header instead of the DKIM signature header, e.g., `configs/test_arc_email_verify.config`.
+    ///
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `options` - the options of resolving the public keys from DNS. The signature index is ignored.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`], or [`dkim::DkimError`] if the ARC chain is invalid.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn try_gen_circuit_from_arc_email_bytes(email_bytes: Vec<u8>, options: &dkim::DkimOptions) -> Result<Self, dkim::DkimError> {
+        let sets = arc::get_arc_sets(&email_bytes)?;
+        let outermost = sets.last().ok_or_else(|| dkim::DkimError::InvalidArcChain("the email has no ARC headers".to_string()))?;
+        let signer = |tag: fn(&arc::ArcSet, &str) -> Option<String>| -> Result<(String, String), dkim::DkimError> {
+            let domain = tag(outermost, "d").ok_or_else(|| dkim::DkimError::MissingTag("d".to_string()))?;
+            let selector = tag(outermost, "s").ok_or_else(|| dkim::DkimError::MissingTag("s".to_string()))?;
+            Ok((domain, selector))
+        };
+        let seal_signer = signer(arc::ArcSet::seal_tag)?;
+        let message_signer = signer(arc::ArcSet::message_signature_tag)?;
+        let seal_key_n = Self::resolve_dns_public_key_n(options, &seal_signer.0, &seal_signer.1).await?;
+        let message_key_n = if message_signer == seal_signer {
+            seal_key_n.clone()
+        } else {
+            Self::resolve_dns_public_key_n(options, &message_signer.0, &message_signer.1).await?
+        };
+        let (header_bytes, body_bytes, signature_bytes) = arc::verify_arc(&email_bytes, &seal_key_n, &message_key_n)?;
+        Ok(builder::EmailCircuitBuilder::new()
+            .header(header_bytes)
+            .body(body_bytes)
+            .signature(signature_bytes)
+            .public_key(message_key_n)
+            .build()?)
+    }
+
+    /// Generate a new circuit from an email already parsed by `mailparse`, e.g., by a server.
+    /// The email is canonicalized with [`dkim::canonicalize_parsed_email`], so the circuit is the same as that from the raw bytes of the email with the given public key.
+    ///
+    /// # Arguments
+    /// * `parsed` - the parsed email.
+    /// * `public_key_n` - `n` parameter of the RSA public key.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`], or [`dkim::DkimError`] if the email cannot be canonicalized or does not fit in the circuit.
+    pub fn try_gen_circuit_from_parsed_mail(parsed: &mailparse::ParsedMail, public_key_n: BigUint) -> Result<Self, dkim::DkimError> {
+        let (header_bytes, body_bytes, signature_bytes) = dkim::canonicalize_parsed_email(parsed)?;
+        Ok(builder::EmailCircuitBuilder::new()
+            .header(header_bytes)
+            .body(body_bytes)
+            .signature(signature_bytes)
+            .public_key(public_key_n)
+            .build()?)
+    }
+
+    // Resolve the RSA public key of the given domain and selector from DNS in the same way as that of the DKIM signature,
+    // for which a DKIM signature header with only the tags required by the resolver is given.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn resolve_dns_public_key_n(options: &dkim::DkimOptions, domain: &str, selector: &str) -> Result<BigUint, dkim::DkimError> {
+        let email_bytes = format!(
+            "DKIM-Signature: v=1; a=rsa-sha256; d={}; s={}; h=from; bh=AAAA; b=AAAA\r\nFrom: {}\r\n\r\n",
+            domain, selector, domain
+        )
+        .into_bytes();
+        if let Some(public_key_n) = dkim::resolve_dnssec_public_key_n(options, domain, selector).await? {
+            return Ok(public_key_n);
+        }
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        match resolve_public_key(&logger, &email_bytes)
+            .await
+            .map_err(|err| dkim::DkimError::UnresolvablePublicKey(format!("{:?}", err)))?
+        {
+            cfdkim::DkimPublicKey::Rsa(_pk) => Ok(BigUint::from_radix_le(&_pk.n().clone().to_radix_le(16), 16).unwrap()),
+            _ => Err(dkim::DkimError::UnsupportedKeyType),
+        }
+    }
+
+    // Resolve the public key from DNS and select the one that verifies the signature among it and the other candidates.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn select_dns_public_key_n(
+        options: &dkim::DkimOptions,
+        email_bytes: &[u8],
+        mut candidates: Vec<BigUint>,
+        pss_salt_len: Option<usize>,
+    ) -> Result<BigUint, dkim::DkimError> {
+        dkim::check_query_method(email_bytes)?;
+        let domain = dkim::get_signing_domain(email_bytes).ok_or_else(|| dkim::DkimError::MissingTag("d".to_string()))?;
+        let selector = dkim::get_dkim_tag(email_bytes, "s").ok_or_else(|| dkim::DkimError::MissingTag("s".to_string()))?;
+        // The key in the DNSSEC-authenticated answer replaces the one resolved without DNSSEC.
+        match dkim::resolve_dnssec_public_key_n(options, &domain, &selector).await? {
+            Some(public_key_n) => candidates.push(public_key_n),
+            None => {
+                let logger = slog::Logger::root(slog::Discard, slog::o!());
+                match resolve_public_key(&logger, email_bytes)
+                    .await
+                    .map_err(|err| dkim::DkimError::UnresolvablePublicKey(format!("{:?}", err)))?
+                {
+                    cfdkim::DkimPublicKey::Rsa(_pk) => candidates.push(BigUint::from_radix_le(&_pk.n().clone().to_radix_le(16), 16).unwrap()),
+                    _ => return Err(dkim::DkimError::UnsupportedKeyType),
+                }
+            }
+        }
+        if let Some(resolved_n) = candidates.last() {
+            dkim::audit_public_key_resolution(options.audit_log_path.as_deref(), email_bytes, resolved_n)?;
+        }
+        dkim::select_public_key_n_with_padding(email_bytes, &candidates, pss_salt_len)
+    }
+
+    /// Compute public input values as [`DefaultEmailVerifyPublicInput`] from the circuit.
+    pub fn gen_default_public_input(&self) -> DefaultEmailVerifyPublicInput {
+        let (header_bytes, body_bytes, signature_bytes) = self.canonicalized_email();
+        let mut public_input =
+            DefaultEmailVerifyPublicInput::from_parts::<F>(&header_bytes, &body_bytes, &signature_bytes, &self.public_key_n).unwrap_or_else(|err| panic!("{}", err));
+        if let Some(bound_block) = self.bound_block.as_ref() {
+            public_input.block_number = Some(bound_block.number);
+            public_input.block_hash = Some(format!("0x{}", hex::encode(bound_block.hash)));
+        }
+        public_input
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn configure_native(meta: &mut ConstraintSystem<F>) -> DefaultEmailVerifyConfig<F> {
+        Self::configure_native_for_emails(meta, 1)
+    }
+
+    /// Configure [`DefaultEmailVerifyConfig`] so that `num_emails` emails can be verified in one circuit.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn configure_native_for_emails(meta: &mut ConstraintSystem<F>, num_emails: usize) -> DefaultEmailVerifyConfig<F> {
+        let params = default_config_params();
+        let range_config = RangeConfig::configure(
+            meta,
+            Vertical,
+            &[params.num_flex_advice],
+            &[params.num_range_lookup_advice],
+            // The fixed columns are as many as the advice columns rather than `num_flex_fixed`, which keeps the verifying keys of the existing configurations.
+            params.num_flex_advice,
+            params.range_lookup_bits,
+            0,
+            params.degree as usize,
+        );
+        let header_params = params.header_config.as_ref().expect("header_config is required");
+        let body_params = params.body_config.as_ref().expect("body_config is required");
+        let sign_verify_params = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+        let sha256_params = params.sha256_config.as_ref().expect("sha256_config is required");
+        assert_eq!(header_params.allstr_filepathes.len(), header_params.substr_filepathes.len());
+        assert_eq!(body_params.allstr_filepathes.len(), body_params.substr_filepathes.len());
+        if let Some(reveal_ranges) = body_params.reveal_ranges.as_ref() {
+            for reveal_range in reveal_ranges.iter() {
+                assert!(
+                    (1..=body_params.substr_regexes.len()).contains(&reveal_range.substr_id),
+                    "the body substring id {} of the reveal range is out of range",
+                    reveal_range.substr_id
+                );
+                assert!(reveal_range.len > 0, "the reveal range of the body substring {} is empty", reveal_range.substr_id);
+            }
+            assert!(reveal_ranges.iter().map(|reveal_range| reveal_range.substr_id).all_unique(), "each body substring can have at most one reveal range");
+        }
+
+        let sha256_config = Sha256DynamicConfig::configure(
+            meta,
+            vec![
+                vec![body_params.max_variable_byte_size, header_params.max_variable_byte_size],
+                SignVerifyConfig::<F>::sha256_max_byte_sizes(sign_verify_params.public_key_bits, sign_verify_params.pss_salt_len),
+            ]
+            .concat()
+            .repeat(num_emails),
+            range_config.clone(),
+            sha256_params.num_bits_lookup,
+            sha256_params.num_advice_columns,
+            false,
+        );
+
+        let sign_verify_config = SignVerifyConfig::configure(range_config.clone(), sign_verify_params.public_key_bits, sign_verify_params.pss_salt_len);
+
+        // Inconsistent regex files would make the regexes silently match nothing, and an over-large regex would fail obscurely in the lookup, so reject them before reading.
+        if let Err(err) = params.check_regex_files() {
+            panic!("invalid regex files: {}", err);
+        }
+        let bodyhash_allstr_def = AllstrRegexDef::read_from_text(&header_params.bodyhash_allstr_filepath);
+        let bodyhash_substr_def = SubstrRegexDef::read_from_text(&header_params.bodyhash_substr_filepath);
+        let bodyhash_defs = RegexDefs {
+            allstr: bodyhash_allstr_def,
+            substrs: vec![bodyhash_substr_def],
+        };
+        let mut bodyhash_substr_id = 1;
+        let header_regex_defs = header_params
+            .allstr_filepathes
+            .iter()
+            .zip(header_params.substr_filepathes.iter())
+            .map(|(allstr_path, substr_pathes)| {
+                let allstr = AllstrRegexDef::read_from_text(&allstr_path);
+                let substrs = substr_pathes.into_iter().map(|path| SubstrRegexDef::read_from_text(&path)).collect_vec();
+                bodyhash_substr_id += substrs.len();
+                RegexDefs { allstr, substrs }
+            })
+            .collect_vec();
+        let header_config = RegexSha2Config::configure(
+            meta,
+            header_params.max_variable_byte_size,
+            header_params.skip_prefix_bytes_size.unwrap_or(0),
+            range_config.clone(),
+            vec![header_regex_defs, vec![bodyhash_defs]].concat(),
+        );
+
+        let body_regex_defs = body_params
+            .allstr_filepathes
+            .iter()
             .zip(body_params.substr_filepathes.iter())
             .map(|(allstr_path, substr_pathes)| {
                 let allstr = AllstrRegexDef::read_from_text(&allstr_path);
@@ -508,73 +1909,1445 @@ impl<F: PrimeField> DefaultEmailVerifyCircuit<F> {
             body_params.skip_prefix_bytes_size.unwrap_or(0),
             range_config,
             body_regex_defs,
+            body_params.hash_len(),
+        );
+        let chars_shift_config = CharsShiftConfig::configure(
+            header_params.max_variable_byte_size,
+            base64_encoded_len(body_params.hash_len()) + MAX_BODYHASH_FOLDING_BYTES,
+            bodyhash_substr_id as u64,
         );
-        let chars_shift_config = CharsShiftConfig::configure(header_params.max_variable_byte_size, 44, bodyhash_substr_id as u64);
+        let header_substr_equalities = Self::configure_substr_equalities(header_params);
+        let header_body_substr_equalities = Self::configure_header_body_substr_equalities(&params, header_params, body_params);
+        let body_forbidden_substrs = Self::configure_forbidden_substrs(body_params);
+        let header_forbidden_fields = Self::configure_forbidden_fields(header_params);
+        let header_dkim_domain_alignments = Self::configure_dkim_domain_alignments(header_params);
+        let header_dkim_timestamp = Self::configure_dkim_timestamp(header_params);
+        let header_received_hops = Self::configure_received_hops(header_params);
+        let header_recipient_lists = Self::configure_recipient_lists(header_params);
+        let body_decoded_tokens = Self::configure_decoded_tokens(body_params);
+        let header_committed_substrs = Self::configure_committed_substrs(header_params);
+        let header_membership_sets = Self::configure_membership_sets(header_params);
+        let body_numeric_predicates = Self::configure_numeric_predicates(body_params);
 
-        let instances = meta.instance_column();
-        meta.enable_equality(instances);
+        let instances = Self::configure_instances(meta);
         DefaultEmailVerifyConfig {
             sha256_config,
             sign_verify_config,
             header_config,
             body_config,
             chars_shift_config,
+            header_substr_equalities,
+            header_body_substr_equalities,
+            body_forbidden_substrs,
+            header_forbidden_fields,
+            header_dkim_domain_alignments,
+            header_dkim_timestamp,
+            header_received_hops,
+            header_recipient_lists,
+            body_decoded_tokens,
+            header_committed_substrs,
+            header_membership_sets,
+            body_numeric_predicates,
             instances,
         }
     }
-}
 
-#[cfg(not(target_arch = "wasm32"))]
-#[cfg(test)]
-mod test {
-    use super::*;
-    use cfdkim::{resolve_public_key, SignerBuilder};
-    use halo2_base::halo2_proofs::{
-        circuit::Value,
-        dev::{CircuitCost, FailureLocation, MockProver, VerifyFailure},
-        halo2curves::bn256::{Fr, G1},
-    };
-    use halo2_regex::vrm::DecomposedRegexConfig;
-    use halo2_rsa::RSAPubE;
-    use mailparse::parse_mail;
-    use num_bigint::BigUint;
-    use rand::thread_rng;
-    use rsa::{traits::PublicKeyParts, RsaPrivateKey};
-    use snark_verifier_sdk::CircuitExt;
-    use std::{fs::File, io::Read, path::Path};
-    use temp_env;
+    pub(crate) fn configure_instances(meta: &mut ConstraintSystem<F>) -> Vec<Column<Instance>> {
+        (0..num_instance_columns())
+            .map(|_| {
+                let column = meta.instance_column();
+                meta.enable_equality(column);
+                column
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn configure_forbidden_substrs(body_params: &BodyConfigParams) -> Vec<ForbiddenSubstrConfig<F>> {
+        body_params
+            .forbidden_substrs
+            .as_ref()
+            .map(|forbidden_substrs| {
+                forbidden_substrs
+                    .iter()
+                    .map(|params| ForbiddenSubstrConfig::configure(&params.substr, params.case_insensitive.unwrap_or(false)))
+                    .collect_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn configure_forbidden_fields(header_params: &HeaderConfigParams) -> Vec<ForbiddenHeaderFieldConfig<F>> {
+        header_params
+            .forbidden_fields
+            .as_ref()
+            .map(|forbidden_fields| {
+                assert_eq!(
+                    header_params.skip_prefix_bytes_size.unwrap_or(0),
+                    0,
+                    "forbidden_fields requires the header to be matched from its first field"
+                );
+                forbidden_fields.iter().map(|name| ForbiddenHeaderFieldConfig::configure(name)).collect_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn configure_header_body_substr_equalities(
+        params: &EmailVerifyConfigParams,
+        header_params: &HeaderConfigParams,
+        body_params: &BodyConfigParams,
+    ) -> Vec<(CharsShiftConfig<F>, CharsShiftConfig<F>)> {
+        params
+            .header_body_substr_equalities
+            .as_ref()
+            .map(|equalities| {
+                equalities
+                    .iter()
+                    .map(|equality| {
+                        (
+                            CharsShiftConfig::configure(header_params.max_variable_byte_size, equality.max_substr_size, equality.header_substr_id as u64),
+                            CharsShiftConfig::configure(body_params.max_variable_byte_size, equality.max_substr_size, equality.body_substr_id as u64),
+                        )
+                    })
+                    .collect_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn configure_dkim_domain_alignments(header_params: &HeaderConfigParams) -> Vec<(CharsShiftConfig<F>, CharsShiftConfig<F>, bool)> {
+        header_params
+            .dkim_domain_alignments
+            .as_ref()
+            .map(|alignments| {
+                alignments
+                    .iter()
+                    .map(|alignment| {
+                        (
+                            CharsShiftConfig::configure(header_params.max_variable_byte_size, alignment.max_substr_size, alignment.substr_id as u64),
+                            CharsShiftConfig::configure(header_params.max_variable_byte_size, alignment.max_substr_size, alignment.dkim_domain_substr_id as u64),
+                            alignment.strict.unwrap_or(false),
+                        )
+                    })
+                    .collect_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn configure_dkim_timestamp(header_params: &HeaderConfigParams) -> Option<CharsShiftConfig<F>> {
+        header_params.dkim_timestamp.as_ref().map(|timestamp| {
+            assert!(
+                (1..=header_params.substr_regexes.len()).contains(&timestamp.substr_id),
+                "the header substring id {} of dkim_timestamp is out of range",
+                timestamp.substr_id
+            );
+            assert!(
+                (1..=19).contains(&timestamp.max_substr_size),
+                "max_substr_size of dkim_timestamp must be between 1 and 19, but it is {}",
+                timestamp.max_substr_size
+            );
+            CharsShiftConfig::configure(header_params.max_variable_byte_size, timestamp.max_substr_size, timestamp.substr_id as u64)
+        })
+    }
+
+    pub(crate) fn configure_received_hops(header_params: &HeaderConfigParams) -> Vec<(CharsShiftConfig<F>, String)> {
+        header_params
+            .received_hops
+            .iter()
+            .flatten()
+            .map(|hop| {
+                assert!(
+                    (1..=header_params.substr_regexes.len()).contains(&hop.substr_id),
+                    "the header substring id {} of received_hops is out of range",
+                    hop.substr_id
+                );
+                assert!(!hop.host.is_empty(), "the host of received_hops must not be empty");
+                // The substring may not be longer than the host, so that a host with a suffix is rejected.
+                let shift_config = CharsShiftConfig::configure(header_params.max_variable_byte_size, hop.host.len(), hop.substr_id as u64);
+                (shift_config, hop.host.clone())
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn configure_recipient_lists(header_params: &HeaderConfigParams) -> Vec<CharsShiftConfig<F>> {
+        header_params
+            .recipients
+            .as_ref()
+            .map(|recipients| {
+                assert!(recipients.substr_ids.iter().all_unique(), "the substring ids of recipients must be distinct");
+                assert!(
+                    recipients.max_list_size > 0 && recipients.max_recipients > 0 && recipients.max_address_size > 0,
+                    "the sizes of recipients must be positive"
+                );
+                recipients
+                    .substr_ids
+                    .iter()
+                    .map(|substr_id| {
+                        assert!(
+                            (1..=header_params.substr_regexes.len()).contains(substr_id),
+                            "the header substring id {} of recipients is out of range",
+                            substr_id
+                        );
+                        CharsShiftConfig::configure(header_params.max_variable_byte_size, recipients.max_list_size, *substr_id as u64)
+                    })
+                    .collect_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn configure_decoded_tokens(body_params: &BodyConfigParams) -> Vec<(CharsShiftConfig<F>, TokenEncoding)> {
+        body_params
+            .decoded_tokens
+            .iter()
+            .flatten()
+            .map(|token| {
+                assert!(
+                    (1..=body_params.substr_regexes.len()).contains(&token.substr_id),
+                    "the body substring id {} of decoded_tokens is out of range",
+                    token.substr_id
+                );
+                let group_size = match token.encoding {
+                    TokenEncoding::Hex => 2,
+                    TokenEncoding::Base64 => 4,
+                };
+                assert!(
+                    token.max_token_size > 0 && token.max_token_size % group_size == 0,
+                    "max_token_size of decoded_tokens must be a positive multiple of {} for {:?}, but it is {}",
+                    group_size,
+                    token.encoding,
+                    token.max_token_size
+                );
+                let shift_config = CharsShiftConfig::configure(body_params.max_variable_byte_size, token.max_token_size, token.substr_id as u64);
+                (shift_config, token.encoding)
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn configure_committed_substrs(header_params: &HeaderConfigParams) -> Vec<CharsShiftConfig<F>> {
+        header_params
+            .committed_substrs
+            .iter()
+            .flatten()
+            .map(|committed| {
+                assert!(
+                    (1..=header_params.substr_regexes.len()).contains(&committed.substr_id),
+                    "the header substring id {} of committed_substrs is out of range",
+                    committed.substr_id
+                );
+                assert!(committed.max_substr_size > 0, "max_substr_size of committed_substrs must be positive");
+                CharsShiftConfig::configure(header_params.max_variable_byte_size, committed.max_substr_size, committed.substr_id as u64)
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn configure_membership_sets(header_params: &HeaderConfigParams) -> Vec<(CharsShiftConfig<F>, Vec<String>)> {
+        header_params
+            .membership_sets
+            .iter()
+            .flatten()
+            .map(|set| {
+                assert!(
+                    (1..=header_params.substr_regexes.len()).contains(&set.substr_id),
+                    "the header substring id {} of membership_sets is out of range",
+                    set.substr_id
+                );
+                assert!(!set.values.is_empty(), "the values of membership_sets must not be empty");
+                assert!(set.values.iter().all(|value| !value.is_empty()), "each value of membership_sets must not be empty");
+                assert!(set.values.iter().all_unique(), "the values of membership_sets must be distinct");
+                // The substring may not be longer than the longest value, so that a value with a suffix is rejected.
+                let max_value_size = set.values.iter().map(|value| value.len()).max().unwrap();
+                let shift_config = CharsShiftConfig::configure(header_params.max_variable_byte_size, max_value_size, set.substr_id as u64);
+                (shift_config, set.values.clone())
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn configure_numeric_predicates(body_params: &BodyConfigParams) -> Vec<(CharsShiftConfig<F>, NumericPredicate)> {
+        body_params
+            .numeric_predicates
+            .iter()
+            .flatten()
+            .map(|predicate| {
+                assert!(
+                    (1..=body_params.substr_regexes.len()).contains(&predicate.substr_id),
+                    "the body substring id {} of numeric_predicates is out of range",
+                    predicate.substr_id
+                );
+                assert!(
+                    (1..=19).contains(&predicate.max_substr_size),
+                    "max_substr_size of numeric_predicates must be between 1 and 19, but it is {}",
+                    predicate.max_substr_size
+                );
+                assert_eq!(
+                    predicate.thresholds.len(),
+                    predicate.predicate.num_thresholds(),
+                    "the {:?} predicate of numeric_predicates requires {} thresholds",
+                    predicate.predicate,
+                    predicate.predicate.num_thresholds()
+                );
+                assert!(
+                    body_params.reveal_ranges.iter().flatten().all(|reveal_range| reveal_range.substr_id != predicate.substr_id),
+                    "the body substring {} of numeric_predicates must not have a reveal range",
+                    predicate.substr_id
+                );
+                let shift_config = CharsShiftConfig::configure(body_params.max_variable_byte_size, predicate.max_substr_size, predicate.substr_id as u64);
+                (shift_config, predicate.predicate)
+            })
+            .collect_vec()
+    }
+
+    pub(crate) fn configure_substr_equalities(header_params: &HeaderConfigParams) -> Vec<Vec<CharsShiftConfig<F>>> {
+        header_params
+            .substr_equalities
+            .as_ref()
+            .map(|equalities| {
+                equalities
+                    .iter()
+                    .map(|equality| {
+                        assert!(equality.substr_ids.len() >= 2, "at least two substrings must be specified in substr_equalities");
+                        equality
+                            .substr_ids
+                            .iter()
+                            .map(|id| CharsShiftConfig::configure(header_params.max_variable_byte_size, equality.max_substr_size, *id as u64))
+                            .collect_vec()
+                    })
+                    .collect_vec()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cfdkim::{resolve_public_key, SignerBuilder};
+    use halo2_base::halo2_proofs::{
+        circuit::Value,
+        dev::{CircuitCost, FailureLocation, MockProver, VerifyFailure},
+        halo2curves::bn256::{Fr, G1},
+    };
+    use halo2_regex::vrm::DecomposedRegexConfig;
+    use halo2_rsa::RSAPubE;
+    use mailparse::parse_mail;
+    use num_bigint::BigUint;
+    use rand::thread_rng;
+    use rsa::{traits::PublicKeyParts, RsaPrivateKey};
+    use snark_verifier_sdk::CircuitExt;
+    use std::{fs::File, io::Read, path::Path};
+    use temp_env;
+
+    #[test]
+    fn test_generated_email1() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
+            let regex_bodyhash_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_defs.json").unwrap()).unwrap();
+            regex_bodyhash_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/bodyhash_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/bodyhash_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_from_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/from_defs.json").unwrap()).unwrap();
+            regex_from_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/from_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/from_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_body_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/test1_email_body_defs.json").unwrap()).unwrap();
+            regex_body_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/test1_email_body_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/test1_email_body_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let params = default_config_params();
+            let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+            let mut rng = thread_rng();
+            let _private_key = RsaPrivateKey::new(&mut rng, sign_verify_config.public_key_bits).expect("failed to generate a key");
+            let public_key = rsa::RsaPublicKey::from(&_private_key);
+            let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key);
+            let message = concat!("From: alice@zkemail.com\r\n", "\r\n", "email was meant for @zkemailverify.",).as_bytes();
+            let email = parse_mail(message).unwrap();
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["From"])
+                .unwrap()
+                .with_private_key(private_key)
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&email).unwrap();
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+            println!("email: {}", String::from_utf8(email_bytes.clone()).unwrap());
+            let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+            let instances = circuit.instances();
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_generated_email2() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test2_email_verify.config"), || {
+            let regex_bodyhash_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_defs.json").unwrap()).unwrap();
+            regex_bodyhash_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/bodyhash_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/bodyhash_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_from_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/from_defs.json").unwrap()).unwrap();
+            regex_from_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/from_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/from_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_to_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/to_defs.json").unwrap()).unwrap();
+            regex_to_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/to_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/to_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_body_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/test2_email_body_defs.json").unwrap()).unwrap();
+            regex_body_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/test2_email_body_allstr.txt").to_path_buf(),
+                    &[
+                        Path::new("./test_data/test2_email_body_substr_0.txt").to_path_buf(),
+                        Path::new("./test_data/test2_email_body_substr_1.txt").to_path_buf(),
+                    ],
+                )
+                .unwrap();
+            let params = default_config_params();
+            let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+            let mut rng = thread_rng();
+            let _private_key = RsaPrivateKey::new(&mut rng, sign_verify_config.public_key_bits).expect("failed to generate a key");
+            let public_key = rsa::RsaPublicKey::from(&_private_key);
+            let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key);
+            let message = concat!(
+                "From: alice@zkemail.com\r\n",
+                "To: bob@example.com\r\n",
+                "\r\n",
+                "email was meant for @zkemailverify and halo.",
+            )
+            .as_bytes();
+            let email = parse_mail(message).unwrap();
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["To", "From"])
+                .unwrap()
+                .with_private_key(private_key)
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&email).unwrap();
+            println!("signature {}", signature);
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+            let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+
+            let instances = circuit.instances();
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_generated_email_invalid_bodyhash_length() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
+            let regex_bodyhash_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_defs.json").unwrap()).unwrap();
+            regex_bodyhash_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/bodyhash_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/bodyhash_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_from_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/from_defs.json").unwrap()).unwrap();
+            regex_from_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/from_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/from_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let regex_body_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/test1_email_body_defs.json").unwrap()).unwrap();
+            regex_body_decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/test1_email_body_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/test1_email_body_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let params = default_config_params();
+            let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+            let mut rng = thread_rng();
+            let _private_key = RsaPrivateKey::new(&mut rng, sign_verify_config.public_key_bits).expect("failed to generate a key");
+            let public_key = rsa::RsaPublicKey::from(&_private_key);
+            let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key);
+            let message = concat!("From: alice@zkemail.com\r\n", "\r\n", "email was meant for @zkemailverify.",).as_bytes();
+            let email = parse_mail(message).unwrap();
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["From"])
+                .unwrap()
+                .with_private_key(private_key)
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&email).unwrap();
+            // Replace the `bh=` value with a base64 string of 16 bytes.
+            let bh_regex = regex_simple::Regex::new(r"bh=[^;]*").unwrap();
+            let signature = bh_regex.replace(&signature, "bh=AAAAAAAAAAAAAAAAAAAAAA==").to_string();
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+            let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+            let prover = MockProver::run(params.degree, &circuit, vec![vec![Fr::zero(); 3]]);
+            assert!(prover.is_err());
+        });
+    }
+
+    /// Generate the regex files of the given pairs of a definition file and a file prefix, which have one substring file each,
+    /// sign the message with a new RSA key over the given header fields, and build the circuit of the signed email.
+    fn sign_test_email(defs: &[(&str, &str)], message: &str, signed_headers: &[&str]) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email_with_domain(defs, message, signed_headers, "zkemail.com")
+    }
+
+    /// Generate the circuit of a signed email in the same way as [`sign_test_email`], signed by the given domain.
+    fn sign_test_email_with_domain(defs: &[(&str, &str)], message: &str, signed_headers: &[&str], signing_domain: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        for (defs_path, prefix) in defs.iter() {
+            let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open(defs_path).unwrap()).unwrap();
+            decomposed
+                .gen_regex_files(
+                    &Path::new(&format!("./test_data/{}_allstr.txt", prefix)).to_path_buf(),
+                    &[Path::new(&format!("./test_data/{}_substr_0.txt", prefix)).to_path_buf()],
+                )
+                .unwrap();
+        }
+        let params = default_config_params();
+        let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+        let _private_key = RsaPrivateKey::new(&mut thread_rng(), sign_verify_config.public_key_bits).expect("failed to generate a key");
+        let public_key = rsa::RsaPublicKey::from(&_private_key);
+        let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key);
+        let email = parse_mail(message.as_bytes()).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(signed_headers)
+            .unwrap()
+            .with_private_key(private_key)
+            .with_selector("default")
+            .with_signing_domain(signing_domain)
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message.as_bytes()].concat();
+        let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+        (params.degree, DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n))
+    }
+
+    fn gen_reply_to_email_circuit(from: &str, reply_to: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/from_domain_defs.json", "from_domain"),
+                ("./test_data/reply_to_domain_defs.json", "reply_to_domain"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &format!("From: {}\r\nReply-To: {}\r\n\r\nemail was meant for @zkemailverify.", from, reply_to),
+            &["From", "Reply-To"],
+        )
+    }
+
+    #[test]
+    fn test_reply_to_same_domain() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_reply_to_email_verify.config"), || {
+            let (degree, circuit) = gen_reply_to_email_circuit("alice@zkemail.com", "bob@zkemail.com");
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_reply_to_different_domain() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_reply_to_email_verify.config"), || {
+            let (degree, circuit) = gen_reply_to_email_circuit("alice@zkemail.com", "alice@zkemai1.com");
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_idn_domain() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_reply_to_email_verify.config").unwrap()).unwrap();
+        config["header_config"]["decode_idn_domains"] = serde_json::Value::Bool(true);
+        let config_json = config.to_string();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config_json.as_str()), || {
+            let (degree, circuit) = gen_reply_to_email_circuit("alice@xn--r8jz45g.jp", "bob@xn--r8jz45g.jp");
+            let public_input = circuit.gen_default_public_input();
+            // The circuit commits the punycode form signed by DKIM.
+            assert_eq!(public_input.header_substrs, vec!["xn--r8jz45g.jp".to_string(), "xn--r8jz45g.jp".to_string()]);
+            assert_eq!(
+                public_input.public_fields(),
+                vec![
+                    ("from_domain".to_string(), "例え.jp".as_bytes().to_vec()),
+                    ("reply_to_domain".to_string(), "例え.jp".as_bytes().to_vec()),
+                    ("test1_email_body".to_string(), b"zkemailverify".to_vec())
+                ]
+            );
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    fn gen_return_path_email_circuit(return_path: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/return_path_domain_defs.json", "return_path_domain"),
+                ("./test_data/dkim_domain_defs.json", "dkim_domain"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &format!("From: alice@zkemail.com\r\nReturn-Path: <{}>\r\n\r\nemail was meant for @zkemailverify.", return_path),
+            &["From", "Return-Path"],
+        )
+    }
+
+    fn gen_dkim_timestamp_email_circuit() -> (u32, DefaultEmailVerifyCircuit<Fr>, u64) {
+        let (degree, circuit) = sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/dkim_timestamp_defs.json", "dkim_timestamp"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            "From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.",
+            &["From"],
+        );
+        let timestamp = get_substr(&String::from_utf8_lossy(&circuit.email_bytes), &[r"(?<=t=)[0-9]+".to_string()])
+            .expect("the signature has no t= tag")
+            .1
+            .parse::<u64>()
+            .unwrap();
+        (degree, circuit, timestamp)
+    }
+
+    #[test]
+    fn test_dkim_timestamp_instance() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_dkim_timestamp_email_verify.config"), || {
+            let (degree, circuit, timestamp) = gen_dkim_timestamp_email_circuit();
+            assert_eq!(circuit.num_instance(), vec![4]);
+            assert_eq!(circuit.gen_default_public_input().dkim_timestamp, Some(timestamp));
+            let instances = circuit.instances();
+            assert_eq!(instances[0][3], Fr::from(timestamp));
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_dkim_timestamp_wrong_instance() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_dkim_timestamp_email_verify.config"), || {
+            let (degree, circuit, timestamp) = gen_dkim_timestamp_email_circuit();
+            let mut public_input = circuit.gen_default_public_input();
+            public_input.dkim_timestamp = Some(timestamp + 1);
+            let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    fn gen_received_hop_email_circuit(received_host: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/received_hop_defs.json", "received_hop"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &format!(
+                "From: alice@zkemail.com\r\nReceived: from {} ({} [192.0.2.1]) by mail.example.org\r\n\r\nemail was meant for @zkemailverify.",
+                received_host, received_host
+            ),
+            &["From", "Received"],
+        )
+    }
+
+    #[test]
+    fn test_received_hop() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_received_hop_email_verify.config"), || {
+            let (degree, circuit) = gen_received_hop_email_circuit("mx.zkemail.com");
+            assert_eq!(circuit.gen_default_public_input().header_substrs, vec!["mx.zkemail.com".to_string()]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_received_hop_other_host() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_received_hop_email_verify.config").unwrap()).unwrap();
+        config["header_config"]["received_hops"][0]["host"] = serde_json::Value::from("mx.zkemail.co");
+        let (degree, circuit, instances) = temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_received_hop_email_verify.config"), || {
+            let (degree, circuit) = gen_received_hop_email_circuit("mx.zkemail.com");
+            let instances = circuit.instances();
+            (degree, circuit, instances)
+        });
+        // The circuit rejects the hop even if its host has the expected host as a prefix.
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string()), || {
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+            assert!(std::panic::catch_unwind(|| circuit.gen_default_public_input()).is_err());
+        });
+    }
+
+    fn gen_recipients_email_circuit(to: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/recipients_defs.json", "recipients"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &format!("From: alice@zkemail.com\r\nTo: {}\r\n\r\nemail was meant for @zkemailverify.", to),
+            &["From", "To"],
+        )
+    }
+
+    #[test]
+    fn test_recipients() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_recipients_email_verify.config"), || {
+            let (degree, circuit) = gen_recipients_email_circuit("alice@zkemail.com, bob@zkemail.com, carol@zkemail.com");
+            let public_input = circuit.gen_default_public_input();
+            // The list is hidden, and each of the three addresses is committed instead.
+            assert_eq!(public_input.header_substrs, vec!["".to_string()]);
+            assert_eq!(public_input.header_recipient_commits.len(), 3);
+            let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+            let mut bob = b"bob@zkemail.com".to_vec();
+            bob.resize(32, 0);
+            let bob_commit = value_commit_wtns_bytes_with_domain(Some(RECIPIENT_COMMIT_DOMAIN), &sign_commit, &bob);
+            assert_eq!(public_input.header_recipient_commits[1], fe_to_biguint(&bob_commit).to_str_radix(10));
+            assert!(public_input.header_recipient_commits.iter().all_unique());
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_keccak_recipient_commits() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_keccak_commit_email_verify.config"), || {
+            let (degree, circuit) = gen_recipients_email_circuit("alice@zkemail.com, bob@zkemail.com");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.header_recipient_commits.len(), 2);
+            // The commitment is recomputed off-chain by Keccak-256 of the domain tag, sign_commit, and the padded address.
+            let sign_commit = fe_to_biguint(&Fr::from_str_vartime(&public_input.sign_commit).unwrap()).to_bytes_be();
+            let mut preimage = RECIPIENT_COMMIT_DOMAIN.to_be_bytes().to_vec();
+            preimage.extend(vec![0u8; 32 - sign_commit.len()]);
+            preimage.extend(sign_commit);
+            let mut bob = b"bob@zkemail.com".to_vec();
+            bob.resize(32, 0);
+            preimage.extend(bob);
+            let hash = sha3::Keccak256::digest(&preimage);
+            assert_eq!(public_input.header_recipient_commits[1], BigUint::from_bytes_be(&hash[1..]).to_str_radix(10));
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_recipients_wrong_commit() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_recipients_email_verify.config"), || {
+            let (degree, circuit) = gen_recipients_email_circuit("alice@zkemail.com, bob@zkemail.com, carol@zkemail.com");
+            let mut public_input = circuit.gen_default_public_input();
+            public_input.header_recipient_commits.swap(0, 1);
+            let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_recipients_too_many() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_recipients_email_verify.config"), || {
+            let (_, circuit) = gen_recipients_email_circuit("alice@zkemail.com, bob@zkemail.com, carol@zkemail.com, dave@zkemail.com");
+            assert!(std::panic::catch_unwind(|| circuit.gen_default_public_input()).is_err());
+        });
+    }
+
+    #[test]
+    fn test_pss_signature() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_pss_email_verify.config"), || {
+            for (defs_path, prefix) in [
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/from_defs.json", "from"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ] {
+                let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open(defs_path).unwrap()).unwrap();
+                decomposed
+                    .gen_regex_files(
+                        &Path::new(&format!("./test_data/{}_allstr.txt", prefix)).to_path_buf(),
+                        &[Path::new(&format!("./test_data/{}_substr_0.txt", prefix)).to_path_buf()],
+                    )
+                    .unwrap();
+            }
+            let params = default_config_params();
+            let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+            let mut rng = thread_rng();
+            let _private_key = RsaPrivateKey::new(&mut rng, sign_verify_config.public_key_bits).expect("failed to generate a key");
+            let public_key = rsa::RsaPublicKey::from(&_private_key);
+            let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key.clone());
+            let message = concat!("From: alice@zkemail.com\r\n", "\r\n", "email was meant for @zkemailverify.",).as_bytes();
+            let email = parse_mail(message).unwrap();
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let signer = SignerBuilder::new()
+                .with_signed_headers(&["From"])
+                .unwrap()
+                .with_private_key(private_key)
+                .with_selector("default")
+                .with_signing_domain("zkemail.com")
+                .with_logger(&logger)
+                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+                .build()
+                .unwrap();
+            let signature = signer.sign(&email).unwrap();
+            let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
+            // Replace the PKCS#1 v1.5 signature made by the DKIM signer with a PSS signature of the same header.
+            let (header_bytes, body_bytes, pkcs1_signature) = canonicalize_signed_email(&email_bytes).unwrap();
+            let header_hash = Sha256::digest(&header_bytes);
+            let pss_signature = _private_key
+                .sign_with_rng(&mut rng, rsa::Pss::new_with_salt::<Sha256>(sign_verify_config.pss_salt_len.unwrap()), &header_hash)
+                .unwrap();
+            let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::from_parts(header_bytes.clone(), body_bytes.clone(), pss_signature, public_key_n.clone());
+            let instances = circuit.instances();
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+            // The PKCS#1 v1.5 signature is rejected behind the PSS configuration.
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::from_parts(header_bytes, body_bytes, pkcs1_signature, public_key_n);
+            let instances = circuit.instances();
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_return_path_aligned() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_return_path_email_verify.config"), || {
+            let (degree, circuit) = gen_return_path_email_circuit("bounce@mail.zkemail.com");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.header_substrs, vec!["mail.zkemail.com".to_string(), "zkemail.com".to_string()]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_return_path_misaligned() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_return_path_email_verify.config"), || {
+            // `badzkemail.com` ends with `zkemail.com` but is not its subdomain.
+            let (degree, circuit) = gen_return_path_email_circuit("bounce@badzkemail.com");
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_return_path_strict_alignment() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_return_path_email_verify.config").unwrap()).unwrap();
+        config["header_config"]["dkim_domain_alignments"][0]["strict"] = serde_json::Value::Bool(true);
+        let config_json = config.to_string();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config_json.as_str()), || {
+            let (degree, circuit) = gen_return_path_email_circuit("bounce@zkemail.com");
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+            let (degree, circuit) = gen_return_path_email_circuit("bounce@mail.zkemail.com");
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    fn gen_forbidden_substr_email_circuit(body: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/from_defs.json", "from"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &format!("From: alice@zkemail.com\r\n\r\n{}", body),
+            &["From"],
+        )
+    }
+
+    #[test]
+    fn test_forbidden_substr_absent() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_compute_expected_instances() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let expected_instances = compute_expected_instances::<Fr>(&circuit.email_bytes, &circuit.public_key_n);
+            assert_eq!(expected_instances, circuit.instances());
+            let prover = MockProver::run(degree, &circuit, expected_instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_circuit_from_parts() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let (header_bytes, body_bytes, signature_bytes) = canonicalize_signed_email(&circuit.email_bytes).unwrap();
+            let parts_circuit = DefaultEmailVerifyCircuit::<Fr>::from_parts(header_bytes, body_bytes, signature_bytes, circuit.public_key_n.clone());
+            assert_eq!(parts_circuit.canonicalized_email(), circuit.canonicalized_email());
+            assert_eq!(parts_circuit.instances(), circuit.instances());
+            let prover = MockProver::run(degree, &parts_circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_circuit_from_witness_json() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let witness_path = "./build/test_witness.json";
+            std::fs::create_dir_all("./build").unwrap();
+            crate::witness_json::EmailWitnessJson::from_circuit(&circuit).write_file(witness_path);
+            let witness = crate::witness_json::EmailWitnessJson::read_file(witness_path).unwrap();
+            let witness_circuit = witness.to_circuit::<Fr>().unwrap();
+            assert_eq!(witness_circuit.canonicalized_email(), circuit.canonicalized_email());
+            assert_eq!(witness_circuit.instances(), circuit.instances());
+            let prover = MockProver::run(degree, &witness_circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            let mut wrong_witness = witness.clone();
+            wrong_witness.header_starts = Some(vec![1000]);
+            assert!(matches!(
+                wrong_witness.to_circuit::<Fr>(),
+                Err(crate::witness_json::WitnessJsonError::SubstrMismatch { field: "header_starts", .. })
+            ));
+            let mut wrong_witness = witness;
+            wrong_witness.signature.pop();
+            assert!(matches!(wrong_witness.to_circuit::<Fr>(), Err(crate::witness_json::WitnessJsonError::LimbCount { name: "signature", .. })));
+        });
+    }
+
+    #[test]
+    fn test_hex_token_decoded_commit() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_hex_token_email_verify.config"), || {
+            let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/body_hex_token_defs.json").unwrap()).unwrap();
+            decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/body_hex_token_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/body_hex_token_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("Sign in with the token 00c0ffeeBEEF before it expires.");
+            let mut public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.body_substrs, vec!["00c0ffeeBEEF".to_string()]);
+            assert_eq!(public_input.body_decoded_commits.len(), 1);
+            let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+            let mut decoded = vec![0x00, 0xc0, 0xff, 0xee, 0xbe, 0xef];
+            decoded.resize(16, 0);
+            let decoded_commit = value_commit_wtns_bytes_with_domain(Some(DECODED_COMMIT_DOMAIN), &sign_commit, &decoded);
+            assert_eq!(public_input.body_decoded_commits[0], fe_to_biguint(&decoded_commit).to_str_radix(10));
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // The commitment of other decoded bytes is rejected.
+            decoded[0] = 0x01;
+            let wrong_commit = value_commit_wtns_bytes_with_domain(Some(DECODED_COMMIT_DOMAIN), &sign_commit, &decoded);
+            public_input.body_decoded_commits[0] = fe_to_biguint(&wrong_commit).to_str_radix(10);
+            let wrong_instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, wrong_instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    fn gen_amount_email_circuit(amount: u64) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/from_defs.json", "from"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+                ("./test_data/body_amount_defs.json", "body_amount"),
+            ],
+            &format!("From: alice@zkemail.com\r\n\r\nThank you for your order. You paid {} dollars.", amount),
+            &["From"],
+        )
+    }
+
+    #[test]
+    fn test_numeric_predicate() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_numeric_predicate_email_verify.config"), || {
+            let (degree, circuit) = gen_amount_email_circuit(150);
+            let public_input = circuit.gen_default_public_input();
+            // The amount is hidden, and only the threshold is public.
+            assert_eq!(public_input.body_substrs, vec!["".to_string()]);
+            assert_eq!(public_input.predicate_thresholds, vec![100]);
+            let instances = circuit.instances();
+            assert_eq!(instances[0][3], Fr::from(100u64));
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // Another threshold in the instances is rejected.
+            let mut public_input = public_input;
+            public_input.predicate_thresholds = vec![99];
+            let wrong_instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, wrong_instances).unwrap();
+            assert!(prover.verify().is_err());
+
+            // An amount not greater than the threshold is rejected.
+            for amount in [50, 100] {
+                let (degree, circuit) = gen_amount_email_circuit(amount);
+                let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+                assert!(prover.verify().is_err());
+            }
+        });
+    }
+
+    #[test]
+    fn test_numeric_predicate_range() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_numeric_predicate_email_verify.config").unwrap()).unwrap();
+        config["body_config"]["numeric_predicates"][0]["predicate"] = serde_json::json!("range");
+        config["body_config"]["numeric_predicates"][0]["thresholds"] = serde_json::json!([100, 150]);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let (degree, circuit) = gen_amount_email_circuit(150);
+            assert_eq!(circuit.instances()[0][3..], [Fr::from(100u64), Fr::from(150u64)]);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+            let (degree, circuit) = gen_amount_email_circuit(151);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_bound_block_hash() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_forbidden_substr_email_verify.config").unwrap()).unwrap();
+        config["bind_block_hash"] = serde_json::json!(true);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let block = BoundBlock { number: 100, hash: [0xab; 32] };
+            let circuit = circuit.with_bound_block(block);
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.block_number, Some(100));
+            assert_eq!(public_input.bound_block(), Some(block));
+            let instances = circuit.instances();
+            assert_eq!(instances[0].len(), num_instances());
+            assert_eq!(instances[0][3..], block.hash_instances::<Fr>());
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // The instances of another block hash are rejected.
+            let mut public_input = public_input;
+            public_input.block_hash = Some(format!("0x{}", "cd".repeat(32)));
+            let wrong_instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, wrong_instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_folded_bodyhash() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let (_, body_bytes, _) = circuit.canonicalized_email();
+            let bodyhash = general_purpose::STANDARD.encode(Sha256::digest(&body_bytes));
+            // The relaxed canonicalization replaces the folding `\r\n\t` with a space.
+            let header_bytes = format!(
+                "from:alice@zkemail.com\r\ndkim-signature:v=1; a=rsa-sha256; c=relaxed/relaxed; d=zkemail.com; s=default; h=from; bh={} {}; b=",
+                &bodyhash[..20],
+                &bodyhash[20..]
+            )
+            .into_bytes();
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+            let signature_bytes = private_key.sign(rsa::Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&header_bytes)).unwrap();
+            let public_key_n = BigUint::from_bytes_be(&private_key.n().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::from_parts(header_bytes, body_bytes, signature_bytes, public_key_n);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_bodyhash_last_tag() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_bodyhash_last_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            // The default bodyhash regex requires `;` after the `bh=` value, so this config uses the regex in which `bh=` ends the header.
+            let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_last_defs.json").unwrap()).unwrap();
+            decomposed
+                .gen_regex_files(
+                    &Path::new("./test_data/bodyhash_last_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/bodyhash_last_substr_0.txt").to_path_buf()],
+                )
+                .unwrap();
+            let (_, body_bytes, _) = circuit.canonicalized_email();
+            let bodyhash = general_purpose::STANDARD.encode(Sha256::digest(&body_bytes));
+            // The `bh=` value is the last tag, so it is terminated by the end of the header instead of `;`.
+            let header_bytes = format!(
+                "from:alice@zkemail.com\r\ndkim-signature:v=1; a=rsa-sha256; c=relaxed/relaxed; d=zkemail.com; s=default; h=from; b=; bh={} {}",
+                &bodyhash[..20],
+                &bodyhash[20..]
+            )
+            .into_bytes();
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+            let signature_bytes = private_key.sign(rsa::Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&header_bytes)).unwrap();
+            let public_key_n = BigUint::from_bytes_be(&private_key.n().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::from_parts(header_bytes, body_bytes, signature_bytes, public_key_n);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_get_bodyhash_value() {
+        assert_eq!(get_bodyhash_value(b"dkim-signature:v=1; bh=abc\r\n\tdef; b="), Some("abcdef".to_string()));
+        assert_eq!(get_bodyhash_value(b"dkim-signature:v=1; b=; bh=abc def"), Some("abcdef".to_string()));
+        assert_eq!(get_bodyhash_value(b"dkim-signature:v=1; b=; bh=abc\r\nfrom:alice@zkemail.com"), Some("abc".to_string()));
+        assert_eq!(get_bodyhash_value(b"dkim-signature:v=1; b="), None);
+    }
+
+    #[test]
+    fn test_zero_body_length() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, _) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            // With `l=0`, the body hash is that of the empty string, whatever the body is.
+            let bodyhash = general_purpose::STANDARD.encode(Sha256::digest(b""));
+            let dkim_value = format!("v=1; a=rsa-sha256; c=relaxed/relaxed; d=zkemail.com; s=default; h=from; l=0; bh={}; b=", bodyhash);
+            let header_bytes = format!("from:alice@zkemail.com\r\ndkim-signature:{}", dkim_value).into_bytes();
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), 2048).unwrap();
+            let signature_bytes = private_key.sign(rsa::Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&header_bytes)).unwrap();
+            let email_bytes = format!(
+                "DKIM-Signature: {}{}\r\nFrom: alice@zkemail.com\r\n\r\nThis body is not signed: email was meant for @mallory.\r\n",
+                dkim_value,
+                general_purpose::STANDARD.encode(&signature_bytes)
+            )
+            .into_bytes();
+            assert_eq!(dkim::check_body_hash(&email_bytes), Ok(bodyhash));
+            let public_key_n = BigUint::from_bytes_be(&private_key.n().to_bytes_be());
+            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+            let (canonicalized_header, canonicalized_body, _) = circuit.canonicalized_email();
+            assert_eq!(canonicalized_header, header_bytes);
+            assert!(canonicalized_body.is_empty());
+            // The unsigned body is not revealed.
+            assert_eq!(circuit.gen_default_public_input().body_substrs, vec!["".to_string()]);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_public_input_write_file() {
+        let public_input_path = "./build/test_write_public_input.json";
+        std::fs::create_dir_all("./build").unwrap();
+        let public_input = DefaultEmailVerifyPublicInput {
+            sign_commit: "1".to_string(),
+            public_key_hash: "2".to_string(),
+            header_starts: vec![5; 64],
+            header_substrs: vec!["alice@zkemail.com".to_string(); 64],
+            body_starts: vec![],
+            body_substrs: vec![],
+            body_hidden_commits: vec![],
+            header_recipient_commits: vec![],
+            body_decoded_commits: vec![],
+            header_hidden_commits: vec![],
+            header_bodyhash_commits: vec![],
+            dkim_timestamp: Some(1700000000),
+            predicate_thresholds: vec![],
+            block_number: None,
+            block_hash: None,
+        };
+        public_input.write_file(public_input_path);
+        assert_eq!(std::fs::read_to_string(public_input_path).unwrap(), serde_json::to_string(&public_input).unwrap());
+    }
+
+    #[test]
+    fn test_public_input_formats() {
+        std::fs::create_dir_all("./build").unwrap();
+        let public_input = DefaultEmailVerifyPublicInput {
+            sign_commit: "255".to_string(),
+            public_key_hash: "7".to_string(),
+            header_starts: vec![5],
+            header_substrs: vec!["alice@zkemail.com".to_string()],
+            body_starts: vec![],
+            body_substrs: vec![],
+            body_hidden_commits: vec!["1".to_string(), "2".to_string()],
+            header_recipient_commits: vec![],
+            body_decoded_commits: vec![],
+            header_hidden_commits: vec![],
+            header_bodyhash_commits: vec![],
+            dkim_timestamp: None,
+            predicate_thresholds: vec![],
+            block_number: None,
+            block_hash: None,
+        };
+        let hex = public_input.to_json_with_format(PublicInputFormat::Hex);
+        assert_eq!(hex["sign_commit"], format!("0x{}ff", "0".repeat(62)));
+        let le_bytes = public_input.to_json_with_format(PublicInputFormat::LeBytes);
+        assert_eq!(le_bytes["sign_commit"], serde_json::json!([vec![255u8], vec![0u8; 31]].concat()));
+        assert_eq!(le_bytes["body_hidden_commits"][1], serde_json::json!([vec![2u8], vec![0u8; 31]].concat()));
+        for name in ["decimal", "hex", "le-bytes"] {
+            let public_input_path = format!("./build/test_public_input_{}.json", name);
+            public_input.write_file_with_format(&public_input_path, PublicInputFormat::from_name(name).unwrap());
+            let read = DefaultEmailVerifyPublicInput::read_file(&public_input_path);
+            assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&public_input).unwrap());
+        }
+        assert!(PublicInputFormat::from_name("base64").is_none());
+        let mut invalid = le_bytes.clone();
+        invalid["sign_commit"] = serde_json::json!([256]);
+        assert!(DefaultEmailVerifyPublicInput::from_json(invalid).is_err());
+    }
+
+    #[test]
+    fn test_public_input_with_non_utf8_body() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let header_bytes = b"from:alice@zkemail.com\r\ndkim-signature:v=1; a=rsa-sha256; d=zkemail.com; s=default; h=from; bh=; b=";
+            let body_bytes = b"caf\xe9 \xff\xfe email was meant for @zkemailverify.\r\n";
+            let public_input = DefaultEmailVerifyPublicInput::from_parts::<Fr>(header_bytes, body_bytes, &[1u8; 256], &BigUint::from(65537u64)).unwrap();
+            assert_eq!(public_input.header_substrs, vec!["alice@zkemail.com".to_string()]);
+            assert_eq!(public_input.body_substrs, vec!["zkemailverify".to_string()]);
+            assert_eq!(public_input.body_starts, vec![29]);
+            assert_eq!(EmailEncoding::Strict.check(body_bytes), Err(3));
+        });
+    }
+
+    #[test]
+    fn test_config_circuit_shape() {
+        let config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_forbidden_substr_email_verify.config").unwrap()).unwrap();
+        let configure = |num_flex_advice: usize, num_range_lookup_advice: usize, num_flex_fixed: usize| {
+            let mut config = config.clone();
+            config["num_flex_advice"] = serde_json::Value::from(num_flex_advice);
+            config["num_range_lookup_advice"] = serde_json::Value::from(num_range_lookup_advice);
+            config["num_flex_fixed"] = serde_json::Value::from(num_flex_fixed);
+            temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string()), || {
+                let mut cs = ConstraintSystem::<Fr>::default();
+                DefaultEmailVerifyCircuit::<Fr>::configure(&mut cs);
+                (cs.num_advice_columns(), cs.num_fixed_columns())
+            })
+        };
+        let (num_advice, num_fixed) = configure(17, 1, 1);
+        // The columns of the flex gate and the range lookup follow the configuration without a rebuild.
+        assert_eq!(configure(18, 1, 1), (num_advice + 1, num_fixed + 1));
+        assert_eq!(configure(17, 2, 1), (num_advice + 1, num_fixed));
+        // num_flex_fixed does not change the fixed columns, so the verifying keys of the existing configurations still match.
+        assert_eq!(configure(17, 1, 2), (num_advice, num_fixed));
+    }
+
+    #[test]
+    fn test_instances_match_constrained_cells() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let max_body_size = default_config_params().body_config.as_ref().unwrap().max_variable_byte_size;
+            let first_line = "email was meant for @zkemailverify.\r\n";
+            // The longest canonicalized body whose SHA256 padding (0x80 and the 8-byte length) fits in max_variable_byte_size.
+            let max_body = format!("{}{}\r\n", first_line, "a".repeat(max_body_size - 9 - first_line.len() - 2));
+            for body in ["", first_line, max_body.as_str()] {
+                let (degree, circuit) = gen_forbidden_substr_email_circuit(body);
+                if body.len() == max_body.len() {
+                    assert_eq!(circuit.canonicalized_email().1.len(), max_body_size - 9);
+                }
+                let instances = circuit.instances();
+                let prover = MockProver::run(degree, &circuit, instances.clone()).unwrap();
+                assert_eq!(prover.verify(), Ok(()));
+                // Every instance is constrained, so an off-by-one in any of the natively computed values is rejected.
+                for (column, values) in instances.iter().enumerate() {
+                    for row in 0..values.len() {
+                        let mut shifted = instances.clone();
+                        shifted[column][row] += Fr::from(1u64);
+                        let prover = MockProver::run(degree, &circuit, shifted).unwrap();
+                        assert!(prover.verify().is_err(), "the instance at column {} and row {} is not constrained for the body {:?}", column, row, body);
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_forbidden_substr_present() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_substr_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify. JavaScript:alert(1)");
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    fn gen_list_unsubscribe_email_circuit(list_unsubscribe: Option<&str>) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        let (message, signed_headers) = match list_unsubscribe {
+            Some(value) => (
+                format!("From: alice@zkemail.com\r\nList-Unsubscribe: {}\r\n\r\nemail was meant for @zkemailverify.", value),
+                vec!["From", "List-Unsubscribe"],
+            ),
+            None => ("From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.".to_string(), vec!["From"]),
+        };
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/from_defs.json", "from"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &message,
+            &signed_headers,
+        )
+    }
+
+    #[test]
+    fn test_forbidden_header_field_absent() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_header_email_verify.config"), || {
+            let (degree, circuit) = gen_list_unsubscribe_email_circuit(None);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_forbidden_header_field_present() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_forbidden_header_email_verify.config"), || {
+            let (degree, circuit) = gen_list_unsubscribe_email_circuit(Some("<mailto:unsubscribe@lists.zkemail.com>"));
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_embedded_public_key() {
+        use rsa::pkcs8::EncodePublicKey;
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let public_key = rsa::RsaPublicKey::new(
+                rsa::BigUint::from_bytes_be(&circuit.public_key_n.to_bytes_be()),
+                rsa::BigUint::from(DefaultEmailVerifyCircuit::<Fr>::DEFAULT_E as u64),
+            )
+            .unwrap();
+            let der = public_key.to_public_key_der().unwrap();
+            let header = format!("{}: v=DKIM1; k=rsa; p={}\r\n", dkim::EMBEDDED_PUBLIC_KEY_HEADER, general_purpose::STANDARD.encode(der.as_bytes()));
+            let email_bytes = vec![header.as_bytes(), &circuit.email_bytes].concat();
+            // No DNS query is made because the key is embedded in the email.
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let embedded_circuit = runtime.block_on(DefaultEmailVerifyCircuit::<Fr>::gen_circuit_from_email_bytes_with_embedded_key(email_bytes, true));
+            assert_eq!(embedded_circuit.public_key_n, circuit.public_key_n);
+            let instances = embedded_circuit.instances();
+            let prover = MockProver::run(degree, &embedded_circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
 
     #[test]
-    fn test_generated_email1() {
+    fn test_multiple_candidate_public_keys() {
+        use rsa::pkcs8::EncodePublicKey;
         temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
-            let regex_bodyhash_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_defs.json").unwrap()).unwrap();
-            regex_bodyhash_decomposed
-                .gen_regex_files(
-                    &Path::new("./test_data/bodyhash_allstr.txt").to_path_buf(),
-                    &[Path::new("./test_data/bodyhash_substr_0.txt").to_path_buf()],
-                )
-                .unwrap();
-            let regex_from_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/from_defs.json").unwrap()).unwrap();
-            regex_from_decomposed
-                .gen_regex_files(
-                    &Path::new("./test_data/from_allstr.txt").to_path_buf(),
-                    &[Path::new("./test_data/from_substr_0.txt").to_path_buf()],
-                )
-                .unwrap();
-            let regex_body_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/test1_email_body_defs.json").unwrap()).unwrap();
-            regex_body_decomposed
-                .gen_regex_files(
-                    &Path::new("./test_data/test1_email_body_allstr.txt").to_path_buf(),
-                    &[Path::new("./test_data/test1_email_body_substr_0.txt").to_path_buf()],
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let wrong_private_key = RsaPrivateKey::new(&mut thread_rng(), circuit.public_key_n.bits() as usize).unwrap();
+            let public_keys = [
+                rsa::RsaPublicKey::from(&wrong_private_key),
+                rsa::RsaPublicKey::new(
+                    rsa::BigUint::from_bytes_be(&circuit.public_key_n.to_bytes_be()),
+                    rsa::BigUint::from(DefaultEmailVerifyCircuit::<Fr>::DEFAULT_E as u64),
                 )
+                .unwrap(),
+            ];
+            let headers = public_keys
+                .iter()
+                .map(|public_key| {
+                    let der = public_key.to_public_key_der().unwrap();
+                    format!("{}: v=DKIM1; k=rsa; p={}\r\n", dkim::EMBEDDED_PUBLIC_KEY_HEADER, general_purpose::STANDARD.encode(der.as_bytes()))
+                })
+                .collect::<String>();
+            let email_bytes = vec![headers.as_bytes(), &circuit.email_bytes].concat();
+            // The second candidate key is selected because the first one does not verify the signature.
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let selected_circuit = runtime.block_on(DefaultEmailVerifyCircuit::<Fr>::gen_circuit_from_email_bytes_with_embedded_key(email_bytes, true));
+            assert_eq!(selected_circuit.public_key_n, circuit.public_key_n);
+            let instances = selected_circuit.instances();
+            let prover = MockProver::run(degree, &selected_circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_pinned_public_key_fallback() {
+        use rsa::pkcs8::EncodePublicKey;
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
+            let (_, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            // The email is signed by a fresh key, which stands for a key rotated out of DNS.
+            let rotated_key = rsa::RsaPublicKey::new(
+                rsa::BigUint::from_bytes_be(&circuit.public_key_n.to_bytes_be()),
+                rsa::BigUint::from(DefaultEmailVerifyCircuit::<Fr>::DEFAULT_E as u64),
+            )
+            .unwrap();
+            let record = format!("v=DKIM1; k=rsa; p={}", general_purpose::STANDARD.encode(rotated_key.to_public_key_der().unwrap().as_bytes()));
+            let pinned_key_n = dkim::parse_pinned_public_key(&record).unwrap();
+            let wrong_private_key = RsaPrivateKey::new(&mut thread_rng(), circuit.public_key_n.bits() as usize).unwrap();
+            let wrong_key_n = BigUint::from_bytes_be(&rsa::RsaPublicKey::from(&wrong_private_key).n().to_bytes_be());
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let (pinned_circuit, source) = runtime
+                .block_on(DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(
+                    circuit.email_bytes.clone(),
+                    false,
+                    &[wrong_key_n.clone(), pinned_key_n],
+                    &dkim::DkimOptions::default(),
+                ))
                 .unwrap();
+            assert_eq!(source, dkim::PublicKeySource::Pinned);
+            assert_eq!(pinned_circuit.public_key_n, circuit.public_key_n);
+            let result = runtime.block_on(DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(
+                circuit.email_bytes.clone(),
+                false,
+                &[wrong_key_n],
+                &dkim::DkimOptions::default(),
+            ));
+            assert!(result.is_err());
+        });
+    }
+
+    #[test]
+    fn test_from_display_name_and_address() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_from_name_addr_email_verify.config"), || {
+            for (defs_path, prefix, num_substrs) in [
+                ("./test_data/bodyhash_defs.json", "bodyhash", 1),
+                ("./test_data/from_name_addr_defs.json", "from_name_addr", 2),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body", 1),
+            ] {
+                let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open(defs_path).unwrap()).unwrap();
+                let substr_pathes = (0..num_substrs)
+                    .map(|idx| Path::new(&format!("./test_data/{}_substr_{}.txt", prefix, idx)).to_path_buf())
+                    .collect_vec();
+                decomposed
+                    .gen_regex_files(&Path::new(&format!("./test_data/{}_allstr.txt", prefix)).to_path_buf(), &substr_pathes)
+                    .unwrap();
+            }
             let params = default_config_params();
             let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
-            let mut rng = thread_rng();
-            let _private_key = RsaPrivateKey::new(&mut rng, sign_verify_config.public_key_bits).expect("failed to generate a key");
+            let _private_key = RsaPrivateKey::new(&mut thread_rng(), sign_verify_config.public_key_bits).expect("failed to generate a key");
             let public_key = rsa::RsaPublicKey::from(&_private_key);
             let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key);
-            let message = concat!("From: alice@zkemail.com\r\n", "\r\n", "email was meant for @zkemailverify.",).as_bytes();
+            let message = concat!("From: Alice <alice@zkemail.com>\r\n", "\r\n", "email was meant for @zkemailverify.",).as_bytes();
             let email = parse_mail(message).unwrap();
             let logger = slog::Logger::root(slog::Discard, slog::o!());
             let signer = SignerBuilder::new()
@@ -590,87 +3363,422 @@ mod test {
                 .unwrap();
             let signature = signer.sign(&email).unwrap();
             let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
-            println!("email: {}", String::from_utf8(email_bytes.clone()).unwrap());
             let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
             let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.header_substrs, vec!["Alice".to_string(), "alice@zkemail.com".to_string()]);
+            assert_eq!(
+                public_input.public_fields(),
+                vec![
+                    ("from_name_addr".to_string(), b"Alice".to_vec()),
+                    ("from_name_addr_1".to_string(), b"alice@zkemail.com".to_vec()),
+                    ("test1_email_body".to_string(), b"zkemailverify".to_vec())
+                ]
+            );
             let instances = circuit.instances();
             let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
             assert_eq!(prover.verify(), Ok(()));
         });
     }
 
+    fn gen_otp_email_circuit(subject_code: &str, body_code: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        gen_otp_email_circuit_with_body(subject_code, &format!("Use {} to sign in.", body_code))
+    }
+
+    fn gen_otp_email_circuit_with_body(subject_code: &str, body: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/subject_otp_defs.json", "subject_otp"),
+                ("./test_data/body_otp_defs.json", "body_otp"),
+            ],
+            &format!("From: alice@zkemail.com\r\nSubject: Your code is {}\r\n\r\n{}", subject_code, body),
+            &["From", "Subject"],
+        )
+    }
+
     #[test]
-    fn test_generated_email2() {
-        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test2_email_verify.config"), || {
-            let regex_bodyhash_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_defs.json").unwrap()).unwrap();
-            regex_bodyhash_decomposed
-                .gen_regex_files(
-                    &Path::new("./test_data/bodyhash_allstr.txt").to_path_buf(),
-                    &[Path::new("./test_data/bodyhash_substr_0.txt").to_path_buf()],
-                )
-                .unwrap();
-            let regex_from_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/from_defs.json").unwrap()).unwrap();
-            regex_from_decomposed
-                .gen_regex_files(
-                    &Path::new("./test_data/from_allstr.txt").to_path_buf(),
-                    &[Path::new("./test_data/from_substr_0.txt").to_path_buf()],
-                )
-                .unwrap();
-            let regex_to_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/to_defs.json").unwrap()).unwrap();
-            regex_to_decomposed
-                .gen_regex_files(
-                    &Path::new("./test_data/to_allstr.txt").to_path_buf(),
-                    &[Path::new("./test_data/to_substr_0.txt").to_path_buf()],
-                )
-                .unwrap();
-            let regex_body_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/test2_email_body_defs.json").unwrap()).unwrap();
-            regex_body_decomposed
+    fn test_header_body_substr_equality() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            let (degree, circuit) = gen_otp_email_circuit("123456", "123456");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.header_substrs, vec!["123456".to_string()]);
+            assert_eq!(public_input.body_substrs, vec!["123456".to_string()]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_body_with_bom_and_control_chars() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            // The body starts with a UTF-8 BOM followed by BEL and ESC, which are fed to the circuit as they are.
+            let (degree, circuit) = gen_otp_email_circuit_with_body("123456", "\u{feff}\u{7}\u{1b}Use 123456 to sign in.\r\n");
+            let (_, body_bytes, _) = circuit.canonicalized_email();
+            assert!(body_bytes.starts_with(&[0xef, 0xbb, 0xbf, 0x07, 0x1b]));
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.body_substrs, vec!["123456".to_string()]);
+            // The start is the byte position after the 3-byte BOM, the 2 control characters, and `Use `.
+            assert_eq!(public_input.body_starts, vec![9]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_circuit_from_parsed_mail() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            let (degree, circuit) = gen_otp_email_circuit_with_body("123456", "Use 123456 to\tsign in. \r\n\r\n\r\n");
+            let parsed = parse_mail(&circuit.email_bytes).unwrap();
+            let parsed_circuit = DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_parsed_mail(&parsed, circuit.public_key_n.clone()).unwrap();
+            assert_eq!(parsed_circuit.canonicalized_email(), circuit.canonicalized_email());
+            assert_eq!(parsed_circuit.instances(), circuit.instances());
+            let instances = parsed_circuit.instances();
+            let prover = MockProver::run(degree, &parsed_circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_header_body_substr_inequality() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            let (degree, circuit) = gen_otp_email_circuit("123456", "654321");
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "field subject_otp exceeds max_size 16")]
+    fn test_substr_equality_over_max_size() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            let (_, circuit) = gen_otp_email_circuit("12345678901234567", "12345678901234567");
+            circuit.gen_default_public_input();
+        });
+    }
+
+    #[test]
+    fn test_public_input_error_over_max_size() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_email_verify.config"), || {
+            let (_, circuit) = gen_otp_email_circuit("12345678901234567", "12345678901234567");
+            let (header_bytes, body_bytes, signature_bytes) = circuit.canonicalized_email();
+            let err = DefaultEmailVerifyPublicInput::from_parts::<Fr>(&header_bytes, &body_bytes, &signature_bytes, &circuit.public_key_n).unwrap_err();
+            assert!(matches!(err, PublicInputError::TooLarge { ref name, limit: "max_size", max_size: 16, .. } if name == "subject_otp"));
+        });
+    }
+
+    #[test]
+    fn test_substr_equality_not_truncated() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_otp_email_verify.config").unwrap()).unwrap();
+        config["header_body_substr_equalities"][0]["max_substr_size"] = serde_json::Value::from(32);
+        let (degree, circuit, instances) = temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let (degree, circuit) = gen_otp_email_circuit("12345678901234567", "12345678901234567");
+            let instances = circuit.instances();
+            (degree, circuit, instances)
+        });
+        // The circuit must not compare only the first 16 bytes of the 17-byte codes.
+        config["header_body_substr_equalities"][0]["max_substr_size"] = serde_json::Value::from(16);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_body_reveal_range() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_reveal_range_email_verify.config"), || {
+            let (degree, circuit) = gen_otp_email_circuit("123456", "123456");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.body_substrs, vec!["3456".to_string()]);
+            assert_eq!(public_input.body_hidden_commits.len(), 1);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_body_reveal_range_wrong_hidden_commit() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_reveal_range_email_verify.config"), || {
+            let (degree, circuit) = gen_otp_email_circuit("123456", "123456");
+            let mut public_input = circuit.gen_default_public_input();
+            // The same revealed digits with different hidden digits.
+            let (_, other_circuit) = gen_otp_email_circuit("993456", "993456");
+            public_input.body_hidden_commits = other_circuit.gen_default_public_input().body_hidden_commits;
+            let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_body_reveal_range_with_nul_bytes() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_reveal_range_email_verify.config"), || {
+            // The NUL bytes are neither the padding nor the masked characters since the substring ids tell them apart.
+            let (degree, circuit) = gen_otp_email_circuit_with_body("123456", "Hello\0\0\r\nUse 123456 to sign in.\0");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.body_substrs, vec!["3456".to_string()]);
+            let (_, body_bytes, _) = circuit.canonicalized_email();
+            assert_eq!(public_input.body_starts, vec![body_bytes.windows(4).position(|window| window == b"3456").unwrap()]);
+            let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+            let mut hidden_bytes = vec![0u8; default_config_params().body_config.as_ref().unwrap().max_variable_byte_size];
+            let start = public_input.body_starts[0] - 2;
+            hidden_bytes[start..start + 6].copy_from_slice(b"123456");
+            let hidden_commit = value_commit_wtns_bytes_with_domain(Some(BODY_COMMIT_DOMAIN), &sign_commit, &hidden_bytes);
+            assert_eq!(public_input.body_hidden_commits, vec![fe_to_biguint(&hidden_commit).to_str_radix(10)]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_json_path_body_commit() {
+        crate::helpers::gen_regex_files("./test_data/body_json_total_defs.json", "./test_data", "body_json_total").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_json_path_email_verify.config"), || {
+            let body = "Your receipt\r\n{\"data\": {\"total\": 1, \"order\": {\"id\": \"A-17\", \"total\": 42.50}}}\r\n";
+            let (degree, circuit) = gen_otp_email_circuit_with_body("123456", body);
+            let public_input = circuit.gen_default_public_input();
+            // The total of the order is committed without being revealed.
+            assert_eq!(public_input.body_substrs, vec!["".to_string()]);
+            let (_, body_bytes, _) = circuit.canonicalized_email();
+            let start = body_bytes.windows(5).position(|window| window == b"42.50").unwrap();
+            let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+            let mut hidden_bytes = vec![0u8; default_config_params().body_config.as_ref().unwrap().max_variable_byte_size];
+            hidden_bytes[start..start + 5].copy_from_slice(b"42.50");
+            let hidden_commit = value_commit_wtns_bytes_with_domain(Some(BODY_COMMIT_DOMAIN), &sign_commit, &hidden_bytes);
+            assert_eq!(public_input.body_hidden_commits, vec![fe_to_biguint(&hidden_commit).to_str_radix(10)]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    fn gen_kyc_email_circuit(from: &str, account_number: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email_with_domain(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/from_domain_defs.json", "from_domain"),
+                ("./test_data/body_account_defs.json", "body_account"),
+            ],
+            &format!("From: {}\r\nSubject: Your statement\r\n\r\nYour account number is {}\r\nThank you.", from, account_number),
+            &["From", "Subject"],
+            "bank.com",
+        )
+    }
+
+    #[test]
+    fn test_kyc_domain_last4_preset() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/kyc_domain_last4.config"), || {
+            let (degree, circuit) = gen_kyc_email_circuit("alice@bank.com", "9876543210");
+            let public_input = circuit.gen_default_public_input();
+            // Only the commitment of the domain and the last 4 digits are exposed.
+            assert_eq!(public_input.header_substrs, vec!["".to_string()]);
+            assert_eq!(public_input.body_substrs, vec!["3210".to_string()]);
+            assert!(public_input.body_hidden_commits.is_empty());
+            assert!(public_input.header_recipient_commits.is_empty());
+            assert!(public_input.body_decoded_commits.is_empty());
+            let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+            let mut domain = b"bank.com".to_vec();
+            domain.resize(40, 0);
+            let domain_commit = value_commit_wtns_bytes_with_domain(Some(HEADER_COMMIT_DOMAIN), &sign_commit, &domain);
+            assert_eq!(public_input.header_hidden_commits, vec![fe_to_biguint(&domain_commit).to_str_radix(10)]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // The commitment of another domain is rejected.
+            let mut public_input = public_input;
+            let (_, other_circuit) = gen_kyc_email_circuit("alice@evil.com", "9876543210");
+            public_input.header_hidden_commits = other_circuit.gen_default_public_input().header_hidden_commits;
+            let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_lowercase_domain_commit() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_lowercase_domain_email_verify.config"), || {
+            let mut domain = b"bank.com".to_vec();
+            domain.resize(40, 0);
+            for from in ["alice@bank.com", "Alice@BANK.com"] {
+                let (degree, circuit) = gen_kyc_email_circuit(from, "9876543210");
+                let public_input = circuit.gen_default_public_input();
+                // Both cases are committed as the lowercase domain with the same randomness.
+                let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+                let domain_commit = value_commit_wtns_bytes_with_domain(Some(HEADER_COMMIT_DOMAIN), &sign_commit, &domain);
+                assert_eq!(public_input.header_hidden_commits, vec![fe_to_biguint(&domain_commit).to_str_radix(10)]);
+                let instances = circuit.instances();
+                let prover = MockProver::run(degree, &circuit, instances).unwrap();
+                assert_eq!(prover.verify(), Ok(()));
+            }
+        });
+    }
+
+    #[test]
+    fn test_from_domain_membership_set() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/test_domain_set_email_verify.config").unwrap()).unwrap();
+        let (degree, circuit, instances) = temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let (degree, circuit) = gen_kyc_email_circuit("alice@zkemail.com", "9876543210");
+            let public_input = circuit.gen_default_public_input();
+            // Neither the domain nor its commitment is exposed.
+            assert_eq!(public_input.header_substrs, vec!["".to_string()]);
+            assert!(public_input.header_hidden_commits.is_empty());
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances.clone()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+            (degree, circuit, instances)
+        });
+        // The same email is rejected by a set without its domain.
+        config["header_config"]["membership_sets"][0]["values"] = serde_json::json!(["bank.com", "zkemail.org", "example.org"]);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    #[test]
+    fn test_bodyhash_commit() {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("./configs/kyc_domain_last4.config").unwrap()).unwrap();
+        config["header_config"]["commit_bodyhash"] = serde_json::Value::Bool(true);
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_JSON_ENV, Some(config.to_string().as_str()), || {
+            let (degree, circuit) = gen_kyc_email_circuit("alice@bank.com", "9876543210");
+            let public_input = circuit.gen_default_public_input();
+            // The committed value is the `bh=` value, i.e., the base64 of the body hash.
+            let (_, body_bytes, _) = circuit.canonicalized_email();
+            let bodyhash = general_purpose::STANDARD.encode(Sha256::digest(&body_bytes));
+            let sign_commit = Fr::from_str_vartime(&public_input.sign_commit).unwrap();
+            let bodyhash_commit = value_commit_wtns_bytes_with_domain(Some(BODYHASH_COMMIT_DOMAIN), &sign_commit, bodyhash.as_bytes());
+            assert_eq!(public_input.header_bodyhash_commits, vec![fe_to_biguint(&bodyhash_commit).to_str_radix(10)]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            let mut public_input = public_input;
+            public_input.header_bodyhash_commits = vec!["1".to_string()];
+            let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+
+    fn smime_signed_body(content: &str) -> String {
+        format!(
+            "This is an S/MIME signed message\r\n\r\n--B\r\nContent-Type: text/plain\r\n\r\n{}\r\n--B\r\nContent-Type: application/pkcs7-signature; name=smime.p7s\r\n\r\nMIAGCSqGSIb3DQEHAqCAMIACAQEx\r\n--B--",
+            content
+        )
+    }
+
+    #[test]
+    fn test_smime_signed_content() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_smime_email_verify.config"), || {
+            let (degree, circuit) = gen_otp_email_circuit_with_body("123456", &smime_signed_body("Use 123456 to sign in."));
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.body_substrs, vec!["123456".to_string()]);
+            let instances = circuit.instances();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "the email body is not an S/MIME multipart/signed message")]
+    fn test_smime_signed_content_not_smime() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_smime_email_verify.config"), || {
+            let (_, circuit) = gen_otp_email_circuit("123456", "123456");
+            circuit.gen_default_public_input();
+        });
+    }
+
+    #[test]
+    fn test_ambiguous_regex_duplicate_match() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_otp_ambiguous_email_verify.config"), || {
+            let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/body_otp_ambiguous_defs.json").unwrap()).unwrap();
+            decomposed
                 .gen_regex_files(
-                    &Path::new("./test_data/test2_email_body_allstr.txt").to_path_buf(),
-                    &[
-                        Path::new("./test_data/test2_email_body_substr_0.txt").to_path_buf(),
-                        Path::new("./test_data/test2_email_body_substr_1.txt").to_path_buf(),
-                    ],
+                    &Path::new("./test_data/body_otp_ambiguous_allstr.txt").to_path_buf(),
+                    &[Path::new("./test_data/body_otp_ambiguous_substr_0.txt").to_path_buf()],
                 )
                 .unwrap();
-            let params = default_config_params();
-            let sign_verify_config = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
-            let mut rng = thread_rng();
-            let _private_key = RsaPrivateKey::new(&mut rng, sign_verify_config.public_key_bits).expect("failed to generate a key");
-            let public_key = rsa::RsaPublicKey::from(&_private_key);
-            let private_key = cfdkim::DkimPrivateKey::Rsa(_private_key);
-            let message = concat!(
-                "From: alice@zkemail.com\r\n",
-                "To: bob@example.com\r\n",
-                "\r\n",
-                "email was meant for @zkemailverify and halo.",
-            )
-            .as_bytes();
-            let email = parse_mail(message).unwrap();
-            let logger = slog::Logger::root(slog::Discard, slog::o!());
-            let signer = SignerBuilder::new()
-                .with_signed_headers(&["To", "From"])
-                .unwrap()
-                .with_private_key(private_key)
-                .with_selector("default")
-                .with_signing_domain("zkemail.com")
-                .with_logger(&logger)
-                .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
-                .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
-                .build()
-                .unwrap();
-            let signature = signer.sign(&email).unwrap();
-            println!("signature {}", signature);
-            let email_bytes = vec![signature.as_bytes(), b"\r\n", message].concat();
-            let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
-            let circuit = DefaultEmailVerifyCircuit::<Fr>::new(email_bytes, public_key_n);
+            let (degree, circuit) = gen_otp_email_circuit("123456", "123456 to sign in. Use 123456");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.body_substrs, vec!["123456".to_string()]);
+            let instances = circuit.instances();
+            // The synthesis fails with the logged SubstrMatchError instead of generating inconsistent commitments.
+            assert!(matches!(MockProver::run(degree, &circuit, instances), Err(Error::Synthesis)));
+        });
+    }
 
+    fn gen_order_email_circuit(subject: &str) -> (u32, DefaultEmailVerifyCircuit<Fr>) {
+        sign_test_email(
+            &[
+                ("./test_data/bodyhash_defs.json", "bodyhash"),
+                ("./test_data/subject_order_defs.json", "subject_order"),
+                ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+            ],
+            &format!("From: shop@zkemail.com\r\nSubject: {}\r\n\r\nemail was meant for @zkemailverify.", subject),
+            &["From", "Subject"],
+        )
+    }
+
+    #[test]
+    fn test_subject_template_conforming() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_order_email_verify.config"), || {
+            let (degree, circuit) = gen_order_email_circuit("Your order #12345 shipped");
+            let public_input = circuit.gen_default_public_input();
+            assert_eq!(public_input.header_substrs, vec!["12345".to_string()]);
+            let order_number: u64 = public_input.header_substrs[0].parse().unwrap();
+            assert_eq!(order_number, 12345);
             let instances = circuit.instances();
-            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
             assert_eq!(prover.verify(), Ok(()));
         });
     }
 
+    #[test]
+    fn test_subject_template_non_conforming() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_order_email_verify.config"), || {
+            let (degree, circuit) = gen_order_email_circuit("Your order #12345 was cancelled");
+            assert_eq!(circuit.gen_default_public_input().header_substrs, vec!["".to_string()]);
+            let instances = circuit.instances();
+            let failed = MockProver::run(degree, &circuit, instances).map(|prover| prover.verify().is_err()).unwrap_or(true);
+            assert!(failed);
+        });
+    }
+
+    #[test]
+    fn test_two_instance_columns() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_two_instance_columns_email_verify.config"), || {
+            let (degree, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            assert_eq!(circuit.num_instance(), vec![2, 1]);
+            let instances = circuit.instances();
+            let public_input = circuit.gen_default_public_input().instances::<Fr>();
+            assert_eq!(instances, vec![vec![public_input[0], public_input[2]], vec![public_input[1]]]);
+            let prover = MockProver::run(degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_dump_debug_witness() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test1_email_verify.config"), || {
+            let (_, circuit) = gen_forbidden_substr_email_circuit("email was meant for @zkemailverify.");
+            let dir = "./build/test_debug_witness";
+            assert!(dump_debug_witness("./configs/test1_email_verify.config", dir, &circuit).unwrap());
+            let (header_bytes, _, _) = canonicalize_signed_email(&circuit.email_bytes).unwrap();
+            let path = format!("{}/{}.json", dir, hex::encode(Sha256::digest(&header_bytes)));
+            let witness: debug_witness::EmailDebugWitness = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+            assert_eq!(witness.header_hash, witness.native_header_hash);
+            assert_eq!(witness.body_hash, witness.native_body_hash);
+            assert_eq!(witness.body_hash_base64, witness.native_body_hash_base64);
+            assert_eq!(witness.extracted_bodyhash, witness.native_body_hash_base64);
+        });
+    }
+
     #[tokio::test]
     async fn test_existing_email1() {
         let regex_bodyhash_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/bodyhash_defs.json").unwrap()).unwrap();
@@ -0,0 +1,227 @@
+use crate::helpers::{create_maybe_compressed, open_maybe_compressed};
+use halo2_base::halo2_proofs::arithmetic::g_to_lagrange;
+use halo2_base::halo2_proofs::halo2curves::bn256::{Bn256, G1Affine, G2Affine};
+use halo2_base::halo2_proofs::halo2curves::group::prime::PrimeCurveAffine;
+use halo2_base::halo2_proofs::halo2curves::group::GroupEncoding;
+use halo2_base::halo2_proofs::halo2curves::serde::SerdeObject;
+use halo2_base::halo2_proofs::poly::commitment::Params;
+use halo2_base::halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use halo2_base::halo2_proofs::SerdeFormat;
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Parse the name of a serialization format of SRS parameters given on the command line.
+///
+/// # Arguments
+/// * `name` - `raw` for [`SerdeFormat::RawBytes`], i.e., the uncompressed points, or `processed` for [`SerdeFormat::Processed`], i.e., the compressed points.
+/// # Return values
+/// Return the format, or an error message if `name` is neither of them.
+pub fn parse_format(name: &str) -> std::result::Result<SerdeFormat, String> {
+    match name {
+        "raw" => Ok(SerdeFormat::RawBytes),
+        "processed" => Ok(SerdeFormat::Processed),
+        _ => Err(format!("unknown params format {}, expected raw or processed", name)),
+    }
+}
+
+/// Return the byte sizes of a G1 point and a G2 point serialized in the given format.
+pub fn point_sizes(format: SerdeFormat) -> (usize, usize) {
+    match format {
+        SerdeFormat::Processed => (G1Affine::generator().to_bytes().as_ref().len(), G2Affine::generator().to_bytes().as_ref().len()),
+        SerdeFormat::RawBytes | SerdeFormat::RawBytesUnchecked => (G1Affine::generator().to_raw_bytes().len(), G2Affine::generator().to_raw_bytes().len()),
+    }
+}
+
+/// Deserialize a point in the given format, returning `None` if it is not a valid point on the curve.
+pub(crate) fn read_point<C: SerdeObject + GroupEncoding>(bytes: &[u8], format: SerdeFormat) -> Option<C> {
+    match format {
+        SerdeFormat::Processed => {
+            let mut repr = C::Repr::default();
+            repr.as_mut().copy_from_slice(bytes);
+            Option::from(C::from_bytes(&repr))
+        }
+        SerdeFormat::RawBytes | SerdeFormat::RawBytesUnchecked => C::read_raw(&mut &bytes[..]).ok(),
+    }
+}
+
+/// Detect the serialization format of SRS parameters from the first point, which is always the generator of G1.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters, which may be gzip-compressed.
+/// # Return values
+/// Return [`SerdeFormat::RawBytes`] or [`SerdeFormat::Processed`], or an error of [`ErrorKind::InvalidData`] if the file starts with neither of them.
+pub fn detect_format(params_path: &str) -> Result<SerdeFormat> {
+    let mut reader = open_maybe_compressed(params_path)?;
+    let raw_generator = G1Affine::generator().to_raw_bytes();
+    let processed_generator = G1Affine::generator().to_bytes();
+    // The k bytes followed by the first point.
+    let mut head = vec![0u8; 4 + raw_generator.len()];
+    reader.read_exact(&mut head)?;
+    if head[4..] == raw_generator[..] {
+        Ok(SerdeFormat::RawBytes)
+    } else if head[4..4 + processed_generator.as_ref().len()] == *processed_generator.as_ref() {
+        Ok(SerdeFormat::Processed)
+    } else {
+        Err(Error::new(ErrorKind::InvalidData, format!("{} does not start with the generator in any params format", params_path)))
+    }
+}
+
+/// Read SRS parameters in either format, which is detected by [`detect_format`].
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters, which may be gzip-compressed.
+/// # Return values
+/// Return the parameters.
+pub fn load(params_path: &str) -> Result<ParamsKZG<Bn256>> {
+    let format = detect_format(params_path)?;
+    let mut reader = open_maybe_compressed(params_path)?;
+    ParamsKZG::<Bn256>::read_custom(&mut reader, format)
+}
+
+/// Read SRS parameters, keeping only the points needed for the SRS size `k`.
+///
+/// For parameters in [`SerdeFormat::RawBytes`], the file is streamed, and only the first `2^k` G1 points, `g2`, and `s_g2` are read.
+/// The other G1 points and the lagrange points are skipped without being deserialized, and the lagrange points of the SRS size `k` are recomputed from the kept points,
+/// so that the peak memory is proportional to `2^k` rather than the size of the file.
+/// Parameters in [`SerdeFormat::Processed`] are read entirely and then downsized, since their compressed points must be decompressed in order.
+/// If `k` is not less than the SRS size of the file, the whole parameters are read.
+///
+/// # Arguments
+/// * `params_path` - a file path of the SRS parameters, which may be gzip-compressed.
+/// * `k` - the SRS size to be kept.
+/// # Return values
+/// Return the parameters whose SRS size is the minimum of `k` and that of the file.
+pub fn load_downsized(params_path: &str, k: u32) -> Result<ParamsKZG<Bn256>> {
+    let format = detect_format(params_path)?;
+    let mut reader = open_maybe_compressed(params_path)?;
+    let mut k_bytes = [0u8; 4];
+    reader.read_exact(&mut k_bytes)?;
+    let file_k = u32::from_le_bytes(k_bytes);
+    if k >= file_k {
+        return load(params_path);
+    }
+    if let SerdeFormat::Processed = format {
+        let mut params = load(params_path)?;
+        params.downsize(k);
+        return Ok(params);
+    }
+    let (g1_size, g2_size) = point_sizes(SerdeFormat::RawBytes);
+    let mut g_bytes = vec![0u8; (1usize << k) * g1_size];
+    reader.read_exact(&mut g_bytes)?;
+    // Skip the rest of the G1 points and all the lagrange points.
+    let num_skipped_bytes = ((1u64 << file_k) - (1u64 << k) + (1u64 << file_k)) * g1_size as u64;
+    if std::io::copy(&mut reader.by_ref().take(num_skipped_bytes), &mut std::io::sink())? != num_skipped_bytes {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "the params file is truncated"));
+    }
+    let mut g2_bytes = vec![0u8; 2 * g2_size];
+    reader.read_exact(&mut g2_bytes)?;
+    let g = g_bytes.chunks(g1_size).map(|mut bytes| G1Affine::read_raw(&mut bytes)).collect::<Result<Vec<_>>>()?;
+    for mut bytes in g2_bytes.chunks(g2_size) {
+        G2Affine::read_raw(&mut bytes)?;
+    }
+    let g_lagrange = g_to_lagrange::<G1Affine>(g.iter().map(|point| point.to_curve()).collect(), k);
+    let g_lagrange_bytes = g_lagrange.iter().flat_map(|point| point.to_raw_bytes()).collect::<Vec<_>>();
+    // All the points have been checked above, so the parameters of the SRS size `k` are read from their bytes without checking them again.
+    let k_bytes = k.to_le_bytes();
+    let mut downsized_reader = (&k_bytes[..]).chain(&g_bytes[..]).chain(&g_lagrange_bytes[..]).chain(&g2_bytes[..]);
+    ParamsKZG::<Bn256>::read_custom(&mut downsized_reader, SerdeFormat::RawBytesUnchecked)
+}
+
+/// Compute the keccak256 fingerprint of SRS parameters from the points that a KZG verifier uses, i.e., the first G1 point, `g2`, and `s_g2`.
+/// It depends on neither the SRS size nor the serialization format, so parameters downsized by [`load_downsized`] have the same fingerprint as the whole ones.
+///
+/// # Arguments
+/// * `params` - the SRS parameters.
+/// # Return values
+/// Return the keccak256 hash of the raw bytes of those points.
+pub fn fingerprint(params: &ParamsKZG<Bn256>) -> [u8; 32] {
+    let mut bytes = params.get_g()[0].to_raw_bytes();
+    bytes.extend(params.g2().to_raw_bytes());
+    bytes.extend(params.s_g2().to_raw_bytes());
+    ethers::utils::keccak256(bytes)
+}
+
+/// Write SRS parameters in the given format.
+/// [`SerdeFormat::Processed`] halves the file size by compressing the points, but reading it costs a square root per point.
+///
+/// # Arguments
+/// * `params_path` - a file path of the output SRS parameters.
+/// * `params` - the SRS parameters.
+/// * `format` - the serialization format. [`SerdeFormat::RawBytesUnchecked`] is written in the same bytes as [`SerdeFormat::RawBytes`].
+/// * `compress` - if `true`, the output file is gzip-compressed.
+pub fn store(params_path: &str, params: &ParamsKZG<Bn256>, format: SerdeFormat, compress: bool) -> Result<()> {
+    create_maybe_compressed(params_path, compress, |mut writer| params.write_custom(&mut writer, format))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::OsRng;
+    use std::fs;
+
+    fn params_bytes(params: &ParamsKZG<Bn256>) -> Vec<u8> {
+        let mut bytes = vec![];
+        params.write_custom(&mut bytes, SerdeFormat::RawBytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_store_load_formats() {
+        fs::create_dir_all("./build").unwrap();
+        let params = ParamsKZG::<Bn256>::setup(6, OsRng);
+        let raw_path = "./build/test_params_raw.params";
+        let processed_path = "./build/test_params_processed.params";
+        store(raw_path, &params, SerdeFormat::RawBytes, false).unwrap();
+        store(processed_path, &params, SerdeFormat::Processed, true).unwrap();
+        assert!(matches!(detect_format(raw_path).unwrap(), SerdeFormat::RawBytes));
+        assert!(matches!(detect_format(processed_path).unwrap(), SerdeFormat::Processed));
+        assert_eq!(params_bytes(&load(raw_path).unwrap()), params_bytes(&params));
+        assert_eq!(params_bytes(&load(processed_path).unwrap()), params_bytes(&params));
+
+        let mut downsized_params = params.clone();
+        downsized_params.downsize(4);
+        assert_eq!(params_bytes(&load_downsized(raw_path, 4).unwrap()), params_bytes(&downsized_params));
+        assert_eq!(params_bytes(&load_downsized(processed_path, 4).unwrap()), params_bytes(&downsized_params));
+        assert_eq!(load_downsized(processed_path, 8).unwrap().k(), 6);
+    }
+
+    #[test]
+    fn test_load_downsized() {
+        fs::create_dir_all("./build").unwrap();
+        let params = ParamsKZG::<Bn256>::setup(6, OsRng);
+        let raw_path = "./build/test_params_downsized_raw.params";
+        let processed_path = "./build/test_params_downsized_processed.params";
+        store(raw_path, &params, SerdeFormat::RawBytes, true).unwrap();
+        store(processed_path, &params, SerdeFormat::Processed, false).unwrap();
+        for k in 1..=6 {
+            let mut downsized_params = params.clone();
+            downsized_params.downsize(k);
+            assert_eq!(params_bytes(&load_downsized(raw_path, k).unwrap()), params_bytes(&downsized_params));
+            assert_eq!(params_bytes(&load_downsized(processed_path, k).unwrap()), params_bytes(&downsized_params));
+        }
+        assert_eq!(params_bytes(&load_downsized(raw_path, 8).unwrap()), params_bytes(&params));
+
+        let truncated_path = "./build/test_params_downsized_truncated.params";
+        let mut bytes = params_bytes(&params);
+        bytes.truncate(bytes.len() - 1);
+        fs::write(truncated_path, bytes).unwrap();
+        assert_eq!(load_downsized(truncated_path, 4).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        fs::create_dir_all("./build").unwrap();
+        let params = ParamsKZG::<Bn256>::setup(6, OsRng);
+        let processed_path = "./build/test_params_fingerprint.params";
+        store(processed_path, &params, SerdeFormat::Processed, false).unwrap();
+        assert_eq!(fingerprint(&load_downsized(processed_path, 4).unwrap()), fingerprint(&params));
+        assert_ne!(fingerprint(&ParamsKZG::<Bn256>::setup(6, OsRng)), fingerprint(&params));
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert!(matches!(parse_format("raw").unwrap(), SerdeFormat::RawBytes));
+        assert!(matches!(parse_format("processed").unwrap(), SerdeFormat::Processed));
+        assert!(parse_format("gzip").is_err());
+    }
+}
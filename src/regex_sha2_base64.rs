@@ -23,11 +23,23 @@ use halo2_regex::{
 };
 use sha2::{Digest, Sha256};
 
+/// The bytes size of the SHA256 hash value.
+pub const SHA256_HASH_BYTES_SIZE: usize = 32;
+/// The bytes size of the base64 encoded SHA256 hash value.
+pub const SHA256_BASE64_BYTES_SIZE: usize = 44;
+
+/// Return the bytes size of the padded base64 encoding of a hash value of `hash_len` bytes, e.g., 28 for SHA-1, 44 for SHA256, and 88 for SHA-512.
+pub fn base64_encoded_len(hash_len: usize) -> usize {
+    (hash_len + 2) / 3 * 4
+}
+
 /// Output type definition of [`RegexSha2Base64Config`].
 #[derive(Debug, Clone)]
 pub struct RegexSha2Base64Result<'a, F: PrimeField> {
     /// The output of [`AssignedRegexResult`].
     pub regex: AssignedRegexResult<'a, F>,
+    /// The assigned bytes of the SHA256 hash value constrained in [`Sha256DynamicConfig`].
+    pub hash_bytes: Vec<AssignedValue<'a, F>>,
     /// The assigned bytes of the base64 encoded SHA256 hash value constrained in [`Sha256DynamicConfig`].
     pub encoded_hash: Vec<AssignedValue<'a, F>>,
     /// The actual bytes of the base64 encoded SHA256 hash value.
@@ -41,6 +53,8 @@ pub struct RegexSha2Base64Config<F: PrimeField> {
     pub regex_sha2: RegexSha2Config<F>,
     /// Configuration for [`Base64Config`].
     pub base64_config: Base64Config<F>,
+    /// The bytes size of the hash value encoded in base64.
+    pub hash_len: usize,
 }
 
 impl<F: PrimeField> RegexSha2Base64Config<F> {
@@ -52,10 +66,18 @@ impl<F: PrimeField> RegexSha2Base64Config<F> {
     /// * `skip_prefix_bytes_size` - the bytes of the skipped input string that do not satisfy the regexes.
     /// * `range_config` - a configuration for [`RangeConfig`].
     /// * `regex_defs` - a definition of regexes that the input string must satisfy.
+    /// * `hash_len` - the bytes size of the hash value encoded in base64, e.g., [`SHA256_HASH_BYTES_SIZE`].
     ///
     /// # Return values
     /// Returns a new [`RegexSha2Base64Config`].
-    pub fn configure(meta: &mut ConstraintSystem<F>, max_byte_size: usize, skip_prefix_bytes_size: usize, range_config: RangeConfig<F>, regex_defs: Vec<RegexDefs>) -> Self {
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        max_byte_size: usize,
+        skip_prefix_bytes_size: usize,
+        range_config: RangeConfig<F>,
+        regex_defs: Vec<RegexDefs>,
+        hash_len: usize,
+    ) -> Self {
         let regex_sha2 = RegexSha2Config::configure(
             meta,
             max_byte_size,
@@ -64,8 +86,8 @@ impl<F: PrimeField> RegexSha2Base64Config<F> {
             range_config,
             regex_defs,
         );
-        let base64_config = Base64Config::configure(meta, 32);
-        Self { regex_sha2, base64_config }
+        let base64_config = Base64Config::configure(meta, hash_len);
+        Self { regex_sha2, base64_config, hash_len }
     }
 
     /// Returns a base64 encoded SHA256 hash value and extracted substrings of the input string.
@@ -86,21 +108,29 @@ impl<F: PrimeField> RegexSha2Base64Config<F> {
         let regex_sha2_result = self.regex_sha2.match_and_hash(ctx, sha256_config, input)?;
 
         let actual_hash = Sha256::digest(input).to_vec();
-        debug_assert_eq!(actual_hash.len(), 32);
+        if actual_hash.len() != self.hash_len || regex_sha2_result.hash_bytes.len() != self.hash_len {
+            return Err(Error::Synthesis);
+        }
+        let base64_len = base64_encoded_len(self.hash_len);
         let mut hash_base64 = Vec::new();
-        hash_base64.resize(44, 0);
+        hash_base64.resize(base64_len, 0);
         let bytes_written = general_purpose::STANDARD
             .encode_slice(&actual_hash, &mut hash_base64)
-            .expect("fail to convert the hash bytes into the base64 strings");
-        debug_assert_eq!(bytes_written, 44);
+            .map_err(|_| Error::Synthesis)?;
+        if bytes_written != base64_len {
+            return Err(Error::Synthesis);
+        }
         let encoded_hash = self.base64_config.encode(ctx, &sha256_config.range().gate(), &regex_sha2_result.hash_bytes)?;
         // let base64_result = self.base64_config.assign_values(&mut ctx.region, &hash_base64)?;
-        debug_assert_eq!(encoded_hash.len(), 44);
+        if encoded_hash.len() != base64_len {
+            return Err(Error::Synthesis);
+        }
         // for (assigned_hash, assigned_decoded) in regex_sha2_result.hash_bytes.into_iter().zip(base64_result.decoded.into_iter()) {
         //     ctx.region.constrain_equal(assigned_hash.cell(), assigned_decoded.cell())?;
         // }
         let result = RegexSha2Base64Result {
             regex: regex_sha2_result.regex,
+            hash_bytes: regex_sha2_result.hash_bytes,
             encoded_hash: encoded_hash,
             encoded_hash_value: hash_base64,
         };
@@ -180,7 +210,7 @@ mod test {
                     );
                     let sha256_config = Sha256DynamicConfig::configure(meta, vec![Self::MAX_BYTES_SIZE], range_config.clone(), 16, 1, false);
                     let regex_defs = $regex_defs;
-                    let inner = RegexSha2Base64Config::configure(meta, Self::MAX_BYTES_SIZE, Self::SKIP_PREFIX_BYTES_SIZE, range_config, regex_defs);
+                    let inner = RegexSha2Base64Config::configure(meta, Self::MAX_BYTES_SIZE, Self::SKIP_PREFIX_BYTES_SIZE, range_config, regex_defs, SHA256_HASH_BYTES_SIZE);
                     let hash_instance = meta.instance_column();
                     meta.enable_equality(hash_instance);
                     let masked_str_instance = meta.instance_column();
@@ -272,6 +302,15 @@ mod test {
         19
     );
 
+    #[test]
+    fn test_base64_encoded_len() {
+        for hash_len in [20, SHA256_HASH_BYTES_SIZE, 64] {
+            let encoded = general_purpose::STANDARD.encode(vec![0xffu8; hash_len]);
+            assert_eq!(base64_encoded_len(hash_len), encoded.len());
+        }
+        assert_eq!(base64_encoded_len(SHA256_HASH_BYTES_SIZE), SHA256_BASE64_BYTES_SIZE);
+    }
+
     #[test]
     fn test_regex_sha2_base64_valid_case1() {
         let regex_from_decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open("./test_data/from_defs.json").unwrap()).unwrap();
@@ -0,0 +1,232 @@
+use crate::*;
+use halo2_base::halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_base::halo2_proofs::plonk::{Circuit, ConstraintSystem, Error};
+use halo2_base::{utils::PrimeField, SKIP_FIRST_PASS};
+
+/// Circuit to verify that two emails are signed with the same RSA public key, i.e., they come from the same sender domain.
+///
+/// Each email is verified in the same way as [`DefaultEmailVerifyCircuit`].
+/// The hash of the public key is not exposed, because anyone could hash the published DKIM keys of candidate domains and find the domain.
+/// Instead, the hash of each email is blinded with the same prover-chosen salt as poseidon(public_key_hash, key_salt), and both blinded hashes are constrained to one instance.
+/// The instance column contains the blinded key hash, followed by the signature commitment and the random linear combination of each email.
+#[derive(Debug, Clone)]
+pub struct SameSenderCircuit<F: PrimeField> {
+    /// Circuits of the two emails.
+    pub emails: [DefaultEmailVerifyCircuit<F>; 2],
+    /// A secret random value chosen by the prover to blind the public key hash. The same salt links other proofs of the same key.
+    pub key_salt: F,
+}
+
+impl<F: PrimeField> Circuit<F> for SameSenderCircuit<F> {
+    type Config = DefaultEmailVerifyConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            emails: [self.emails[0].without_witnesses(), self.emails[1].without_witnesses()],
+            key_salt: F::zero(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        DefaultEmailVerifyCircuit::configure_native_for_emails(meta, 2)
+    }
+
+    fn synthesize(&self, mut config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        config.sha256_config.range().load_lookup_table(&mut layouter)?;
+        config.sha256_config.load(&mut layouter)?;
+        config.header_config.load(&mut layouter)?;
+        config.body_config.load(&mut layouter)?;
+        let mut first_pass = SKIP_FIRST_PASS;
+        let mut blinded_key_hash_cells = vec![];
+        let mut email_cells = vec![];
+        let canonicalized_emails = self
+            .emails
+            .iter()
+            .map(|email| email.canonicalized_email())
+            .collect_vec();
+
+        layouter.assign_region(
+            || "zkemail_same_sender",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let ctx = &mut config.sha256_config.new_context(region);
+                let range = config.sha256_config.range().clone();
+                let gate = range.gate.clone();
+                let key_salt = gate.assign_witnesses(ctx, vec![Value::known(self.key_salt)]).pop().unwrap();
+                for (email, (header_bytes, body_bytes, signature_bytes)) in self.emails.iter().zip(canonicalized_emails.iter()) {
+                    // The DKIM timestamp and the predicate thresholds are not exposed by this circuit.
+                    let (sign_commit, public_key_n_hash, rlc, _, _) = DefaultEmailVerifyCircuit::assign_email(
+                        ctx,
+                        &mut config,
+                        header_bytes,
+                        body_bytes,
+                        signature_bytes,
+                        &email.public_key_n,
+                        email.debug_witness_dir.as_deref(),
+                    )?;
+                    // Both emails are blinded with the same salt cell, so equal blinded hashes imply equal public key hashes.
+                    let poseidon = PoseidonChipBn254_8_58::new(ctx, &gate);
+                    let blinded_key_hash = poseidon.hash_elements(ctx, &gate, &[public_key_n_hash, key_salt.clone()]).unwrap().0[0].clone();
+                    blinded_key_hash_cells.push(blinded_key_hash.cell());
+                    email_cells.push(sign_commit.cell());
+                    email_cells.push(rlc.cell());
+                }
+                range.finalize(ctx);
+                Ok(())
+            },
+        )?;
+        // Both blinded public key hashes are constrained to the same instance, which asserts that they are equal.
+        let num_columns = config.instances.len();
+        for cell in blinded_key_hash_cells.into_iter() {
+            let (column, row) = instance_position(0, num_columns);
+            layouter.constrain_instance(cell, config.instances[column], row)?;
+        }
+        for (idx, cell) in email_cells.into_iter().enumerate() {
+            let (column, row) = instance_position(1 + idx, num_columns);
+            layouter.constrain_instance(cell, config.instances[column], row)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> CircuitExt<F> for SameSenderCircuit<F> {
+    fn num_instance(&self) -> Vec<usize> {
+        split_instances(&[(); 5], num_instance_columns()).iter().map(|column| column.len()).collect()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        let mut instances = vec![];
+        for (idx, email) in self.emails.iter().enumerate() {
+            let email_instances = email.gen_default_public_input().instances::<F>();
+            if idx == 0 {
+                instances.push(blind_public_key_hash(email_instances[1], self.key_salt));
+            }
+            instances.push(email_instances[0]);
+            instances.push(email_instances[2]);
+        }
+        split_instances(&instances, num_instance_columns())
+    }
+}
+
+impl<F: PrimeField> SameSenderCircuit<F> {
+    /// Create a new [`SameSenderCircuit`].
+    /// # Arguments
+    /// * `email1` - a circuit of the first email.
+    /// * `email2` - a circuit of the second email.
+    /// * `key_salt` - a secret random value to blind the public key hash, e.g., sampled with `F::random(OsRng)`.
+    ///
+    /// # Return values
+    /// Return a new [`SameSenderCircuit`].
+    pub fn new(email1: DefaultEmailVerifyCircuit<F>, email2: DefaultEmailVerifyCircuit<F>, key_salt: F) -> Self {
+        Self {
+            emails: [email1, email2],
+            key_salt,
+        }
+    }
+}
+
+/// Compute the blinded public key hash exposed by [`SameSenderCircuit`], i.e., poseidon(public_key_hash, key_salt).
+///
+/// # Arguments
+/// * `public_key_hash` - the poseidon hash of the `n` parameter in the RSA public key.
+/// * `key_salt` - the salt chosen by the prover.
+/// # Return values
+/// Return the blinded public key hash.
+pub fn blind_public_key_hash<F: PrimeField>(public_key_hash: F, key_salt: F) -> F {
+    poseidon_hash_fields(&[public_key_hash, key_salt])
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cfdkim::SignerBuilder;
+    use halo2_base::halo2_proofs::{arithmetic::Field, dev::MockProver, halo2curves::bn256::Fr};
+    use halo2_regex::vrm::DecomposedRegexConfig;
+    use mailparse::parse_mail;
+    use rand::{rngs::OsRng, thread_rng};
+    use rsa::{traits::PublicKeyParts, RsaPrivateKey};
+    use std::path::Path;
+
+    fn gen_regex_files() {
+        for (defs_path, prefix) in [
+            ("./test_data/bodyhash_defs.json", "bodyhash"),
+            ("./test_data/from_defs.json", "from"),
+            ("./test_data/test1_email_body_defs.json", "test1_email_body"),
+        ] {
+            let decomposed: DecomposedRegexConfig = serde_json::from_reader(File::open(defs_path).unwrap()).unwrap();
+            decomposed
+                .gen_regex_files(
+                    &Path::new(&format!("./test_data/{}_allstr.txt", prefix)).to_path_buf(),
+                    &[Path::new(&format!("./test_data/{}_substr_0.txt", prefix)).to_path_buf()],
+                )
+                .unwrap();
+        }
+    }
+
+    fn gen_email_circuit(private_key: &RsaPrivateKey, from: &str) -> DefaultEmailVerifyCircuit<Fr> {
+        let public_key = rsa::RsaPublicKey::from(private_key);
+        let message = format!("From: {}\r\n\r\nemail was meant for @zkemailverify.", from);
+        let email = parse_mail(message.as_bytes()).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key.clone()))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message.as_bytes()].concat();
+        let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+        DefaultEmailVerifyCircuit::new(email_bytes, public_key_n)
+    }
+
+    #[test]
+    fn test_same_sender() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_same_sender_email_verify.config"), || {
+            gen_regex_files();
+            let params = default_config_params();
+            let public_key_bits = params.sign_verify_config.as_ref().unwrap().public_key_bits;
+            let private_key = RsaPrivateKey::new(&mut thread_rng(), public_key_bits).expect("failed to generate a key");
+            let email1 = gen_email_circuit(&private_key, "alice@zkemail.com");
+            let email2 = gen_email_circuit(&private_key, "bob@zkemail.com");
+            let public_key_hash = email1.gen_default_public_input().instances::<Fr>()[1];
+            let circuit = SameSenderCircuit::new(email1, email2, Fr::random(OsRng));
+            let instances = circuit.instances();
+            // Neither the public key hash nor the blinded hash with another salt appears in the instances.
+            let other_salt = SameSenderCircuit::new(circuit.emails[0].clone(), circuit.emails[1].clone(), Fr::random(OsRng));
+            assert_ne!(instances[0][0], public_key_hash);
+            assert_ne!(instances[0][0], other_salt.instances()[0][0]);
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_different_senders() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/test_same_sender_email_verify.config"), || {
+            gen_regex_files();
+            let params = default_config_params();
+            let public_key_bits = params.sign_verify_config.as_ref().unwrap().public_key_bits;
+            let private_key1 = RsaPrivateKey::new(&mut thread_rng(), public_key_bits).expect("failed to generate a key");
+            let private_key2 = RsaPrivateKey::new(&mut thread_rng(), public_key_bits).expect("failed to generate a key");
+            let circuit = SameSenderCircuit::new(
+                gen_email_circuit(&private_key1, "alice@zkemail.com"),
+                gen_email_circuit(&private_key2, "bob@zkemail.org"),
+                Fr::random(OsRng),
+            );
+            let instances = circuit.instances();
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+}
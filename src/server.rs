@@ -0,0 +1,264 @@
+use crate::dkim::DkimOptions;
+use crate::helpers::ProverContext;
+use crate::{DefaultEmailVerifyCircuit, DefaultEmailVerifyPublicInput};
+use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// The maximum byte size of the request body, i.e., the email, accepted by [`serve`].
+pub const MAX_REQUEST_BODY_BYTES: usize = 1 << 20;
+
+/// The time within which [`serve`] must receive the whole request, including its body, before it answers `408`.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The response of the `/prove` endpoint.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ProveResponse {
+    /// A hex string of the proof, whose decoded bytes are accepted by [`verify`](crate::helpers::verify).
+    pub proof: String,
+    /// The public input of the proof.
+    pub public_input: DefaultEmailVerifyPublicInput,
+}
+
+/// An HTTP request read by [`read_request`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// The request method, e.g., `POST`.
+    pub method: String,
+    /// The request path, e.g., `/prove`.
+    pub path: String,
+    /// The request body.
+    pub body: Vec<u8>,
+}
+
+/// Serve the `POST /prove` endpoint, which takes the raw email bytes as the request body and returns [`ProveResponse`] as json.
+/// The requests are handled one by one, since a proof generation already uses all the cores.
+/// A client that does not send the whole request within [`REQUEST_TIMEOUT`] is answered with `408`, so that it does not block the other requests.
+/// The RSA public key is resolved in the same way as [`DefaultEmailVerifyCircuit::try_gen_circuit_from_email_bytes_with_pinned_keys`] without pinned keys,
+/// but an invalid email is answered with an error response instead of a panic.
+///
+/// # Arguments
+/// * `addr` - an address to listen on, e.g., `127.0.0.1:8080`.
+/// * `context` - the SRS parameters and the proving key loaded in advance.
+/// * `use_embedded_key` - a flag whether the public keys embedded in the email are used if present.
+/// * `dkim_options` - the options of selecting the DKIM signature and resolving its public key from DNS.
+pub async fn serve(addr: &str, context: ProverContext, use_embedded_key: bool, dkim_options: DkimOptions) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    serve_listener(listener, context, use_embedded_key, dkim_options).await
+}
+
+/// Serve the `POST /prove` endpoint on a bound listener in the same way as [`serve`], e.g., on port 0 to let the OS choose a free port.
+///
+/// # Arguments
+/// * `listener` - a bound listener.
+/// * `context` - the SRS parameters and the proving key loaded in advance.
+/// * `use_embedded_key` - a flag whether the public keys embedded in the email are used if present.
+/// * `dkim_options` - the options of selecting the DKIM signature and resolving its public key from DNS.
+pub async fn serve_listener(listener: TcpListener, context: ProverContext, use_embedded_key: bool, dkim_options: DkimOptions) -> std::io::Result<()> {
+    log::info!("listening on {}", listener.local_addr()?);
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let (reader, mut writer) = stream.split();
+        let (status, body) = match read_request_with_timeout(&mut BufReader::new(reader), REQUEST_TIMEOUT).await {
+            Ok(request) => handle_request(request, &context, use_embedded_key, &dkim_options).await,
+            Err(response) => response,
+        };
+        let header = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            reason_phrase(status),
+            body.len()
+        );
+        if let Err(err) = async {
+            writer.write_all(header.as_bytes()).await?;
+            writer.write_all(body.as_bytes()).await?;
+            writer.flush().await
+        }
+        .await
+        {
+            log::error!("failed to send the response: {}", err);
+        }
+    }
+}
+
+// Read a request as [`read_request`] within the timeout, and return the status code and the json body of the error response if it fails.
+async fn read_request_with_timeout<R: AsyncBufRead + Unpin>(reader: &mut R, timeout: Duration) -> Result<HttpRequest, (u16, String)> {
+    match tokio::time::timeout(timeout, read_request(reader)).await {
+        Ok(Ok(request)) => Ok(request),
+        Ok(Err(err)) => Err((400, error_json(&err))),
+        Err(_) => Err((408, error_json(&format!("the request is not received within {} ms", timeout.as_millis())))),
+    }
+}
+
+/// Read an HTTP/1.1 request whose body length is given by the `Content-Length` header.
+///
+/// # Arguments
+/// * `reader` - a reader of the request.
+/// # Return values
+/// Return the [`HttpRequest`], or an error message if the request is malformed or its body exceeds [`MAX_REQUEST_BODY_BYTES`].
+pub async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<HttpRequest, String> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await.map_err(|err| err.to_string())?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(format!("malformed request line {:?}", request_line.trim_end())),
+    };
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.map_err(|err| err.to_string())? == 0 {
+            return Err("the request ends before the end of the headers".to_string());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().map_err(|_| format!("invalid Content-Length {}", value.trim()))?;
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_BODY_BYTES {
+        return Err(format!("the request body of {} bytes exceeds {} bytes", content_length, MAX_REQUEST_BODY_BYTES));
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await.map_err(|err| err.to_string())?;
+    Ok(HttpRequest { method, path, body })
+}
+
+/// Return the status code and the json body of the response to the request.
+async fn handle_request(request: HttpRequest, context: &ProverContext, use_embedded_key: bool, dkim_options: &DkimOptions) -> (u16, String) {
+    if request.path != "/prove" {
+        return (404, error_json(&format!("{} is not found", request.path)));
+    }
+    if request.method != "POST" {
+        return (405, error_json("only POST is allowed"));
+    }
+    let circuit = match DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_pinned_keys(request.body, use_embedded_key, &[], dkim_options).await {
+        Ok((circuit, _)) => circuit,
+        Err(err) => return (400, error_json(&err.to_string())),
+    };
+    // The circuit panics if the email does not fit in the configured sizes.
+    let result = tokio::task::block_in_place(|| {
+        catch_unwind(AssertUnwindSafe(|| {
+            let public_input = circuit.gen_default_public_input();
+            let proof = context.prove(circuit, false);
+            (proof, public_input)
+        }))
+    });
+    match result {
+        Ok((proof, public_input)) => {
+            let response = ProveResponse {
+                proof: format!("0x{}", hex::encode(proof)),
+                public_input,
+            };
+            (200, serde_json::to_string(&response).unwrap())
+        }
+        Err(_) => (500, error_json("failed to generate a proof for the email")),
+    }
+}
+
+fn error_json(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config_params::default_config_params;
+    use crate::helpers::{gen_keys, gen_params, gen_regex_files};
+    use crate::EMAIL_VERIFY_CONFIG_ENV;
+    use halo2_base::halo2_proofs::SerdeFormat;
+    use snark_verifier_sdk::CircuitExt;
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn test_read_request() {
+        let raw = b"POST /prove HTTP/1.1\r\nHost: localhost\r\ncontent-length: 11\r\n\r\nFrom: alice";
+        let request = read_request(&mut BufReader::new(&raw[..])).await.unwrap();
+        assert_eq!(
+            request,
+            HttpRequest {
+                method: "POST".to_string(),
+                path: "/prove".to_string(),
+                body: b"From: alice".to_vec()
+            }
+        );
+        let raw = format!("POST /prove HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_REQUEST_BODY_BYTES + 1);
+        assert!(read_request(&mut BufReader::new(raw.as_bytes())).await.is_err());
+        assert!(read_request(&mut BufReader::new(&b"POST /prove HTTP/1.1\r\n"[..])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_request_timeout() {
+        // A client that sends the Content-Length header without the body is answered with 408.
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(b"POST /prove HTTP/1.1\r\nContent-Length: 11\r\n\r\n").await.unwrap();
+        let (status, _) = read_request_with_timeout(&mut BufReader::new(server), Duration::from_millis(10)).await.unwrap_err();
+        assert_eq!(status, 408);
+        let (status, _) = read_request_with_timeout(&mut BufReader::new(&b"POST /prove HTTP/1.1\r\n"[..]), Duration::from_millis(10)).await.unwrap_err();
+        assert_eq!(status, 400);
+    }
+
+    #[ignore]
+    #[test]
+    fn test_serve_prove() {
+        let circuit_config_path = "./configs/app_bench.config";
+        let params_path = "./build/test_serve.params";
+        let pk_path = "./build/test_serve.pk";
+        let vk_path = "./build/test_serve.vk";
+        for (defs_path, prefix) in [
+            ("./test_data/bodyhash_defs.json", "body_hash"),
+            ("./test_data/from_defs.json", "from"),
+            ("./test_data/to_defs.json", "to"),
+            ("./test_data/subject_defs.json", "subject"),
+            ("./test_data/test_ex1_email_body_defs.json", "test_ex1_email_body"),
+        ] {
+            gen_regex_files(defs_path, "./test_data", prefix).unwrap();
+        }
+        let email_bytes = std::fs::read("./test_data/test_email1.eml").unwrap();
+        std::fs::create_dir_all("./build").unwrap();
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some(circuit_config_path), || {
+            // The runtime is created inside the closure so that the server reads the configuration; `block_in_place` requires the multi-threaded one.
+            let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let circuit = DefaultEmailVerifyCircuit::<Fr>::try_gen_circuit_from_email_bytes_with_embedded_key(email_bytes.clone(), false).await.unwrap();
+                let instances = circuit.instances();
+                gen_params(params_path, default_config_params().degree, SerdeFormat::RawBytes, false).unwrap();
+                gen_keys(params_path, circuit_config_path, pk_path, vk_path, circuit, false).unwrap();
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = listener.local_addr().unwrap();
+                let context = ProverContext::new::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, pk_path);
+                tokio::spawn(serve_listener(listener, context, false, DkimOptions::default()));
+
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream.write_all(format!("POST /prove HTTP/1.1\r\nContent-Length: {}\r\n\r\n", email_bytes.len()).as_bytes()).await.unwrap();
+                stream.write_all(&email_bytes).await.unwrap();
+                let mut response = vec![];
+                stream.read_to_end(&mut response).await.unwrap();
+                assert!(response.starts_with(b"HTTP/1.1 200 OK\r\n"));
+                let body_start = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+                let response: ProveResponse = serde_json::from_slice(&response[body_start..]).unwrap();
+                let proof = hex::decode(response.proof.trim_start_matches("0x")).unwrap();
+                let context = ProverContext::new::<DefaultEmailVerifyCircuit<Fr>>(params_path, circuit_config_path, pk_path);
+                assert!(context.verify(&proof, &instances).unwrap());
+            });
+        });
+    }
+}
@@ -0,0 +1,218 @@
+use crate::*;
+use halo2_base::halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_base::halo2_proofs::plonk::{Circuit, Column, ConstraintSystem, Error, Instance};
+use halo2_base::{utils::PrimeField, SKIP_FIRST_PASS};
+
+/// Configuration for [`SignOnlyCircuit`].
+#[derive(Debug, Clone)]
+pub struct SignOnlyConfig<F: PrimeField> {
+    /// Configuration for [`Sha256DynamicConfig`].
+    pub sha256_config: Sha256DynamicConfig<F>,
+    /// Configuration for [`SignVerifyConfig`].
+    pub sign_verify_config: SignVerifyConfig<F>,
+    /// Instance column.
+    pub instances: Column<Instance>,
+}
+
+/// Circuit to prove only that the email header has a valid DKIM signature, revealing nothing about the email contents.
+///
+/// Unlike [`DefaultEmailVerifyCircuit`], it neither verifies the body hash nor matches any regex, so it is much smaller.
+/// The signing domain is identified by the hash of its RSA public key, which the verifier compares with the hash of the public key registered in DNS.
+/// The instance column contains the signature commitment and the hash of the public key.
+///
+/// It requires only `sha256_config` and `sign_verify_config` in the configuration parameters.
+/// The maximum header size is given by `sign_verify_config.max_header_bytes_size`, or `header_config.max_variable_byte_size` if omitted.
+#[derive(Debug, Clone)]
+pub struct SignOnlyCircuit<F: PrimeField> {
+    /// The bytes of the email.
+    pub email_bytes: Vec<u8>,
+    /// The `n` parameter of the RSA public key.
+    pub public_key_n: BigUint,
+    _f: PhantomData<F>,
+}
+
+impl<F: PrimeField> Circuit<F> for SignOnlyCircuit<F> {
+    type Config = SignOnlyConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            email_bytes: vec![],
+            public_key_n: self.public_key_n.clone(),
+            _f: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let params = default_config_params();
+        let range_config = RangeConfig::configure(
+            meta,
+            Vertical,
+            &[params.num_flex_advice],
+            &[params.num_range_lookup_advice],
+            params.num_flex_fixed,
+            params.range_lookup_bits,
+            0,
+            params.degree as usize,
+        );
+        let sign_verify_params = params.sign_verify_config.as_ref().expect("sign_verify_config is required");
+        let sha256_params = params.sha256_config.as_ref().expect("sha256_config is required");
+        let sha256_config = Sha256DynamicConfig::configure(
+            meta,
+            vec![
+                vec![Self::max_header_bytes_size()],
+                SignVerifyConfig::<F>::sha256_max_byte_sizes(sign_verify_params.public_key_bits, sign_verify_params.pss_salt_len),
+            ]
+            .concat(),
+            range_config.clone(),
+            sha256_params.num_bits_lookup,
+            sha256_params.num_advice_columns,
+            false,
+        );
+        let sign_verify_config = SignVerifyConfig::configure(range_config, sign_verify_params.public_key_bits, sign_verify_params.pss_salt_len);
+        let instances = meta.instance_column();
+        meta.enable_equality(instances);
+        SignOnlyConfig {
+            sha256_config,
+            sign_verify_config,
+            instances,
+        }
+    }
+
+    fn synthesize(&self, mut config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        config.sha256_config.range().load_lookup_table(&mut layouter)?;
+        config.sha256_config.load(&mut layouter)?;
+        let mut first_pass = SKIP_FIRST_PASS;
+        let mut public_hash_cell = vec![];
+        let (header_bytes, _, signature_bytes) = canonicalize_signed_email(&self.email_bytes).unwrap();
+
+        layouter.assign_region(
+            || "zkemail_sign_only",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let ctx = &mut config.sha256_config.new_context(region);
+                let range = config.sha256_config.range().clone();
+                let gate = range.gate.clone();
+                let header_hash = config.sha256_config.digest(ctx, &header_bytes, None)?;
+                let e = RSAPubE::Fix(BigUint::from(DefaultEmailVerifyCircuit::<F>::DEFAULT_E));
+                let public_key = RSAPublicKey::<F>::new(Value::known(self.public_key_n.clone()), e);
+                let signature = RSASignature::<F>::new(Value::known(BigUint::from_bytes_be(&signature_bytes)));
+                let (assigned_public_key, assigned_signature) =
+                    config
+                        .sign_verify_config
+                        .verify_signature(ctx, &mut config.sha256_config, &header_hash.output_bytes, public_key, signature)?;
+                let poseidon = PoseidonChipBn254_8_58::new(ctx, &gate);
+                let sign_commit = poseidon.hash_elements(ctx, &gate, &assigned_signature.c.limbs()).unwrap().0[0].clone();
+                let public_key_n_hash = poseidon.hash_elements(ctx, &gate, &assigned_public_key.n.limbs()).unwrap().0[0].clone();
+                public_hash_cell.push(sign_commit.cell());
+                public_hash_cell.push(public_key_n_hash.cell());
+                range.finalize(ctx);
+                Ok(())
+            },
+        )?;
+        for (idx, cell) in public_hash_cell.into_iter().enumerate() {
+            layouter.constrain_instance(cell, config.instances, idx)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> CircuitExt<F> for SignOnlyCircuit<F> {
+    fn num_instance(&self) -> Vec<usize> {
+        vec![2]
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        let (_, _, signature_bytes) = canonicalize_signed_email(&self.email_bytes).unwrap();
+        let config_params = default_config_params();
+        let num_limbs = config_params.sign_verify_config.as_ref().unwrap().public_key_bits / LIMB_BITS;
+        let sign_commit: F = poseidon_hash_fields(&decompose_biguint(&BigUint::from_bytes_be(&signature_bytes), num_limbs, LIMB_BITS));
+        let public_key_hash: F = poseidon_hash_fields(&decompose_biguint(&self.public_key_n, num_limbs, LIMB_BITS));
+        vec![vec![sign_commit, public_key_hash]]
+    }
+}
+
+impl<F: PrimeField> SignOnlyCircuit<F> {
+    /// Create a new [`SignOnlyCircuit`].
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    /// * `public_key_n` - `n` parameter of the RSA public key.
+    ///
+    /// # Return values
+    /// Return a new [`SignOnlyCircuit`].
+    pub fn new(email_bytes: Vec<u8>, public_key_n: BigUint) -> Self {
+        Self {
+            email_bytes,
+            public_key_n,
+            _f: PhantomData,
+        }
+    }
+
+    pub(crate) fn max_header_bytes_size() -> usize {
+        let params = default_config_params();
+        params
+            .sign_verify_config
+            .as_ref()
+            .and_then(|sign_verify_params| sign_verify_params.max_header_bytes_size)
+            .or_else(|| params.header_config.as_ref().map(|header_params| header_params.max_variable_byte_size))
+            .expect("sign_verify_config.max_header_bytes_size or header_config is required")
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cfdkim::SignerBuilder;
+    use halo2_base::halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
+    use mailparse::parse_mail;
+    use rand::thread_rng;
+    use rsa::{traits::PublicKeyParts, RsaPrivateKey};
+
+    fn gen_sign_only_circuit(tamper: bool) -> (u32, SignOnlyCircuit<Fr>) {
+        let params = default_config_params();
+        let public_key_bits = params.sign_verify_config.as_ref().unwrap().public_key_bits;
+        let private_key = RsaPrivateKey::new(&mut thread_rng(), public_key_bits).expect("failed to generate a key");
+        let public_key = rsa::RsaPublicKey::from(&private_key);
+        let message = "From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = parse_mail(message.as_bytes()).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        let message = if tamper { message.replace("alice", "bob__") } else { message.to_string() };
+        let email_bytes = vec![signature.as_bytes(), b"\r\n", message.as_bytes()].concat();
+        let public_key_n = BigUint::from_bytes_be(&public_key.n().clone().to_bytes_be());
+        (params.degree, SignOnlyCircuit::new(email_bytes, public_key_n))
+    }
+
+    #[test]
+    fn test_sign_only() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/sign_only.config"), || {
+            let (degree, circuit) = gen_sign_only_circuit(false);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_sign_only_tampered_header() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/sign_only.config"), || {
+            let (degree, circuit) = gen_sign_only_circuit(true);
+            let prover = MockProver::run(degree, &circuit, circuit.instances()).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+}
@@ -8,15 +8,21 @@ use halo2_base::{
 use halo2_base::{AssignedValue, QuantumCell};
 use halo2_dynamic_sha256::Sha256DynamicConfig;
 use halo2_rsa::{AssignedRSAPublicKey, AssignedRSASignature, RSAConfig, RSAInstructions, RSAPubE, RSAPublicKey, RSASignature};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
 
 /// Configuration to verify the RSA signature.
 #[derive(Debug, Clone)]
 pub struct SignVerifyConfig<F: PrimeField> {
     /// Configuration for [`RSAConfig`].
     pub rsa_config: RSAConfig<F>,
+    /// The salt length in bytes of the RSASSA-PSS padding, or `None` for the PKCS#1 v1.5 padding.
+    pub pss_salt_len: Option<usize>,
 }
 
 pub const LIMB_BITS: usize = 64;
+/// The byte size of the SHA-256 hash used in the RSASSA-PSS padding and its mask generation function MGF1.
+pub const PSS_HASH_BYTES_SIZE: usize = 32;
 
 impl<F: PrimeField> SignVerifyConfig<F> {
     /// Construct a new [`SignVerifyConfig`].
@@ -24,12 +30,44 @@ impl<F: PrimeField> SignVerifyConfig<F> {
     /// # Arguments
     /// * `range_config` - a configuration for [`RangeConfig`].
     /// * `public_key_bits` - the number of bits of RSA public key.
+    /// * `pss_salt_len` - the salt length in bytes if the signature has the RSASSA-PSS padding with SHA-256 and MGF1-SHA-256, or `None` for the PKCS#1 v1.5 padding.
     /// # Return values
     /// Return a new [`SignVerifyConfig`].
-    pub fn configure(range_config: RangeConfig<F>, public_key_bits: usize) -> Self {
+    pub fn configure(range_config: RangeConfig<F>, public_key_bits: usize, pss_salt_len: Option<usize>) -> Self {
+        if let Some(salt_len) = pss_salt_len {
+            assert!(
+                public_key_bits / 8 >= PSS_HASH_BYTES_SIZE + salt_len + 2,
+                "the PSS salt of {} bytes is too long for the {} bits RSA public key",
+                salt_len,
+                public_key_bits
+            );
+        }
         let biguint_config = halo2_rsa::BigUintConfig::construct(range_config, LIMB_BITS);
         let rsa_config = RSAConfig::construct(biguint_config, public_key_bits, 5);
-        Self { rsa_config }
+        Self { rsa_config, pss_salt_len }
+    }
+
+    /// Return the maximum byte sizes of the SHA-256 inputs hashed by [`SignVerifyConfig::verify_signature`], which must follow those of the other hashes in [`Sha256DynamicConfig`].
+    /// For the RSASSA-PSS padding, they are the MGF1 blocks of the mask followed by the salted message hash. For the PKCS#1 v1.5 padding, nothing is hashed.
+    ///
+    /// # Arguments
+    /// * `public_key_bits` - the number of bits of RSA public key, which must be a multiple of 8 for the RSASSA-PSS padding.
+    /// * `pss_salt_len` - the salt length in bytes of the RSASSA-PSS padding, or `None` for the PKCS#1 v1.5 padding.
+    /// # Return values
+    /// Return the maximum byte sizes in the order of the hashes.
+    pub fn sha256_max_byte_sizes(public_key_bits: usize, pss_salt_len: Option<usize>) -> Vec<usize> {
+        match pss_salt_len {
+            Some(salt_len) => {
+                // The SHA-256 padding adds at least 9 bytes to the input.
+                let padded_size = |input_size: usize| (input_size + 9 + 63) / 64 * 64;
+                let db_len = public_key_bits / 8 - PSS_HASH_BYTES_SIZE - 1;
+                let num_mask_blocks = (db_len + PSS_HASH_BYTES_SIZE - 1) / PSS_HASH_BYTES_SIZE;
+                let mut sizes = vec![padded_size(PSS_HASH_BYTES_SIZE + 4); num_mask_blocks];
+                sizes.push(padded_size(8 + PSS_HASH_BYTES_SIZE + salt_len));
+                sizes
+            }
+            None => vec![],
+        }
     }
 
     /// Assign the given RSA public key.
@@ -55,9 +93,11 @@ impl<F: PrimeField> SignVerifyConfig<F> {
     }
 
     /// Verify the given RSA signature with the given RSA public key and the given assgined bytes.
+    /// If `pss_salt_len` is set, the signature is verified with the RSASSA-PSS padding, whose hashes are computed with `sha256_config`.
     ///
     /// # Arguments
     /// * `ctx` - a region context.
+    /// * `sha256_config` - a configuration for [`Sha256DynamicConfig`] with the maximum byte sizes of [`SignVerifyConfig::sha256_max_byte_sizes`] left.
     /// * `hash_bytes` - a list of the assigned bytes.
     /// * `public_key` - an RSA public key.
     /// * `signature` - an RSA signature.
@@ -68,10 +108,23 @@ impl<F: PrimeField> SignVerifyConfig<F> {
     pub fn verify_signature<'v: 'a, 'a>(
         &self,
         ctx: &mut Context<'v, F>,
+        sha256_config: &mut Sha256DynamicConfig<F>,
         hash_bytes: &[AssignedValue<'v, F>],
         public_key: RSAPublicKey<F>,
         signature: RSASignature<F>,
     ) -> Result<(AssignedRSAPublicKey<'a, F>, AssignedRSASignature<'a, F>), Error> {
+        if let Some(salt_len) = self.pss_salt_len {
+            let e = match &public_key.e {
+                RSAPubE::Fix(e) => e.clone(),
+                RSAPubE::Var(_) => panic!("the PSS padding requires a fixed public exponent"),
+            };
+            let mut encoded_message = None;
+            public_key.n.as_ref().zip(signature.c.as_ref()).map(|(n, c)| encoded_message = Some(c.modpow(&e, n)));
+            let public_key = self.rsa_config.assign_public_key(ctx, public_key)?;
+            let signature = self.rsa_config.assign_signature(ctx, signature)?;
+            self.verify_pss_padding(ctx, sha256_config, hash_bytes, &public_key, &signature, encoded_message, salt_len)?;
+            return Ok((public_key, signature));
+        }
         let gate = self.rsa_config.gate();
         let mut hash_bytes = hash_bytes.to_vec();
         hash_bytes.reverse();
@@ -98,4 +151,108 @@ impl<F: PrimeField> SignVerifyConfig<F> {
 
         Ok((public_key, signature))
     }
+
+    /// Verify the RSASSA-PSS padding of the given signature as in EMSA-PSS-VERIFY of RFC 8017, where the encoded message has `public_key_bits - 1` bits.
+    fn verify_pss_padding<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        sha256_config: &mut Sha256DynamicConfig<F>,
+        hash_bytes: &[AssignedValue<'v, F>],
+        public_key: &AssignedRSAPublicKey<'v, F>,
+        signature: &AssignedRSASignature<'v, F>,
+        encoded_message: Option<BigUint>,
+        salt_len: usize,
+    ) -> Result<(), Error> {
+        let gate = self.rsa_config.gate();
+        let em = self.rsa_config.modpow_public_key(ctx, &signature.c, public_key)?;
+        // The bits of each big-endian byte of the encoded message in little-endian order.
+        let em_bits = em.limbs().iter().flat_map(|limb| gate.num_to_bits(ctx, limb, LIMB_BITS)).collect::<Vec<_>>();
+        let em_len = em_bits.len() / 8;
+        let em_byte_bits = (0..em_len).map(|idx| em_bits[8 * (em_len - 1 - idx)..8 * (em_len - idx)].to_vec()).collect::<Vec<_>>();
+        let db_len = em_len - PSS_HASH_BYTES_SIZE - 1;
+        let ps_len = db_len - salt_len - 1;
+        let native_em = encoded_message.map(|em| em.to_bytes_be()).unwrap_or_default();
+        let native_em = [vec![0u8; em_len.saturating_sub(native_em.len())], native_em].concat();
+
+        // 1. The encoded message must end with 0xbc, and its leftmost bit beyond `public_key_bits - 1` bits must be zero.
+        let trailer = Self::bits_to_byte(ctx, gate, &em_byte_bits[em_len - 1]);
+        gate.assert_is_const(ctx, &trailer, F::from(0xbc));
+        gate.assert_is_const(ctx, &em_byte_bits[0][7], F::zero());
+        let h_bytes = em_byte_bits[db_len..em_len - 1].iter().map(|bits| Self::bits_to_byte(ctx, gate, bits)).collect::<Vec<_>>();
+        let native_h = &native_em[db_len..em_len - 1];
+
+        // 2. Unmask the data block with MGF1(H), i.e., the concatenation of SHA-256(H || counter).
+        let mut db_mask = vec![];
+        let mut native_db_mask = vec![];
+        for counter in 0..(db_len + PSS_HASH_BYTES_SIZE - 1) / PSS_HASH_BYTES_SIZE {
+            let counter_bytes = (counter as u32).to_be_bytes();
+            let input = [native_h, &counter_bytes].concat();
+            let result = sha256_config.digest(ctx, &input, None)?;
+            gate.assert_is_const(ctx, &result.input_len, F::from(input.len() as u64));
+            for (assigned_byte, h_byte) in result.input_bytes.iter().zip(h_bytes.iter()) {
+                gate.assert_equal(ctx, QuantumCell::Existing(assigned_byte), QuantumCell::Existing(h_byte));
+            }
+            for (idx, counter_byte) in counter_bytes.iter().enumerate() {
+                gate.assert_is_const(ctx, &result.input_bytes[PSS_HASH_BYTES_SIZE + idx], F::from(*counter_byte as u64));
+            }
+            db_mask.extend(result.output_bytes);
+            native_db_mask.extend(Sha256::digest(&input));
+        }
+        let mut db_bytes = vec![];
+        for (idx, (masked_bits, mask_byte)) in em_byte_bits[..db_len].iter().zip(db_mask.iter()).enumerate() {
+            let mask_bits = gate.num_to_bits(ctx, mask_byte, 8);
+            let mut bits = vec![];
+            for (a, b) in masked_bits.iter().zip(mask_bits.iter()) {
+                // a xor b = a + b - 2ab
+                let sum = gate.add(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
+                let product = gate.mul(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
+                bits.push(gate.mul_add(ctx, QuantumCell::Constant(-F::from(2)), QuantumCell::Existing(&product), QuantumCell::Existing(&sum)));
+            }
+            // The leftmost bit of the data block is cleared.
+            if idx == 0 {
+                bits[7] = gate.load_zero(ctx);
+            }
+            db_bytes.push(Self::bits_to_byte(ctx, gate, &bits));
+        }
+
+        // 3. The data block must be the zero padding, 0x01, and the salt.
+        for ps_byte in db_bytes[..ps_len].iter() {
+            gate.assert_is_const(ctx, ps_byte, F::zero());
+        }
+        gate.assert_is_const(ctx, &db_bytes[ps_len], F::one());
+        let salt_bytes = &db_bytes[ps_len + 1..];
+        let native_salt = native_em[ps_len + 1..db_len]
+            .iter()
+            .zip(native_db_mask[ps_len + 1..db_len].iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<u8>>();
+
+        // 4. H must be SHA-256 of eight zero bytes, the message hash, and the salt.
+        let native_hash = hash_bytes
+            .iter()
+            .map(|byte| {
+                let mut value = 0u8;
+                byte.value().map(|v| value = v.get_lower_32() as u8);
+                value
+            })
+            .collect::<Vec<u8>>();
+        let input = [vec![0u8; 8], native_hash, native_salt].concat();
+        let result = sha256_config.digest(ctx, &input, None)?;
+        gate.assert_is_const(ctx, &result.input_len, F::from(input.len() as u64));
+        for assigned_byte in result.input_bytes[..8].iter() {
+            gate.assert_is_const(ctx, assigned_byte, F::zero());
+        }
+        for (assigned_byte, expected_byte) in result.input_bytes[8..].iter().zip(hash_bytes.iter().chain(salt_bytes.iter())) {
+            gate.assert_equal(ctx, QuantumCell::Existing(assigned_byte), QuantumCell::Existing(expected_byte));
+        }
+        for (a, b) in result.output_bytes.iter().zip(h_bytes.iter()) {
+            gate.assert_equal(ctx, QuantumCell::Existing(a), QuantumCell::Existing(b));
+        }
+        Ok(())
+    }
+
+    fn bits_to_byte<'v>(ctx: &mut Context<'v, F>, gate: &FlexGateConfig<F>, bits: &[AssignedValue<'v, F>]) -> AssignedValue<'v, F> {
+        let bases = (0..8).map(|i| QuantumCell::Constant(F::from(1u64 << i))).collect::<Vec<_>>();
+        gate.inner_product(ctx, bits.iter().map(QuantumCell::Existing).collect::<Vec<_>>(), bases)
+    }
 }
@@ -0,0 +1,232 @@
+use crate::forbidden_substr::ForbiddenHeaderFieldConfig;
+use crate::sign_only::SignOnlyCircuit;
+use crate::*;
+use halo2_base::halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_base::halo2_proofs::plonk::{Circuit, Column, ConstraintSystem, Error, Instance};
+use halo2_base::ContextParams;
+use halo2_base::{utils::PrimeField, SKIP_FIRST_PASS};
+
+/// The name of the header field whose presence is proven by [`SignaturePresenceCircuit`].
+pub const DKIM_SIGNATURE_FIELD_NAME: &str = "DKIM-Signature";
+
+/// Configuration for [`SignaturePresenceCircuit`].
+#[derive(Debug, Clone)]
+pub struct SignaturePresenceConfig<F: PrimeField> {
+    /// Configuration for [`RangeConfig`].
+    pub range_config: RangeConfig<F>,
+    /// Configuration to find the `DKIM-Signature` header field.
+    pub signature_field_config: ForbiddenHeaderFieldConfig<F>,
+    /// Instance column.
+    pub instances: Column<Instance>,
+}
+
+/// Circuit to prove only that the email header has a `DKIM-Signature` header field with a non-empty `b=` value, as a fast path for triage.
+///
+/// **It does NOT authenticate the email.** It neither verifies the RSA signature nor checks any public key,
+/// so anyone can generate a valid proof for a forged email with a random `b=` value.
+/// Use it only to filter emails cheaply before generating a full proof with [`DefaultEmailVerifyCircuit`] or [`SignOnlyCircuit`].
+///
+/// The instance column contains the signature commitment, which is the same as that of the full proofs so that they can be linked later,
+/// and the commitment of the canonicalized header padded with zero to the maximum header size,
+/// i.e., [`value_commit_wtns_bytes_with_domain`] with [`HEADER_COMMIT_DOMAIN`] and the signature commitment as the randomness.
+///
+/// It requires only `sign_verify_config` in the configuration parameters.
+/// The maximum header size is given by `sign_verify_config.max_header_bytes_size`, or `header_config.max_variable_byte_size` if omitted.
+#[derive(Debug, Clone)]
+pub struct SignaturePresenceCircuit<F: PrimeField> {
+    /// The bytes of the email.
+    pub email_bytes: Vec<u8>,
+    _f: PhantomData<F>,
+}
+
+impl<F: PrimeField> Circuit<F> for SignaturePresenceCircuit<F> {
+    type Config = SignaturePresenceConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            email_bytes: vec![],
+            _f: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let params = default_config_params();
+        let range_config = RangeConfig::configure(
+            meta,
+            Vertical,
+            &[params.num_flex_advice],
+            &[params.num_range_lookup_advice],
+            params.num_flex_fixed,
+            params.range_lookup_bits,
+            0,
+            params.degree as usize,
+        );
+        let signature_field_config = ForbiddenHeaderFieldConfig::configure(DKIM_SIGNATURE_FIELD_NAME);
+        let instances = meta.instance_column();
+        meta.enable_equality(instances);
+        SignaturePresenceConfig {
+            range_config,
+            signature_field_config,
+            instances,
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        config.range_config.load_lookup_table(&mut layouter)?;
+        let mut first_pass = SKIP_FIRST_PASS;
+        let mut public_hash_cell = vec![];
+        let header_bytes = Self::padded_header_bytes(&self.email_bytes);
+        let signature_limbs = Self::signature_limbs(&self.email_bytes);
+
+        layouter.assign_region(
+            || "zkemail_signature_presence",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let range = config.range_config.clone();
+                let gate = range.gate.clone();
+                let ctx = &mut Context::new(
+                    region,
+                    ContextParams {
+                        max_rows: gate.max_rows,
+                        num_context_ids: 1,
+                        fixed_columns: gate.constants.clone(),
+                    },
+                );
+                // 1. Assert that the header has the `DKIM-Signature` field.
+                // The header bytes must be range checked, otherwise their commitment does not bind the header.
+                let assigned_header = gate.assign_witnesses(ctx, header_bytes.iter().map(|byte| Value::known(F::from(*byte as u64))).collect());
+                for assigned_byte in assigned_header.iter() {
+                    range.range_check(ctx, assigned_byte, 8);
+                }
+                let is_present = config.signature_field_config.is_present(ctx, &gate, &assigned_header);
+                gate.assert_equal(ctx, QuantumCell::Existing(&is_present), QuantumCell::Constant(F::one()));
+
+                // 2. Assert that the signature, i.e., the decoded `b=` value, is not zero.
+                let assigned_limbs = gate.assign_witnesses(ctx, signature_limbs.iter().map(|limb| Value::known(*limb)).collect());
+                let mut is_all_zero = gate.load_constant(ctx, F::one());
+                for assigned_limb in assigned_limbs.iter() {
+                    let is_zero = gate.is_equal(ctx, QuantumCell::Existing(assigned_limb), QuantumCell::Constant(F::zero()));
+                    is_all_zero = gate.mul(ctx, QuantumCell::Existing(&is_all_zero), QuantumCell::Existing(&is_zero));
+                }
+                gate.assert_equal(ctx, QuantumCell::Existing(&is_all_zero), QuantumCell::Constant(F::zero()));
+
+                // 3. Compute public input values.
+                let poseidon = PoseidonChipBn254_8_58::new(ctx, &gate);
+                let sign_commit = poseidon.hash_elements(ctx, &gate, &assigned_limbs).unwrap().0[0].clone();
+                let header_commit = assigned_commit_wtns_bytes_with_domain(ctx, &gate, &poseidon, Some(HEADER_COMMIT_DOMAIN), &sign_commit, &assigned_header);
+                public_hash_cell.push(sign_commit.cell());
+                public_hash_cell.push(header_commit.cell());
+                range.finalize(ctx);
+                Ok(())
+            },
+        )?;
+        for (idx, cell) in public_hash_cell.into_iter().enumerate() {
+            layouter.constrain_instance(cell, config.instances, idx)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> CircuitExt<F> for SignaturePresenceCircuit<F> {
+    fn num_instance(&self) -> Vec<usize> {
+        vec![2]
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        let sign_commit: F = poseidon_hash_fields(&Self::signature_limbs(&self.email_bytes));
+        let header_commit = value_commit_wtns_bytes_with_domain(Some(HEADER_COMMIT_DOMAIN), &sign_commit, &Self::padded_header_bytes(&self.email_bytes));
+        vec![vec![sign_commit, header_commit]]
+    }
+}
+
+impl<F: PrimeField> SignaturePresenceCircuit<F> {
+    /// Create a new [`SignaturePresenceCircuit`].
+    /// # Arguments
+    /// * `email_bytes` - email bytes.
+    ///
+    /// # Return values
+    /// Return a new [`SignaturePresenceCircuit`].
+    pub fn new(email_bytes: Vec<u8>) -> Self {
+        Self { email_bytes, _f: PhantomData }
+    }
+
+    fn padded_header_bytes(email_bytes: &[u8]) -> Vec<u8> {
+        let (mut header_bytes, _, _) = canonicalize_signed_email(email_bytes).unwrap();
+        let max_header_bytes_size = SignOnlyCircuit::<F>::max_header_bytes_size();
+        assert!(
+            header_bytes.len() <= max_header_bytes_size,
+            "the canonicalized header of {} bytes exceeds {} bytes",
+            header_bytes.len(),
+            max_header_bytes_size
+        );
+        header_bytes.resize(max_header_bytes_size, 0);
+        header_bytes
+    }
+
+    fn signature_limbs(email_bytes: &[u8]) -> Vec<F> {
+        let (_, _, signature_bytes) = canonicalize_signed_email(email_bytes).unwrap();
+        let config_params = default_config_params();
+        let num_limbs = config_params.sign_verify_config.as_ref().expect("sign_verify_config is required").public_key_bits / LIMB_BITS;
+        decompose_biguint(&BigUint::from_bytes_be(&signature_bytes), num_limbs, LIMB_BITS)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cfdkim::SignerBuilder;
+    use halo2_base::halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
+    use mailparse::parse_mail;
+    use rand::thread_rng;
+    use rsa::RsaPrivateKey;
+
+    fn gen_signed_email(public_key_bits: usize) -> String {
+        let private_key = RsaPrivateKey::new(&mut thread_rng(), public_key_bits).expect("failed to generate a key");
+        let message = "From: alice@zkemail.com\r\n\r\nemail was meant for @zkemailverify.";
+        let email = parse_mail(message.as_bytes()).unwrap();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let signer = SignerBuilder::new()
+            .with_signed_headers(&["From"])
+            .unwrap()
+            .with_private_key(cfdkim::DkimPrivateKey::Rsa(private_key))
+            .with_selector("default")
+            .with_signing_domain("zkemail.com")
+            .with_logger(&logger)
+            .with_header_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .with_body_canonicalization(cfdkim::canonicalization::Type::Relaxed)
+            .build()
+            .unwrap();
+        let signature = signer.sign(&email).unwrap();
+        format!("{}\r\n{}", signature, message)
+    }
+
+    #[test]
+    fn test_signature_presence() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/signature_presence.config"), || {
+            let params = default_config_params();
+            let email = gen_signed_email(params.sign_verify_config.as_ref().unwrap().public_key_bits);
+            let circuit = SignaturePresenceCircuit::<Fr>::new(email.into_bytes());
+            let prover = MockProver::run(params.degree, &circuit, circuit.instances()).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_signature_presence_wrong_header_commit() {
+        temp_env::with_var(EMAIL_VERIFY_CONFIG_ENV, Some("./configs/signature_presence.config"), || {
+            let params = default_config_params();
+            let email = gen_signed_email(params.sign_verify_config.as_ref().unwrap().public_key_bits);
+            let circuit = SignaturePresenceCircuit::<Fr>::new(email.clone().into_bytes());
+            let mut instances = circuit.instances();
+            let other_circuit = SignaturePresenceCircuit::<Fr>::new(email.replace("alice", "bob__").into_bytes());
+            instances[0][1] = other_circuit.instances()[0][1];
+            let prover = MockProver::run(params.degree, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        });
+    }
+}
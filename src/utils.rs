@@ -1,7 +1,9 @@
 use crate::*;
 use fancy_regex::Regex;
 use itertools::Itertools;
+use std::collections::HashSet;
 use std::fs::File;
+use thiserror::Error;
 
 /// Compute expected masked chars and substring ids from the given list of the substrings and their start positions.
 ///
@@ -24,6 +26,143 @@ pub fn get_expected_substr_chars_and_ids(max_byte_size: usize, substrs: &[Option
     (expected_masked_chars, expected_substr_ids)
 }
 
+/// An error of the substrings extracted by the regex circuit at different positions from those in the public input.
+/// The commitment of the substrings computed in the circuit then differs from the one computed from the public input.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SubstrMatchError {
+    /// The regex circuit extracts the substring more than once, e.g., an ambiguous regex matches the same field twice, while the public input reveals only its first match.
+    #[error("the {part} substring {substr_id} matches {} times in the regex circuit at bytes {starts:?}, but the public input reveals only one match; make the regex match the substring only once", starts.len())]
+    DuplicateMatch { part: String, substr_id: usize, starts: Vec<usize> },
+    /// The regex circuit extracts the substring at other bytes than the substring regex in the configuration.
+    #[error("the {part} substring {substr_id} is extracted at bytes {circuit_starts:?} by the regex circuit but at bytes {native_starts:?} by its substring regex")]
+    Mismatch {
+        part: String,
+        substr_id: usize,
+        circuit_starts: Vec<usize>,
+        native_starts: Vec<usize>,
+    },
+}
+
+/// Check that the substring ids assigned by the regex circuit are the same as the ones computed from the extracted substrings by [`get_expected_substr_chars_and_ids`].
+/// The ids larger than the number of the substrings, e.g., that of the bodyhash in the header, are ignored.
+/// If the assigned values are unknown, e.g., during the key generation, nothing is checked.
+///
+/// # Arguments
+/// * `part` - `header` or `body`, used in the error message.
+/// * `assigned_substr_ids` - the substring ids assigned by the regex circuit.
+/// * `substrs` - the substrings and their start positions extracted by the substring regexes.
+/// # Return values
+/// Return `Ok(())` if the ids are the same, otherwise [`SubstrMatchError`].
+pub fn check_substr_ids<'a, F: PrimeField>(part: &str, assigned_substr_ids: &[AssignedValue<'a, F>], substrs: &[Option<(usize, String)>]) -> Result<(), SubstrMatchError> {
+    let mut circuit_ids = vec![];
+    for assigned_id in assigned_substr_ids.iter() {
+        let mut known = None;
+        assigned_id.value().map(|v| known = Some(v.get_lower_32() as usize));
+        match known {
+            Some(id) => circuit_ids.push(if id > substrs.len() { 0 } else { id }),
+            None => return Ok(()),
+        }
+    }
+    let (_, native_ids) = get_expected_substr_chars_and_ids(circuit_ids.len(), substrs);
+    // The start positions of the runs of the characters with the given id.
+    let starts = |ids: &[usize], substr_id: usize| {
+        (0..ids.len())
+            .filter(|idx| ids[*idx] == substr_id && (*idx == 0 || ids[*idx - 1] != substr_id))
+            .collect_vec()
+    };
+    let native_ids = native_ids.into_iter().map(|id| id as usize).collect_vec();
+    for substr_id in 1..=substrs.len() {
+        let circuit_starts = starts(&circuit_ids, substr_id);
+        let native_starts = starts(&native_ids, substr_id);
+        if circuit_starts.len() > 1 && native_starts.len() <= 1 {
+            return Err(SubstrMatchError::DuplicateMatch {
+                part: part.to_string(),
+                substr_id,
+                starts: circuit_starts,
+            });
+        }
+        let is_same = (0..circuit_ids.len()).all(|idx| (circuit_ids[idx] == substr_id) == (native_ids[idx] == substr_id));
+        if !is_same {
+            return Err(SubstrMatchError::Mismatch {
+                part: part.to_string(),
+                substr_id,
+                circuit_starts,
+                native_starts,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// How the canonicalized email bytes are decoded into a string to run the substring regexes natively.
+/// The circuit always processes the raw bytes, so the encoding does not change the constraints.
+/// Both encodings decode valid UTF-8 to the same string, so the circuit and its public input always decode the email lossily,
+/// and the strict encoding is checked once on the input with [`EmailEncoding::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailEncoding {
+    /// The bytes must be valid UTF-8.
+    Strict,
+    /// Each byte of invalid UTF-8 sequences is replaced with NUL, so that the other characters keep their byte offsets.
+    Lossy,
+}
+
+impl EmailEncoding {
+    /// Return the encoding of the given name, i.e., `strict` or `lossy`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "strict" => Some(Self::Strict),
+            "lossy" => Some(Self::Lossy),
+            _ => None,
+        }
+    }
+
+    /// Check that the bytes can be decoded with this encoding, i.e., they are valid UTF-8 if it is [`EmailEncoding::Strict`].
+    ///
+    /// # Arguments
+    /// * `bytes` - the canonicalized email header or body bytes.
+    /// # Return values
+    /// Return `Err(offset)` of the first invalid byte if the bytes cannot be decoded, otherwise `Ok(())`.
+    pub fn check(&self, bytes: &[u8]) -> Result<(), usize> {
+        match (self, std::str::from_utf8(bytes)) {
+            (Self::Strict, Err(err)) => Err(err.valid_up_to()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Decode the bytes into a string whose byte offsets are the same as those of the bytes.
+    ///
+    /// # Arguments
+    /// * `bytes` - the canonicalized email header or body bytes.
+    /// # Return values
+    /// Return the decoded string. It panics if the encoding is [`EmailEncoding::Strict`] and the bytes are not valid UTF-8.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let mut decoded = String::with_capacity(bytes.len());
+        let mut rest = bytes;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    decoded.push_str(valid);
+                    return decoded;
+                }
+                Err(err) => {
+                    let valid_up_to = err.valid_up_to();
+                    if *self == Self::Strict {
+                        panic!(
+                            "the email is not valid UTF-8 at byte {}, which is accepted only with the lossy email encoding",
+                            bytes.len() - rest.len() + valid_up_to
+                        );
+                    }
+                    let (valid, invalid) = rest.split_at(valid_up_to);
+                    decoded.push_str(std::str::from_utf8(valid).expect("the prefix is valid UTF-8"));
+                    let invalid_len = err.error_len().unwrap_or(invalid.len());
+                    decoded.extend(std::iter::repeat('\0').take(invalid_len));
+                    rest = &invalid[invalid_len..];
+                }
+            }
+        }
+    }
+}
+
 /// Extract substrings and their start positions from the given header and body strings.
 ///
 /// # Arguments
@@ -84,3 +223,854 @@ pub fn get_substr(input_str: &str, regexes: &[String]) -> Option<(usize, String)
     // println!("start {}", start);
     Some((start, substr.to_string()))
 }
+
+/// Split an mbox archive into the raw bytes of its messages.
+/// The `From ` separator lines are removed and the `>From ` quoting of the mboxrd format is reverted, so that the DKIM signatures of the messages remain valid.
+///
+/// # Arguments
+/// * `mbox_bytes` - The bytes of the mbox archive.
+/// # Return values
+/// Return a list of the raw bytes of the messages.
+pub fn split_mbox(mbox_bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages: Vec<Vec<u8>> = vec![];
+    let mut current: Option<Vec<u8>> = None;
+    let mut prev_blank = true;
+    for line in mbox_bytes.split_inclusive(|byte| *byte == b'\n') {
+        if prev_blank && line.starts_with(b"From ") {
+            if let Some(message) = current.take() {
+                messages.push(message);
+            }
+            current = Some(vec![]);
+            prev_blank = false;
+            continue;
+        }
+        prev_blank = line == b"\n" || line == b"\r\n";
+        if let Some(message) = current.as_mut() {
+            let num_quotes = line.iter().take_while(|byte| **byte == b'>').count();
+            if num_quotes > 0 && line[num_quotes..].starts_with(b"From ") {
+                message.extend_from_slice(&line[1..]);
+            } else {
+                message.extend_from_slice(line);
+            }
+        }
+    }
+    if let Some(message) = current.take() {
+        messages.push(message);
+    }
+    // Remove the blank line that separates each message from the next `From ` line.
+    for message in messages.iter_mut() {
+        if message.ends_with(b"\r\n\r\n") {
+            message.truncate(message.len() - 2);
+        } else if message.ends_with(b"\n\n") {
+            message.truncate(message.len() - 1);
+        }
+    }
+    messages
+}
+
+/// Read the messages in a Maildir, i.e., the files in its `new` and `cur` subdirectories, as raw bytes.
+/// If the directory has neither subdirectory, it is read as a `new` or `cur` directory itself. The `tmp` subdirectory and hidden files are ignored.
+///
+/// # Arguments
+/// * `maildir_path` - The path of the Maildir.
+/// # Return values
+/// Return a list of (the unique name, the raw bytes) of the messages sorted by the unique name, which is the file name without the `:2,` flags.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_maildir(maildir_path: &std::path::Path) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    let subdirs = ["new", "cur"].into_iter().map(|subdir| maildir_path.join(subdir)).filter(|subdir| subdir.is_dir()).collect_vec();
+    let dirs = if subdirs.is_empty() { vec![maildir_path.to_path_buf()] } else { subdirs };
+    let mut messages = vec![];
+    for dir in dirs {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            if !path.is_file() || file_name.starts_with('.') {
+                continue;
+            }
+            let unique_name = file_name.split(':').next().unwrap_or_default().to_string();
+            messages.push((unique_name, std::fs::read(&path)?));
+        }
+    }
+    messages.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(messages)
+}
+
+/// Locate the signed content of an S/MIME `multipart/signed` message body, i.e., its first MIME part including the part headers.
+/// The boundary is the first line starting with `--`, and the second part must be an `application/pkcs7-signature` (or `application/x-pkcs7-signature`) part.
+///
+/// # Arguments
+/// * `body_bytes` - the canonicalized email body bytes.
+/// # Return values
+/// Return the byte range of the signed content without the line break before the next boundary, or `None` if the body is not `multipart/signed`.
+pub fn smime_signed_content_range(body_bytes: &[u8]) -> Option<std::ops::Range<usize>> {
+    let mut boundary: Option<&[u8]> = None;
+    let mut content_start = 0;
+    let mut offset = 0;
+    for line in body_bytes.split_inclusive(|byte| *byte == b'\n') {
+        let trimmed = line.strip_suffix(b"\r\n").or_else(|| line.strip_suffix(b"\n")).unwrap_or(line);
+        match boundary {
+            None if trimmed.len() > 2 && trimmed.starts_with(b"--") => {
+                boundary = Some(trimmed);
+                content_start = offset + line.len();
+            }
+            Some(boundary) if trimmed == boundary => {
+                let preceding = &body_bytes[..offset];
+                let line_break_len = if preceding.ends_with(b"\r\n") {
+                    2
+                } else if preceding.ends_with(b"\n") {
+                    1
+                } else {
+                    0
+                };
+                let signature_part = &body_bytes[offset + line.len()..];
+                let part_headers_end = find_subsequence(signature_part, b"\r\n\r\n")
+                    .or_else(|| find_subsequence(signature_part, b"\n\n"))
+                    .unwrap_or(signature_part.len());
+                let part_headers = signature_part[..part_headers_end].to_ascii_lowercase();
+                if find_subsequence(&part_headers, b"application/pkcs7-signature").is_none() && find_subsequence(&part_headers, b"application/x-pkcs7-signature").is_none() {
+                    return None;
+                }
+                return Some(content_start..(offset - line_break_len).max(content_start));
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn find_subsequence(bytes: &[u8], target: &[u8]) -> Option<usize> {
+    bytes.windows(target.len()).position(|window| window == target)
+}
+
+/// Decode the RFC2047 encoded words, e.g., `=?UTF-8?B?...?=` and `=?UTF-8?Q?...?=`, in a header value.
+///
+/// # Arguments
+/// * `value` - a header value that may contain encoded words.
+/// # Return values
+/// Return the decoded Unicode text, or `value` as it is if it cannot be decoded.
+pub fn decode_encoded_words(value: &str) -> String {
+    match mailparse::parse_header(format!("X: {}", value).as_bytes()) {
+        Ok((header, _)) => header.get_value(),
+        Err(_) => value.to_string(),
+    }
+}
+
+/// Decode the punycode labels, i.e., the labels starting with `xn--`, of internationalized domains in a header value, e.g., `alice@xn--r8jz45g.jp` to `alice@例え.jp`.
+///
+/// # Arguments
+/// * `value` - a domain or an email address that may contain punycode labels.
+/// # Return values
+/// Return the value whose punycode labels are decoded to Unicode. The labels that cannot be decoded are kept as they are.
+pub fn decode_idn_domain(value: &str) -> String {
+    value
+        .split('@')
+        .map(|part| {
+            part.split('.')
+                .map(|label| match label.get(..4) {
+                    Some(prefix) if prefix.eq_ignore_ascii_case("xn--") => idna::punycode::decode_to_string(&label[4..]).unwrap_or_else(|| label.to_string()),
+                    _ => label.to_string(),
+                })
+                .join(".")
+        })
+        .join("@")
+}
+
+/// Split a comma-separated address list, e.g., the value of the `To` field, into its addresses in the same way as [`CharsShiftConfig::split_list`](crate::chars_shift::CharsShiftConfig::split_list).
+/// The folding whitespace characters are removed from each address, and an empty item, e.g., after a trailing comma, is kept as an empty address.
+///
+/// # Arguments
+/// * `list` - The address list.
+/// # Return values
+/// Return the addresses in the order of the list.
+pub fn split_address_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|address| address.chars().filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n')).collect::<String>())
+        .collect_vec()
+}
+
+/// Derive the field names of the substrings from the allstr file pathes of their regexes.
+/// The i-th substring of an allstr file is named `{stem}_{i}`, except that the first one is named `{stem}`, where `stem` is the file stem without `_allstr`, e.g., `subject` for `./test_data/subject_allstr.txt`.
+/// A substring file generated from a named capture group, e.g., `./test_data/otp_substr_code.txt` for `(?P<code>...)`, is instead named after the group, i.e., `code`.
+///
+/// # Arguments
+/// * `allstr_filepathes` - the allstr file pathes in the header or body configuration.
+/// * `substr_filepathes` - the substring file pathes for each allstr file.
+/// # Return values
+/// Return the field names in the order of the substring ids.
+pub fn substr_field_names(allstr_filepathes: &[String], substr_filepathes: &[Vec<String>]) -> Vec<String> {
+    allstr_filepathes
+        .iter()
+        .zip(substr_filepathes.iter())
+        .flat_map(|(allstr_filepath, substr_pathes)| {
+            let stem = std::path::Path::new(allstr_filepath)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().trim_end_matches("_allstr").to_string())
+                .unwrap_or_default();
+            substr_pathes.iter().enumerate().map(move |(idx, substr_path)| match named_substr_file_group(substr_path) {
+                Some(group_name) => group_name,
+                None if idx == 0 => stem.clone(),
+                None => format!("{}_{}", stem, idx),
+            })
+        })
+        .collect_vec()
+}
+
+/// Return the group name of a substring file `{prefix}_substr_{name}.txt` generated from a named capture group, or `None` if `name` is an index.
+fn named_substr_file_group(substr_path: &str) -> Option<String> {
+    let stem = std::path::Path::new(substr_path).file_stem()?.to_string_lossy().to_string();
+    let (_, name) = stem.rsplit_once("_substr_")?;
+    if name.is_empty() || name.chars().all(|c| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// An error of a regex with named capture groups that cannot be decomposed by [`decompose_named_regex`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum NamedRegexError {
+    /// The regex has no named capture group, so nothing is revealed.
+    #[error("the regex has no named capture group (?P<name>...)")]
+    NoNamedGroup,
+    /// The group name is not an identifier.
+    #[error("the group name {0:?} must consist of ASCII letters, digits, and underscores and must not start with a digit")]
+    InvalidName(String),
+    /// Two groups have the same name.
+    #[error("the group name {0} is used more than once")]
+    DuplicateName(String),
+    /// The named group is inside another group, so it is not a part of the decomposed regex.
+    #[error("the named group {0} must not be inside another group")]
+    NestedGroup(String),
+    /// The named group is repeated by a quantifier, so it does not match a single substring.
+    #[error("the named group {0} must not be followed by a quantifier")]
+    QuantifiedGroup(String),
+    /// A parenthesis or a bracket is not closed.
+    #[error("unbalanced parentheses or brackets at byte {0}")]
+    Unbalanced(usize),
+}
+
+/// Decompose a regex with named capture groups, e.g., `Your code is (?P<code>(0|1|2|3|4|5|6|7|8|9)+)\.`, into the parts of a decomposed regex config.
+/// Each named group, i.e., `(?P<name>...)` or `(?<name>...)`, becomes a public part, and the regexes between the groups become private parts.
+/// The named groups must be at the top level of the regex, and the other groups must not be named.
+///
+/// # Arguments
+/// * `regex` - a regex with named capture groups.
+/// # Return values
+/// Return a list of the group name, which is `None` for a private part, and the regex of each part in order, or [`NamedRegexError`].
+pub fn decompose_named_regex(regex: &str) -> Result<Vec<(Option<String>, String)>, NamedRegexError> {
+    let bytes = regex.as_bytes();
+    let mut parts = vec![];
+    let mut names = HashSet::new();
+    let mut private_start = 0;
+    let mut depth = 0usize;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 1,
+            b'[' => idx = skip_char_class(bytes, idx)?,
+            b'(' => {
+                if let Some((name, content_start)) = parse_group_name(regex, idx)? {
+                    if depth != 0 {
+                        return Err(NamedRegexError::NestedGroup(name));
+                    }
+                    if !names.insert(name.clone()) {
+                        return Err(NamedRegexError::DuplicateName(name));
+                    }
+                    let group_end = find_group_end(bytes, content_start)?;
+                    let content = &regex[content_start..group_end];
+                    if let Some((inner_idx, _)) = content.match_indices('(').find(|(inner_idx, _)| matches!(parse_group_name(content, *inner_idx), Ok(Some(_)))) {
+                        return Err(NamedRegexError::NestedGroup(parse_group_name(content, inner_idx)?.unwrap().0));
+                    }
+                    if matches!(bytes.get(group_end + 1), Some(b'*' | b'+' | b'?' | b'{')) {
+                        return Err(NamedRegexError::QuantifiedGroup(name));
+                    }
+                    if private_start < idx {
+                        parts.push((None, regex[private_start..idx].to_string()));
+                    }
+                    parts.push((Some(name), content.to_string()));
+                    private_start = group_end + 1;
+                    idx = group_end;
+                } else {
+                    depth += 1;
+                }
+            }
+            b')' => depth = depth.checked_sub(1).ok_or(NamedRegexError::Unbalanced(idx))?,
+            _ => {}
+        }
+        idx += 1;
+    }
+    if depth != 0 {
+        return Err(NamedRegexError::Unbalanced(regex.len()));
+    }
+    if names.is_empty() {
+        return Err(NamedRegexError::NoNamedGroup);
+    }
+    if private_start < regex.len() {
+        parts.push((None, regex[private_start..].to_string()));
+    }
+    Ok(parts)
+}
+
+/// Parse the name of a named group starting at `idx`, returning the name and the start of the group content, or `None` if the group is not named.
+fn parse_group_name(regex: &str, idx: usize) -> Result<Option<(String, usize)>, NamedRegexError> {
+    let rest = &regex[idx..];
+    let name_start = if rest.starts_with("(?P<") {
+        idx + 4
+    } else if rest.starts_with("(?<") && !rest.starts_with("(?<=") && !rest.starts_with("(?<!") {
+        idx + 3
+    } else {
+        return Ok(None);
+    };
+    let name_end = regex[name_start..].find('>').map(|offset| name_start + offset).ok_or(NamedRegexError::Unbalanced(idx))?;
+    let name = &regex[name_start..name_end];
+    let is_valid = !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid {
+        return Err(NamedRegexError::InvalidName(name.to_string()));
+    }
+    Ok(Some((name.to_string(), name_end + 1)))
+}
+
+/// Find the index of the `)` closing the group whose content starts at `content_start`.
+fn find_group_end(bytes: &[u8], content_start: usize) -> Result<usize, NamedRegexError> {
+    let mut depth = 0usize;
+    let mut idx = content_start;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 1,
+            b'[' => idx = skip_char_class(bytes, idx)?,
+            b'(' => depth += 1,
+            b')' if depth == 0 => return Ok(idx),
+            b')' => depth -= 1,
+            _ => {}
+        }
+        idx += 1;
+    }
+    Err(NamedRegexError::Unbalanced(content_start))
+}
+
+/// Return the index of the `]` closing the character class starting at `idx`.
+fn skip_char_class(bytes: &[u8], idx: usize) -> Result<usize, NamedRegexError> {
+    let mut end = idx + 1;
+    // A `]` right after `[` or `[^` is a literal.
+    if bytes.get(end) == Some(&b'^') {
+        end += 1;
+    }
+    if bytes.get(end) == Some(&b']') {
+        end += 1;
+    }
+    while end < bytes.len() {
+        match bytes[end] {
+            b'\\' => end += 1,
+            b']' => return Ok(end),
+            _ => {}
+        }
+        end += 1;
+    }
+    Err(NamedRegexError::Unbalanced(idx))
+}
+
+/// The type of the value at the end of a JSON path, which selects the characters matched by [`json_path_regex_parts`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonValueType {
+    /// A JSON number, e.g., `42.50` or `-3`, matched by its digits, `-`, and `.`.
+    #[default]
+    Number,
+    /// A non-empty JSON string without escaped characters, matched between its double quotes.
+    String,
+}
+
+/// An error of a JSON path that cannot be converted by [`json_path_regex_parts`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum JsonPathError {
+    /// The path has no key.
+    #[error("the json path is empty")]
+    Empty,
+    /// The key is not an identifier, e.g., it contains a quote, a dot, or an array index.
+    #[error("the key {0:?} must consist of ASCII letters, digits, and underscores and must not start with a digit")]
+    InvalidKey(String),
+}
+
+/// The printable ASCII characters and whitespaces of a JSON text, in the order of the catch-all parts of the regex definitions.
+const JSON_TEXT_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~ \t\n\r";
+/// The regex of optional JSON whitespaces.
+const JSON_WS_REGEX: &str = "( |\t|\n|\r)*";
+
+/// Return the regex matching one of [`JSON_TEXT_CHARS`] except `excluded`, escaping the operators of the regex definitions.
+fn json_chars_regex(excluded: &[char]) -> String {
+    let chars = JSON_TEXT_CHARS
+        .chars()
+        .filter(|c| !excluded.contains(c))
+        .map(|c| match c {
+            '(' | ')' | '*' | '+' | '?' | '|' | '\\' => format!("\\{}", c),
+            _ => c.to_string(),
+        })
+        .join("|");
+    format!("({})", chars)
+}
+
+fn parse_json_path(path: &str) -> Result<Vec<&str>, JsonPathError> {
+    if path.is_empty() {
+        return Err(JsonPathError::Empty);
+    }
+    path.split('.')
+        .map(|key| {
+            let is_valid = !key.is_empty() && !key.starts_with(|c: char| c.is_ascii_digit()) && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if is_valid {
+                Ok(key)
+            } else {
+                Err(JsonPathError::InvalidKey(key.to_string()))
+            }
+        })
+        .collect()
+}
+
+/// Convert a simple JSON path, e.g., `data.order.total`, into the parts of a decomposed regex config that reveal the value at the path in a JSON text embedded in the email body.
+/// Each key must be followed by `:` and, except the last one, by an object whose members before the next key contain no `{` or `}`.
+/// So the next key is a direct member of the object, as long as the preceding members are scalars and no string contains a brace.
+/// The value is a public part named after the last key, and the rest are private parts, in the same format as [`decompose_named_regex`].
+///
+/// # Arguments
+/// * `path` - keys separated by `.`, each of which is an identifier.
+/// * `value_type` - the type of the value at the path.
+/// # Return values
+/// Return a list of the part name, which is `None` for a private part, and the regex of each part in order, or [`JsonPathError`].
+pub fn json_path_regex_parts(path: &str, value_type: JsonValueType) -> Result<Vec<(Option<String>, String)>, JsonPathError> {
+    let keys = parse_json_path(path)?;
+    let member_chars = format!("{}*", json_chars_regex(&['{', '}']));
+    // The braces are put in groups so that they are not parsed as a counted repetition of the preceding whitespaces.
+    let mut prefix = keys.iter().map(|key| format!("\"{}\"{}:{}", key, JSON_WS_REGEX, JSON_WS_REGEX)).join(&format!("({{){}", member_chars));
+    let (value, terminator) = match value_type {
+        JsonValueType::Number => ("(0|1|2|3|4|5|6|7|8|9|-|.)+".to_string(), "(,|}| |\t|\n|\r)".to_string()),
+        JsonValueType::String => {
+            prefix.push('"');
+            (format!("{}+", json_chars_regex(&['"', '\\', '\n', '\r'])), "\"".to_string())
+        }
+    };
+    Ok(vec![(None, prefix), (Some(keys[keys.len() - 1].to_string()), value), (None, terminator)])
+}
+
+/// Return the `substr_regexes` that extract the value at a JSON path natively, which match the same substring as [`json_path_regex_parts`].
+/// The first regex matches the keys and the value, and the second one takes the value out of the match.
+///
+/// # Arguments
+/// * `path` - keys separated by `.`, each of which is an identifier.
+/// * `value_type` - the type of the value at the path.
+/// # Return values
+/// Return the regexes to be listed in `substr_regexes` of `body_config`, or [`JsonPathError`].
+pub fn json_path_substr_regexes(path: &str, value_type: JsonValueType) -> Result<Vec<String>, JsonPathError> {
+    let keys = parse_json_path(path)?;
+    let prefix = keys.iter().map(|key| format!(r#""{}"\s*:\s*"#, key)).join(r"\{[^{}]*");
+    let regexes = match value_type {
+        JsonValueType::Number => vec![format!(r"{}[0-9.\-]+(?=[,}}\s])", prefix), r"[0-9.\-]+$".to_string()],
+        JsonValueType::String => vec![format!(r#"{}"[^"\\\r\n]+""#, prefix), r#"(?<=")[^"\\\r\n]+(?="$)"#.to_string()],
+    };
+    Ok(regexes)
+}
+
+/// An error of inconsistent or over-large regex files generated by [`DecomposedRegexConfig::gen_regex_files`](crate::vrm::DecomposedRegexConfig::gen_regex_files).
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RegexFilesError {
+    /// The file cannot be read.
+    #[error("failed to read the regex file {path}: {message}")]
+    Io { path: String, message: String },
+    /// The line is not in the format of the regex file.
+    #[error("{path}:{line}: {message}")]
+    Malformed { path: String, line: usize, message: String },
+    /// The state is larger than the largest state of the allstr regex.
+    #[error("{path}:{line}: the state {state} exceeds the largest state {largest_state} of {allstr_path}")]
+    UnknownState {
+        path: String,
+        line: usize,
+        state: u64,
+        largest_state: u64,
+        allstr_path: String,
+    },
+    /// The accepted state cannot be reached from the first state.
+    #[error("{path}: the accepted state {accepted_state} is not reachable from the first state {first_state}, so no string is accepted")]
+    UnreachableAcceptedState { path: String, first_state: u64, accepted_state: u64 },
+    /// The substring transition is not a transition of the allstr regex.
+    #[error("{path}:{line}: the transition {cur_state} -> {next_state} is not a transition of {allstr_path}, so the substring is never extracted")]
+    UnknownTransition {
+        path: String,
+        line: usize,
+        cur_state: u64,
+        next_state: u64,
+        allstr_path: String,
+    },
+    /// The allstr regex has more transitions than the rows of its lookup table.
+    #[error("{path}: the regex needs {num_states} states with {num_transitions} transitions but the lookup table holds {num_rows} rows; increase degree = {degree}")]
+    ExceedsLookupCapacity {
+        path: String,
+        num_states: u64,
+        num_transitions: usize,
+        num_rows: usize,
+        degree: u32,
+    },
+}
+
+/// Check that an allstr regex file and its substring regex files are consistent with each other, i.e.,
+/// every state is at most the largest state, the accepted state is reachable, and every substring transition is a transition of the allstr regex.
+/// Inconsistent files do not make the circuit unsatisfiable but make the regex silently match nothing.
+///
+/// # Arguments
+/// * `allstr_path` - a file path of the allstr regex.
+/// * `substr_pathes` - file pathes of the substring regexes of `allstr_path`.
+/// # Return values
+/// Return `Ok(())` if the files are consistent, otherwise [`RegexFilesError`].
+pub fn check_regex_files(allstr_path: &str, substr_pathes: &[String]) -> Result<(), RegexFilesError> {
+    let allstr_lines = read_regex_file_lines(allstr_path)?;
+    let header = |idx: usize, name: &str| -> Result<u64, RegexFilesError> {
+        let line = allstr_lines.get(idx).map(|line| line.as_str()).unwrap_or_default();
+        parse_regex_file_numbers(allstr_path, idx, line, 1).map(|numbers| numbers[0]).map_err(|_| RegexFilesError::Malformed {
+            path: allstr_path.to_string(),
+            line: idx + 1,
+            message: format!("expected the {}", name),
+        })
+    };
+    let first_state = header(0, "first state")?;
+    let accepted_state = header(1, "accepted state")?;
+    let largest_state = header(2, "largest state")?;
+    let check_state = |path: &str, idx: usize, state: u64| {
+        if state > largest_state {
+            Err(RegexFilesError::UnknownState {
+                path: path.to_string(),
+                line: idx + 1,
+                state,
+                largest_state,
+                allstr_path: allstr_path.to_string(),
+            })
+        } else {
+            Ok(())
+        }
+    };
+    check_state(allstr_path, 1, first_state)?;
+    check_state(allstr_path, 2, accepted_state)?;
+    let mut transitions = HashSet::new();
+    for (idx, line) in allstr_lines.iter().enumerate().skip(3).filter(|(_, line)| !line.trim().is_empty()) {
+        let numbers = parse_regex_file_numbers(allstr_path, idx, line, 3)?;
+        check_state(allstr_path, idx, numbers[0])?;
+        check_state(allstr_path, idx, numbers[1])?;
+        if numbers[2] > u8::MAX as u64 {
+            return Err(RegexFilesError::Malformed {
+                path: allstr_path.to_string(),
+                line: idx + 1,
+                message: format!("the character {} is not a byte", numbers[2]),
+            });
+        }
+        transitions.insert((numbers[0], numbers[1]));
+    }
+    let mut reachable = HashSet::from([first_state]);
+    loop {
+        let next_states = transitions.iter().filter(|(cur, next)| reachable.contains(cur) && !reachable.contains(next)).map(|(_, next)| *next).collect_vec();
+        if next_states.is_empty() {
+            break;
+        }
+        reachable.extend(next_states);
+    }
+    if !reachable.contains(&accepted_state) {
+        return Err(RegexFilesError::UnreachableAcceptedState {
+            path: allstr_path.to_string(),
+            first_state,
+            accepted_state,
+        });
+    }
+    for substr_path in substr_pathes {
+        let substr_lines = read_regex_file_lines(substr_path)?;
+        for idx in 0..3 {
+            parse_regex_file_numbers(substr_path, idx, substr_lines.get(idx).map(|line| line.as_str()).unwrap_or_default(), 1)?;
+        }
+        // The 4th and 5th lines list the states where the substring starts and ends, respectively.
+        for idx in 3..5 {
+            let line = substr_lines.get(idx).map(|line| line.as_str()).unwrap_or_default();
+            for state in parse_regex_file_numbers(substr_path, idx, line, 0)? {
+                check_state(substr_path, idx, state)?;
+            }
+        }
+        for (idx, line) in substr_lines.iter().enumerate().skip(5).filter(|(_, line)| !line.trim().is_empty()) {
+            let numbers = parse_regex_file_numbers(substr_path, idx, line, 2)?;
+            if !transitions.contains(&(numbers[0], numbers[1])) {
+                return Err(RegexFilesError::UnknownTransition {
+                    path: substr_path.to_string(),
+                    line: idx + 1,
+                    cur_state: numbers[0],
+                    next_state: numbers[1],
+                    allstr_path: allstr_path.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that an allstr regex file fits in the lookup table of the regex circuit, which has one row for each transition in the `2^degree` rows.
+/// An over-large regex otherwise fails with an opaque error of the key generation or the prover.
+///
+/// # Arguments
+/// * `allstr_path` - a file path of the allstr regex.
+/// * `degree` - the degree of the circuit.
+/// # Return values
+/// Return `Ok(())` if the transitions fit, otherwise [`RegexFilesError::ExceedsLookupCapacity`] with the numbers of the states and the transitions.
+pub fn check_regex_capacity(allstr_path: &str, degree: u32) -> Result<(), RegexFilesError> {
+    let allstr_lines = read_regex_file_lines(allstr_path)?;
+    let largest_state = parse_regex_file_numbers(allstr_path, 2, allstr_lines.get(2).map(|line| line.as_str()).unwrap_or_default(), 1)?[0];
+    let num_transitions = allstr_lines.iter().skip(3).filter(|line| !line.trim().is_empty()).count();
+    let num_rows = 1usize << degree;
+    if num_transitions >= num_rows {
+        return Err(RegexFilesError::ExceedsLookupCapacity {
+            path: allstr_path.to_string(),
+            num_states: largest_state + 1,
+            num_transitions,
+            num_rows,
+            degree,
+        });
+    }
+    Ok(())
+}
+
+fn read_regex_file_lines(path: &str) -> Result<Vec<String>, RegexFilesError> {
+    let text = std::fs::read_to_string(path).map_err(|err| RegexFilesError::Io {
+        path: path.to_string(),
+        message: err.to_string(),
+    })?;
+    Ok(text.lines().map(|line| line.to_string()).collect_vec())
+}
+
+/// Parse the space-separated numbers in the `idx`-th line. If `num_numbers` is 0, any number of numbers is accepted.
+fn parse_regex_file_numbers(path: &str, idx: usize, line: &str, num_numbers: usize) -> Result<Vec<u64>, RegexFilesError> {
+    let malformed = |message: String| RegexFilesError::Malformed {
+        path: path.to_string(),
+        line: idx + 1,
+        message,
+    };
+    let numbers = line
+        .split_whitespace()
+        .map(|number| number.parse::<u64>().map_err(|_| malformed(format!("{} is not a number", number))))
+        .collect::<Result<Vec<_>, _>>()?;
+    if num_numbers != 0 && numbers.len() != num_numbers {
+        return Err(malformed(format!("expected {} numbers, found {}", num_numbers, numbers.len())));
+    }
+    Ok(numbers)
+}
+
+/// Get the position of the `idx`-th instance when the instances are distributed across the instance columns in a round-robin manner.
+///
+/// # Arguments
+/// * `idx` - the index of the instance in the flattened instances.
+/// * `num_columns` - the number of the instance columns.
+/// # Return values
+/// Return a tuple of the column index and the row index.
+pub fn instance_position(idx: usize, num_columns: usize) -> (usize, usize) {
+    (idx % num_columns, idx / num_columns)
+}
+
+/// Distribute the flattened instances across the instance columns in the same manner as [`instance_position`].
+///
+/// # Arguments
+/// * `instances` - the flattened instances.
+/// * `num_columns` - the number of the instance columns.
+/// # Return values
+/// Return the instances of each column.
+pub fn split_instances<T: Clone>(instances: &[T], num_columns: usize) -> Vec<Vec<T>> {
+    let mut columns = vec![vec![]; num_columns];
+    for (idx, instance) in instances.iter().enumerate() {
+        let (column, _) = instance_position(idx, num_columns);
+        columns[column].push(instance.clone());
+    }
+    columns
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_mbox() {
+        let mut mbox = vec![];
+        for idx in 0..5 {
+            mbox.extend_from_slice(format!("From alice@zkemail.com Mon Jan  1 00:00:0{} 2024\r\n", idx).as_bytes());
+            mbox.extend_from_slice(format!("From: alice@zkemail.com\r\nSubject: email {}\r\n\r\nbody\r\n>From the quoted line\r\n", idx).as_bytes());
+            mbox.extend_from_slice(b"\r\n");
+        }
+        let messages = split_mbox(&mbox);
+        assert_eq!(messages.len(), 5);
+        for (idx, message) in messages.iter().enumerate() {
+            let expected = format!("From: alice@zkemail.com\r\nSubject: email {}\r\n\r\nbody\r\nFrom the quoted line\r\n", idx);
+            assert_eq!(message, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_read_maildir() {
+        let dir = std::path::Path::new("./build/test_read_maildir");
+        let _ = std::fs::remove_dir_all(dir);
+        for subdir in ["new", "cur", "tmp"] {
+            std::fs::create_dir_all(dir.join(subdir)).unwrap();
+        }
+        let message = b"From: alice@zkemail.com\r\nSubject: email\r\n\r\nFrom the body\r\n";
+        std::fs::write(dir.join("new/1700000002.M2P1.host"), message).unwrap();
+        std::fs::write(dir.join("cur/1700000001.M1P1.host:2,S"), message).unwrap();
+        std::fs::write(dir.join("cur/.hidden"), b"").unwrap();
+        std::fs::write(dir.join("tmp/1700000003.M3P1.host"), message).unwrap();
+        let messages = read_maildir(dir).unwrap();
+        assert_eq!(
+            messages.iter().map(|(name, _)| name.as_str()).collect_vec(),
+            vec!["1700000001.M1P1.host", "1700000002.M2P1.host"]
+        );
+        assert!(messages.iter().all(|(_, bytes)| bytes == message));
+        assert_eq!(read_maildir(&dir.join("cur")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_smime_signed_content_range() {
+        let content = "Content-Type: text/plain\r\n\r\nemail was meant for @zkemailverify.\r\n";
+        let body = format!(
+            "This is an S/MIME signed message\r\n\r\n--B\r\n{}\r\n--B\r\nContent-Type: application/pkcs7-signature; name=smime.p7s\r\n\r\nMIAGCSqGSIb3DQEHAqCAMIACAQEx\r\n\r\n--B--\r\n",
+            content
+        );
+        let range = smime_signed_content_range(body.as_bytes()).unwrap();
+        assert_eq!(&body[range], content);
+        let unsigned = body.replace("application/pkcs7-signature", "text/plain");
+        assert_eq!(smime_signed_content_range(unsigned.as_bytes()), None);
+        assert_eq!(smime_signed_content_range(b"email was meant for @zkemailverify.\r\n"), None);
+    }
+
+    #[test]
+    fn test_split_address_list() {
+        assert_eq!(
+            split_address_list("alice@zkemail.com, bob@zkemail.com,\r\n carol@zkemail.com"),
+            vec!["alice@zkemail.com", "bob@zkemail.com", "carol@zkemail.com"]
+        );
+        assert_eq!(split_address_list("alice@zkemail.com,"), vec!["alice@zkemail.com", ""]);
+    }
+
+    #[test]
+    fn test_check_regex_files() {
+        let dir = "./build/test_check_regex_files";
+        std::fs::create_dir_all(dir).unwrap();
+        let allstr_path = format!("{}/allstr.txt", dir);
+        let substr_path = format!("{}/substr_0.txt", dir);
+        // `ab+` where `b+` is the substring.
+        std::fs::write(&allstr_path, "0\n2\n2\n0 1 97\n1 2 98\n2 2 98\n").unwrap();
+        std::fs::write(&substr_path, "8\n0\n7\n1\n2\n1 2\n2 2\n").unwrap();
+        assert_eq!(check_regex_files(&allstr_path, &[substr_path.clone()]), Ok(()));
+
+        std::fs::write(&substr_path, "8\n0\n7\n1\n2\n1 2\n2 1\n").unwrap();
+        let err = check_regex_files(&allstr_path, &[substr_path.clone()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{}:7: the transition 2 -> 1 is not a transition of {}, so the substring is never extracted", substr_path, allstr_path)
+        );
+
+        std::fs::write(&substr_path, "8\n0\n7\n1\n3\n1 2\n").unwrap();
+        assert!(matches!(check_regex_files(&allstr_path, &[substr_path.clone()]), Err(RegexFilesError::UnknownState { state: 3, .. })));
+
+        std::fs::write(&allstr_path, "0\n2\n2\n0 1 97\n2 2 98\n").unwrap();
+        assert!(matches!(
+            check_regex_files(&allstr_path, &[]),
+            Err(RegexFilesError::UnreachableAcceptedState { accepted_state: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_regex_capacity() {
+        let dir = "./build/test_check_regex_capacity";
+        std::fs::create_dir_all(dir).unwrap();
+        let allstr_path = format!("{}/allstr.txt", dir);
+        std::fs::write(&allstr_path, "0\n2\n2\n0 1 97\n1 2 98\n2 2 98\n0 1 99\n").unwrap();
+        assert_eq!(check_regex_capacity(&allstr_path, 3), Ok(()));
+        let err = check_regex_capacity(&allstr_path, 2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{}: the regex needs 3 states with 4 transitions but the lookup table holds 4 rows; increase degree = 2", allstr_path)
+        );
+    }
+
+    #[test]
+    fn test_email_encoding() {
+        let bytes = b"caf\xe9 email was meant for @zkemailverify.";
+        let decoded = EmailEncoding::Lossy.decode(bytes);
+        assert_eq!(decoded.len(), bytes.len());
+        assert_eq!(decoded, "caf\0 email was meant for @zkemailverify.");
+        assert_eq!(EmailEncoding::Strict.decode("例え.jp".as_bytes()), "例え.jp");
+        assert!(std::panic::catch_unwind(|| EmailEncoding::Strict.decode(bytes)).is_err());
+        assert_eq!(EmailEncoding::Strict.check(bytes), Err(3));
+        assert_eq!(EmailEncoding::Lossy.check(bytes), Ok(()));
+        assert_eq!(EmailEncoding::from_name("strict"), Some(EmailEncoding::Strict));
+        assert_eq!(EmailEncoding::from_name("utf16"), None);
+    }
+
+    #[test]
+    fn test_decode_idn_domain() {
+        assert_eq!(decode_idn_domain("xn--r8jz45g.jp"), "例え.jp");
+        assert_eq!(decode_idn_domain("alice@XN--R8JZ45G.jp"), "alice@例え.jp");
+        assert_eq!(decode_idn_domain("alice@zkemail.com"), "alice@zkemail.com");
+    }
+
+    #[test]
+    fn test_decode_encoded_words() {
+        assert_eq!(decode_encoded_words("=?UTF-8?B?44GT44KT44Gr44Gh44Gv?="), "こんにちは");
+        assert_eq!(decode_encoded_words("=?UTF-8?Q?Caf=C3=A9?= menu"), "Café menu");
+        assert_eq!(decode_encoded_words("Hello"), "Hello");
+    }
+
+    #[test]
+    fn test_decompose_named_regex() {
+        let parts = decompose_named_regex(r"Your code is (?P<code>(0|1|2|3|4|5|6|7|8|9)+) for (?<user>[a-z\]]+)\.").unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                (None, "Your code is ".to_string()),
+                (Some("code".to_string()), "(0|1|2|3|4|5|6|7|8|9)+".to_string()),
+                (None, " for ".to_string()),
+                (Some("user".to_string()), r"[a-z\]]+".to_string()),
+                (None, r"\.".to_string()),
+            ]
+        );
+        assert_eq!(decompose_named_regex("(a|b)+"), Err(NamedRegexError::NoNamedGroup));
+        assert_eq!(decompose_named_regex("(?P<a>x)(?P<a>y)"), Err(NamedRegexError::DuplicateName("a".to_string())));
+        assert_eq!(decompose_named_regex("(x(?P<a>y))"), Err(NamedRegexError::NestedGroup("a".to_string())));
+        assert_eq!(decompose_named_regex("(?P<a>x(?P<b>y))"), Err(NamedRegexError::NestedGroup("b".to_string())));
+        assert_eq!(decompose_named_regex("(?P<a>x)+"), Err(NamedRegexError::QuantifiedGroup("a".to_string())));
+        assert_eq!(decompose_named_regex("(?P<1a>x)"), Err(NamedRegexError::InvalidName("1a".to_string())));
+        assert!(matches!(decompose_named_regex("(?P<a>x"), Err(NamedRegexError::Unbalanced(_))));
+    }
+
+    #[test]
+    fn test_json_path_regexes() {
+        let parts = json_path_regex_parts("data.order.total", JsonValueType::Number).unwrap();
+        assert_eq!(parts.iter().map(|(name, _)| name.clone()).collect_vec(), vec![None, Some("total".to_string()), None]);
+        assert!(parts[0].1.starts_with("\"data\""));
+        assert_eq!(json_path_regex_parts("", JsonValueType::Number), Err(JsonPathError::Empty));
+        assert_eq!(json_path_regex_parts("data.items.0", JsonValueType::Number), Err(JsonPathError::InvalidKey("0".to_string())));
+        assert_eq!(json_path_substr_regexes("data..total", JsonValueType::String), Err(JsonPathError::InvalidKey("".to_string())));
+
+        let body = "Your order:\r\n{\"data\": {\"total\": 1, \"order\": {\"id\": \"A-17\", \"total\": 42.50}}}\r\n";
+        let total = json_path_substr_regexes("data.order.total", JsonValueType::Number).unwrap();
+        assert_eq!(get_substr(body, &total), Some((body.find("42.50").unwrap(), "42.50".to_string())));
+        let id = json_path_substr_regexes("data.order.id", JsonValueType::String).unwrap();
+        assert_eq!(get_substr(body, &id), Some((body.find("A-17").unwrap(), "A-17".to_string())));
+        // A key of a nested object is not a member of its parent.
+        let body = "{\"data\": {\"order\": {\"item\": {\"total\": 3}}}}";
+        assert_eq!(get_substr(body, &total), None);
+    }
+
+    #[test]
+    fn test_named_substr_field_names() {
+        let allstr = vec!["./test_data/otp_allstr.txt".to_string(), "./test_data/subject_allstr.txt".to_string()];
+        let substrs = vec![
+            vec!["./test_data/otp_substr_code.txt".to_string(), "./test_data/otp_substr_user_id.txt".to_string()],
+            vec!["./test_data/subject_substr_0.txt".to_string(), "./test_data/subject_substr_1.txt".to_string()],
+        ];
+        assert_eq!(substr_field_names(&allstr, &substrs), vec!["code", "user_id", "subject", "subject_1"]);
+    }
+
+    #[test]
+    fn test_split_instances() {
+        assert_eq!(split_instances(&[1, 2, 3], 1), vec![vec![1, 2, 3]]);
+        assert_eq!(split_instances(&[1, 2, 3], 2), vec![vec![1, 3], vec![2]]);
+        assert_eq!(instance_position(2, 2), (0, 1));
+    }
+}
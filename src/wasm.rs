@@ -85,6 +85,7 @@ pub fn init_configs(
     log::set_logger(&DEFAULT_LOGGER).unwrap();
     log::set_max_level(log::LevelFilter::Info);
     let config_params: EmailVerifyConfigParams = serde_json::from_str(&config_params).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    config_params.check_capacity().map_err(|err| JsValue::from_str(&err.to_string()))?;
     log_1(&JsValue::from_str(&format!("bodyhash_allstr_def: {}", bodyhash_allstr_def)));
     let bodyhash_allstr_def: AllstrRegexDef = {
         let mut bytes = bodyhash_allstr_def.as_bytes();
@@ -269,7 +270,8 @@ pub fn verify_email_proof(params: JsValue, vk: JsValue, proof: JsValue, public_i
     let proof = proof.as_string().unwrap();
     let proof = hex::decode(&proof[2..]).map_err(|err| JsValue::from_str(&err.to_string()))?;
 
-    let instances = public_input.instances::<Fr>();
+    let instances = split_instances(&public_input.instances::<Fr>(), num_instance_columns());
+    let instances = instances.iter().map(|column| column.as_slice()).collect_vec();
     let result = {
         let mut transcript_read = PoseidonTranscript::<NativeLoader, &[u8]>::new(&proof);
         VerificationStrategy::<_, VerifierSHPLONK<Bn256>>::finalize(
@@ -277,7 +279,7 @@ pub fn verify_email_proof(params: JsValue, vk: JsValue, proof: JsValue, public_i
                 params.verifier_params(),
                 &vk,
                 AccumulatorStrategy::new(params.verifier_params()),
-                &[&[instances.as_slice()]],
+                &[instances.as_slice()],
                 &mut transcript_read,
             )
             .map_err(|err| JsValue::from_str(&format!("verification error: {}", err.to_string())))?,
@@ -298,6 +300,7 @@ pub(crate) fn configure_wasm<F: PrimeField>(meta: &mut ConstraintSystem<F>) -> D
         Vertical,
         &[params.num_flex_advice],
         &[params.num_range_lookup_advice],
+        // The fixed columns are as many as the advice columns rather than `num_flex_fixed`, which keeps the verifying keys of the existing configurations.
         params.num_flex_advice,
         params.range_lookup_bits,
         0,
@@ -312,14 +315,18 @@ pub(crate) fn configure_wasm<F: PrimeField>(meta: &mut ConstraintSystem<F>) -> D
 
     let sha256_config = Sha256DynamicConfig::configure(
         meta,
-        vec![body_params.max_variable_byte_size, header_params.max_variable_byte_size],
+        vec![
+            vec![body_params.max_variable_byte_size, header_params.max_variable_byte_size],
+            SignVerifyConfig::<F>::sha256_max_byte_sizes(sign_verify_params.public_key_bits, sign_verify_params.pss_salt_len),
+        ]
+        .concat(),
         range_config.clone(),
         sha256_params.num_bits_lookup,
         sha256_params.num_advice_columns,
         false,
     );
 
-    let sign_verify_config = SignVerifyConfig::configure(range_config.clone(), sign_verify_params.public_key_bits);
+    let sign_verify_config = SignVerifyConfig::configure(range_config.clone(), sign_verify_params.public_key_bits, sign_verify_params.pss_salt_len);
     let (bodyhash_defs, bodyhash_substr_id) = GLOBAL_BODYHASH_DEFS_AND_ID.get().expect("bodyhash_defs is not set").clone();
     let header_regex_defs = GLOBAL_HEADER_DEFS.get().expect("header_regex_defs is not set").clone();
     let header_config = RegexSha2Config::configure(
@@ -336,17 +343,45 @@ pub(crate) fn configure_wasm<F: PrimeField>(meta: &mut ConstraintSystem<F>) -> D
         body_params.skip_prefix_bytes_size.unwrap_or(0),
         range_config,
         body_regex_defs,
+        body_params.hash_len(),
     );
-    let chars_shift_config = CharsShiftConfig::configure(header_params.max_variable_byte_size, 44, bodyhash_substr_id as u64);
+    let chars_shift_config = CharsShiftConfig::configure(
+        header_params.max_variable_byte_size,
+        base64_encoded_len(body_params.hash_len()) + MAX_BODYHASH_FOLDING_BYTES,
+        bodyhash_substr_id as u64,
+    );
+    let header_substr_equalities = DefaultEmailVerifyCircuit::<F>::configure_substr_equalities(header_params);
+    let header_body_substr_equalities = DefaultEmailVerifyCircuit::<F>::configure_header_body_substr_equalities(&params, header_params, body_params);
+    let body_forbidden_substrs = DefaultEmailVerifyCircuit::<F>::configure_forbidden_substrs(body_params);
+    let header_forbidden_fields = DefaultEmailVerifyCircuit::<F>::configure_forbidden_fields(header_params);
+    let header_dkim_domain_alignments = DefaultEmailVerifyCircuit::<F>::configure_dkim_domain_alignments(header_params);
+    let header_dkim_timestamp = DefaultEmailVerifyCircuit::<F>::configure_dkim_timestamp(header_params);
+    let header_received_hops = DefaultEmailVerifyCircuit::<F>::configure_received_hops(header_params);
+    let header_recipient_lists = DefaultEmailVerifyCircuit::<F>::configure_recipient_lists(header_params);
+    let body_decoded_tokens = DefaultEmailVerifyCircuit::<F>::configure_decoded_tokens(body_params);
+    let header_committed_substrs = DefaultEmailVerifyCircuit::<F>::configure_committed_substrs(header_params);
+    let header_membership_sets = DefaultEmailVerifyCircuit::<F>::configure_membership_sets(header_params);
+    let body_numeric_predicates = DefaultEmailVerifyCircuit::<F>::configure_numeric_predicates(body_params);
 
-    let instances = meta.instance_column();
-    meta.enable_equality(instances);
+    let instances = DefaultEmailVerifyCircuit::<F>::configure_instances(meta);
     DefaultEmailVerifyConfig {
         sha256_config,
         sign_verify_config,
         header_config,
         body_config,
         chars_shift_config,
+        header_substr_equalities,
+        header_body_substr_equalities,
+        body_forbidden_substrs,
+        header_forbidden_fields,
+        header_dkim_domain_alignments,
+        header_dkim_timestamp,
+        header_received_hops,
+        header_recipient_lists,
+        body_decoded_tokens,
+        header_committed_substrs,
+        header_membership_sets,
+        body_numeric_predicates,
         instances,
     }
 }
@@ -357,6 +392,10 @@ fn build_circuit<F: PrimeField>(email_str: &str, public_key_n: &str) -> DefaultE
     DefaultEmailVerifyCircuit {
         email_bytes,
         public_key_n,
+        canonicalized_parts: None,
+        bound_block: None,
+        debug_witness_dir: None,
+        print_stats: false,
         _f: PhantomData,
     }
 }
@@ -0,0 +1,146 @@
+use crate::config_params::default_config_params;
+use crate::sign_verify::LIMB_BITS;
+use crate::DefaultEmailVerifyCircuit;
+use halo2_base::utils::PrimeField;
+use itertools::Itertools;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use thiserror::Error;
+
+/// A pre-computed witness assignment of the email verification circuit, e.g., exported from circom tooling.
+/// The bytes are arrays of numbers and the big integers are arrays of decimal limbs, as in the input json of circom.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EmailWitnessJson {
+    /// The canonicalized email header bytes.
+    pub header: Vec<u8>,
+    /// The canonicalized email body bytes.
+    pub body: Vec<u8>,
+    /// Decimal strings of the [`LIMB_BITS`]-bit limbs of the RSA signature in little-endian order.
+    pub signature: Vec<String>,
+    /// Decimal strings of the [`LIMB_BITS`]-bit limbs of the `n` parameter of the RSA public key in little-endian order.
+    pub public_key: Vec<String>,
+    /// The start positions of the substrings in the email header, which are checked against those extracted by the configured regexes if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_starts: Option<Vec<usize>>,
+    /// The substrings in the email header, which are checked against those extracted by the configured regexes if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_substrs: Option<Vec<String>>,
+    /// The start positions of the substrings in the email body, which are checked against those extracted by the configured regexes if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_starts: Option<Vec<usize>>,
+    /// The substrings in the email body, which are checked against those extracted by the configured regexes if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_substrs: Option<Vec<String>>,
+}
+
+/// An error of [`EmailWitnessJson`] that cannot be converted into the circuit.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WitnessJsonError {
+    /// The json file cannot be read or parsed.
+    #[error("fail to read the witness json: {0}")]
+    Read(String),
+    /// The number of the limbs differs from that of the configured public key size.
+    #[error("{name} has {actual} limbs, but the configured public key size requires {expected}")]
+    LimbCount { name: &'static str, expected: usize, actual: usize },
+    /// The limb is not a decimal number less than 2^LIMB_BITS.
+    #[error("{name}[{idx}] = {value} is not a decimal number less than 2^{bits}", bits = LIMB_BITS)]
+    InvalidLimb { name: &'static str, idx: usize, value: String },
+    /// The given substrings or their start positions differ from those extracted by the configured regexes.
+    #[error("{field} {given} differ from {extracted} extracted by the configured regexes")]
+    SubstrMismatch { field: &'static str, given: String, extracted: String },
+}
+
+impl EmailWitnessJson {
+    /// Export the witness assignment of the email verification circuit.
+    ///
+    /// # Arguments
+    /// * `circuit` - an email verification circuit.
+    /// # Return values
+    /// Return a new [`EmailWitnessJson`] including the extracted substrings.
+    pub fn from_circuit<F: PrimeField>(circuit: &DefaultEmailVerifyCircuit<F>) -> Self {
+        let (header, body, signature_bytes) = circuit.canonicalized_email();
+        let public_input = circuit.gen_default_public_input();
+        let num_limbs = default_config_params().sign_verify_config.as_ref().expect("sign_verify_config is required").public_key_bits / LIMB_BITS;
+        Self {
+            header,
+            body,
+            signature: biguint_to_limbs(&BigUint::from_bytes_be(&signature_bytes), num_limbs),
+            public_key: biguint_to_limbs(&circuit.public_key_n, num_limbs),
+            header_starts: Some(public_input.header_starts),
+            header_substrs: Some(public_input.header_substrs),
+            body_starts: Some(public_input.body_starts),
+            body_substrs: Some(public_input.body_substrs),
+        }
+    }
+
+    /// Construct the email verification circuit from the witness assignment without canonicalizing any email.
+    /// The substrings are extracted by the configured regexes as [`DefaultEmailVerifyCircuit::from_parts`] does, and compared with the given ones if any.
+    ///
+    /// # Return values
+    /// Return a new [`DefaultEmailVerifyCircuit`], or [`WitnessJsonError`] if the witness is inconsistent with the configuration.
+    pub fn to_circuit<F: PrimeField>(&self) -> Result<DefaultEmailVerifyCircuit<F>, WitnessJsonError> {
+        let public_key_bits = default_config_params().sign_verify_config.as_ref().expect("sign_verify_config is required").public_key_bits;
+        let num_limbs = public_key_bits / LIMB_BITS;
+        let signature = limbs_to_biguint("signature", &self.signature, num_limbs)?;
+        let public_key_n = limbs_to_biguint("public_key", &self.public_key, num_limbs)?;
+        let signature_bytes = {
+            let bytes = signature.to_bytes_be();
+            vec![vec![0u8; (public_key_bits / 8).saturating_sub(bytes.len())], bytes].concat()
+        };
+        let circuit = DefaultEmailVerifyCircuit::from_parts(self.header.clone(), self.body.clone(), signature_bytes, public_key_n);
+        let public_input = circuit.gen_default_public_input();
+        let mismatch = |field: &'static str, given: String, extracted: String| Err(WitnessJsonError::SubstrMismatch { field, given, extracted });
+        for (field, given, extracted) in [("header_starts", &self.header_starts, &public_input.header_starts), ("body_starts", &self.body_starts, &public_input.body_starts)] {
+            if let Some(given) = given.as_ref().filter(|given| given != &extracted) {
+                return mismatch(field, format!("{:?}", given), format!("{:?}", extracted));
+            }
+        }
+        for (field, given, extracted) in [("header_substrs", &self.header_substrs, &public_input.header_substrs), ("body_substrs", &self.body_substrs, &public_input.body_substrs)] {
+            if let Some(given) = given.as_ref().filter(|given| given != &extracted) {
+                return mismatch(field, format!("{:?}", given), format!("{:?}", extracted));
+            }
+        }
+        Ok(circuit)
+    }
+
+    /// Read [`EmailWitnessJson`] from a json file.
+    ///
+    /// # Arguments
+    /// * `witness_path` - a file path of the witness json.
+    pub fn read_file(witness_path: &str) -> Result<Self, WitnessJsonError> {
+        let file = File::open(witness_path).map_err(|err| WitnessJsonError::Read(format!("{}: {}", witness_path, err)))?;
+        serde_json::from_reader(file).map_err(|err| WitnessJsonError::Read(format!("{}: {}", witness_path, err)))
+    }
+
+    /// Output [`EmailWitnessJson`] to a json file.
+    ///
+    /// # Arguments
+    /// * `witness_path` - a file path of the output json file.
+    pub fn write_file(&self, witness_path: &str) {
+        serde_json::to_writer_pretty(File::create(witness_path).expect("witness_path creation failed"), self).unwrap();
+    }
+}
+
+fn biguint_to_limbs(value: &BigUint, num_limbs: usize) -> Vec<String> {
+    let mask = (BigUint::from(1u64) << LIMB_BITS) - 1u64;
+    (0..num_limbs).map(|idx| ((value >> (idx * LIMB_BITS)) & &mask).to_str_radix(10)).collect_vec()
+}
+
+fn limbs_to_biguint(name: &'static str, limbs: &[String], num_limbs: usize) -> Result<BigUint, WitnessJsonError> {
+    if limbs.len() != num_limbs {
+        return Err(WitnessJsonError::LimbCount {
+            name,
+            expected: num_limbs,
+            actual: limbs.len(),
+        });
+    }
+    let mut value = BigUint::from(0u64);
+    for (idx, limb) in limbs.iter().enumerate().rev() {
+        let limb_value = BigUint::parse_bytes(limb.as_bytes(), 10)
+            .filter(|limb_value| limb_value.bits() as usize <= LIMB_BITS)
+            .ok_or_else(|| WitnessJsonError::InvalidLimb { name, idx, value: limb.clone() })?;
+        value = (value << LIMB_BITS) + limb_value;
+    }
+    Ok(value)
+}
@@ -0,0 +1,147 @@
+use halo2_base::halo2_proofs::arithmetic::FieldExt;
+use halo2_base::{
+    gates::{flex_gate::FlexGateConfig, GateInstructions},
+    AssignedValue, Context,
+    QuantumCell::{Constant, Existing},
+};
+
+/// The rate of Keccak-256 in bytes, i.e., the number of bytes absorbed per permutation.
+pub const KECCAK256_RATE_BYTES: usize = 136;
+
+/// The rotation offsets of the rho step, indexed by `x + 5 * y`.
+const ROTATION_OFFSETS: [usize; 25] = [0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14];
+
+/// The round constants of the iota step.
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Compute the Keccak-256 hash, i.e., the original Keccak padding used by Ethereum, of the given bytes in the circuit.
+/// Each byte is given as its 8 bits in little-endian order, and every bit must be constrained to be 0 or 1 by the caller.
+/// The number of the bytes is fixed in the circuit, so the padding bytes are constants.
+///
+/// Each bit of the state is a cell, so one permutation for every [`KECCAK256_RATE_BYTES`] bytes costs about 300k gates.
+///
+/// # Arguments
+/// * `input_bits` - the bits of the hashed bytes.
+/// # Return values
+/// Return the bits of the 32 bytes of the hash in the same order.
+pub fn keccak256_bits<'a, F: FieldExt>(ctx: &mut Context<'_, F>, gate: &FlexGateConfig<F>, input_bits: &[Vec<AssignedValue<'a, F>>]) -> Vec<Vec<AssignedValue<'a, F>>> {
+    let zero = gate.load_zero(ctx);
+    let one = gate.load_constant(ctx, F::one());
+    let num_blocks = input_bits.len() / KECCAK256_RATE_BYTES + 1;
+    let mut pad_bytes = vec![0u8; num_blocks * KECCAK256_RATE_BYTES - input_bits.len()];
+    pad_bytes[0] |= 0x01;
+    *pad_bytes.last_mut().unwrap() |= 0x80;
+    let mut padded_bits = input_bits.to_vec();
+    for byte in pad_bytes.into_iter() {
+        padded_bits.push((0..8).map(|idx| if (byte >> idx) & 1 == 1 { one.clone() } else { zero.clone() }).collect());
+    }
+
+    let mut state: Option<Vec<Vec<AssignedValue<'a, F>>>> = None;
+    for block_bits in padded_bits.chunks(KECCAK256_RATE_BYTES) {
+        // Each lane is the little-endian 64 bits of 8 bytes.
+        let block_lanes = block_bits.chunks(8).map(|lane_bits| lane_bits.concat()).collect::<Vec<_>>();
+        let absorbed = match state {
+            None => block_lanes.into_iter().chain((block_bits.len() / 8..25).map(|_| vec![zero.clone(); 64])).collect(),
+            Some(state) => state
+                .iter()
+                .enumerate()
+                .map(|(idx, lane)| match block_lanes.get(idx) {
+                    Some(block_lane) => xor_lane(ctx, gate, lane, block_lane),
+                    None => lane.clone(),
+                })
+                .collect(),
+        };
+        state = Some(keccak_f(ctx, gate, absorbed));
+    }
+    state.unwrap()[0..4].concat().chunks(8).map(|bits| bits.to_vec()).collect()
+}
+
+/// The Keccak-f[1600] permutation on the state of 25 lanes indexed by `x + 5 * y`.
+fn keccak_f<'a, F: FieldExt>(ctx: &mut Context<'_, F>, gate: &FlexGateConfig<F>, mut state: Vec<Vec<AssignedValue<'a, F>>>) -> Vec<Vec<AssignedValue<'a, F>>> {
+    for round_constant in ROUND_CONSTANTS.iter() {
+        // theta
+        let mut parities = vec![];
+        for x in 0..5 {
+            let mut parity = state[x].clone();
+            for y in 1..5 {
+                parity = xor_lane(ctx, gate, &parity, &state[x + 5 * y]);
+            }
+            parities.push(parity);
+        }
+        let mut diffs = vec![];
+        for x in 0..5 {
+            diffs.push(xor_lane(ctx, gate, &parities[(x + 4) % 5], &rotate_lane(&parities[(x + 1) % 5], 1)));
+        }
+        for (idx, lane) in state.iter_mut().enumerate() {
+            *lane = xor_lane(ctx, gate, lane, &diffs[idx % 5]);
+        }
+        // rho and pi
+        let mut moved = state.clone();
+        for x in 0..5 {
+            for y in 0..5 {
+                moved[y + 5 * ((2 * x + 3 * y) % 5)] = rotate_lane(&state[x + 5 * y], ROTATION_OFFSETS[x + 5 * y]);
+            }
+        }
+        // chi
+        for (idx, lane) in state.iter_mut().enumerate() {
+            let (x, y) = (idx % 5, idx / 5);
+            let (next, next_next) = (&moved[(x + 1) % 5 + 5 * y], &moved[(x + 2) % 5 + 5 * y]);
+            *lane = (0..64)
+                .map(|bit_idx| {
+                    // a xor (not(b) and c), where not(b) and c = c - bc.
+                    let product = gate.mul(ctx, Existing(&next[bit_idx]), Existing(&next_next[bit_idx]));
+                    let masked = gate.sub(ctx, Existing(&next_next[bit_idx]), Existing(&product));
+                    xor_bit(ctx, gate, &moved[idx][bit_idx], &masked)
+                })
+                .collect();
+        }
+        // iota
+        for (bit_idx, bit) in state[0].iter_mut().enumerate() {
+            if (round_constant >> bit_idx) & 1 == 1 {
+                let flipped = gate.sub(ctx, Constant(F::one()), Existing(bit));
+                *bit = flipped;
+            }
+        }
+    }
+    state
+}
+
+/// Rotate the lane to the left by `offset` bits.
+fn rotate_lane<'a, F: FieldExt>(lane: &[AssignedValue<'a, F>], offset: usize) -> Vec<AssignedValue<'a, F>> {
+    (0..64).map(|idx| lane[(idx + 64 - offset) % 64].clone()).collect()
+}
+
+fn xor_lane<'a, F: FieldExt>(ctx: &mut Context<'_, F>, gate: &FlexGateConfig<F>, a: &[AssignedValue<'a, F>], b: &[AssignedValue<'a, F>]) -> Vec<AssignedValue<'a, F>> {
+    a.iter().zip(b.iter()).map(|(a, b)| xor_bit(ctx, gate, a, b)).collect()
+}
+
+fn xor_bit<'a, F: FieldExt>(ctx: &mut Context<'_, F>, gate: &FlexGateConfig<F>, a: &AssignedValue<'a, F>, b: &AssignedValue<'a, F>) -> AssignedValue<'a, F> {
+    // a xor b = a + b(1 - 2a)
+    let not_double_a = gate.mul_add(ctx, Constant(-F::from(2)), Existing(a), Constant(F::one()));
+    gate.mul_add(ctx, Existing(b), Existing(&not_double_a), Existing(a))
+}
@@ -1,69 +1,160 @@
+pub mod keccak_circuit;
 pub mod poseidon_circuit;
+use self::keccak_circuit::keccak256_bits;
 use self::poseidon_circuit::{poseidon_hash_fields, HasherChip, PoseidonChipBn254_8_58};
 use halo2_base::halo2_proofs::{arithmetic::FieldExt, plonk::*};
+use halo2_base::utils::fe_to_biguint;
 use halo2_base::{
     gates::{flex_gate::FlexGateConfig, GateInstructions},
     AssignedValue, Context, QuantumCell,
 };
+use sha3::{Digest, Keccak256};
 
+/// A domain tag for commitments of the email header fields.
+pub const HEADER_COMMIT_DOMAIN: u64 = 1;
+/// A domain tag for commitments of the email body fields.
+pub const BODY_COMMIT_DOMAIN: u64 = 2;
+/// A domain tag for commitments of the recipient addresses in the email header.
+pub const RECIPIENT_COMMIT_DOMAIN: u64 = 3;
+/// A domain tag for commitments of the decoded hex or base64 tokens in the email body.
+pub const DECODED_COMMIT_DOMAIN: u64 = 4;
+/// A domain tag for the commitment of the `bh=` value in the DKIM signature header.
+pub const BODYHASH_COMMIT_DOMAIN: u64 = 5;
+
+/// The default number of bytes packed into one field element, which is the largest number of bytes whose value is always less than the BN254 scalar field modulus.
+pub const DEFAULT_BYTES_PER_FIELD: usize = 31;
+
+/// The hash function of the commitments of the witness bytes, which is selected by `commitment_scheme` in the configuration.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitmentScheme {
+    /// [`value_commit_wtns_bytes_with_domain`], which is cheap in the circuit.
+    #[default]
+    Poseidon,
+    /// [`value_keccak_commit_wtns_bytes`], which is cheap to recompute on-chain but costs one Keccak permutation in the circuit for every 136 committed bytes.
+    Keccak,
+}
+
+/// Commit the given bytes with the randomness, packing [`DEFAULT_BYTES_PER_FIELD`] bytes into each field element.
 pub fn value_commit_wtns_bytes<F: FieldExt>(rand: &F, wtns_bytes: &[u8]) -> F {
-    let inputs = vec![vec![rand.clone()], value_bytes2fields(wtns_bytes)].concat();
+    value_commit_wtns_bytes_with_domain(None, rand, wtns_bytes)
+}
+
+/// Commit the given bytes with the randomness, where the domain tag, if given, is absorbed first.
+/// The same bytes committed under different domain tags result in different commitments.
+pub fn value_commit_wtns_bytes_with_domain<F: FieldExt>(domain: Option<u64>, rand: &F, wtns_bytes: &[u8]) -> F {
+    value_commit_wtns_bytes_with_packing(domain, DEFAULT_BYTES_PER_FIELD, rand, wtns_bytes)
+}
+
+/// Commit the given bytes with the randomness, packing `bytes_per_field` bytes into each field element.
+/// The commitment is `poseidon([domain,] rand, limb_0, limb_1, ...)`, where the limbs are given by [`value_bytes2fields_with_packing`].
+/// An external verifier must use the same packing to recompute the commitment.
+///
+/// # Arguments
+/// * `domain` - a domain tag absorbed first if given.
+/// * `bytes_per_field` - the number of bytes packed into one field element, from 1 to [`DEFAULT_BYTES_PER_FIELD`].
+/// * `rand` - the randomness.
+/// * `wtns_bytes` - the committed bytes.
+/// # Return values
+/// Return the commitment.
+pub fn value_commit_wtns_bytes_with_packing<F: FieldExt>(domain: Option<u64>, bytes_per_field: usize, rand: &F, wtns_bytes: &[u8]) -> F {
+    warn_zero_rand(rand);
+    let domain = domain.map(|domain| vec![F::from(domain)]).unwrap_or_default();
+    let inputs = vec![domain, vec![rand.clone()], value_bytes2fields_with_packing(wtns_bytes, bytes_per_field)].concat();
     poseidon_hash_fields(&inputs)
 }
 
+/// Commit the given bytes with the randomness by the given scheme, where the domain tag, if given, is absorbed first.
+pub fn value_commit_wtns_bytes_with_scheme<F: FieldExt>(scheme: CommitmentScheme, domain: Option<u64>, rand: &F, wtns_bytes: &[u8]) -> F {
+    match scheme {
+        CommitmentScheme::Poseidon => value_commit_wtns_bytes_with_domain(domain, rand, wtns_bytes),
+        CommitmentScheme::Keccak => value_keccak_commit_wtns_bytes(domain, rand, wtns_bytes),
+    }
+}
+
+/// Commit the given bytes with the randomness by Keccak-256.
+/// The commitment is the big-endian integer of the last [`DEFAULT_BYTES_PER_FIELD`] bytes of `keccak256([domain,] rand, wtns_bytes)`,
+/// where the domain tag is 8 big-endian bytes and the randomness is 32 big-endian bytes,
+/// i.e., `uint256(keccak256(abi.encodePacked(uint64(domain), rand, wtns_bytes))) & (2**248 - 1)` in Solidity.
+///
+/// # Arguments
+/// * `domain` - a domain tag absorbed first if given.
+/// * `rand` - the randomness.
+/// * `wtns_bytes` - the committed bytes.
+/// # Return values
+/// Return the commitment.
+pub fn value_keccak_commit_wtns_bytes<F: FieldExt>(domain: Option<u64>, rand: &F, wtns_bytes: &[u8]) -> F {
+    warn_zero_rand(rand);
+    let domain = domain.map(|domain| domain.to_be_bytes().to_vec()).unwrap_or_default();
+    let rand_bytes = fe_to_biguint(rand).to_bytes_be();
+    let rand_bytes = [vec![0u8; 32 - rand_bytes.len()], rand_bytes].concat();
+    let hash = Keccak256::digest(&[domain, rand_bytes, wtns_bytes.to_vec()].concat());
+    let mut commit_bytes = hash[32 - DEFAULT_BYTES_PER_FIELD..].to_vec();
+    commit_bytes.reverse();
+    value_bytes2fields_with_packing(&commit_bytes, DEFAULT_BYTES_PER_FIELD)[0]
+}
+
+/// Return true if the randomness is zero, e.g., the `F::zero()` left by a placeholder, with which the commitments of low-entropy bytes can be brute-forced by anyone.
+pub fn is_zero_rand<F: FieldExt>(rand: &F) -> bool {
+    *rand == F::zero()
+}
+
+fn warn_zero_rand<F: FieldExt>(rand: &F) {
+    if is_zero_rand(rand) {
+        log::warn!("the randomness of the commitment is zero, which does not hide the committed bytes. Use the sign_commit of the email or a uniformly random value as the randomness.");
+    }
+}
+
+/// Pack the given bytes into field elements, [`DEFAULT_BYTES_PER_FIELD`] bytes per element.
 pub fn value_bytes2fields<F: FieldExt>(wtns_bytes: &[u8]) -> Vec<F> {
-    let byte_len = wtns_bytes.len();
-    let remaining_bytes = byte_len % 31;
-    let num_limbs = if remaining_bytes == 0 { byte_len / 31 } else { byte_len / 31 + 1 };
+    value_bytes2fields_with_packing(wtns_bytes, DEFAULT_BYTES_PER_FIELD)
+}
 
-    let mut inputs = vec![];
-    for idx in 0..num_limbs {
-        let mut sum = F::zero();
-        let mut coeff = F::one();
-        if idx == num_limbs - 1 && remaining_bytes != 0 {
-            for j in 0..remaining_bytes {
-                sum += F::from(wtns_bytes[idx * 31 + j] as u64) * coeff;
+/// Pack the given bytes into field elements, `bytes_per_field` bytes per element.
+/// Each element is the little-endian integer of its bytes, i.e., `sum(bytes[j] * 256^j)`, and the last element packs the remaining bytes without padding.
+///
+/// # Arguments
+/// * `wtns_bytes` - the packed bytes.
+/// * `bytes_per_field` - the number of bytes packed into one field element, from 1 to [`DEFAULT_BYTES_PER_FIELD`].
+/// # Return values
+/// Return the field elements.
+pub fn value_bytes2fields_with_packing<F: FieldExt>(wtns_bytes: &[u8], bytes_per_field: usize) -> Vec<F> {
+    check_bytes_per_field(bytes_per_field);
+    wtns_bytes
+        .chunks(bytes_per_field)
+        .map(|chunk| {
+            let mut sum = F::zero();
+            let mut coeff = F::one();
+            for byte in chunk.iter() {
+                sum += F::from(*byte as u64) * coeff;
                 coeff *= F::from(256u64);
             }
-        } else {
-            for j in 0..31 {
-                sum += F::from(wtns_bytes[idx * 31 + j] as u64) * coeff;
-                coeff *= F::from(256u64);
-            }
-        }
-        inputs.push(sum);
-    }
-    inputs
+            sum
+        })
+        .collect()
 }
 
+/// Pack the given assigned bytes into field elements in the circuit, [`DEFAULT_BYTES_PER_FIELD`] bytes per element.
 pub fn assigned_bytes2fields<'v: 'a, 'a, F: FieldExt>(ctx: &mut Context<'v, F>, gate: &FlexGateConfig<F>, wtns_bytes: &'a [AssignedValue<F>]) -> Vec<AssignedValue<'a, F>> {
-    let byte_len = wtns_bytes.len();
-    let remaining_bytes = byte_len % 31;
-    let num_limbs = if remaining_bytes == 0 { byte_len / 31 } else { byte_len / 31 + 1 };
+    assigned_bytes2fields_with_packing(ctx, gate, wtns_bytes, DEFAULT_BYTES_PER_FIELD)
+}
+
+/// Pack the given assigned bytes into field elements in the circuit, `bytes_per_field` bytes per element.
+/// It is the in-circuit counterpart of [`value_bytes2fields_with_packing`].
+pub fn assigned_bytes2fields_with_packing<'v: 'a, 'a, F: FieldExt>(
+    ctx: &mut Context<'v, F>,
+    gate: &FlexGateConfig<F>,
+    wtns_bytes: &'a [AssignedValue<F>],
+    bytes_per_field: usize,
+) -> Vec<AssignedValue<'a, F>> {
+    check_bytes_per_field(bytes_per_field);
     let mut inputs = vec![];
-    for idx in 0..num_limbs {
+    for chunk in wtns_bytes.chunks(bytes_per_field) {
         let mut sum = gate.load_zero(ctx);
         let mut coeff = F::one();
-        if idx == num_limbs - 1 && remaining_bytes != 0 {
-            for j in 0..remaining_bytes {
-                sum = gate.mul_add(
-                    ctx,
-                    QuantumCell::Existing(&wtns_bytes[idx * 31 + j]),
-                    QuantumCell::Constant(coeff),
-                    QuantumCell::Existing(&sum),
-                );
-                coeff *= F::from(256u64);
-            }
-        } else {
-            for j in 0..31 {
-                sum = gate.mul_add(
-                    ctx,
-                    QuantumCell::Existing(&wtns_bytes[idx * 31 + j]),
-                    QuantumCell::Constant(coeff),
-                    QuantumCell::Existing(&sum),
-                );
-                coeff *= F::from(256u64);
-            }
+        for byte in chunk.iter() {
+            sum = gate.mul_add(ctx, QuantumCell::Existing(byte), QuantumCell::Constant(coeff), QuantumCell::Existing(&sum));
+            coeff *= F::from(256u64);
         }
         inputs.push(sum);
     }
@@ -77,7 +168,179 @@ pub fn assigned_commit_wtns_bytes<'v: 'a, 'a, F: FieldExt>(
     rand: &'a AssignedValue<F>,
     wtns_bytes: &'a [AssignedValue<F>],
 ) -> AssignedValue<'a, F> {
-    let inputs = vec![vec![rand.clone()], assigned_bytes2fields(ctx, gate, wtns_bytes)].concat();
+    assigned_commit_wtns_bytes_with_domain(ctx, gate, poseidon, None, rand, wtns_bytes)
+}
+
+/// Commit the given assigned bytes with the randomness in the circuit, where the domain tag, if given, is absorbed first as a constant.
+/// It is the in-circuit counterpart of [`value_commit_wtns_bytes_with_domain`].
+pub fn assigned_commit_wtns_bytes_with_domain<'v: 'a, 'a, F: FieldExt>(
+    ctx: &mut Context<'v, F>,
+    gate: &FlexGateConfig<F>,
+    poseidon: &'a PoseidonChipBn254_8_58<F>,
+    domain: Option<u64>,
+    rand: &'a AssignedValue<F>,
+    wtns_bytes: &'a [AssignedValue<F>],
+) -> AssignedValue<'a, F> {
+    assigned_commit_wtns_bytes_with_packing(ctx, gate, poseidon, domain, DEFAULT_BYTES_PER_FIELD, rand, wtns_bytes)
+}
+
+/// Commit the given assigned bytes with the randomness in the circuit, packing `bytes_per_field` bytes into each field element.
+/// It is the in-circuit counterpart of [`value_commit_wtns_bytes_with_packing`].
+pub fn assigned_commit_wtns_bytes_with_packing<'v: 'a, 'a, F: FieldExt>(
+    ctx: &mut Context<'v, F>,
+    gate: &FlexGateConfig<F>,
+    poseidon: &'a PoseidonChipBn254_8_58<F>,
+    domain: Option<u64>,
+    bytes_per_field: usize,
+    rand: &'a AssignedValue<F>,
+    wtns_bytes: &'a [AssignedValue<F>],
+) -> AssignedValue<'a, F> {
+    rand.value().map(warn_zero_rand);
+    let domain = domain.map(|domain| vec![gate.load_constant(ctx, F::from(domain))]).unwrap_or_default();
+    let inputs = vec![domain, vec![rand.clone()], assigned_bytes2fields_with_packing(ctx, gate, wtns_bytes, bytes_per_field)].concat();
     let result = poseidon.hash_elements(ctx, gate, &inputs).unwrap().0;
     result[0].clone()
 }
+
+/// Commit the given assigned bytes with the randomness by the given scheme in the circuit.
+/// It is the in-circuit counterpart of [`value_commit_wtns_bytes_with_scheme`].
+pub fn assigned_commit_wtns_bytes_with_scheme<'v: 'a, 'a, F: FieldExt>(
+    ctx: &mut Context<'v, F>,
+    gate: &FlexGateConfig<F>,
+    poseidon: &'a PoseidonChipBn254_8_58<F>,
+    scheme: CommitmentScheme,
+    domain: Option<u64>,
+    rand: &'a AssignedValue<F>,
+    wtns_bytes: &'a [AssignedValue<F>],
+) -> AssignedValue<'a, F> {
+    match scheme {
+        CommitmentScheme::Poseidon => assigned_commit_wtns_bytes_with_domain(ctx, gate, poseidon, domain, rand, wtns_bytes),
+        CommitmentScheme::Keccak => assigned_keccak_commit_wtns_bytes(ctx, gate, domain, rand, wtns_bytes),
+    }
+}
+
+/// Commit the given assigned bytes with the randomness by Keccak-256 in the circuit.
+/// It is the in-circuit counterpart of [`value_keccak_commit_wtns_bytes`].
+/// Each committed byte is constrained to be less than 256, and the bytes of the randomness are constrained to be its unique big-endian bytes less than the field modulus.
+pub fn assigned_keccak_commit_wtns_bytes<'a, F: FieldExt>(
+    ctx: &mut Context<'_, F>,
+    gate: &FlexGateConfig<F>,
+    domain: Option<u64>,
+    rand: &AssignedValue<'a, F>,
+    wtns_bytes: &[AssignedValue<'a, F>],
+) -> AssignedValue<'a, F> {
+    rand.value().map(warn_zero_rand);
+    let zero = gate.load_zero(ctx);
+    let one = gate.load_constant(ctx, F::one());
+    let mut input_bits = vec![];
+    for byte in domain.map(|domain| domain.to_be_bytes().to_vec()).unwrap_or_default() {
+        input_bits.push((0..8).map(|idx| if (byte >> idx) & 1 == 1 { one.clone() } else { zero.clone() }).collect::<Vec<_>>());
+    }
+    let mut rand_bits = gate.num_to_bits(ctx, rand, F::NUM_BITS as usize);
+    assert_less_than_modulus(ctx, gate, &rand_bits);
+    rand_bits.resize(256, zero.clone());
+    for idx in (0..32).rev() {
+        input_bits.push(rand_bits[8 * idx..8 * (idx + 1)].to_vec());
+    }
+    for byte in wtns_bytes.iter() {
+        input_bits.push(gate.num_to_bits(ctx, byte, 8));
+    }
+    let hash_bits = keccak256_bits(ctx, gate, &input_bits);
+    let mut commit = gate.load_zero(ctx);
+    for byte_bits in hash_bits[32 - DEFAULT_BYTES_PER_FIELD..].iter() {
+        commit = gate.mul(ctx, QuantumCell::Existing(&commit), QuantumCell::Constant(F::from(256u64)));
+        for (idx, bit) in byte_bits.iter().enumerate() {
+            commit = gate.mul_add(ctx, QuantumCell::Existing(bit), QuantumCell::Constant(F::from(1u64 << idx)), QuantumCell::Existing(&commit));
+        }
+    }
+    commit
+}
+
+/// Constrain the little-endian bits to represent a value less than the field modulus, so that every field element has the unique bits.
+fn assert_less_than_modulus<'a, F: FieldExt>(ctx: &mut Context<'_, F>, gate: &FlexGateConfig<F>, bits: &[AssignedValue<'a, F>]) {
+    let modulus = fe_to_biguint(&-F::one()) + 1u64;
+    let mut is_less = gate.load_zero(ctx);
+    let mut is_equal = gate.load_constant(ctx, F::one());
+    // Compare the bits from the most significant one, where is_less and is_equal are exclusive.
+    for (idx, bit) in bits.iter().enumerate().rev() {
+        let equal_and_bit = gate.mul(ctx, QuantumCell::Existing(&is_equal), QuantumCell::Existing(bit));
+        if modulus.bit(idx as u64) {
+            let equal_and_not_bit = gate.sub(ctx, QuantumCell::Existing(&is_equal), QuantumCell::Existing(&equal_and_bit));
+            is_less = gate.add(ctx, QuantumCell::Existing(&is_less), QuantumCell::Existing(&equal_and_not_bit));
+            is_equal = equal_and_bit;
+        } else {
+            is_equal = gate.sub(ctx, QuantumCell::Existing(&is_equal), QuantumCell::Existing(&equal_and_bit));
+        }
+    }
+    gate.assert_is_const(ctx, &is_less, F::one());
+}
+
+fn check_bytes_per_field(bytes_per_field: usize) {
+    assert!(
+        (1..=DEFAULT_BYTES_PER_FIELD).contains(&bytes_per_field),
+        "bytes_per_field must be from 1 to {}, but it is {}",
+        DEFAULT_BYTES_PER_FIELD,
+        bytes_per_field
+    );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_commit_with_domain() {
+        let rand = Fr::from(7u64);
+        let bytes = b"alice@zkemail.com";
+        let header_commit = value_commit_wtns_bytes_with_domain(Some(HEADER_COMMIT_DOMAIN), &rand, bytes);
+        let body_commit = value_commit_wtns_bytes_with_domain(Some(BODY_COMMIT_DOMAIN), &rand, bytes);
+        assert_ne!(header_commit, body_commit);
+        assert_eq!(value_commit_wtns_bytes_with_domain(None, &rand, bytes), value_commit_wtns_bytes(&rand, bytes));
+    }
+
+    #[test]
+    fn test_keccak_commit() {
+        let rand = Fr::from(7u64);
+        let bytes = b"alice@zkemail.com";
+        let commit = value_keccak_commit_wtns_bytes(Some(RECIPIENT_COMMIT_DOMAIN), &rand, bytes);
+        // An external verifier can recompute the commitment with Keccak-256 of the packed domain, randomness, and bytes.
+        let mut preimage = RECIPIENT_COMMIT_DOMAIN.to_be_bytes().to_vec();
+        preimage.extend([vec![0u8; 31], vec![7u8]].concat());
+        preimage.extend(bytes);
+        let hash = Keccak256::digest(&preimage);
+        assert_eq!(fe_to_biguint(&commit), BigUint::from_bytes_be(&hash[1..]));
+        assert_eq!(value_commit_wtns_bytes_with_scheme(CommitmentScheme::Keccak, Some(RECIPIENT_COMMIT_DOMAIN), &rand, bytes), commit);
+        assert_eq!(
+            value_commit_wtns_bytes_with_scheme(CommitmentScheme::Poseidon, Some(RECIPIENT_COMMIT_DOMAIN), &rand, bytes),
+            value_commit_wtns_bytes_with_domain(Some(RECIPIENT_COMMIT_DOMAIN), &rand, bytes)
+        );
+        assert_ne!(value_keccak_commit_wtns_bytes(Some(BODY_COMMIT_DOMAIN), &rand, bytes), commit);
+    }
+
+    #[test]
+    fn test_zero_rand() {
+        assert!(is_zero_rand(&Fr::from(0u64)));
+        assert!(!is_zero_rand(&Fr::from(7u64)));
+    }
+
+    #[test]
+    fn test_bytes_packing() {
+        let rand = Fr::from(7u64);
+        let bytes = (0..70u8).collect::<Vec<_>>();
+        // An external verifier can recompute the limbs as the little-endian integers of the byte chunks.
+        for bytes_per_field in [1, 16, DEFAULT_BYTES_PER_FIELD] {
+            let fields = value_bytes2fields_with_packing::<Fr>(&bytes, bytes_per_field);
+            let expected = bytes.chunks(bytes_per_field).map(BigUint::from_bytes_le).collect::<Vec<_>>();
+            assert_eq!(fields.iter().map(fe_to_biguint).collect::<Vec<_>>(), expected);
+            let commit = value_commit_wtns_bytes_with_packing(None, bytes_per_field, &rand, &bytes);
+            assert_eq!(commit, poseidon_hash_fields(&vec![vec![rand], fields].concat()));
+        }
+        assert_eq!(value_bytes2fields::<Fr>(&bytes), value_bytes2fields_with_packing::<Fr>(&bytes, DEFAULT_BYTES_PER_FIELD));
+        assert_eq!(value_commit_wtns_bytes_with_packing(None, DEFAULT_BYTES_PER_FIELD, &rand, &bytes), value_commit_wtns_bytes(&rand, &bytes));
+        assert_ne!(value_commit_wtns_bytes_with_packing(None, 1, &rand, &bytes), value_commit_wtns_bytes(&rand, &bytes));
+        assert!(std::panic::catch_unwind(|| value_bytes2fields_with_packing::<Fr>(&bytes, 32)).is_err());
+    }
+}